@@ -0,0 +1,180 @@
+use crate::audioinfo::AudioMeta;
+use serde::Deserialize;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// AcoustID endpoint used to look up fingerprints.
+const LOOKUP_URL: &str = "https://api.acoustid.org/v2/lookup";
+/// Maximum time to wait for a response.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Results with a lower score are ignored.
+const MIN_SCORE: f64 = 0.8;
+
+/// A Chromaprint fingerprint of a track.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Fingerprint {
+    /// Length of the track in seconds
+    pub duration: f64,
+    /// The compressed fingerprint
+    pub fingerprint: String,
+}
+
+/// The response of a lookup request.
+#[derive(Debug, Deserialize)]
+struct LookupResponse {
+    status: String,
+    #[serde(default)]
+    results: Vec<LookupResult>,
+    error: Option<LookupError>,
+}
+
+/// An error returned by the AcoustID API.
+#[derive(Debug, Deserialize)]
+struct LookupError {
+    message: String,
+}
+
+/// A track matching the fingerprint.
+#[derive(Debug, Deserialize)]
+struct LookupResult {
+    score: f64,
+    #[serde(default)]
+    recordings: Vec<Recording>,
+}
+
+/// A MusicBrainz recording linked to a track.
+#[derive(Debug, Deserialize)]
+struct Recording {
+    title: Option<String>,
+    #[serde(default)]
+    artists: Vec<Artist>,
+    #[serde(default)]
+    releasegroups: Vec<ReleaseGroup>,
+}
+
+/// An artist of a recording.
+#[derive(Debug, Deserialize)]
+struct Artist {
+    name: String,
+    #[serde(default)]
+    joinphrase: String,
+}
+
+/// A release group (album) which contains a recording.
+#[derive(Debug, Deserialize)]
+struct ReleaseGroup {
+    title: String,
+}
+
+impl Fingerprint {
+    /// Computes the fingerprint of an audio file.
+    ///
+    /// # Arguments
+    /// * `file` - A [`String`](String) containing the path to the audio file.
+    ///
+    /// ### Notes
+    /// This uses `fpcalc` (part of [Chromaprint](https://acoustid.org/chromaprint)),
+    /// and fails if it's not installed.
+    pub fn compute(file: &str) -> Result<Self, String> {
+        let output = Command::new("fpcalc")
+            .args(["-json", file])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|e| format!("Unable to start fpcalc: {e}"))?;
+
+        if !output.status.success() {
+            return Err("Unable to compute fingerprint".to_owned());
+        }
+
+        serde_json::from_slice(&output.stdout).map_err(|e| format!("Invalid fpcalc output: {e}"))
+    }
+
+    /// Looks up the fingerprint on AcoustID, and returns the metadata of the best match.
+    ///
+    /// # Arguments
+    /// * `api_key` - AcoustID application API key
+    ///
+    /// ### Notes
+    /// Returns `Ok(None)` if there is no good match.
+    pub fn lookup(&self, api_key: &str) -> Result<Option<AudioMeta>, String> {
+        let response: LookupResponse = ureq::AgentBuilder::new()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .post(LOOKUP_URL)
+            .send_form(&[
+                ("client", api_key),
+                ("meta", "recordings releasegroups"),
+                ("duration", &(self.duration as u64).to_string()),
+                ("fingerprint", &self.fingerprint),
+            ])
+            .or_else(|e| match e {
+                /* The API also describes errors in the response body */
+                ureq::Error::Status(_, response) => Ok(response),
+                e => Err(format!("Request failed: {e}")),
+            })?
+            .into_json()
+            .map_err(|e| format!("Invalid response: {e}"))?;
+
+        if response.status != "ok" {
+            return Err(response.error.map(|e| e.message).unwrap_or(response.status));
+        }
+
+        let recording = response
+            .results
+            .into_iter()
+            .filter(|result| result.score >= MIN_SCORE)
+            .flat_map(|result| result.recordings)
+            .find(|recording| recording.title.is_some());
+        let Some(recording) = recording else {
+            return Ok(None);
+        };
+
+        let artist = recording
+            .artists
+            .iter()
+            .map(|artist| format!("{}{}", artist.name, artist.joinphrase))
+            .collect::<String>();
+
+        Ok(Some(AudioMeta {
+            title: recording.title.unwrap(),
+            album: recording
+                .releasegroups
+                .first()
+                .map(|group| group.title.clone())
+                .unwrap_or("Unknown".to_owned()),
+            artist: if artist.is_empty() {
+                "Unknown".to_owned()
+            } else {
+                artist
+            },
+            track: None,
+            track_total: None,
+            genre: None,
+            year: None,
+            album_artist: None,
+            compilation: false,
+            inferred: Vec::new(),
+        }))
+    }
+}
+
+/// Identifies a track by its fingerprint in a background thread, so playback is not blocked.
+///
+/// The returned channel receives a single message: the metadata of the track,
+/// `None` if it couldn't be identified, or an error message.
+pub fn lookup_in_background(
+    file: String,
+    api_key: String,
+) -> Receiver<Result<Option<AudioMeta>, String>> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let result = Fingerprint::compute(&file).and_then(|fp| fp.lookup(&api_key));
+        let _ = tx.send(result);
+    });
+
+    rx
+}
@@ -0,0 +1,112 @@
+use crate::clock;
+use crate::timer::Timer;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long the volume ramps up after the alarm went off.
+pub const ALARM_RAMP: Duration = Duration::from_secs(60);
+
+/// Starts playback at a given (local) time, like an alarm clock.
+/// Until then, playback is paused and the time left is shown. When the alarm goes off,
+/// the volume ramps up from silence over [`ALARM_RAMP`](ALARM_RAMP).
+pub struct Alarm {
+    /// The time the alarm goes off (hour and minute)
+    time: (u8, u8),
+    /// When the alarm goes off
+    at: SystemTime,
+    /// The volume ramp, once the alarm went off
+    ramp: Option<Timer>,
+}
+
+/// Parses a time of day, like `07:30`.
+///
+/// ## Errors
+/// Fails if it isn't a valid time in the format `HH:MM`.
+pub fn parse_time(text: &str) -> Result<(u8, u8), String> {
+    let invalid = || format!("Invalid time: {text} (expected HH:MM)");
+    let (hour, minute) = text.trim().split_once(':').ok_or_else(invalid)?;
+    /* The minutes need two digits, so `7:5` isn't taken for 07:05 */
+    if minute.len() != 2 {
+        return Err(invalid());
+    }
+    match (hour.parse(), minute.parse()) {
+        (Ok(hour @ 0..=23), Ok(minute @ 0..=59)) => Ok((hour, minute)),
+        _ => Err(invalid()),
+    }
+}
+
+impl Alarm {
+    /// Sets an alarm, which goes off at the next time the clock shows `hour:minute`
+    /// (today, or tomorrow if that time has passed).
+    pub fn new((hour, minute): (u8, u8)) -> Self {
+        let now = SystemTime::now();
+        let target = hour as i64 * 3600 + minute as i64 * 60;
+        let wait = (target - seconds_of_day()).rem_euclid(86400) as u64;
+        Self {
+            time: (hour, minute),
+            at: now + Duration::from_secs(wait),
+            ramp: None,
+        }
+    }
+
+    /// Returns the time the alarm goes off, like `07:30`.
+    pub fn label(&self) -> String {
+        format!("{:02}:{:02}", self.time.0, self.time.1)
+    }
+
+    /// Returns the time left until the alarm goes off.
+    /// Returns `None` once it went off.
+    pub fn remaining(&self) -> Option<Duration> {
+        match self.ramp {
+            Some(_) => None,
+            None => Some(
+                self.at
+                    .duration_since(SystemTime::now())
+                    .unwrap_or(Duration::ZERO),
+            ),
+        }
+    }
+
+    /// Returns whether it's time for the alarm to go off (and it hasn't yet).
+    pub fn is_due(&self) -> bool {
+        self.ramp.is_none() && SystemTime::now() >= self.at
+    }
+
+    /// Makes the alarm go off, which starts the volume ramp.
+    pub fn ring(&mut self) {
+        self.ramp = Some(Timer::new(ALARM_RAMP));
+    }
+
+    /// Returns whether the alarm went off and the volume ramped up fully.
+    pub fn is_finished(&self) -> bool {
+        self.ramp.is_some_and(|ramp| ramp.expired())
+    }
+
+    /// Returns the level the audio is faded to while the volume ramps up
+    /// (from `0.0` to `1.0`, rising slowly at first as loudness isn't linear).
+    pub fn fade_level(&self) -> f32 {
+        self.ramp.map_or(1.0, |ramp| ramp.progress().powi(2))
+    }
+
+    /// Stops the volume ramp while the audio is paused, and lets it continue once it plays.
+    pub fn set_playing(&mut self, playing: bool) {
+        let Some(ramp) = &mut self.ramp else {
+            return;
+        };
+        match (playing, ramp.is_paused()) {
+            (true, true) => ramp.resume(),
+            (false, false) => ramp.pause(),
+            _ => (),
+        }
+    }
+}
+
+/// Returns how many seconds of the local day have passed.
+/// If the local time can't be read, UTC is used.
+fn seconds_of_day() -> i64 {
+    match clock::local_time() {
+        Some(time) => time.tm_hour as i64 * 3600 + time.tm_min as i64 * 60 + time.tm_sec as i64,
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs() as i64 % 86400),
+    }
+}
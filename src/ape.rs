@@ -0,0 +1,339 @@
+use std::fs::File;
+use std::io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+/// Size of the APEv2 tag header/footer.
+const APETAG_FOOTER_SIZE: u64 = 32;
+/// Size of an ID3v1 tag, which may follow the APEv2 tag.
+const ID3V1_SIZE: u64 = 128;
+/// APEv2 tag version
+const APETAG_VERSION: u32 = 2000;
+/// Header/footer flag: the tag contains a header
+const FLAG_HAS_HEADER: u32 = 1 << 31;
+/// Header/footer flag: this is the header, not the footer
+const FLAG_IS_HEADER: u32 = 1 << 29;
+/// Item flag: UTF-8 text
+const ITEM_TEXT: u32 = 0;
+/// Item flag: binary data
+const ITEM_BINARY: u32 = 1 << 1;
+
+/// An APEv2 tag, used by both Monkey's Audio and WavPack files.
+#[derive(Debug, Clone, Default)]
+pub struct ApeTag {
+    /// Text items as `(KEY, value)` pairs
+    pub items: Vec<(String, String)>,
+    /// Binary items (such as cover art) as `(KEY, value)` pairs
+    pub binary: Vec<(String, Vec<u8>)>,
+}
+
+/// Information about a Monkey's Audio (`.ape`) file.
+#[derive(Debug, Clone)]
+pub struct ApeInfo {
+    /// Compression level (`1000` - `5000`)
+    pub compression_level: u16,
+    /// Number of channels
+    pub channels: u16,
+    /// Sample rate
+    pub sample_rate: u32,
+    /// Amount of samples (per channel)
+    pub frames: u64,
+    /// APEv2 tag
+    pub tag: ApeTag,
+}
+
+impl ApeTag {
+    /// Reads the APEv2 tag at the end of a file.
+    /// If the file doesn't have an APEv2 tag, an empty tag is returned.
+    ///
+    /// ## Errors
+    /// Fails if the file can't be read, or the tag is corrupt.
+    ///
+    /// ### Notes
+    /// Tags followed by an ID3v1 tag are supported as well.
+    pub fn read<R: Read + Seek>(data: &mut R) -> std::io::Result<Self> {
+        let Some((footer_end, footer)) = Self::find_footer(data)? else {
+            return Ok(Self::default());
+        };
+
+        /* The tag size includes the footer, but not the header */
+        let tag_size = u32_at(&footer, 12) as u64;
+        let count = u32_at(&footer, 16);
+
+        let mut items = vec![0u8; (tag_size - APETAG_FOOTER_SIZE) as usize];
+        data.seek(SeekFrom::Start(footer_end - tag_size))?;
+        data.read_exact(&mut items)?;
+
+        Ok(Self::parse_items(&items, count))
+    }
+
+    /// Finds the APEv2 tag at the end of a file and returns its position (including the header).
+    /// If the file doesn't have an APEv2 tag, an empty range is returned at the position
+    /// where a new tag should be inserted (before the ID3v1 tag, if there's one).
+    ///
+    /// ## Errors
+    /// Fails if the file can't be read, or the tag is corrupt.
+    pub fn locate<R: Read + Seek>(data: &mut R) -> std::io::Result<Range<u64>> {
+        if let Some((footer_end, footer)) = Self::find_footer(data)? {
+            let tag_size = u32_at(&footer, 12) as u64;
+            let flags = u32_at(&footer, 20);
+            let header_size = if flags & FLAG_HAS_HEADER != 0 {
+                APETAG_FOOTER_SIZE
+            } else {
+                0
+            };
+
+            return Ok((footer_end - tag_size).saturating_sub(header_size)..footer_end);
+        }
+
+        let size = data.seek(SeekFrom::End(0))?;
+        let mut end = size;
+        if size >= ID3V1_SIZE {
+            let mut magic = [0u8; 3];
+            data.seek(SeekFrom::Start(size - ID3V1_SIZE))?;
+            data.read_exact(&mut magic)?;
+            if &magic == b"TAG" {
+                end -= ID3V1_SIZE;
+            }
+        }
+
+        Ok(end..end)
+    }
+
+    /// Finds the footer of the APEv2 tag.
+    /// Returns the position of the end of the footer, and the footer itself.
+    ///
+    /// ## Errors
+    /// Fails if the size in the footer is smaller than the footer, or larger than the file
+    /// before it ends (which only a corrupt tag has).
+    fn find_footer<R: Read + Seek>(
+        data: &mut R,
+    ) -> std::io::Result<Option<(u64, [u8; APETAG_FOOTER_SIZE as usize])>> {
+        let size = data.seek(SeekFrom::End(0))?;
+
+        for offset in [APETAG_FOOTER_SIZE, APETAG_FOOTER_SIZE + ID3V1_SIZE] {
+            if size < offset {
+                break;
+            }
+
+            let mut footer = [0u8; APETAG_FOOTER_SIZE as usize];
+            data.seek(SeekFrom::Start(size - offset))?;
+            data.read_exact(&mut footer)?;
+
+            if &footer[0..8] != b"APETAGEX" {
+                continue;
+            }
+
+            let footer_end = size - offset + APETAG_FOOTER_SIZE;
+            let tag_size = u32_at(&footer, 12) as u64;
+            if !(APETAG_FOOTER_SIZE..=footer_end).contains(&tag_size) {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid APEv2 tag size: {tag_size}"),
+                ));
+            }
+
+            return Ok(Some((footer_end, footer)));
+        }
+
+        Ok(None)
+    }
+
+    /// Parses the items of an APEv2 tag.
+    fn parse_items(data: &[u8], count: u32) -> Self {
+        let mut items = Vec::new();
+        let mut binary = Vec::new();
+        let mut pos = 0;
+
+        for _ in 0..count {
+            if pos + 8 > data.len() {
+                break;
+            }
+            let len = u32_at(data, pos) as usize;
+            let flags = u32_at(data, pos + 4);
+            pos += 8;
+
+            let Some(key_len) = data[pos..].iter().position(|&b| b == 0) else {
+                break;
+            };
+            let key = String::from_utf8_lossy(&data[pos..pos + key_len]).to_ascii_uppercase();
+            pos += key_len + 1;
+
+            let Some(value) = data.get(pos..pos + len) else {
+                break;
+            };
+            pos += len;
+
+            /* Bits 1-2 of the flags describe the item type */
+            match (flags >> 1) & 0b11 {
+                0 => items.push((key, String::from_utf8_lossy(value).into_owned())),
+                1 => binary.push((key, value.to_vec())),
+                _ => (),
+            }
+        }
+
+        Self { items, binary }
+    }
+
+    /// Gets the value of an item.
+    ///
+    /// ### Notes
+    /// Item keys are __not__ case-sensitive.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.items
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.clone())
+    }
+
+    /// Gets the value of a binary item.
+    ///
+    /// ### Notes
+    /// Item keys are __not__ case-sensitive.
+    pub fn get_binary(&self, key: &str) -> Option<&[u8]> {
+        self.binary
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_slice())
+    }
+
+    /// Sets the value of a text item, replacing any previous values.
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.items.retain(|(k, _)| !k.eq_ignore_ascii_case(key));
+        self.items.push((key.to_owned(), value.to_owned()));
+    }
+
+    /// Serializes the tag (with a header and footer).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut items = Vec::new();
+        let text = self.items.iter().map(|(k, v)| (k, v.as_bytes(), ITEM_TEXT));
+        let binary = self
+            .binary
+            .iter()
+            .map(|(k, v)| (k, v.as_slice(), ITEM_BINARY));
+
+        for (key, value, flags) in text.chain(binary) {
+            items.extend((value.len() as u32).to_le_bytes());
+            items.extend(flags.to_le_bytes());
+            items.extend(key.as_bytes());
+            items.push(0);
+            items.extend(value);
+        }
+
+        let count = (self.items.len() + self.binary.len()) as u32;
+        let tag_size = items.len() as u32 + APETAG_FOOTER_SIZE as u32;
+        let header_footer = |flags: u32| {
+            let mut data = Vec::with_capacity(APETAG_FOOTER_SIZE as usize);
+            data.extend(b"APETAGEX");
+            data.extend(APETAG_VERSION.to_le_bytes());
+            data.extend(tag_size.to_le_bytes());
+            data.extend(count.to_le_bytes());
+            data.extend(flags.to_le_bytes());
+            data.extend([0u8; 8]);
+            data
+        };
+
+        let mut result = header_footer(FLAG_HAS_HEADER | FLAG_IS_HEADER);
+        result.extend(items);
+        result.extend(header_footer(FLAG_HAS_HEADER));
+        result
+    }
+}
+
+impl ApeInfo {
+    /// Reads the header and tags of a Monkey's Audio file.
+    ///
+    /// # Arguments
+    /// * `file` - A [`String`](String) containing the path to the audio file.
+    pub fn probe(file: &str) -> Result<Self, String> {
+        let mut reader =
+            BufReader::new(File::open(file).map_err(|e| format!("Unable to open file: {e}"))?);
+        let mut header = [0u8; 76];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| "File is too short".to_owned())?;
+
+        if &header[0..4] != b"MAC " {
+            return Err("Missing Monkey's Audio header".to_owned());
+        }
+        let version = u16_at(&header, 4);
+
+        let mut info = if version >= 3980 {
+            Self::parse_new_header(&header)?
+        } else {
+            Self::parse_old_header(&header, version)
+        };
+        info.tag = ApeTag::read(&mut reader).map_err(|e| format!("Unable to read tags: {e}"))?;
+
+        Ok(info)
+    }
+
+    /// Parses the `APE_DESCRIPTOR` and `APE_HEADER` used since version 3.98.
+    fn parse_new_header(data: &[u8]) -> Result<Self, String> {
+        let descriptor_size = u32_at(data, 8) as usize;
+        let header = data
+            .get(descriptor_size..descriptor_size + 24)
+            .ok_or("Malformed Monkey's Audio header")?;
+
+        let blocks_per_frame = u32_at(header, 4) as u64;
+        let final_frame_blocks = u32_at(header, 8) as u64;
+        let total_frames = u32_at(header, 12) as u64;
+
+        Ok(Self {
+            compression_level: u16_at(header, 0),
+            channels: u16_at(header, 18),
+            sample_rate: u32_at(header, 20),
+            frames: total_frames.saturating_sub(1) * blocks_per_frame + final_frame_blocks,
+            tag: ApeTag::default(),
+        })
+    }
+
+    /// Parses the header used before version 3.98.
+    fn parse_old_header(data: &[u8], version: u16) -> Self {
+        let compression_level = u16_at(data, 6);
+        let total_frames = u32_at(data, 24) as u64;
+        let final_frame_blocks = u32_at(data, 28) as u64;
+
+        let blocks_per_frame = if version >= 3950 {
+            73728 * 4
+        } else if version >= 3900 || (version >= 3800 && compression_level == 4000) {
+            73728
+        } else {
+            9216
+        };
+
+        Self {
+            compression_level,
+            channels: u16_at(data, 10),
+            sample_rate: u32_at(data, 12),
+            frames: total_frames.saturating_sub(1) * blocks_per_frame + final_frame_blocks,
+            tag: ApeTag::default(),
+        }
+    }
+
+    /// Returns the length of the track in seconds.
+    pub fn length(&self) -> f64 {
+        self.frames as f64 / self.sample_rate as f64
+    }
+
+    /// Returns the name of the compression level.
+    pub fn compression_name(&self) -> &'static str {
+        match self.compression_level {
+            1000 => "Fast",
+            2000 => "Normal",
+            3000 => "High",
+            4000 => "Extra High",
+            5000 => "Insane",
+            _ => "Unknown",
+        }
+    }
+}
+
+/// Reads a little-endian [`u16`](u16) at the given offset.
+pub fn u16_at(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([data[pos], data[pos + 1]])
+}
+
+/// Reads a little-endian [`u32`](u32) at the given offset.
+pub fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
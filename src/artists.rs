@@ -0,0 +1,76 @@
+/// Separators between the artists of an artist tag.
+/// Commas and `&` are not included, as they're part of the names of many bands
+/// (like `Earth, Wind & Fire`).
+const SEPARATORS: [&str; 3] = [";", " / ", "\0"];
+/// Words which introduce featured artists (__not__ case-sensitive), also in parentheses
+/// (like `Artist (feat. Guest)`).
+const FEATURING: [&str; 6] = [
+    " feat. ",
+    " ft. ",
+    " featuring ",
+    " (feat. ",
+    " (ft. ",
+    " (featuring ",
+];
+
+/// The artists of a track, split from its artist tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artists {
+    /// The main artists, in the order they're tagged
+    pub main: Vec<String>,
+    /// The featured artists (after `feat.`), in the order they're tagged
+    pub featured: Vec<String>,
+}
+
+impl Artists {
+    /// Splits an artist tag into the artists, like `A; B feat. C` into `A`, `B` and `C` (featured).
+    /// The tag itself isn't changed, this is only used to show and look up the artists.
+    pub fn parse(tag: &str) -> Self {
+        let lowercase = tag.to_ascii_lowercase();
+        let (main, featured) = match FEATURING
+            .iter()
+            .filter_map(|word| lowercase.find(word).map(|index| (index, word.len())))
+            .min()
+        {
+            Some((index, len)) => (&tag[..index], &tag[index + len..]),
+            None => (tag, ""),
+        };
+        /* Featured artists in parentheses, like `Artist (feat. Guest)` */
+        let featured = featured.trim().trim_end_matches(')');
+
+        Self {
+            main: split(main),
+            featured: split(featured),
+        }
+    }
+
+    /// Returns the first main artist, which is used to look up the track.
+    pub fn primary(&self) -> Option<&str> {
+        self.main.first().map(String::as_str)
+    }
+
+    /// Returns all artists (the featured ones last), joined by a separator.
+    pub fn join(&self, separator: &str) -> String {
+        self.main
+            .iter()
+            .chain(&self.featured)
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+}
+
+/// Splits a list of artists at the [separators](SEPARATORS), leaving out empty names.
+fn split(artists: &str) -> Vec<String> {
+    let mut names = vec![artists.to_owned()];
+    for separator in SEPARATORS {
+        names = names
+            .iter()
+            .flat_map(|name| name.split(separator))
+            .map(|name| name.trim().to_owned())
+            .collect();
+    }
+
+    names.retain(|name| !name.is_empty());
+    names
+}
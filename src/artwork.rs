@@ -0,0 +1,423 @@
+use crate::ape::ApeTag;
+use crate::audioinfo::AudioFormat;
+use crate::comments;
+use crate::opus::OpusInfo;
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// File names (without extension) which are checked when looking for cover art
+/// next to an audio file.
+const COVER_FILE_NAMES: [&str; 4] = ["cover", "folder", "front", "album"];
+/// File extensions which are checked when looking for cover art next to an audio file.
+const COVER_FILE_EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+/// ID3v2/FLAC picture type of the front cover.
+const PICTURE_TYPE_FRONT_COVER: u32 = 3;
+
+/// Represents a cover art image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Artwork {
+    /// MIME type of the image (e.g. `image/jpeg`)
+    pub mime: String,
+    /// Raw (encoded) image data
+    pub data: Vec<u8>,
+    /// Where the image was found
+    pub source: ArtworkSource,
+}
+
+/// Describes where a cover art image was found.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArtworkSource {
+    /// The image is embedded in the audio file
+    Embedded,
+    /// The image is a separate file in the audio file's directory
+    File(PathBuf),
+}
+
+impl Artwork {
+    /// Reads the cover art of an audio file.
+    ///
+    /// The following embedded pictures are supported:
+    /// - FLAC `PICTURE` metadata blocks
+    /// - ID3v2 `APIC`/`PIC` frames (also inside WAV files)
+    /// - MP4 `covr` atoms
+    /// - APEv2 `Cover Art (Front)` items (WavPack and Monkey's Audio)
+    /// - `METADATA_BLOCK_PICTURE` comments (Ogg Vorbis and Opus)
+    ///
+    /// If the file doesn't contain a picture, image files like `cover.jpg` or `folder.png`
+    /// in the same directory are used instead.
+    ///
+    /// # Arguments
+    /// * `file` - A [`String`](String) containing the path to the audio file.
+    pub fn find(file: &str) -> Option<Self> {
+        Self::read_embedded(file).or_else(|| Self::read_directory(file))
+    }
+
+    /// Reads a picture embedded in the audio file.
+    fn read_embedded(file: &str) -> Option<Self> {
+        let mut reader = BufReader::new(File::open(file).ok()?);
+        let mut magic = [0u8; 12];
+        reader.read_exact(&mut magic).ok()?;
+        reader.rewind().ok()?;
+
+        let picture = match &magic {
+            m if &m[0..4] == b"fLaC" => read_flac(&mut reader),
+            m if &m[0..3] == b"ID3" => read_id3(&mut reader),
+            m if &m[0..4] == b"RIFF" && &m[8..12] == b"WAVE" => read_wav(&mut reader),
+            m if &m[4..8] == b"ftyp" => read_mp4(&mut reader),
+            m if &m[0..4] == b"OggS" => read_ogg(file),
+            m if &m[0..4] == b"wvpk" || &m[0..4] == b"MAC " => read_ape(&mut reader),
+            _ => None,
+        }?;
+
+        Some(Self {
+            mime: picture.0,
+            data: picture.1,
+            source: ArtworkSource::Embedded,
+        })
+    }
+
+    /// Looks for an image file in the audio file's directory.
+    fn read_directory(file: &str) -> Option<Self> {
+        let dir = Path::new(file).parent()?;
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+
+        let mut candidates: Vec<PathBuf> = fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                let matches = |name: Option<&std::ffi::OsStr>, list: &[&str]| {
+                    name.map(|name| name.to_string_lossy().to_lowercase())
+                        .is_some_and(|name| list.contains(&name.as_str()))
+                };
+                matches(path.file_stem(), &COVER_FILE_NAMES)
+                    && matches(path.extension(), &COVER_FILE_EXTENSIONS)
+            })
+            .collect();
+
+        /* Prefer names in the order they appear in COVER_FILE_NAMES */
+        candidates.sort_by_key(|path| {
+            let stem = path.file_stem().unwrap().to_string_lossy().to_lowercase();
+            COVER_FILE_NAMES.iter().position(|name| *name == stem)
+        });
+
+        candidates.into_iter().find_map(|path| {
+            let data = fs::read(&path).ok()?;
+            Some(Self {
+                mime: guess_mime(&data)?.to_owned(),
+                data,
+                source: ArtworkSource::File(path),
+            })
+        })
+    }
+}
+
+/// A `(MIME type, image data)` pair.
+type Picture = (String, Vec<u8>);
+
+/// Reads the front cover (or the first picture) from the metadata blocks of a FLAC file.
+fn read_flac<R: Read + Seek>(reader: &mut R) -> Option<Picture> {
+    reader.seek(SeekFrom::Start(4)).ok()?;
+    let mut found = None;
+
+    loop {
+        let mut header = [0u8; 4];
+        reader.read_exact(&mut header).ok()?;
+        let last = header[0] & 0x80 != 0;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+
+        /* Block type 6 is PICTURE */
+        if header[0] & 0x7F == 6 {
+            let mut block = vec![0u8; len];
+            reader.read_exact(&mut block).ok()?;
+            if let Some((kind, picture)) = parse_flac_picture(&block) {
+                if kind == PICTURE_TYPE_FRONT_COVER {
+                    return Some(picture);
+                }
+                found = found.or(Some(picture));
+            }
+        } else {
+            reader.seek(SeekFrom::Current(len as i64)).ok()?;
+        }
+
+        if last {
+            return found;
+        }
+    }
+}
+
+/// Parses a FLAC `PICTURE` block, returning the picture type and the picture.
+/// This is also used for `METADATA_BLOCK_PICTURE` comments.
+fn parse_flac_picture(block: &[u8]) -> Option<(u32, Picture)> {
+    let kind = be_u32(block, 0)?;
+    let mime_len = be_u32(block, 4)? as usize;
+    let mime = String::from_utf8_lossy(block.get(8..8 + mime_len)?).into_owned();
+
+    let desc_len = be_u32(block, 8 + mime_len)? as usize;
+    /* Skip the description, width, height, color depth and color count */
+    let data_pos = 8 + mime_len + 4 + desc_len + 16;
+    let data_len = be_u32(block, data_pos)? as usize;
+    let data = block.get(data_pos + 4..data_pos + 4 + data_len)?;
+
+    Some((kind, (mime, data.to_vec())))
+}
+
+/// Reads the front cover (or the first picture) from an ID3v2 tag at the current position.
+fn read_id3<R: Read + Seek>(reader: &mut R) -> Option<Picture> {
+    let mut header = [0u8; 10];
+    reader.read_exact(&mut header).ok()?;
+    if &header[0..3] != b"ID3" {
+        return None;
+    }
+
+    let version = header[3];
+    let flags = header[5];
+    let mut tag = vec![0u8; syncsafe(&header[6..10]) as usize];
+    reader.read_exact(&mut tag).ok()?;
+
+    /* Unsynchronisation: a 0x00 byte was inserted after every 0xFF byte */
+    if flags & 0x80 != 0 {
+        let mut result = Vec::with_capacity(tag.len());
+        for (i, &byte) in tag.iter().enumerate() {
+            if !(byte == 0x00 && i > 0 && tag[i - 1] == 0xFF) {
+                result.push(byte);
+            }
+        }
+        tag = result;
+    }
+
+    let mut pos = 0;
+    if flags & 0x40 != 0 && version >= 3 {
+        /* Skip the extended header */
+        let size = tag.get(0..4)?;
+        pos = match version {
+            3 => u32::from_be_bytes(size.try_into().ok()?) as usize + 4,
+            _ => syncsafe(size) as usize,
+        };
+    }
+
+    let (id_len, header_len) = if version == 2 { (3, 6) } else { (4, 10) };
+    let mut found = None;
+
+    while pos + header_len <= tag.len() && tag[pos] != 0 {
+        let id = &tag[pos..pos + id_len];
+        let size = match version {
+            2 => u32::from_be_bytes([0, tag[pos + 3], tag[pos + 4], tag[pos + 5]]),
+            3 => u32::from_be_bytes(tag[pos + 4..pos + 8].try_into().ok()?),
+            _ => syncsafe(&tag[pos + 4..pos + 8]),
+        } as usize;
+        let frame = tag.get(pos + header_len..pos + header_len + size)?;
+        pos += header_len + size;
+
+        if id != b"APIC" && id != b"PIC" {
+            continue;
+        }
+        if let Some((kind, picture)) = parse_id3_picture(frame, version) {
+            if kind == PICTURE_TYPE_FRONT_COVER {
+                return Some(picture);
+            }
+            found = found.or(Some(picture));
+        }
+    }
+
+    found
+}
+
+/// Parses an ID3v2 `APIC` (or ID3v2.2 `PIC`) frame, returning the picture type and the picture.
+fn parse_id3_picture(frame: &[u8], version: u8) -> Option<(u32, Picture)> {
+    let encoding = *frame.first()?;
+    let (mime, mut pos) = if version == 2 {
+        let format = frame.get(1..4)?;
+        let mime = match format.to_ascii_uppercase().as_slice() {
+            b"PNG" => "image/png",
+            _ => "image/jpeg",
+        };
+        (mime.to_owned(), 4)
+    } else {
+        let len = frame[1..].iter().position(|&b| b == 0)?;
+        let mime = String::from_utf8_lossy(&frame[1..1 + len]).into_owned();
+        (mime, 1 + len + 1)
+    };
+
+    let kind = *frame.get(pos)? as u32;
+    pos += 1;
+
+    /* Skip the description, which is terminated by a single or double null byte */
+    if encoding == 1 || encoding == 2 {
+        while frame.get(pos..pos + 2)? != [0, 0] {
+            pos += 2;
+        }
+        pos += 2;
+    } else {
+        pos += frame[pos..].iter().position(|&b| b == 0)? + 1;
+    }
+
+    let data = frame.get(pos..)?.to_vec();
+    let mime = match mime.as_str() {
+        /* Some taggers only store the image format */
+        "" | "image/" => guess_mime(&data)?.to_owned(),
+        m if !m.contains('/') => format!("image/{}", m.to_lowercase()),
+        _ => mime,
+    };
+
+    Some((kind, (mime, data)))
+}
+
+/// Reads the cover art from the ID3v2 chunk of a WAV file.
+fn read_wav<R: Read + Seek>(reader: &mut R) -> Option<Picture> {
+    reader.seek(SeekFrom::Start(12)).ok()?;
+
+    loop {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).ok()?;
+        let len = u32::from_le_bytes(header[4..8].try_into().ok()?) as i64;
+
+        if header[0..4].eq_ignore_ascii_case(b"id3 ") {
+            return read_id3(reader);
+        }
+        /* Chunks are padded to an even size */
+        reader.seek(SeekFrom::Current(len + (len & 1))).ok()?;
+    }
+}
+
+/// Reads the cover art from the `moov/udta/meta/ilst/covr` atom of an MP4 file.
+fn read_mp4<R: Read + Seek>(reader: &mut R) -> Option<Picture> {
+    let end = reader.seek(SeekFrom::End(0)).ok()?;
+    reader.rewind().ok()?;
+
+    let mut range = (0, end);
+    for (name, skip) in [
+        (b"moov", 0),
+        (b"udta", 0),
+        (b"meta", 4),
+        (b"ilst", 0),
+        (b"covr", 0),
+        (b"data", 8),
+    ] {
+        range = find_mp4_atom(reader, range, name)?;
+        range.0 += skip;
+    }
+
+    let mut data = vec![0u8; (range.1 - range.0) as usize];
+    reader.seek(SeekFrom::Start(range.0)).ok()?;
+    reader.read_exact(&mut data).ok()?;
+
+    Some((guess_mime(&data)?.to_owned(), data))
+}
+
+/// Finds a child atom within the given `(start, end)` byte range,
+/// returning the byte range of the atom's contents.
+fn find_mp4_atom<R: Read + Seek>(
+    reader: &mut R,
+    range: (u64, u64),
+    name: &[u8; 4],
+) -> Option<(u64, u64)> {
+    let mut pos = range.0;
+
+    while pos + 8 <= range.1 {
+        let mut header = [0u8; 8];
+        reader.seek(SeekFrom::Start(pos)).ok()?;
+        reader.read_exact(&mut header).ok()?;
+
+        let (size, header_len) = match u32::from_be_bytes(header[0..4].try_into().ok()?) {
+            0 => (range.1 - pos, 8),
+            1 => {
+                let mut large = [0u8; 8];
+                reader.read_exact(&mut large).ok()?;
+                (u64::from_be_bytes(large), 16)
+            }
+            size => (size as u64, 8),
+        };
+        if size < header_len {
+            return None;
+        }
+
+        if &header[4..8] == name {
+            return Some((pos + header_len, (pos + size).min(range.1)));
+        }
+        pos += size;
+    }
+
+    None
+}
+
+/// Reads the `METADATA_BLOCK_PICTURE` comment of an Ogg Opus or Vorbis file.
+fn read_ogg(file: &str) -> Option<Picture> {
+    let comment = match OpusInfo::probe(file) {
+        Ok(info) => info.comment("METADATA_BLOCK_PICTURE"),
+        Err(_) => comments::read(file, AudioFormat::OGG)
+            .ok()
+            .flatten()?
+            .get("METADATA_BLOCK_PICTURE"),
+    };
+    let block = decode_base64(&comment?)?;
+    parse_flac_picture(&block).map(|(_, picture)| picture)
+}
+
+/// Reads the `Cover Art (Front)` item of an APEv2 tag.
+fn read_ape<R: Read + Seek>(reader: &mut R) -> Option<Picture> {
+    let tag = ApeTag::read(reader).ok()?;
+    let item = tag.get_binary("Cover Art (Front)")?;
+
+    /* The item starts with the original file name */
+    let start = item.iter().position(|&b| b == 0)? + 1;
+    let data = item[start..].to_vec();
+    Some((guess_mime(&data)?.to_owned(), data))
+}
+
+/// Reads a big-endian [`u32`](u32) at the given offset.
+fn be_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+/// Decodes a syncsafe integer (7 bits per byte) used by ID3v2.
+fn syncsafe(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |acc, &b| (acc << 7) | (b & 0x7F) as u32)
+}
+
+/// Guesses the MIME type of an image from its first few bytes.
+fn guess_mime(data: &[u8]) -> Option<&'static str> {
+    match data {
+        [0xFF, 0xD8, ..] => Some("image/jpeg"),
+        [0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+        [b'G', b'I', b'F', ..] => Some("image/gif"),
+        [b'B', b'M', ..] => Some("image/bmp"),
+        _ => None,
+    }
+}
+
+/// Decodes a standard base64 string.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut result = Vec::with_capacity(text.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in text
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+    {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = ((buffer << 6) | value as u32) & 0xFFFF;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(result)
+}
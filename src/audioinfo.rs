@@ -1,12 +1,165 @@
+use crate::ape::{ApeInfo, ApeTag};
+use crate::artwork::Artwork;
+use crate::comments;
+use crate::opus::OpusInfo;
+use crate::pathmeta;
+use crate::wavpack::{WavPackInfo, WavPackMode};
+use serde::{Deserialize, Serialize};
 use sndfile::*;
 use std::path::Path;
 
+/// Album artist of compilations (__not__ case-sensitive when it's read from tags).
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
+
 /// This structure represents metadata of an Audio file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AudioMeta {
     pub title: String,
     pub album: String,
     pub artist: String,
+    /// Position of the track on the album
+    pub track: Option<u32>,
+    /// Amount of tracks on the album
+    pub track_total: Option<u32>,
+    /// Genre of the track
+    pub genre: Option<String>,
+    /// Release year
+    pub year: Option<u32>,
+    /// Artist of the whole album (`ALBUMARTIST`), if it differs from the track's artist
+    #[serde(default)]
+    pub album_artist: Option<String>,
+    /// Whether the track is part of a compilation (`COMPILATION=1`)
+    #[serde(default)]
+    pub compilation: bool,
+    /// Names of the fields (`title`, `album`, `artist` and `track`) which weren't tagged,
+    /// but inferred from the path of the file (see [`infer_missing()`](Self::infer_missing))
+    #[serde(skip)]
+    pub inferred: Vec<&'static str>,
+}
+
+impl AudioMeta {
+    /// Creates metadata with every field missing.
+    pub fn unknown() -> Self {
+        Self {
+            title: "Unknown".to_owned(),
+            album: "Unknown".to_owned(),
+            artist: "Unknown".to_owned(),
+            track: None,
+            track_total: None,
+            genre: None,
+            year: None,
+            album_artist: None,
+            compilation: false,
+            inferred: Vec::new(),
+        }
+    }
+
+    /// Replaces the missing (or inferred) fields with the fields of `other`.
+    pub fn fill_missing(&mut self, other: &AudioMeta) {
+        for (name, field, value) in [
+            ("title", &mut self.title, &other.title),
+            ("album", &mut self.album, &other.album),
+            ("artist", &mut self.artist, &other.artist),
+        ] {
+            if (field == "Unknown" || self.inferred.contains(&name)) && value != "Unknown" {
+                field.clone_from(value);
+                self.inferred.retain(|inferred| *inferred != name);
+            }
+        }
+
+        if self.track.is_none() || self.inferred.contains(&"track") {
+            self.track = other.track.or(self.track);
+            self.inferred.retain(|inferred| *inferred != "track");
+        }
+        self.track_total = self.track_total.or(other.track_total);
+        self.genre = self.genre.take().or_else(|| other.genre.clone());
+        self.year = self.year.or(other.year);
+        self.album_artist = self
+            .album_artist
+            .take()
+            .or_else(|| other.album_artist.clone());
+        self.compilation |= other.compilation;
+    }
+
+    /// Fills the missing fields with the ones inferred from the path of the file
+    /// (see [`pathmeta::infer()`](pathmeta::infer)), and marks them as inferred.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the file
+    /// * `folders` - Whether the album and artist are taken from the directories of the file
+    ///   (`Artist/Album/Track`), even if the file name doesn't start with a track number
+    pub fn infer_missing(&mut self, path: &str, folders: bool) {
+        let inferred = pathmeta::infer(path, folders);
+        for (name, field, value) in [
+            ("title", &mut self.title, inferred.title),
+            ("album", &mut self.album, inferred.album),
+            ("artist", &mut self.artist, inferred.artist),
+        ] {
+            if field == "Unknown" && value != "Unknown" {
+                *field = value;
+                self.inferred.push(name);
+            }
+        }
+
+        if let (None, Some(track)) = (self.track, inferred.track) {
+            self.track = Some(track);
+            self.inferred.push("track");
+        }
+    }
+
+    /// Returns whether a field is missing: not tagged (`"Unknown"`), or only inferred.
+    fn is_missing(&self, name: &str, value: &str) -> bool {
+        value == "Unknown" || self.inferred.contains(&name)
+    }
+
+    /// Returns whether any of the fields is missing (`"Unknown"` or inferred).
+    pub fn is_incomplete(&self) -> bool {
+        [
+            ("title", &self.title),
+            ("album", &self.album),
+            ("artist", &self.artist),
+        ]
+        .iter()
+        .any(|(name, field)| self.is_missing(name, field))
+    }
+
+    /// Returns whether all of the fields are missing (`"Unknown"` or inferred).
+    pub fn is_untagged(&self) -> bool {
+        [
+            ("title", &self.title),
+            ("album", &self.album),
+            ("artist", &self.artist),
+        ]
+        .iter()
+        .all(|(name, field)| self.is_missing(name, field))
+    }
+
+    /// Returns whether the track is part of a compilation: it's tagged as one,
+    /// or its album artist is [`VARIOUS_ARTISTS`](VARIOUS_ARTISTS).
+    pub fn is_compilation(&self) -> bool {
+        self.compilation
+            || self
+                .album_artist
+                .as_deref()
+                .is_some_and(|artist| artist.trim().eq_ignore_ascii_case(VARIOUS_ARTISTS))
+    }
+
+    /// Returns the artist the track is grouped by: the album artist if it has one
+    /// ([`VARIOUS_ARTISTS`](VARIOUS_ARTISTS) for compilations without one), otherwise its artist.
+    /// This keeps the tracks of an album together, even if each of them has a different artist.
+    pub fn album_artist(&self) -> &str {
+        match (&self.album_artist, self.compilation) {
+            (Some(artist), _) if !artist.trim().is_empty() => artist,
+            (_, true) => VARIOUS_ARTISTS,
+            _ => &self.artist,
+        }
+    }
+
+    /// Returns whether a field (`title`, `album`, `artist` or `track`) was inferred
+    /// from the path of the file.
+    pub fn is_inferred(&self, name: &str) -> bool {
+        self.inferred.contains(&name)
+    }
 }
 
 /// Identifies an audio file format
@@ -19,6 +172,49 @@ pub enum AudioFormat {
     WAV,
     /// Ogg Vorbis
     OGG,
+    /// Ogg Opus
+    OPUS,
+    /// WavPack
+    WAVPACK,
+    /// Monkey's Audio
+    APE,
+}
+
+/// A speaker position, used to describe which speaker a channel belongs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speaker {
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    LowFrequency,
+    BackLeft,
+    BackRight,
+    BackCenter,
+    SideLeft,
+    SideRight,
+}
+
+/// Identifies the channel layout of an audio file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelLayout {
+    /// 1 channel
+    Mono,
+    /// 2 channels
+    Stereo,
+    /// 3 channels (front left, right and center)
+    Surround30,
+    /// 4 channels (front and back left/right)
+    Quad,
+    /// 5 channels
+    Surround50,
+    /// 6 channels (5.1)
+    Surround51,
+    /// 7 channels (6.1)
+    Surround61,
+    /// 8 channels (7.1)
+    Surround71,
+    /// Any other amount of channels
+    Other(u16),
 }
 
 /// This structure represents an Audio file
@@ -28,16 +224,22 @@ pub struct AudioFile {
     pub file_name: String,
     /// File format
     pub format: AudioFormat,
-    /// Length of the track in seconds
+    /// Length of the track in seconds (`0.0` if unknown, e.g. for streams)
     pub length: f64,
     /// Sample rate
     pub sample_rate: usize,
-    /// Whether the audio track is stereo
-    /// This is only true if the number if channels is 2
-    pub stereo: bool,
+    /// Channel layout
+    pub layout: ChannelLayout,
     /// Whether the audio file is in a lossless format
-    /// This is only `true` if `format` is [`AudioFormat::FLAC`](AudioFormat::FLAC) or [`AudioFormat::WAV`](AudioFormat::FLAC)
+    /// This is `true` if `format` is [`AudioFormat::FLAC`](AudioFormat::FLAC), [`AudioFormat::WAV`](AudioFormat::WAV)
+    /// or [`AudioFormat::APE`](AudioFormat::APE), and if it's [`AudioFormat::WAVPACK`](AudioFormat::WAVPACK)
+    /// unless it's a hybrid file without its correction file
     pub lossless: bool,
+    /// Additional details about how the audio was encoded
+    /// (e.g. the compression level of a Monkey's Audio file)
+    pub codec_mode: Option<String>,
+    /// How the samples are encoded (e.g. `24-bit PCM`), if `sndfile` reports it
+    pub encoding: Option<String>,
     /// Metadata
     pub metadata: AudioMeta,
 }
@@ -49,36 +251,179 @@ impl AudioFile {
     /// # Arguments
     /// * `file` - A [`String`](String) containing the path to the audio file.
     ///
-    /// ## Panics
-    /// If the given path to the audio file is invalid, this will panic.
-    pub fn new(file: &str) -> Self {
-        let mut snd = Self::open_file(file);
+    /// ## Errors
+    /// Fails if the file can't be read, or its format isn't supported.
+    pub fn open(file: &str) -> Result<Self, String> {
+        let fmt = AudioFormat::from_path(file).map_err(|_| "Failed to parse format".to_owned())?;
+        match fmt {
+            AudioFormat::OPUS => return Self::new_opus(file),
+            AudioFormat::WAVPACK => return Self::new_wavpack(file),
+            AudioFormat::APE => return Self::new_ape(file),
+            _ => (),
+        }
+
+        let mut snd = Self::open_file(file)?;
         let samplerate: usize = snd.get_samplerate();
-        let n_frame = snd.len().unwrap();
-        let fmt = AudioFormat::from_path(file).expect("Failed to parse format");
+        let n_frame = snd
+            .len()
+            .map_err(|_| "Failed to read the length of the file".to_owned())?;
+        let layout = ChannelLayout::from_channels(snd.get_channels() as u16);
+        let encoding = describe_subtype(snd.get_subtype_format());
 
-        Self {
+        let mut metadata: AudioMeta = snd.into();
+        /* `sndfile` doesn't expose these tags, so they're read from the Vorbis comments */
+        if let Ok(Some(comment)) = comments::read(file, fmt) {
+            metadata.album_artist = comment
+                .get("ALBUMARTIST")
+                .or_else(|| comment.get("ALBUM ARTIST"));
+            metadata.compilation = comment
+                .get("COMPILATION")
+                .is_some_and(|flag| parse_flag(&flag));
+        }
+
+        Ok(Self {
             file_name: file.to_string(),
             format: fmt,
             length: n_frame as f64 / samplerate as f64,
             sample_rate: samplerate,
-            stereo: snd.get_channels() > 1,
+            layout,
             lossless: fmt.is_lossless(),
-            metadata: snd.into(),
+            codec_mode: None,
+            encoding,
+            metadata,
+        })
+    }
+
+    /// Generates an [`AudioFile`](AudioFile) structure for an HTTP(S) stream (or the standard input).
+    /// The length and tags of streams are unknown, so only the details of the decoded
+    /// audio are used.
+    ///
+    /// # Arguments
+    /// * `url` - The URL of the stream (or [`STDIN`](crate::stream::STDIN))
+    /// * `format` - Format of the stream
+    /// * `sample_rate` - Sample rate of the decoded audio
+    /// * `channels` - Amount of channels of the decoded audio
+    pub fn new_stream(url: &str, format: AudioFormat, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            file_name: url.to_string(),
+            format,
+            length: 0.0,
+            sample_rate: sample_rate as usize,
+            layout: ChannelLayout::from_channels(channels),
+            lossless: format.is_lossless(),
+            codec_mode: Some("Stream".to_owned()),
+            encoding: None,
+            metadata: AudioMeta::unknown(),
         }
     }
 
-    /// Opens an audio file with [`sndfile`](sndfile)
+    /// Generates an [`AudioFile`](AudioFile) structure from an Ogg Opus file.
+    /// `sndfile` is not used here, as it can't reliably read Opus files.
     ///
     /// # Arguments
     /// * `file` - A [`String`](String) containing the path to the audio file.
     ///
-    /// ## Panics
-    /// If the given path to the audio file is invalid, this will panic.
-    fn open_file(file: &str) -> SndFile {
+    /// ### Notes
+    /// Fails if the file is not a valid Ogg Opus file.
+    fn new_opus(file: &str) -> Result<Self, String> {
+        let info = OpusInfo::probe(file).map_err(|e| format!("Failed to read Opus file: {e}"))?;
+
+        Ok(Self {
+            file_name: file.to_string(),
+            format: AudioFormat::OPUS,
+            length: info.length(),
+            sample_rate: crate::opus::OPUS_SAMPLE_RATE as usize,
+            layout: ChannelLayout::from_channels(info.header.channels as u16),
+            lossless: false,
+            codec_mode: None,
+            encoding: None,
+            metadata: (&info).into(),
+        })
+    }
+
+    /// Generates an [`AudioFile`](AudioFile) structure from a WavPack file.
+    /// Hybrid files are only considered lossless if their correction file is available.
+    ///
+    /// # Arguments
+    /// * `file` - A [`String`](String) containing the path to the audio file.
+    ///
+    /// ### Notes
+    /// Fails if the file is not a valid WavPack file.
+    fn new_wavpack(file: &str) -> Result<Self, String> {
+        let info =
+            WavPackInfo::probe(file).map_err(|e| format!("Failed to read WavPack file: {e}"))?;
+
+        Ok(Self {
+            file_name: file.to_string(),
+            format: AudioFormat::WAVPACK,
+            length: info.length(),
+            sample_rate: info.sample_rate as usize,
+            layout: ChannelLayout::from_channels(info.channels),
+            lossless: info.mode != WavPackMode::HybridLossy,
+            codec_mode: match info.mode {
+                WavPackMode::Lossless => None,
+                mode => Some(mode.to_string()),
+            },
+            encoding: None,
+            metadata: (&info.tag).into(),
+        })
+    }
+
+    /// Generates an [`AudioFile`](AudioFile) structure from a Monkey's Audio file.
+    ///
+    /// # Arguments
+    /// * `file` - A [`String`](String) containing the path to the audio file.
+    ///
+    /// ### Notes
+    /// Fails if the file is not a valid Monkey's Audio file.
+    fn new_ape(file: &str) -> Result<Self, String> {
+        let info =
+            ApeInfo::probe(file).map_err(|e| format!("Failed to read Monkey's Audio file: {e}"))?;
+
+        Ok(Self {
+            file_name: file.to_string(),
+            format: AudioFormat::APE,
+            length: info.length(),
+            sample_rate: info.sample_rate as usize,
+            layout: ChannelLayout::from_channels(info.channels),
+            lossless: true,
+            codec_mode: Some(info.compression_name().to_owned()),
+            encoding: None,
+            metadata: (&info.tag).into(),
+        })
+    }
+
+    /// Returns the average bitrate of the file (in kbps), from its size and length,
+    /// as most formats don't store it.
+    ///
+    /// ### Notes
+    /// Returns `None` for streams and files of unknown length.
+    pub fn bitrate(&self) -> Option<u32> {
+        if self.length <= 0.0 {
+            return None;
+        }
+
+        let size = std::fs::metadata(&self.file_name).ok()?.len();
+        Some((size as f64 * 8.0 / self.length / 1000.0).round() as u32)
+    }
+
+    /// Reads the cover art of the audio file.  
+    /// See [`Artwork::find()`](Artwork::find) for the supported sources.
+    ///
+    /// ### Notes
+    /// Returns `None` if no cover art was found.
+    pub fn artwork(&self) -> Option<Artwork> {
+        Artwork::find(&self.file_name)
+    }
+
+    /// Opens an audio file with [`sndfile`](sndfile)
+    ///
+    /// # Arguments
+    /// * `file` - A [`String`](String) containing the path to the audio file.
+    fn open_file(file: &str) -> Result<SndFile, String> {
         sndfile::OpenOptions::ReadOnly(ReadOptions::Auto)
             .from_path(file)
-            .unwrap()
+            .map_err(|e| format!("Failed to open file: {e:?}"))
     }
 }
 
@@ -89,25 +434,165 @@ impl AudioFormat {
     /// # Arguments
     /// * `file` - A [`String`](String) containing the path to the audio file.
     ///
-    /// ## Panics
-    /// If the file has an extension other than `.wav`, `.flac` or `.ogg` this will panic.
+    /// ## Errors
+    /// Returns `Err(())` if the file has no extension, or an extension other than `.wav`, `.flac`,
+    /// `.ogg`, `.opus`, `.wv` or `.ape`.
     ///
     /// ### Notes
     /// This function is __not__ case-sensitive, as the given file path is converted to
     /// lowercase, before it's compared.
     pub fn from_path(path: &str) -> Result<Self, ()> {
-        let ext = Path::new(path).extension().unwrap().to_string_lossy();
+        let Some(ext) = Path::new(path).extension() else {
+            return Err(());
+        };
 
+        Self::from_extension(&ext.to_string_lossy())
+    }
+
+    /// Gets the format of files with an extension (which is __not__ case-sensitive).
+    pub fn from_extension(ext: &str) -> Result<Self, ()> {
         match ext.to_lowercase().as_str() {
             "flac" => Ok(AudioFormat::FLAC),
             "wav" => Ok(AudioFormat::WAV),
             "ogg" => Ok(AudioFormat::OGG),
+            "opus" => Ok(AudioFormat::OPUS),
+            "wv" => Ok(AudioFormat::WAVPACK),
+            "ape" => Ok(AudioFormat::APE),
             _ => Err(()),
         }
     }
 
     pub fn is_lossless(&self) -> bool {
-        matches!(self, AudioFormat::FLAC | AudioFormat::WAV)
+        matches!(
+            self,
+            AudioFormat::FLAC | AudioFormat::WAV | AudioFormat::WAVPACK | AudioFormat::APE
+        )
+    }
+}
+
+impl ChannelLayout {
+    /// Gets the (default) channel layout for the given amount of channels.
+    pub fn from_channels(channels: u16) -> Self {
+        match channels {
+            1 => Self::Mono,
+            2 => Self::Stereo,
+            3 => Self::Surround30,
+            4 => Self::Quad,
+            5 => Self::Surround50,
+            6 => Self::Surround51,
+            7 => Self::Surround61,
+            8 => Self::Surround71,
+            n => Self::Other(n),
+        }
+    }
+
+    /// Returns the amount of channels.
+    pub fn channels(&self) -> u16 {
+        match self {
+            Self::Mono => 1,
+            Self::Stereo => 2,
+            Self::Surround30 => 3,
+            Self::Quad => 4,
+            Self::Surround50 => 5,
+            Self::Surround51 => 6,
+            Self::Surround61 => 7,
+            Self::Surround71 => 8,
+            Self::Other(n) => *n,
+        }
+    }
+
+    /// Returns the speaker position of each channel, in the order they appear in the
+    /// decoded audio.
+    ///
+    /// ### Notes
+    /// Ogg Vorbis (and Opus) uses a different channel order than WAV/FLAC.  
+    /// Returns `None` for [`ChannelLayout::Other`](ChannelLayout::Other).
+    pub fn speakers(&self, format: AudioFormat) -> Option<Vec<Speaker>> {
+        use Speaker::*;
+
+        let vorbis = matches!(format, AudioFormat::OGG | AudioFormat::OPUS);
+        Some(match self {
+            Self::Mono => vec![FrontCenter],
+            Self::Stereo => vec![FrontLeft, FrontRight],
+            Self::Surround30 if vorbis => vec![FrontLeft, FrontCenter, FrontRight],
+            Self::Surround30 => vec![FrontLeft, FrontRight, FrontCenter],
+            Self::Quad => vec![FrontLeft, FrontRight, BackLeft, BackRight],
+            Self::Surround50 if vorbis => {
+                vec![FrontLeft, FrontCenter, FrontRight, BackLeft, BackRight]
+            }
+            Self::Surround50 => vec![FrontLeft, FrontRight, FrontCenter, BackLeft, BackRight],
+            Self::Surround51 if vorbis => vec![
+                FrontLeft,
+                FrontCenter,
+                FrontRight,
+                BackLeft,
+                BackRight,
+                LowFrequency,
+            ],
+            Self::Surround51 => vec![
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackLeft,
+                BackRight,
+            ],
+            Self::Surround61 if vorbis => vec![
+                FrontLeft,
+                FrontCenter,
+                FrontRight,
+                SideLeft,
+                SideRight,
+                BackCenter,
+                LowFrequency,
+            ],
+            Self::Surround61 => vec![
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackCenter,
+                SideLeft,
+                SideRight,
+            ],
+            Self::Surround71 if vorbis => vec![
+                FrontLeft,
+                FrontCenter,
+                FrontRight,
+                SideLeft,
+                SideRight,
+                BackLeft,
+                BackRight,
+                LowFrequency,
+            ],
+            Self::Surround71 => vec![
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackLeft,
+                BackRight,
+                SideLeft,
+                SideRight,
+            ],
+            Self::Other(_) => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for ChannelLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mono => write!(f, "Mono"),
+            Self::Stereo => write!(f, "Stereo"),
+            Self::Surround30 => write!(f, "3.0"),
+            Self::Quad => write!(f, "Quad"),
+            Self::Surround50 => write!(f, "5.0"),
+            Self::Surround51 => write!(f, "5.1"),
+            Self::Surround61 => write!(f, "6.1"),
+            Self::Surround71 => write!(f, "7.1"),
+            Self::Other(n) => write!(f, "{n} channels"),
+        }
     }
 }
 
@@ -120,6 +605,9 @@ impl std::fmt::Display for AudioFormat {
                 Self::FLAC => "FLAC",
                 Self::OGG => "OGG",
                 Self::WAV => "WAV",
+                Self::OPUS => "Opus",
+                Self::WAVPACK => "WavPack",
+                Self::APE => "APE",
             }
         )
     }
@@ -128,8 +616,11 @@ impl std::fmt::Display for AudioFormat {
 impl From<SndFile> for AudioMeta {
     /// Gets the necessary metadata from an opened audio file ([`SndFile`](SndFile)).  
     /// It'll read: `Title` ([`TagType::Title`](TagType::Title)),
-    ///             `Album` ([`TagType::Album`](TagType::Album)) and
-    ///             `Artist` ([`TagType::Artist`](TagType::Artist))
+    ///             `Album` ([`TagType::Album`](TagType::Album)),
+    ///             `Artist` ([`TagType::Artist`](TagType::Artist)),
+    ///             `Track number` ([`TagType::Tracknumber`](TagType::Tracknumber)),
+    ///             `Genre` ([`TagType::Genre`](TagType::Genre)) and
+    ///             `Date` ([`TagType::Date`](TagType::Date))
     ///
     /// # Arguments
     /// * `sndfile` - An opened audio file ([`SndFile`](SndFile)).
@@ -150,6 +641,132 @@ impl From<SndFile> for AudioMeta {
             artist: value
                 .get_tag(TagType::Artist)
                 .unwrap_or("Unknown".to_owned()),
+            track: value
+                .get_tag(TagType::Tracknumber)
+                .and_then(|track| parse_track_number(&track)),
+            track_total: value
+                .get_tag(TagType::Tracknumber)
+                .and_then(|track| parse_track_total(&track)),
+            genre: value.get_tag(TagType::Genre),
+            year: value
+                .get_tag(TagType::Date)
+                .and_then(|date| parse_year(&date)),
+            album_artist: None,
+            compilation: false,
+            inferred: Vec::new(),
+        }
+    }
+}
+
+impl From<&OpusInfo> for AudioMeta {
+    /// Gets the necessary metadata from the user comments of an Ogg Opus file.
+    /// It'll read: `TITLE`, `ALBUM`, `ARTIST`, `TRACKNUMBER`, `TRACKTOTAL` (or `TOTALTRACKS`),
+    /// `GENRE`, `DATE`, `ALBUMARTIST` (or `ALBUM ARTIST`) and `COMPILATION`
+    ///
+    /// ### Notes
+    /// In case the read comment is not defined, `"Unknown"` is used as a placeholder.
+    fn from(value: &OpusInfo) -> Self {
+        Self {
+            title: value.comment("TITLE").unwrap_or("Unknown".to_owned()),
+            album: value.comment("ALBUM").unwrap_or("Unknown".to_owned()),
+            artist: value.comment("ARTIST").unwrap_or("Unknown".to_owned()),
+            track: value
+                .comment("TRACKNUMBER")
+                .and_then(|track| parse_track_number(&track)),
+            track_total: value
+                .comment("TRACKTOTAL")
+                .or_else(|| value.comment("TOTALTRACKS"))
+                .and_then(|total| total.trim().parse().ok())
+                .or_else(|| {
+                    value
+                        .comment("TRACKNUMBER")
+                        .and_then(|track| parse_track_total(&track))
+                }),
+            genre: value.comment("GENRE"),
+            year: value.comment("DATE").and_then(|date| parse_year(&date)),
+            album_artist: value
+                .comment("ALBUMARTIST")
+                .or_else(|| value.comment("ALBUM ARTIST")),
+            compilation: value
+                .comment("COMPILATION")
+                .is_some_and(|flag| parse_flag(&flag)),
+            inferred: Vec::new(),
+        }
+    }
+}
+
+impl From<&ApeTag> for AudioMeta {
+    /// Gets the necessary metadata from an APEv2 tag.
+    /// It'll read: `Title`, `Album`, `Artist`, `Track`, `Genre`, `Year`,
+    /// `Album Artist` (or `AlbumArtist`) and `Compilation`
+    ///
+    /// ### Notes
+    /// In case the read item is not defined, `"Unknown"` is used as a placeholder.
+    fn from(value: &ApeTag) -> Self {
+        Self {
+            title: value.get("Title").unwrap_or("Unknown".to_owned()),
+            album: value.get("Album").unwrap_or("Unknown".to_owned()),
+            artist: value.get("Artist").unwrap_or("Unknown".to_owned()),
+            track: value
+                .get("Track")
+                .and_then(|track| parse_track_number(&track)),
+            track_total: value
+                .get("Track")
+                .and_then(|track| parse_track_total(&track)),
+            genre: value.get("Genre"),
+            year: value.get("Year").and_then(|date| parse_year(&date)),
+            album_artist: value
+                .get("Album Artist")
+                .or_else(|| value.get("AlbumArtist")),
+            compilation: value
+                .get("Compilation")
+                .is_some_and(|flag| parse_flag(&flag)),
+            inferred: Vec::new(),
         }
     }
 }
+
+/// Parses a track number tag.  
+/// Tags in the `4/9` form (track/total) are supported as well.
+fn parse_track_number(tag: &str) -> Option<u32> {
+    tag.split('/').next()?.trim().parse().ok()
+}
+
+/// Parses the amount of tracks of a track number tag in the `4/9` form (track/total).
+fn parse_track_total(tag: &str) -> Option<u32> {
+    tag.split_once('/')?.1.trim().parse().ok()
+}
+
+/// Describes how the samples of a file are encoded, like `24-bit PCM`.
+/// Returns `None` for compressed formats (like Vorbis), which have no fixed sample encoding.
+fn describe_subtype(subtype: SubtypeFormat) -> Option<String> {
+    Some(
+        match subtype {
+            SubtypeFormat::PCM_S8 => "8-bit PCM",
+            SubtypeFormat::PCM_U8 => "8-bit PCM (unsigned)",
+            SubtypeFormat::PCM_16 => "16-bit PCM",
+            SubtypeFormat::PCM_24 => "24-bit PCM",
+            SubtypeFormat::PCM_32 => "32-bit PCM",
+            SubtypeFormat::FLOAT => "32-bit float",
+            SubtypeFormat::DOUBLE => "64-bit float",
+            SubtypeFormat::ULAW => "µ-law",
+            SubtypeFormat::ALAW => "A-law",
+            SubtypeFormat::IMA_ADPCM => "IMA ADPCM",
+            SubtypeFormat::MS_ADPCM => "Microsoft ADPCM",
+            SubtypeFormat::VORBIS => return None,
+            other => return Some(format!("{other:?}")),
+        }
+        .to_owned(),
+    )
+}
+
+/// Parses a flag tag, like `COMPILATION` (`1`, or `true`/`yes`).
+fn parse_flag(tag: &str) -> bool {
+    matches!(tag.trim().to_lowercase().as_str(), "1" | "true" | "yes")
+}
+
+/// Parses the year of a date tag.  
+/// Full dates (like `2001-05-03`) are supported as well.
+fn parse_year(tag: &str) -> Option<u32> {
+    tag.trim().get(..4)?.parse().ok()
+}
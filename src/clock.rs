@@ -0,0 +1,11 @@
+use std::mem;
+
+/// Returns the local time (as the C library's broken-down time), or `None` if it can't be read.
+pub fn local_time() -> Option<libc::tm> {
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut time: libc::tm = unsafe { mem::zeroed() };
+    match unsafe { libc::localtime_r(&now, &mut time) }.is_null() {
+        true => None,
+        false => Some(time),
+    }
+}
@@ -0,0 +1,157 @@
+use crate::artwork::{Artwork, ArtworkSource};
+use crate::audioinfo::{AudioFile, AudioFormat};
+use crate::comments;
+use crate::opus::OpusInfo;
+use ogg::PacketReader;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+
+/// Details about how an audio file was encoded, shown in the track details overlay.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CodecDetails {
+    /// Encoder which created the file (the vendor string of Vorbis comments)
+    pub encoder: Option<String>,
+    /// Encoder settings, if they were tagged (`ENCODERSETTINGS` or `ENCODER_OPTIONS`)
+    pub settings: Option<String>,
+    /// Sample encoding (e.g. `24-bit PCM`), as reported by `sndfile`
+    pub encoding: Option<String>,
+    /// Nominal bitrate of Ogg Vorbis files (in kbps), which the encoder aimed at
+    pub nominal_bitrate: Option<u32>,
+    /// Size of a FLAC file compared to the uncompressed audio (e.g. `0.58`)
+    pub compression_ratio: Option<f64>,
+    /// The cover art, embedded in the file or stored next to it
+    pub artwork: Option<Artwork>,
+}
+
+impl CodecDetails {
+    /// Reads the encoding details of an audio file.
+    /// Details which can't be read are left out.
+    ///
+    /// ### Notes
+    /// FLAC files don't store the compression level they were encoded with, so the
+    /// [compression ratio](Self::compression_ratio) is shown instead
+    /// (along with the encoder settings, if they were tagged).
+    pub fn read(afile: &AudioFile) -> Self {
+        let file = afile.file_name.as_str();
+        let mut details = Self {
+            encoding: afile.encoding.clone(),
+            artwork: afile.artwork(),
+            ..Self::default()
+        };
+
+        match afile.format {
+            AudioFormat::FLAC | AudioFormat::OGG => {
+                if let Ok(Some(comment)) = comments::read(file, afile.format) {
+                    details.encoder = Some(comment.vendor());
+                    details.settings = comment
+                        .get("ENCODERSETTINGS")
+                        .or_else(|| comment.get("ENCODER_OPTIONS"));
+                }
+            }
+            AudioFormat::OPUS => {
+                if let Ok(info) = OpusInfo::probe(file) {
+                    details.encoder = Some(info.vendor.clone());
+                    details.settings = info.comment("ENCODER_OPTIONS");
+                }
+            }
+            AudioFormat::WAV | AudioFormat::WAVPACK | AudioFormat::APE => (),
+        }
+
+        match afile.format {
+            AudioFormat::FLAC => details.compression_ratio = flac_compression_ratio(file),
+            AudioFormat::OGG => details.nominal_bitrate = vorbis_nominal_bitrate(file),
+            _ => (),
+        }
+
+        details
+    }
+
+    /// Returns the details as names and values, as shown in the overlay.
+    ///
+    /// # Arguments
+    /// * `afile` - The audio file, for the details which are already known
+    ///   (the [codec mode](AudioFile::codec_mode) and the average bitrate)
+    pub fn rows(&self, afile: &AudioFile) -> Vec<(&'static str, String)> {
+        let unknown = || "unknown".to_owned();
+        let mut rows = vec![
+            ("Format:", afile.format.to_string()),
+            ("Encoder:", self.encoder.clone().unwrap_or_else(unknown)),
+            ("Encoding:", self.encoding.clone().unwrap_or_else(unknown)),
+            (
+                "Bitrate:",
+                afile
+                    .bitrate()
+                    .map_or_else(unknown, |bitrate| format!("{bitrate} kbps average")),
+            ),
+        ];
+
+        if let Some(bitrate) = self.nominal_bitrate {
+            rows.push(("Nominal:", format!("{bitrate} kbps")));
+        }
+        if let Some(ratio) = self.compression_ratio {
+            rows.push(("Compression:", format!("{:.0}% of PCM", ratio * 100.0)));
+        }
+        if let Some(mode) = &afile.codec_mode {
+            rows.push(("Mode:", mode.clone()));
+        }
+        if let Some(settings) = &self.settings {
+            rows.push(("Settings:", settings.clone()));
+        }
+        rows.push((
+            "Cover art:",
+            self.artwork
+                .as_ref()
+                .map_or("none".to_owned(), describe_artwork),
+        ));
+
+        rows
+    }
+}
+
+/// Describes cover art by its type, size and where it was found (e.g. `image/jpeg, 84 KiB, cover.jpg`).
+fn describe_artwork(artwork: &Artwork) -> String {
+    let source = match &artwork.source {
+        ArtworkSource::Embedded => "embedded".to_owned(),
+        ArtworkSource::File(path) => path.file_name().map_or(path.display().to_string(), |name| {
+            name.to_string_lossy().into_owned()
+        }),
+    };
+    let size = artwork.data.len().div_ceil(1024);
+
+    format!("{}, {size} KiB, {source}", artwork.mime)
+}
+
+/// Returns the size of a FLAC file compared to the uncompressed audio,
+/// using the `STREAMINFO` block (which is always the first metadata block).
+fn flac_compression_ratio(file: &str) -> Option<f64> {
+    let mut input = BufReader::new(File::open(file).ok()?);
+    let mut header = [0u8; 8];
+    input.read_exact(&mut header).ok()?;
+    if &header[..4] != b"fLaC" || header[4] & 0x7F != 0 {
+        return None;
+    }
+
+    let mut info = [0u8; 34];
+    input.read_exact(&mut info).ok()?;
+    let channels = ((info[12] >> 1) & 0x07) as u64 + 1;
+    let bits = (((info[12] & 0x01) << 4) | (info[13] >> 4)) as u64 + 1;
+    let frames = ((info[13] & 0x0F) as u64) << 32
+        | u32::from_be_bytes(info[14..18].try_into().unwrap()) as u64;
+
+    let uncompressed = frames * channels * bits / 8;
+    let size = fs::metadata(file).ok()?.len();
+    (uncompressed > 0).then(|| size as f64 / uncompressed as f64)
+}
+
+/// Returns the nominal bitrate (in kbps) from the identification header of an Ogg Vorbis file.
+/// Returns `None` if the encoder didn't set it.
+fn vorbis_nominal_bitrate(file: &str) -> Option<u32> {
+    let mut reader = PacketReader::new(BufReader::new(File::open(file).ok()?));
+    let packet = reader.read_packet().ok()??;
+    if !packet.data.starts_with(b"\x01vorbis") {
+        return None;
+    }
+
+    let nominal = i32::from_le_bytes(packet.data.get(20..24)?.try_into().unwrap());
+    (nominal > 0).then(|| (nominal as f64 / 1000.0).round() as u32)
+}
@@ -0,0 +1,193 @@
+use crate::alarm;
+use crate::display::DisplayEvent;
+use crate::queue::RepeatMode;
+use crate::sleep::MAX_SLEEP_MINUTES;
+
+/// Names of the commands, shown in the command prompt.
+pub const COMMANDS: [&str; 17] = [
+    "seek",
+    "volume",
+    "repeat",
+    "save-playlist",
+    "clear",
+    "shuffle",
+    "undo",
+    "fix-tags",
+    "profile",
+    "sleep",
+    "alarm",
+    "play",
+    "pause",
+    "mute",
+    "next",
+    "previous",
+    "quit",
+];
+
+/// A textual command, typed into the command prompt (like `seek 1:23`).
+///
+/// Commands are an alternative to remembering the keys, and some (like `repeat`) have no key.
+#[derive(Clone, PartialEq)]
+pub enum Command {
+    /// Seek to a position (a time or a percentage of the track, see [`parse_position()`](crate::seek::parse_position))
+    Seek(String),
+    /// Set the volume (from 0 to 100%)
+    Volume(u8),
+    /// Set what to play after the current track finishes
+    Repeat(RepeatMode),
+    /// Save the queue as an M3U playlist, to the given path
+    SavePlaylist(String),
+    /// Remove every entry of the queue except the current one
+    Clear,
+    /// Shuffle the entries of the queue after the current one
+    Shuffle,
+    /// Undo the last destructive change of the queue
+    Undo,
+    /// Look up the missing (`"Unknown"`) tags of the queue, and write them after a preview
+    /// (see [`fix_in_background()`](crate::tagfix::fix_in_background))
+    FixTags,
+    /// Switch to the [profile](crate::config::Profile) with the given name
+    Profile(String),
+    /// Arm the [sleep timer](crate::sleep::SleepTimer) for the given minutes, or turn it off (`None`)
+    Sleep(Option<u32>),
+    /// Set the [alarm](crate::alarm::Alarm) to a time (hour and minute), or turn it off (`None`)
+    Alarm(Option<(u8, u8)>),
+    /// Do the same as a key
+    Event(DisplayEvent),
+}
+
+impl Command {
+    /// Parses a command.
+    /// Command names are __not__ case-sensitive, arguments are separated by whitespace.
+    ///
+    /// ## Errors
+    /// Fails if the command is unknown, or if its arguments are missing or invalid.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim().trim_start_matches(':');
+        let (name, argument) = match input.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (input, ""),
+        };
+        let name = name.to_ascii_lowercase();
+
+        let usage = |arguments: &str| format!("Usage: {name} {arguments}");
+        let command = match name.as_str() {
+            "seek" if !argument.is_empty() => Self::Seek(argument.to_owned()),
+            "seek" => return Err(usage("mm:ss|PERCENT%")),
+            "volume" => match argument.trim_end_matches('%').parse() {
+                Ok(volume @ 0..=100) => Self::Volume(volume),
+                _ => return Err(usage("0-100")),
+            },
+            "repeat" => match RepeatMode::from_name(argument) {
+                Some(mode) => Self::Repeat(mode),
+                None => return Err(usage("off|one|all")),
+            },
+            "save-playlist" if !argument.is_empty() => Self::SavePlaylist(argument.to_owned()),
+            "save-playlist" => return Err(usage("NAME")),
+            "clear" => Self::Clear,
+            "shuffle" => Self::Shuffle,
+            "undo" => Self::Undo,
+            "fix-tags" => Self::FixTags,
+            "profile" if !argument.is_empty() => Self::Profile(argument.to_owned()),
+            "profile" => return Err(usage("NAME")),
+            "sleep" if argument.eq_ignore_ascii_case("off") => Self::Sleep(None),
+            "sleep" => match argument.parse() {
+                Ok(minutes @ 1..=MAX_SLEEP_MINUTES) => Self::Sleep(Some(minutes)),
+                _ => return Err(usage(&format!("1-{MAX_SLEEP_MINUTES}|off"))),
+            },
+            "alarm" if argument.eq_ignore_ascii_case("off") => Self::Alarm(None),
+            "alarm" if !argument.is_empty() => Self::Alarm(Some(alarm::parse_time(argument)?)),
+            "alarm" => return Err(usage("HH:MM|off")),
+            "play" => Self::Event(DisplayEvent::MakePlay),
+            "pause" => Self::Event(DisplayEvent::MakePause),
+            "mute" => Self::Event(DisplayEvent::ToggleMute),
+            "next" => Self::Event(DisplayEvent::JumpNext),
+            "previous" | "prev" => Self::Event(DisplayEvent::JumpBack),
+            "quit" | "q" => Self::Event(DisplayEvent::Quit),
+            "" => return Err("No command given".to_owned()),
+            _ => return Err(format!("Unknown command: {name}")),
+        };
+
+        let takes_argument = matches!(
+            command,
+            Self::Seek(_)
+                | Self::Volume(_)
+                | Self::Repeat(_)
+                | Self::SavePlaylist(_)
+                | Self::Profile(_)
+                | Self::Sleep(_)
+                | Self::Alarm(_)
+        );
+        if !takes_argument && !argument.is_empty() {
+            return Err(format!("{name} doesn't take any arguments"));
+        }
+        Ok(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a command, `Command` can't be printed so it's compared in `assert!()`.
+    fn parses(input: &str, command: Command) -> bool {
+        Command::parse(input) == Ok(command)
+    }
+
+    fn error(input: &str) -> String {
+        Command::parse(input).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn parse_reads_commands_and_arguments() {
+        assert!(parses("seek 1:23", Command::Seek("1:23".to_owned())));
+        assert!(parses(":Volume 40%", Command::Volume(40)));
+        assert!(parses("repeat ALL", Command::Repeat(RepeatMode::All)));
+        assert!(parses(
+            "save-playlist  My Mix.m3u ",
+            Command::SavePlaylist("My Mix.m3u".to_owned())
+        ));
+        assert!(parses(
+            "profile night",
+            Command::Profile("night".to_owned())
+        ));
+        assert!(parses("sleep 30", Command::Sleep(Some(30))));
+        assert!(parses("sleep off", Command::Sleep(None)));
+        assert!(parses("alarm 07:30", Command::Alarm(Some((7, 30)))));
+        assert!(parses("alarm OFF", Command::Alarm(None)));
+    }
+
+    #[test]
+    fn parse_reads_commands_without_arguments() {
+        assert!(parses("clear", Command::Clear));
+        assert!(parses("shuffle", Command::Shuffle));
+        assert!(parses("undo", Command::Undo));
+        assert!(parses("fix-tags", Command::FixTags));
+        assert!(parses("FIX-TAGS", Command::FixTags));
+        assert!(parses("prev", Command::Event(DisplayEvent::JumpBack)));
+        assert!(parses("q", Command::Event(DisplayEvent::Quit)));
+    }
+
+    #[test]
+    fn parse_knows_every_listed_command() {
+        for name in COMMANDS {
+            assert!(
+                !error(name).starts_with("Unknown command"),
+                "{name} isn't parsed"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_reports_errors() {
+        assert_eq!(error(""), "No command given");
+        assert_eq!(error("rewind"), "Unknown command: rewind");
+        assert_eq!(error("seek"), "Usage: seek mm:ss|PERCENT%");
+        assert_eq!(error("volume 101"), "Usage: volume 0-100");
+        assert_eq!(error("repeat twice"), "Usage: repeat off|one|all");
+        assert_eq!(error("sleep 0"), "Usage: sleep 1-720|off");
+        assert_eq!(error("alarm 7:5"), "Invalid time: 7:5 (expected HH:MM)");
+        assert_eq!(error("clear all"), "clear doesn't take any arguments");
+        assert_eq!(error("fix-tags all"), "fix-tags doesn't take any arguments");
+    }
+}
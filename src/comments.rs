@@ -0,0 +1,172 @@
+use crate::audioinfo::AudioFormat;
+use ogg::PacketReader;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+
+/// Vendor string used when a file doesn't have a Vorbis comment block yet.
+const VENDOR: &str = concat!("rustyplay ", env!("CARGO_PKG_VERSION"));
+/// FLAC metadata block type: Vorbis comment
+pub const FLAC_COMMENT_BLOCK: u8 = 4;
+
+/// The Vorbis comment block used by FLAC, Ogg Vorbis and Opus files.
+pub struct VorbisComment {
+    /// Vendor string
+    vendor: Vec<u8>,
+    /// Comments in `KEY=value` form
+    comments: Vec<Vec<u8>>,
+    /// Data following the comments (the framing bit in Ogg Vorbis, or padding in Opus)
+    rest: Vec<u8>,
+}
+
+impl VorbisComment {
+    /// Creates an empty comment block.
+    pub fn new() -> Self {
+        Self {
+            vendor: VENDOR.as_bytes().to_vec(),
+            comments: Vec::new(),
+            rest: Vec::new(),
+        }
+    }
+
+    /// Parses a comment block (without the Ogg packet signature).
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let malformed = || "Malformed Vorbis comment block".to_owned();
+        let mut pos = 0;
+        let read_bytes = |pos: &mut usize| -> Option<Vec<u8>> {
+            let len = u32::from_le_bytes(data.get(*pos..*pos + 4)?.try_into().unwrap()) as usize;
+            let bytes = data.get(*pos + 4..*pos + 4 + len)?;
+            *pos += 4 + len;
+            Some(bytes.to_vec())
+        };
+
+        let vendor = read_bytes(&mut pos).ok_or_else(malformed)?;
+        let count = data.get(pos..pos + 4).ok_or_else(malformed)?;
+        let count = u32::from_le_bytes(count.try_into().unwrap());
+        pos += 4;
+
+        let comments = (0..count)
+            .map(|_| read_bytes(&mut pos))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(malformed)?;
+
+        Ok(Self {
+            vendor,
+            comments,
+            rest: data[pos..].to_vec(),
+        })
+    }
+
+    /// Sets the value of a field, replacing any previous values.
+    ///
+    /// ### Notes
+    /// Field names are __not__ case-sensitive.
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.comments.retain(|comment| {
+            comment.len() <= key.len()
+                || comment[key.len()] != b'='
+                || !comment[..key.len()].eq_ignore_ascii_case(key.as_bytes())
+        });
+        self.comments.push(format!("{key}={value}").into_bytes());
+    }
+
+    /// Returns the vendor string, which names the encoder (like `reference libFLAC 1.4.3`).
+    pub fn vendor(&self) -> String {
+        String::from_utf8_lossy(&self.vendor).into_owned()
+    }
+
+    /// Returns the first value of a field.
+    ///
+    /// ### Notes
+    /// Field names are __not__ case-sensitive.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.comments.iter().find_map(|comment| {
+            let comment = String::from_utf8_lossy(comment);
+            let (name, value) = comment.split_once('=')?;
+            name.eq_ignore_ascii_case(key).then(|| value.to_owned())
+        })
+    }
+
+    /// Serializes the comment block (without the Ogg packet signature).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.extend((self.vendor.len() as u32).to_le_bytes());
+        result.extend(&self.vendor);
+        result.extend((self.comments.len() as u32).to_le_bytes());
+        for comment in &self.comments {
+            result.extend((comment.len() as u32).to_le_bytes());
+            result.extend(comment);
+        }
+        result.extend(&self.rest);
+        result
+    }
+}
+
+/// Reads the Vorbis comment block of a FLAC or Ogg Vorbis file.
+/// This is used for tags which `sndfile` doesn't expose, like the album artist.
+///
+/// # Arguments
+/// * `file` - A [`String`](String) containing the path to the audio file.
+/// * `format` - Format of the audio file
+///
+/// ### Notes
+/// Returns `Ok(None)` if the file has no comment block, or if the format doesn't use
+/// Vorbis comments (Opus files are read by [`OpusInfo`](crate::opus::OpusInfo)).
+pub fn read(file: &str, format: AudioFormat) -> Result<Option<VorbisComment>, String> {
+    let mut input =
+        BufReader::new(File::open(file).map_err(|e| format!("Unable to open file: {e}"))?);
+    let result = match format {
+        AudioFormat::FLAC => read_flac(&mut input),
+        AudioFormat::OGG => read_ogg(input),
+        _ => return Ok(None),
+    };
+
+    match result.map_err(|e| format!("Unable to read file: {e}"))? {
+        Some(data) => VorbisComment::parse(&data).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Reads the data of the Vorbis comment block from the metadata blocks of a FLAC file.
+fn read_flac<R: Read>(input: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut magic = [0u8; 4];
+    input.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        return Err(io::Error::other("Missing FLAC header"));
+    }
+
+    loop {
+        let mut header = [0u8; 4];
+        input.read_exact(&mut header)?;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let mut data = vec![0u8; len];
+        input.read_exact(&mut data)?;
+
+        if header[0] & 0x7F == FLAC_COMMENT_BLOCK {
+            return Ok(Some(data));
+        }
+        if header[0] & 0x80 != 0 {
+            return Ok(None);
+        }
+    }
+}
+
+/// Reads the data of the comment header (the second packet of the first stream)
+/// of an Ogg Vorbis file.
+fn read_ogg<R: Read + io::Seek>(input: R) -> io::Result<Option<Vec<u8>>> {
+    const MAGIC: &[u8] = b"\x03vorbis";
+    let mut reader = PacketReader::new(input);
+    let mut first_serial = None;
+    let mut index = 0;
+
+    while let Some(packet) = reader.read_packet().map_err(io::Error::other)? {
+        if *first_serial.get_or_insert(packet.stream_serial()) != packet.stream_serial() {
+            continue;
+        }
+        if index == 1 {
+            return Ok(packet.data.strip_prefix(MAGIC).map(<[u8]>::to_vec));
+        }
+        index += 1;
+    }
+
+    Ok(None)
+}
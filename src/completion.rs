@@ -0,0 +1,61 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Expands a leading `~` to the home directory.
+pub fn expand_home(input: &str) -> PathBuf {
+    match (input.strip_prefix('~'), env::var_os("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(format!("{}{rest}", home.to_string_lossy()))
+        }
+        _ => PathBuf::from(input),
+    }
+}
+
+/// Completes a path typed into a prompt, like shells do when `Tab` is pressed.
+///
+/// The last component is completed as far as all the matching entries of its directory agree.
+/// If only one entry matches and it's a directory, a `/` is appended so that its entries can be
+/// completed next. Hidden entries only match if the component starts with a `.`.
+///
+/// Returns the completed input (the input itself if nothing matches).
+pub fn complete_path(input: &str) -> String {
+    let (dir, prefix) = match input.rfind('/') {
+        Some(index) => (&input[..=index], &input[index + 1..]),
+        None => ("", input),
+    };
+    let search_dir = match dir.is_empty() {
+        true => PathBuf::from("."),
+        false => expand_home(dir),
+    };
+
+    let Ok(entries) = fs::read_dir(&search_dir) else {
+        return input.to_owned();
+    };
+    let matches: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.starts_with(prefix))
+        .filter(|name| prefix.starts_with('.') || !name.starts_with('.'))
+        .collect();
+
+    match matches.as_slice() {
+        [] => input.to_owned(),
+        [name] => match search_dir.join(name).is_dir() {
+            true => format!("{dir}{name}/"),
+            false => format!("{dir}{name}"),
+        },
+        [first, rest @ ..] => {
+            let common = rest.iter().fold(first.as_str(), |common, name| {
+                let len = common
+                    .char_indices()
+                    .zip(name.chars())
+                    .take_while(|((_, a), b)| a == b)
+                    .last()
+                    .map_or(0, |((index, a), _)| index + a.len_utf8());
+                &common[..len]
+            });
+            format!("{dir}{common}")
+        }
+    }
+}
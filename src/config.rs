@@ -0,0 +1,812 @@
+use crate::alarm;
+use crate::audioinfo::AudioFormat;
+use crate::display::{self, StatusSegment};
+use crate::dsp::{self, Effects, Levels, Resampler, Silence, ToneControls};
+use crate::filter::Filter;
+use crate::keymap::Keymap;
+use crate::mixer::MixerMode;
+use crate::output::Backend;
+use crate::paths;
+use crate::player::{DecodeOptions, DecoderOverride, DownmixMode};
+use crate::prefetch;
+use crate::schedule::{ScheduleEntry, ScheduledAction};
+use crate::snapcast::Snapcast;
+use crate::sort::SortMode;
+use crate::tap::Taps;
+use crate::theme::Theme;
+use crate::translate::Translator;
+use crate::visualizer::{
+    self, Color, Scope, Slot, Spectrum, SpectrumSettings, Visualizer, VisualizerKind, VuMeter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::ops::Bound;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Name of the configuration file.
+const CONFIG_FILE_NAME: &str = "config.json";
+/// Prefix of the environment variables which override options (e.g. `RUSTYPLAY_THEME`).
+const ENV_PREFIX: &str = "RUSTYPLAY_";
+
+/// Represents the user's configuration.
+///
+/// The configuration is loaded from `config.json` in the [configuration directory](paths::config_dir)
+/// (usually `~/.config/rustyplay`). Every option is optional, missing
+/// options use their default values.
+/// Options can also be set by environment variables and flags, see [`Config::resolve()`](Config::resolve).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Whether to look up missing tags on MusicBrainz.
+    /// *Disabled by default, as it sends the track's tags to a third party.*
+    pub musicbrainz_lookup: bool,
+    /// AcoustID application API key, used to identify untagged files by their fingerprint.
+    /// *Fingerprinting is disabled if no key is set.*
+    pub acoustid_key: Option<String>,
+    /// Code of the language to translate lyrics into (e.g. `en`), when they're translated.
+    pub translation_language: Option<String>,
+    /// URL of a LibreTranslate compatible server, or the path to an offline dictionary
+    /// (a JSON object of lines or words and their translations), used to translate lyrics.
+    pub translation_endpoint: Option<String>,
+    /// API key of the translation server, if it requires one.
+    pub translation_key: Option<String>,
+    /// Directories scanned into the music library (recursively).
+    pub library_roots: Vec<PathBuf>,
+    /// Whether untagged files take their album and artist from their directories
+    /// (`Artist/Album/Track`), in the library and the track info.
+    pub folder_metadata: bool,
+    /// How to order directories and library views.
+    pub sort: SortMode,
+    /// Smart playlists: names and the [filters](crate::filter::Filter) selecting their tracks.
+    pub playlists: BTreeMap<String, String>,
+    /// Times to pause or start playback at (e.g. pausing at 23:00 on weekdays),
+    /// see [`ScheduleEntry`](ScheduleEntry).
+    pub schedule: Vec<ScheduleEntry>,
+    /// Whether to play YouTube and SoundCloud URLs using `yt-dlp`.
+    /// *Disabled by default, as it runs an external program.*
+    pub ytdlp: bool,
+    /// Name of the DLNA renderer (e.g. a smart TV) to play on, instead of the audio device.
+    /// *See `--list-renderers` for the names of the renderers on the network.*
+    pub dlna_renderer: Option<String>,
+    /// Snapcast source to feed the audio into, instead of the audio device
+    /// (e.g. `/tmp/snapfifo` or `tcp://127.0.0.1:4953`).
+    pub snapcast: Option<String>,
+    /// Audio backend to use (e.g. `ALSA`), instead of the one picked by the system.
+    /// *See `--list-backends` for the available backends and devices.*
+    pub audio_backend: Option<String>,
+    /// Output device of the audio backend to use, instead of its default device.
+    pub audio_device: Option<String>,
+    /// Latency of the output in milliseconds (e.g. of Bluetooth headphones), so lyrics and
+    /// the progress match what's heard. *If not set, the known latency of the output is used,
+    /// or the latency audio devices report while they play.*
+    pub output_latency_ms: Option<u64>,
+    /// Size of the buffer of the audio device in milliseconds, larger buffers are more resistant
+    /// to dropouts (e.g. of Bluetooth or USB devices) but add latency.
+    /// *If not set, the audio backend chooses it.*
+    pub output_buffer_ms: Option<u64>,
+    /// Which volume the volume keys control: the player's own gain (`software`), the system's
+    /// mixer (`system`), or both of them (`both`), see [`MixerMode`](MixerMode).
+    pub mixer: MixerMode,
+    /// Gain in dB applied to every track, with a limiter so boosted audio can't clip.
+    pub preamp_db: f32,
+    /// Level of the bass in dB (from -12 to 12), changed with the `U` and `J` keys.
+    pub bass_db: i32,
+    /// Level of the treble in dB (from -12 to 12), changed with the `I` and `K` keys.
+    pub treble_db: i32,
+    /// Whether to skip silence at the start and the end of tracks.
+    pub skip_silence: bool,
+    /// Audio below this level (in dBFS) is considered silent. *-60 dBFS by default.*
+    pub silence_threshold_db: Option<f32>,
+    /// Silence shorter than this (in milliseconds) isn't skipped. *1000 ms by default.*
+    pub silence_min_ms: Option<u64>,
+    /// Whether to shorten long pauses in the middle of tracks (e.g. in podcasts),
+    /// toggled with the `P` key.
+    pub skip_pauses: bool,
+    /// Whether night mode compresses the audio, so quiet parts stay audible at a low volume,
+    /// toggled with `Ctrl+N`.
+    pub night_mode: bool,
+    /// How much audio (in milliseconds) is decoded ahead of playback, `0` decodes it while
+    /// it's played. *500 ms by default.*
+    pub decode_buffer_ms: Option<u64>,
+    /// Decoders to use instead of the usual ones, by file extension (e.g. `flac`),
+    /// for files which the usual decoder mishandles.
+    pub decoders: BTreeMap<String, DecoderOverride>,
+    /// When audio with more than two channels is mixed into stereo, switched with `Ctrl+X`.
+    /// *Only when the output has fewer channels by default.*
+    pub downmix: DownmixMode,
+    /// How the audio is resampled when the output plays at another sample rate.
+    pub resampler: Resampler,
+    /// Whether to add dither when the output has a lower bit depth (e.g. 16-bit).
+    pub dither: bool,
+    /// Named pipe to write the played audio into, for external visualizers (like cava).
+    pub visualizer_fifo: Option<PathBuf>,
+    /// Whether to show a visualizer below the lyrics, switched with the `V` key.
+    pub visualizer: bool,
+    /// Which visualizer is shown.
+    pub visualizer_kind: VisualizerKind,
+    /// How fast the bars of the visualizer fall, higher values make them fall faster.
+    /// *1.0 by default.*
+    pub visualizer_gravity: Option<f32>,
+    /// How much the bars of the visualizer are smoothed (from 0.0 to 1.0), higher values
+    /// make them rise slower. *0.7 by default.*
+    pub visualizer_smoothing: Option<f32>,
+    /// Whether the scope draws stereo audio as a lissajous figure, instead of the waveform.
+    pub visualizer_lissajous: bool,
+    /// Amount of samples the spectrum analyzes at once (a power of 2, from 256 to 16384).
+    /// *2048 by default.*
+    pub visualizer_fft_size: Option<usize>,
+    /// Amount of bars of the spectrum. *By default, the width is filled with bars.*
+    pub visualizer_bars: Option<usize>,
+    /// Colors of the visualizer, from the quietest to the loudest parts of the audio.
+    /// *Green, yellow and red by default.*
+    pub visualizer_colors: Option<[Color; 3]>,
+    /// How often the visualizer is updated per second (from 1 to 100). *60 by default.*
+    pub visualizer_frame_rate: Option<u32>,
+    /// Whether to enable mouse support, to seek by clicking or dragging the progress bar.
+    pub mouse: bool,
+    /// Whether to seek while the progress bar is dragged, instead of when it's released.
+    pub scrub_live: bool,
+    /// Whether ratings are also written to the `RATING` tag of the files (from 1 to 5).
+    pub write_ratings: bool,
+    /// Segments of the status bar, in the order they're shown. *All of them by default.*
+    pub status_bar: Option<Vec<StatusSegment>>,
+    /// Whether the current time and how long was listened in this session are shown in the header.
+    pub header_clock: bool,
+    /// Text between the artists of a track in the track info (e.g. `" & "`),
+    /// see [`Artists`](crate::artists::Artists). *`", "` by default.*
+    pub artist_separator: Option<String>,
+    /// How often the UI is updated and checks for keys per second (from 1 to 500). *100 by default.*
+    pub refresh_rate: Option<u32>,
+    /// How often the progress bar and the playback time are updated per second (from 1 to 500).
+    /// *As often as the UI by default.*
+    pub progress_rate: Option<u32>,
+    /// Preset of the colors and attributes of the TUI.
+    pub theme: Theme,
+    /// Colors of genres (e.g. `"jazz": "blue"`), which color the genre in the track info and
+    /// the tracks in the queue and the search results, see [`Display::set_genre_colors()`](display::Display::set_genre_colors).
+    pub genre_colors: BTreeMap<String, Color>,
+    /// Keys of actions, instead of their default keys (e.g. `"play": "p"`), see [`Keymap`](Keymap).
+    pub keys: BTreeMap<String, String>,
+    /// Named bundles of settings (e.g. `headphones` or `speakers`), see [`Profile`](Profile).
+    pub profiles: BTreeMap<String, Profile>,
+    /// Name of the profile to use, updated when another profile is switched to.
+    pub profile: Option<String>,
+    /// Where the options come from, see [`Config::resolve()`](Config::resolve).
+    /// Options which are not listed have their default values.
+    #[serde(skip)]
+    origins: BTreeMap<String, Origin>,
+    /// Options set by the profile, an environment variable or a flag, with their value in the
+    /// file (if the file sets them) and the value they were overridden with.
+    #[serde(skip)]
+    overridden: BTreeMap<String, (Option<Value>, Value)>,
+    /// Options set on the command line, kept for resolving the configuration again.
+    #[serde(skip)]
+    flags: Vec<(String, Value)>,
+}
+
+/// Where the value of an option comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// The option isn't set anywhere
+    Default,
+    /// The configuration file
+    File,
+    /// A profile, with its name
+    Profile(String),
+    /// An environment variable, with its name
+    Environment(String),
+    /// A command line flag
+    Flag,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::File => write!(f, "configuration file"),
+            Self::Profile(name) => write!(f, "profile {name}"),
+            Self::Environment(name) => write!(f, "{name}"),
+            Self::Flag => write!(f, "command line"),
+        }
+    }
+}
+
+/// A named bundle of settings, which override the ones of the configuration while it's used.
+/// Settings which are not set keep their current values.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// Audio backend to use, see [`Config::audio_backend`](Config::audio_backend)
+    pub audio_backend: Option<String>,
+    /// Output device of the audio backend to use
+    pub audio_device: Option<String>,
+    /// Gain in dB applied to every track
+    pub preamp_db: Option<f32>,
+    /// Level of the bass in dB (from -12 to 12)
+    pub bass_db: Option<i32>,
+    /// Level of the treble in dB (from -12 to 12)
+    pub treble_db: Option<i32>,
+    /// Playback volume (from 0 to 100%)
+    pub volume: Option<u8>,
+    /// Preset of the colors and attributes of the TUI
+    pub theme: Option<Theme>,
+}
+
+impl Config {
+    /// Loads the options set in the configuration file.
+    /// If the file does not exist, no options are set.
+    ///
+    /// ## Errors
+    /// Returns an error if the file exists but can't be read or isn't a JSON object.
+    pub fn load() -> Result<Map<String, Value>, String> {
+        let Some(path) = Self::path() else {
+            return Ok(Map::new());
+        };
+
+        let value = match File::open(&path) {
+            Ok(file) => serde_json::from_reader(file)
+                .map_err(|e| format!("Invalid configuration file {}: {e}", path.display()))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Map::new()),
+            Err(e) => return Err(format!("Unable to open {}: {e}", path.display())),
+        };
+
+        match value {
+            Value::Object(options) => Ok(options),
+            _ => Err(format!(
+                "Invalid configuration file {}: expected an object",
+                path.display()
+            )),
+        }
+    }
+
+    /// Resolves the configuration from its layers, each overriding the ones before it:
+    /// the default values, the configuration file, the profile in use,
+    /// `RUSTYPLAY_*` environment variables (e.g. `RUSTYPLAY_THEME=no_color`), and the `flags`.
+    ///
+    /// # Arguments
+    /// * `flags` - Options set on the command line, with their values
+    ///
+    /// ## Errors
+    /// Returns an error if the file exists but can't be read, if an option is unknown,
+    /// or if a value is invalid.
+    pub fn resolve(flags: Vec<(String, Value)>) -> Result<Self, String> {
+        let file = Self::load()?;
+
+        let mut overrides: Vec<(Origin, String, Value)> = Vec::new();
+        let mut env_vars: Vec<(String, String)> = env::vars()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .collect();
+        env_vars.sort();
+        for (name, value) in env_vars {
+            let option = name[ENV_PREFIX.len()..].to_lowercase();
+            /* Values which aren't JSON are taken as strings, so they don't need to be quoted */
+            let value = serde_json::from_str(&value).unwrap_or(Value::String(value));
+            overrides.push((Origin::Environment(name), option, value));
+        }
+        for (option, value) in &flags {
+            overrides.push((Origin::Flag, option.clone(), value.clone()));
+        }
+
+        let known = Self::known_options();
+        for (origin, option, value) in &overrides {
+            if !known.contains_key(option) {
+                return Err(format!("Unknown option {option} (from {origin})"));
+            }
+            let single = Value::Object(Map::from_iter([(option.clone(), value.clone())]));
+            serde_json::from_value::<Self>(single)
+                .map_err(|e| format!("Invalid value of {option} (from {origin}): {e}"))?;
+        }
+
+        /* The profile can be chosen by any layer, but its settings are below the overrides */
+        let mut merged = file.clone();
+        for (_, option, value) in &overrides {
+            merged.insert(option.clone(), value.clone());
+        }
+        let chosen: Self = serde_json::from_value(Value::Object(merged))
+            .map_err(|e| format!("Invalid configuration: {e}"))?;
+        let profile = chosen
+            .profile
+            .as_ref()
+            .and_then(|name| Some((name, chosen.profiles.get(name)?)));
+        if let Some((name, profile)) = profile {
+            let Ok(Value::Object(settings)) = serde_json::to_value(profile) else {
+                return Err(format!("Invalid profile: {name}"));
+            };
+            let settings = settings
+                .into_iter()
+                .filter(|(option, value)| known.contains_key(option) && !value.is_null())
+                .map(|(option, value)| (Origin::Profile(name.clone()), option, value));
+            overrides.splice(0..0, settings);
+        }
+
+        let mut options = file.clone();
+        let mut origins: BTreeMap<String, Origin> = file
+            .keys()
+            .map(|option| (option.clone(), Origin::File))
+            .collect();
+        for (origin, option, value) in overrides {
+            options.insert(option.clone(), value);
+            origins.insert(option, origin);
+        }
+
+        let mut config: Self = serde_json::from_value(Value::Object(options.clone()))
+            .map_err(|e| format!("Invalid configuration: {e}"))?;
+        config
+            .validate()
+            .map_err(|e| format!("Invalid configuration: {e}"))?;
+
+        config.overridden = origins
+            .iter()
+            .filter(|(_, origin)| **origin != Origin::File)
+            .map(|(option, _)| {
+                (
+                    option.clone(),
+                    (file.get(option).cloned(), options[option].clone()),
+                )
+            })
+            .collect();
+        config.origins = origins;
+        config.flags = flags;
+        Ok(config)
+    }
+
+    /// Resolves the configuration again (after the file changed), with the same flags.
+    ///
+    /// ## Errors
+    /// See [`Config::resolve()`](Config::resolve).
+    pub fn reload(&self) -> Result<Self, String> {
+        Self::resolve(self.flags.clone())
+    }
+
+    /// Returns where the value of an option comes from.
+    pub fn origin(&self, option: &str) -> Origin {
+        self.origins.get(option).cloned().unwrap_or(Origin::Default)
+    }
+
+    /// Checks the values of the options, which can't be checked while parsing the file.
+    fn validate(&self) -> Result<(), String> {
+        if let Some(size) = self.visualizer_fft_size {
+            if !size.is_power_of_two() || !visualizer::FFT_SIZES.contains(&size) {
+                return Err(format!(
+                    "visualizer_fft_size must be a power of 2 from {} to {}",
+                    visualizer::FFT_SIZES.start(),
+                    visualizer::FFT_SIZES.end()
+                ));
+            }
+        }
+        if self.visualizer_bars == Some(0) {
+            return Err("visualizer_bars must be at least 1".to_owned());
+        }
+        if self
+            .visualizer_gravity
+            .is_some_and(|gravity| gravity < 0.0 || !gravity.is_finite())
+        {
+            return Err("visualizer_gravity must be 0.0 or more".to_owned());
+        }
+        if self
+            .visualizer_smoothing
+            .is_some_and(|smoothing| !(0.0..=1.0).contains(&smoothing))
+        {
+            return Err("visualizer_smoothing must be from 0.0 to 1.0".to_owned());
+        }
+        if let Some(rate) = self.visualizer_frame_rate {
+            if !visualizer::FRAME_RATES.contains(&rate) {
+                return Err(format!(
+                    "visualizer_frame_rate must be from {} to {}",
+                    visualizer::FRAME_RATES.start(),
+                    visualizer::FRAME_RATES.end()
+                ));
+            }
+        }
+        for (name, rate) in [
+            ("refresh_rate", self.refresh_rate),
+            ("progress_rate", self.progress_rate),
+        ] {
+            if rate.is_some_and(|rate| !display::REFRESH_RATES.contains(&rate)) {
+                return Err(format!(
+                    "{name} must be from {} to {}",
+                    display::REFRESH_RATES.start(),
+                    display::REFRESH_RATES.end()
+                ));
+            }
+        }
+        for extension in self.decoders.keys() {
+            if AudioFormat::from_extension(extension).is_err() {
+                return Err(format!("Unsupported format in decoders: {extension}"));
+            }
+        }
+        Keymap::new(&self.keys)?;
+        if self.translation_language.is_some() != self.translation_endpoint.is_some() {
+            return Err(
+                "translation_language and translation_endpoint must be set together".to_owned(),
+            );
+        }
+        if let Some(name) = &self.profile {
+            if !self.profiles.contains_key(name) {
+                return Err(format!("Unknown profile: {name}"));
+            }
+        }
+        for entry in &self.schedule {
+            alarm::parse_time(&entry.at).map_err(|e| format!("Invalid schedule: {e}"))?;
+            let Some(name) = &entry.playlist else {
+                continue;
+            };
+            if entry.action != ScheduledAction::Play {
+                return Err("Only play entries of the schedule can have a playlist".to_owned());
+            }
+            if !self.playlists.contains_key(name) {
+                return Err(format!("Unknown playlist in schedule: {name}"));
+            }
+        }
+        for (name, profile) in &self.profiles {
+            if profile.volume.is_some_and(|volume| volume > 100) {
+                return Err(format!(
+                    "The volume of profile {name} must be from 0 to 100"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds problems which don't stop the player from starting, but are likely mistakes:
+    /// unknown options in the file (which are ignored), library roots which don't exist,
+    /// invalid smart playlists, and outputs which can't be found.
+    ///
+    /// ### Notes
+    /// The audio device is looked up, so a device which is unplugged is reported too.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        let known = Self::known_options();
+        for option in Self::load().unwrap_or_default().keys() {
+            if !known.contains_key(option) {
+                warnings.push(format!("Unknown option {option} is ignored"));
+            }
+        }
+        for root in &self.library_roots {
+            if !root.is_dir() {
+                warnings.push(format!("Library root {} isn't a directory", root.display()));
+            }
+        }
+        for (name, rule) in &self.playlists {
+            if let Err(e) = Filter::parse(rule) {
+                warnings.push(format!("Invalid filter of playlist {name}: {e}"));
+            }
+        }
+        if let Some(Err(e)) = self.snapcast.as_deref().map(Snapcast::parse) {
+            warnings.push(e);
+        }
+        if let Some(fifo) = &self.visualizer_fifo {
+            if fifo
+                .parent()
+                .is_some_and(|dir| !dir.as_os_str().is_empty() && !dir.is_dir())
+            {
+                warnings.push(format!(
+                    "The directory of visualizer_fifo {} doesn't exist",
+                    fifo.display()
+                ));
+            }
+        }
+        if self.dlna_renderer.is_none() && self.snapcast.is_none() {
+            if let Err(e) = self.backend().device() {
+                warnings.push(e);
+            }
+        }
+
+        warnings
+    }
+
+    /// Returns the names of every option, with their default values.
+    fn known_options() -> Map<String, Value> {
+        match serde_json::to_value(Self::default()) {
+            Ok(Value::Object(defaults)) => defaults,
+            _ => Map::new(),
+        }
+    }
+
+    /// Saves the configuration file, creating its directory if needed.
+    pub fn save(&self) -> Result<(), String> {
+        /* Overrides are kept out of the file, unless they were changed while running */
+        let mut options = match serde_json::to_value(self) {
+            Ok(Value::Object(options)) => options,
+            _ => return Err("Unable to save the configuration".to_owned()),
+        };
+        for (option, (saved, overriding)) in &self.overridden {
+            if options.get(option) != Some(overriding) {
+                continue;
+            }
+            match saved {
+                Some(saved) => options.insert(option.clone(), saved.clone()),
+                None => options.remove(option),
+            };
+        }
+
+        Self::write(&options)
+    }
+
+    /// Replaces the configuration file with other options (like the ones of another machine).
+    /// The previous file is kept next to it, as `config.json.bak`.
+    ///
+    /// ## Errors
+    /// Returns an error if an option is unknown or a value is invalid (the file is left as it is),
+    /// or if the file can't be written.
+    pub fn replace(options: Map<String, Value>) -> Result<(), String> {
+        let known = Self::known_options();
+        if let Some(option) = options.keys().find(|option| !known.contains_key(*option)) {
+            return Err(format!("Unknown option {option}"));
+        }
+        let config: Self = serde_json::from_value(Value::Object(options.clone()))
+            .map_err(|e| format!("Invalid configuration: {e}"))?;
+        config
+            .validate()
+            .map_err(|e| format!("Invalid configuration: {e}"))?;
+
+        let path = Self::path().ok_or("Unable to find the configuration directory")?;
+        if path.exists() {
+            let backup = path.with_extension("json.bak");
+            fs::copy(&path, &backup)
+                .map_err(|e| format!("Unable to create {}: {e}", backup.display()))?;
+        }
+        Self::write(&options)
+    }
+
+    /// Writes options to the configuration file, creating its directory if needed.
+    fn write(options: &Map<String, Value>) -> Result<(), String> {
+        let path = Self::path().ok_or("Unable to find the configuration directory")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Unable to create {}: {e}", dir.display()))?;
+        }
+
+        let file =
+            File::create(&path).map_err(|e| format!("Unable to create {}: {e}", path.display()))?;
+        serde_json::to_writer_pretty(file, options)
+            .map_err(|e| format!("Unable to save {}: {e}", path.display()))
+    }
+
+    /// Takes the settings which can be changed while the player is running (like the theme and
+    /// the options of the visualizer) from another configuration, and keeps the others (like the
+    /// audio device), which are only used at startup.
+    /// Returns whether any of the settings changed.
+    pub fn apply_live(&mut self, other: Self) -> bool {
+        let before = serde_json::to_value(&*self).ok();
+
+        self.musicbrainz_lookup = other.musicbrainz_lookup;
+        self.acoustid_key = other.acoustid_key;
+        self.sort = other.sort;
+        self.playlists = other.playlists;
+        self.schedule = other.schedule;
+        self.ytdlp = other.ytdlp;
+        self.visualizer = other.visualizer;
+        self.visualizer_kind = other.visualizer_kind;
+        self.visualizer_gravity = other.visualizer_gravity;
+        self.visualizer_smoothing = other.visualizer_smoothing;
+        self.visualizer_lissajous = other.visualizer_lissajous;
+        self.visualizer_fft_size = other.visualizer_fft_size;
+        self.visualizer_bars = other.visualizer_bars;
+        self.visualizer_colors = other.visualizer_colors;
+        self.visualizer_frame_rate = other.visualizer_frame_rate;
+        self.scrub_live = other.scrub_live;
+        self.write_ratings = other.write_ratings;
+        self.status_bar = other.status_bar;
+        self.header_clock = other.header_clock;
+        self.artist_separator = other.artist_separator;
+        self.refresh_rate = other.refresh_rate;
+        self.progress_rate = other.progress_rate;
+        self.theme = other.theme;
+        self.genre_colors = other.genre_colors;
+        self.keys = other.keys;
+        self.profiles = other.profiles;
+
+        before != serde_json::to_value(&*self).ok()
+    }
+
+    /// Switches to a profile: the settings it sets override the current ones,
+    /// and it's remembered as the profile in use.
+    /// Returns the profile.
+    ///
+    /// ### Notes
+    /// Like the profile chosen at startup, its settings are kept out of the file when it's saved
+    /// (only the name of the profile is saved).
+    ///
+    /// ## Errors
+    /// Fails if there's no profile with that name.
+    pub fn apply_profile(&mut self, name: &str) -> Result<Profile, String> {
+        let profile = self
+            .profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Unknown profile: {name}"))?;
+
+        if let Some(backend) = &profile.audio_backend {
+            self.audio_backend = Some(backend.clone());
+        }
+        if let Some(device) = &profile.audio_device {
+            self.audio_device = Some(device.clone());
+        }
+        self.preamp_db = profile.preamp_db.unwrap_or(self.preamp_db);
+        self.bass_db = profile.bass_db.unwrap_or(self.bass_db);
+        self.treble_db = profile.treble_db.unwrap_or(self.treble_db);
+        self.theme = profile.theme.unwrap_or(self.theme);
+        self.profile = Some(name.to_owned());
+
+        if let (Ok(Value::Object(settings)), Ok(Value::Object(options))) =
+            (serde_json::to_value(&profile), serde_json::to_value(&*self))
+        {
+            let file = Self::load().unwrap_or_default();
+            let applied = settings
+                .into_iter()
+                .filter(|(option, value)| !value.is_null() && options.contains_key(option));
+            for (option, _) in applied {
+                /* Saving keeps the value in the file, unless it's changed while running */
+                let saved = file.get(&option).cloned();
+                self.overridden
+                    .insert(option.clone(), (saved, options[&option].clone()));
+                self.origins
+                    .insert(option, Origin::Profile(name.to_owned()));
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Returns the name of the profile after the one in use (in alphabetical order),
+    /// starting over after the last one. If no profile is in use, the first one is returned.
+    pub fn next_profile(&self) -> Option<&str> {
+        let mut names = self.profiles.keys();
+        let Some(current) = &self.profile else {
+            return names.next().map(String::as_str);
+        };
+
+        self.profiles
+            .range::<String, _>((Bound::Excluded(current), Bound::Unbounded))
+            .next()
+            .map(|(name, _)| name)
+            .or_else(|| names.next())
+            .map(String::as_str)
+    }
+
+    /// Returns the configured audio backend and device.
+    pub fn backend(&self) -> Backend {
+        Backend {
+            host: self.audio_backend.clone(),
+            device: self.audio_device.clone(),
+            buffer_ms: self.output_buffer_ms,
+        }
+    }
+
+    /// Returns the configured effects.
+    pub fn effects(&self) -> Effects {
+        Effects {
+            preamp_db: self.preamp_db,
+            tone: Arc::new(ToneControls::new(self.bass_db, self.treble_db)),
+            channel_mode: Arc::default(),
+            mono: Arc::default(),
+            night: Arc::new(AtomicBool::new(self.night_mode)),
+            bypass: Arc::default(),
+            fade: Arc::default(),
+            silence: Silence {
+                threshold_db: self
+                    .silence_threshold_db
+                    .unwrap_or(dsp::DEFAULT_SILENCE_THRESHOLD_DB),
+                min_duration: self
+                    .silence_min_ms
+                    .map_or(dsp::DEFAULT_SILENCE_DURATION, Duration::from_millis),
+                trim: self.skip_silence,
+                pauses: Arc::new(AtomicBool::new(self.skip_pauses)),
+            },
+            resampler: self.resampler,
+            dither: self.dither,
+            taps: Taps::default(),
+            levels: Levels::default(),
+        }
+    }
+
+    /// Returns how files are decoded: how much audio is decoded ahead of playback,
+    /// and the decoders chosen for some formats.
+    pub fn decode_options(&self) -> DecodeOptions {
+        DecodeOptions {
+            buffer: self
+                .decode_buffer_ms
+                .map_or(prefetch::DEFAULT_BUFFER, Duration::from_millis),
+            decoders: self
+                .decoders
+                .iter()
+                .map(|(extension, decoder)| (extension.to_lowercase(), decoder.clone()))
+                .collect(),
+            downmix: self.downmix,
+        }
+    }
+
+    /// Creates the configured visualizer (listening to the played audio), in the slot below the lyrics.
+    ///
+    /// # Arguments
+    /// * `taps` - The played audio
+    /// * `latency` - Latency of the output
+    pub fn visualizer(&self, taps: &Taps, latency: Duration) -> Slot {
+        let visualizer: Box<dyn Visualizer> = match self.visualizer_kind {
+            VisualizerKind::Spectrum => Box::new(Spectrum::new(
+                taps,
+                latency,
+                SpectrumSettings {
+                    fft_size: self
+                        .visualizer_fft_size
+                        .unwrap_or(visualizer::DEFAULT_FFT_SIZE),
+                    bars: self.visualizer_bars,
+                    gravity: self
+                        .visualizer_gravity
+                        .unwrap_or(visualizer::DEFAULT_GRAVITY),
+                    smoothing: self
+                        .visualizer_smoothing
+                        .unwrap_or(visualizer::DEFAULT_SMOOTHING),
+                },
+            )),
+            VisualizerKind::Vu => Box::new(VuMeter::new(taps, latency)),
+            VisualizerKind::Scope => Box::new(Scope::new(taps, latency, self.visualizer_lissajous)),
+        };
+        let frame_rate = self
+            .visualizer_frame_rate
+            .unwrap_or(visualizer::DEFAULT_FRAME_RATE);
+        Slot::new(visualizer, self.visualizer_kind, frame_rate)
+    }
+
+    /// Returns the colors of the visualizer, from the quietest to the loudest parts of the audio.
+    pub fn visualizer_colors(&self) -> [Color; 3] {
+        self.visualizer_colors
+            .unwrap_or_else(|| self.theme.visualizer_colors())
+    }
+
+    /// Returns the text between the artists of a track in the track info.
+    pub fn artist_separator(&self) -> &str {
+        self.artist_separator.as_deref().unwrap_or(", ")
+    }
+
+    /// Returns the time between updates of the UI.
+    pub fn refresh_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.refresh_rate.unwrap_or(display::DEFAULT_REFRESH_RATE)
+    }
+
+    /// Returns the time between updates of the progress bar.
+    pub fn progress_interval(&self) -> Duration {
+        match self.progress_rate {
+            Some(rate) => Duration::from_secs(1) / rate,
+            None => self.refresh_interval(),
+        }
+    }
+
+    /// Returns which key does what, with the configured keys.
+    /// Falls back to the default keys if they're invalid (which the validation reports).
+    pub fn keymap(&self) -> Keymap {
+        Keymap::new(&self.keys).unwrap_or_default()
+    }
+
+    /// Returns the segments of the status bar, in the order they're shown.
+    pub fn status_bar(&self) -> Vec<StatusSegment> {
+        self.status_bar
+            .clone()
+            .unwrap_or_else(|| display::STATUS_SEGMENTS.to_vec())
+    }
+
+    /// Returns the translator of lyrics, or `None` if translation isn't set up.
+    pub fn translator(&self) -> Option<Translator> {
+        Some(Translator::new(
+            self.translation_endpoint.as_ref()?,
+            self.translation_key.clone(),
+            self.translation_language.clone()?,
+        ))
+    }
+
+    /// Returns the path to the configuration file.
+    /// Returns `None` if the home directory is unknown.
+    pub fn path() -> Option<PathBuf> {
+        Some(paths::config_dir()?.join(CONFIG_FILE_NAME))
+    }
+}
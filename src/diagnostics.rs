@@ -0,0 +1,106 @@
+use std::time::{Duration, Instant};
+
+/// How long the statistics are collected before they're shown.
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Statistics of the main loop, shown in the debug overlay to pinpoint stutter or CPU usage:
+/// how often the loop runs and redraws the screen, how long its work takes, and how many
+/// events (keys and mouse events) it handled.
+///
+/// The rates are measured over a window of one second, and only change once it's over.
+pub struct Diagnostics {
+    /// Start of the current window
+    window_start: Instant,
+    /// Ticks of the loop in the current window
+    ticks: u32,
+    /// Time spent working (not sleeping) in the current window
+    busy: Duration,
+    /// The longest tick in the current window
+    slowest: Duration,
+    /// Events in the current window
+    events: u32,
+    /// Redraws counted when the current window started
+    redraws: u64,
+    /// Events since the statistics were started
+    total_events: u64,
+    /// Statistics of the last window
+    last: Option<Stats>,
+}
+
+/// Statistics of a window, see [`Diagnostics`](Diagnostics).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    /// Ticks of the loop per second
+    pub tick_rate: f64,
+    /// Average time a tick spent working
+    pub average_tick: Duration,
+    /// The longest tick
+    pub slowest_tick: Duration,
+    /// Redraws of the screen per second
+    pub redraw_rate: f64,
+    /// Events per second
+    pub event_rate: f64,
+    /// Events since the statistics were started
+    pub events: u64,
+}
+
+impl Diagnostics {
+    /// Starts collecting statistics.
+    ///
+    /// # Arguments
+    /// * `redraws` - How many times the screen was redrawn so far, see [`Display::redraws()`](crate::display::Display::redraws)
+    pub fn new(redraws: u64) -> Self {
+        Self {
+            window_start: Instant::now(),
+            ticks: 0,
+            busy: Duration::ZERO,
+            slowest: Duration::ZERO,
+            events: 0,
+            redraws,
+            total_events: 0,
+            last: None,
+        }
+    }
+
+    /// Counts a tick of the loop.
+    ///
+    /// # Arguments
+    /// * `work` - How long the tick worked, before it started waiting for the next one
+    pub fn tick(&mut self, work: Duration) {
+        self.ticks += 1;
+        self.busy += work;
+        self.slowest = self.slowest.max(work);
+    }
+
+    /// Counts a handled event.
+    pub fn event(&mut self) {
+        self.events += 1;
+        self.total_events += 1;
+    }
+
+    /// Returns the statistics of the last window, once the first one is over.
+    ///
+    /// # Arguments
+    /// * `redraws` - How many times the screen was redrawn so far
+    pub fn stats(&mut self, redraws: u64) -> Option<Stats> {
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= WINDOW {
+            let seconds = elapsed.as_secs_f64();
+            self.last = Some(Stats {
+                tick_rate: self.ticks as f64 / seconds,
+                average_tick: self.busy / self.ticks.max(1),
+                slowest_tick: self.slowest,
+                redraw_rate: redraws.saturating_sub(self.redraws) as f64 / seconds,
+                event_rate: self.events as f64 / seconds,
+                events: self.total_events,
+            });
+
+            self.window_start = Instant::now();
+            (self.ticks, self.events) = (0, 0);
+            (self.busy, self.slowest) = (Duration::ZERO, Duration::ZERO);
+            self.redraws = redraws;
+        }
+
+        self.last
+    }
+}
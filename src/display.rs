@@ -1,10 +1,37 @@
-use crate::audioinfo::{AudioFile, AudioMeta};
-use crate::lyrics::{LyricsBank, LYRICS_BANK_SIZE};
+use crate::artists::Artists;
+use crate::audioinfo::{AudioFile, AudioFormat, AudioMeta};
+use crate::clock;
+use crate::command;
+use crate::dsp::ChannelMode;
+use crate::envelope::{Envelope, Loudness};
+use crate::history::{self, HistoryView};
+use crate::keymap::{self, Keymap};
+use crate::library::{unix_time, PlayCount};
+use crate::locale::tr;
+use crate::lyrics::{LyricsError, LyricsPage, Section, LYRICS_PAGE_SIZE};
+use crate::lyrics_parse::LyricsEntry;
+use crate::output::OutputFormat;
+use crate::queue::{Queue, QueueView, RepeatMode};
 use crate::scrolledbuf::*;
+use crate::search::{Launcher, Search, SearchScope};
+use crate::seek::SeekDirection;
+use crate::tagfix::TagFix;
+use crate::tagwriter::TagChange;
+use crate::theme::{self, ColorDepth, Theme};
 use crate::timer::Timer;
+use crate::visualizer::{Color, Frame, Heat};
 use ncurses::*;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::mem;
+use std::ops::RangeInclusive;
 use std::path::Path;
-use std::time::{Duration, Instant};
+use std::time::{Duration, SystemTime};
 
 /// Title string
 const HEADER: &str = "[br0kenpixel's Music Player]";
@@ -14,21 +41,231 @@ const INFOVIEW_OFFSET: i32 = 8;
 const STATUSMSG_OFFSET: i32 = 6;
 /// The default display time for a status message in seconds.
 const STATUSMSG_DISPLAYTIME: u64 = 2;
+/// How long an on-screen display widget is shown in milliseconds (including its fade).
+const OSD_DISPLAY_TIME: u64 = 1000;
+/// How long an on-screen display widget fades (is shown dimmed) before it disappears
+/// in milliseconds.
+const OSD_FADE_TIME: u64 = 300;
+/// Width of the volume bar in characters.
+const VOLUME_BAR_WIDTH: usize = 20;
 /// Amount of time to wait before scrolling the text in milliseconds.
 const SCROLL_SHORT_TIME: u64 = 200;
 /// Amount of time to wait before reversing the scroll direction.
 const SCROLL_PAUSE_TIME: u64 = 3000;
+/// Amount of time between updates of the clock in the header in milliseconds.
+const CLOCK_UPDATE_TIME: u64 = 1000;
+/// Width of the position of the track on the album (like `Track 12/14`), after the title.
+const POSITION_WIDTH: usize = 13;
+/// Width of the genre, after the album.
+const GENRE_WIDTH: usize = 17;
+/// How long to wait for a key while idle in milliseconds.
+const IDLE_TIMEOUT: i32 = 1000;
+/// Used to adjust the location of the visualizer (below the scrolling file name).
+const VISUALIZER_OFFSET: i32 = INFOVIEW_OFFSET + 8;
+/// Color pairs of the visualizer, from the quietest to the loudest parts of the audio.
+const VISUALIZER_COLORS: [(i16, i16); 3] = [(1, COLOR_GREEN), (2, COLOR_YELLOW), (3, COLOR_RED)];
+/// Color pair of the clipping indicator.
+const CLIP_COLOR: (i16, i16) = (4, COLOR_RED);
+/// Keyboard shortcuts shown at the bottom of the TUI, as rows of keys and their descriptions.
+const CONTROLS: [&[(&[DisplayEvent], &str)]; 2] = [
+    &[
+        (
+            &[DisplayEvent::JumpBack, DisplayEvent::JumpNext],
+            "Prev/Next",
+        ),
+        (
+            &[DisplayEvent::MakePlay, DisplayEvent::MakePause],
+            "Play/Pause",
+        ),
+        (&[DisplayEvent::VolUp, DisplayEvent::VolDown], "Vol"),
+        (&[DisplayEvent::ToggleMute], "Mute"),
+        (&[DisplayEvent::ToggleMono], "Mono"),
+        (&[DisplayEvent::CycleVisualizer], "Visual"),
+        (&[DisplayEvent::Search], "Search"),
+    ],
+    &[
+        (&[DisplayEvent::CycleSort], "Sort"),
+        (&[DisplayEvent::History], "History"),
+        (&[DisplayEvent::BassUp, DisplayEvent::BassDown], "Bass"),
+        (
+            &[DisplayEvent::TrebleUp, DisplayEvent::TrebleDown],
+            "Treble",
+        ),
+        (&[DisplayEvent::CycleChannels], "Channels"),
+        (&[DisplayEvent::TogglePauses], "Pauses"),
+    ],
+];
+/// First color pair of the gradient (from the quietest to the loudest color of the visualizer),
+/// used by the visualizer and the progress bar on terminals with more than 8 colors.
+const GRADIENT_PAIR: i16 = 16;
+/// First of the color pairs of genres, one for each [`Color`](Color) (in the order they're declared).
+const GENRE_PAIR: i16 = 8;
+/// Amount of colors in the gradient.
+const GRADIENT_STEPS: usize = 24;
+/// How often the UI is updated and checks for keys (per second), unless configured otherwise.
+pub const DEFAULT_REFRESH_RATE: u32 = 100;
+/// Allowed refresh rates of the UI (and of the progress bar).
+pub const REFRESH_RATES: RangeInclusive<u32> = 1..=500;
+/// Segments of the status bar, in the order they're shown by default.
+pub const STATUS_SEGMENTS: [StatusSegment; 6] = [
+    StatusSegment::State,
+    StatusSegment::Volume,
+    StatusSegment::Flags,
+    StatusSegment::Format,
+    StatusSegment::Bitrate,
+    StatusSegment::Clock,
+];
 
 /// Represents the terminal UI (TUI)
 pub struct Display {
-    /// Lyrics subwindow
+    /// Lyrics subwindow (drawn off-screen, see [`Display::refresh_infoview()`](Display::refresh_infoview))
     infoview: WINDOW,
     /// Scrollable text (used to scroll the file name across the UI)
     scrolledname: ScrolledBuf,
     /// Timer that handles scrolling
     scroll_timer: Timer,
+    /// Timer that handles updating the clock in the header
+    clock_timer: Timer,
     /// Timer that handles removing the status message after it's expired
-    message_timer: Option<Instant>,
+    message_timer: Option<Timer>,
+    /// The on-screen display widget being shown (in place of the status message)
+    osd: Option<ShownOsd>,
+    /// Whether the progress bar is being dragged with the mouse
+    scrubbing: bool,
+    /// What the mouse pointer is over
+    hovered: Option<Clickable>,
+    /// Which keyboard shortcut is being clicked (the button was pressed, but not released yet)
+    pressed: Option<Clickable>,
+    /// Segments of the status bar, in the order they're shown
+    status_segments: Vec<StatusSegment>,
+    /// The status bar as it's currently drawn
+    status_bar: String,
+    /// Whether the status bar is drawn highlighted (it flashes while the sleep timer fades)
+    status_flash: bool,
+    /// What's drawn in the regions which are updated on every tick (as hashes)
+    drawn: RefCell<HashMap<Region, u64>>,
+    /// Colors and attributes of the TUI
+    theme: Theme,
+    /// How many colors the terminal can show
+    color_depth: ColorDepth,
+    /// Whether the gradient color pairs are set up
+    gradient: bool,
+    /// Which key does what
+    keymap: Keymap,
+    /// How many times the screen was redrawn, shown in the debug overlay
+    redraws: Cell<u64>,
+    /// Who's credited for the lyrics, shown on the border of the `Lyrics` subwindow
+    lyrics_credits: RefCell<Option<String>>,
+    /// Text between the artists of a track in the track info
+    artist_separator: String,
+    /// Colors of genres (lowercase), see [`set_genre_colors()`](Display::set_genre_colors)
+    genre_colors: Vec<(String, Color)>,
+}
+
+/// An on-screen display widget: a short-lived indicator, which is shown in place of the
+/// status message when something changes, and fades after [`OSD_DISPLAY_TIME`](OSD_DISPLAY_TIME).
+/// See [`Display::show_osd()`](Display::show_osd).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Osd {
+    /// A bar showing the playback volume (from 0 to 100%), dimmed and labeled `Muted` while muted
+    Volume { volume: u8, muted: bool },
+}
+
+/// An on-screen display widget which is being shown.
+struct ShownOsd {
+    /// The widget
+    osd: Osd,
+    /// Timer that handles fading and removing it
+    timer: Timer,
+    /// Whether it's drawn faded
+    faded: bool,
+}
+
+/// A part of the TUI which can be clicked with the mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Clickable {
+    /// The progress bar
+    ProgressBar,
+    /// A keyboard shortcut (by its index in [`control_labels()`](control_labels))
+    Control(usize),
+}
+
+/// A keyboard shortcut shown at the bottom of the TUI, like `[G/B] Play/Pause`.
+struct ControlLabel {
+    /// Row of the label
+    ypos: i32,
+    /// Column where the label starts
+    xpos: i32,
+    /// The keys (like `g` and `b`)
+    keys: Vec<char>,
+    /// The label, with the description translated
+    text: String,
+    /// Whether the label is followed by a separator
+    separated: bool,
+}
+
+/// A part of the TUI which is updated on every tick, but only redrawn when what it shows changed.
+/// See [`Display::redraw()`](Display::redraw).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Region {
+    /// The playback time
+    Playtime,
+    /// The progress bar (or the buffer health of a stream)
+    Progress,
+    /// The gain, below the file quality
+    Gain,
+    /// The peak levels
+    Levels,
+    /// The clipping indicator
+    Clipping,
+    /// The skipped silence
+    TimeSaved,
+    /// The lyrics, and which line is highlighted
+    Lyrics,
+}
+
+/// A segment of the status bar, which is drawn over the bottom border of the TUI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusSegment {
+    /// Whether the track is playing or paused (or the time left until the alarm goes off)
+    State,
+    /// The playback volume (or that the audio is muted)
+    Volume,
+    /// The repeat mode, whether the effects are bypassed and the time left on the sleep timer
+    /// (each hidden while it's off)
+    Flags,
+    /// Format of the file
+    Format,
+    /// Average bitrate of the file (hidden for streams)
+    Bitrate,
+    /// The current (local) time
+    Clock,
+}
+
+/// What the status bar shows, see [`Display::set_status_bar()`](Display::set_status_bar).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatusBar {
+    /// Whether the track is playing
+    pub playing: bool,
+    /// Playback volume (from 0 to 100%)
+    pub volume: u8,
+    /// Whether the audio is muted
+    pub muted: bool,
+    /// What to play after the current track finishes
+    pub repeat: RepeatMode,
+    /// Whether the effects are bypassed
+    pub bypass: bool,
+    /// Time left on the sleep timer (if it's armed)
+    pub sleep: Option<Duration>,
+    /// Whether the sleep timer is fading the audio out
+    pub sleep_fading: bool,
+    /// Time left until the alarm goes off (if it's set)
+    pub alarm: Option<Duration>,
+    /// Format of the file
+    pub format: AudioFormat,
+    /// Average bitrate of the file (in kbps)
+    pub bitrate: Option<u32>,
 }
 
 /// Represents different events that occur when
@@ -51,32 +288,166 @@ pub enum DisplayEvent {
     /// The program was requested to decrease the playback volume.
     #[allow(dead_code)]
     VolDown,
+    /// The program was requested to accept the current prompt
+    /// (suggested metadata or tag changes).
+    Accept,
+    /// The program was requested to dismiss the current prompt.
+    Dismiss,
+    /// The program was requested to write the metadata to the file.
+    WriteTags,
+    /// The program was requested to open the search prompt.
+    Search,
+    /// The program was requested to switch to the next sort mode.
+    CycleSort,
+    /// The program was requested to show the listening history.
+    History,
+    /// The program was requested to boost the bass.
+    BassUp,
+    /// The program was requested to cut the bass.
+    BassDown,
+    /// The program was requested to boost the treble.
+    TrebleUp,
+    /// The program was requested to cut the treble.
+    TrebleDown,
+    /// The program was requested to switch to the next [channel mode](ChannelMode).
+    CycleChannels,
+    /// The program was requested to switch to the next [downmix mode](crate::player::DownmixMode).
+    CycleDownmix,
+    /// The program was requested to turn skipping pauses on or off.
+    TogglePauses,
+    /// The program was requested to sum the channels to mono, or to stop doing so.
+    ToggleMono,
+    /// The program was requested to turn [night mode](crate::dsp::NightMode) on or off.
+    ToggleNight,
+    /// The program was requested to bypass the effects, or to apply them again.
+    ToggleBypass,
+    /// The program was requested to show or hide the debug overlay.
+    ToggleDebug,
+    /// The program was requested to show or hide the details of how the track was encoded.
+    ToggleDetails,
+    /// The program was requested to show or hide the listening statistics.
+    ToggleStats,
+    /// The program was requested to switch to the next visualizer (or to hide it).
+    CycleVisualizer,
+    /// The program was requested to switch to the next [profile](crate::config::Profile).
+    CycleProfile,
+    /// The program was requested to read the lyrics of the current track again.
+    ReloadLyrics,
+    /// The program was requested to read the tags of the current track again.
+    RefreshMetadata,
+    /// The program was requested to show or hide the translation of the lyrics.
+    ToggleTranslation,
+    /// The program was requested to stretch the lyrics to the length of the track
+    /// (or to restore their timestamps).
+    RescaleLyrics,
+    /// The progress bar is being dragged to a position (as a fraction of the track).
+    Scrub(f64),
+    /// The program was requested to rate the current track (from 1 to 5 stars, `0` removes the rating).
+    Rate(u8),
+    /// The program was requested to add the current track to the favorites (or remove it).
+    ToggleFavorite,
+    /// The program was requested to play the favorites.
+    PlayFavorites,
+    /// The program was requested to show the queue.
+    EditQueue,
+    /// The program was requested to open the prompt for adding files to the queue.
+    AddFiles,
+    /// The program was requested to open the quick launcher.
+    Launch,
+    /// The program was requested to open the command prompt.
+    CommandPrompt,
+    /// The program was requested to open the jump-to-time prompt.
+    JumpTo,
+    /// The program was requested to seek to a position (as a fraction of the track),
+    /// e.g. when the progress bar was clicked or released after dragging it.
+    Seek(f64),
+    /// The seek key of a direction was pressed (or repeated, while it's held).
+    SeekBy(SeekDirection),
     /// The user pressed a key which is not bound to any command.
     Invalid(char),
     /// The program was requested to stop playing and exit.
     Quit,
 }
 
+/// Represents a key pressed while a text prompt (such as the search prompt) is open.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum PromptKey {
+    /// A printable character
+    Char(char),
+    /// Remove the last character
+    Backspace,
+    /// Confirm the prompt
+    Enter,
+    /// Close the prompt
+    Escape,
+    /// Switch between modes of the prompt
+    Tab,
+    /// Select the previous item
+    Up,
+    /// Select the next item
+    Down,
+    /// Mark the selected item and the previous one (`Shift+Up`)
+    ShiftUp,
+    /// Mark the selected item and the next one (`Shift+Down`)
+    ShiftDown,
+    /// Any other key
+    Other,
+}
+
 /// This implementation contains all the functions that are used to draw the TUI.
 impl Display {
     /// Creates the TUI and initializes [`ncurses`](ncurses).
     /// This function __does not__ draw the static components of the TUI.
-    pub fn new(file: &String) -> Display {
+    pub fn new(file: &str) -> Display {
         let locale_conf = LcCategory::all;
         setlocale(locale_conf, "en_US.UTF-8");
 
         initscr();
         noecho();
         timeout(0);
+        keypad(stdscr(), true);
+        set_escdelay(25);
         curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+        if has_colors() {
+            start_color();
+            use_default_colors();
+            for (pair, color) in VISUALIZER_COLORS.into_iter().chain([CLIP_COLOR]) {
+                init_pair(pair, color, -1);
+            }
+        }
 
         let filename = Path::new(file).file_name().unwrap().to_string_lossy();
 
         Display {
-            infoview: newwin(6, COLS() - 8, INFOVIEW_OFFSET, 4),
+            infoview: newpad(6, COLS() - 8),
             scrolledname: ScrolledBuf::new(filename, COLS() - 8, ScrollDirection::LeftToRight),
             scroll_timer: Timer::new(Duration::from_millis(SCROLL_SHORT_TIME)),
+            clock_timer: {
+                /* The clock is shown right away */
+                let mut timer = Timer::repeating(Duration::from_millis(CLOCK_UPDATE_TIME));
+                timer.expire();
+                timer
+            },
             message_timer: None,
+            osd: None,
+            scrubbing: false,
+            hovered: None,
+            pressed: None,
+            status_segments: STATUS_SEGMENTS.to_vec(),
+            status_bar: String::new(),
+            status_flash: false,
+            drawn: RefCell::new(HashMap::new()),
+            theme: Theme::default(),
+            color_depth: match has_colors() {
+                true => ColorDepth::detect(),
+                false => ColorDepth::Basic,
+            },
+            gradient: false,
+            keymap: Keymap::default(),
+            redraws: Cell::new(0),
+            lyrics_credits: RefCell::new(None),
+            artist_separator: ", ".to_owned(),
+            genre_colors: Vec::new(),
         }
     }
 
@@ -131,18 +502,35 @@ impl Display {
         box_(self.infoview, ACS_VLINE(), ACS_HLINE());
         touchwin(self.infoview);
         self.wmoveto(0, 2, self.infoview);
-        self.waddstr("[ Lyrics ]", self.infoview);
-        wrefresh(self.infoview);
+        self.waddstring(&format!("[ {} ]", tr("Lyrics")), self.infoview);
+        if let Some(credits) = self.lyrics_credits.borrow().as_deref() {
+            let width = (COLS() - 16) as usize;
+            let credits: String = credits.chars().take(width).collect();
+            self.wmoveto(5, 2, self.infoview);
+            self.waddstring(&format!("[ {credits} ]"), self.infoview);
+        }
+        self.refresh_infoview();
+    }
+
+    /// Set who's credited for the lyrics (shown on the bottom border of the `Lyrics` subwindow),
+    /// or `None` to remove the credits.
+    pub fn set_lyrics_credits(&self, credits: Option<String>) {
+        if *self.lyrics_credits.borrow() == credits {
+            return;
+        }
+        *self.lyrics_credits.borrow_mut() = credits;
+        self.print_lyricsarea();
     }
 
-    /// Draws the static parts of the metadata display (`Track:`, `Album:`, `Artist(s):`)
+    /// Draws the static parts of the metadata display (`Title:`, `Album:`, `Artist(s):`).
+    /// The title isn't labeled `Track:`, as the position of the track (`Track 4/9`) is shown after it.
     fn print_trackinfoui(&self) {
         self.moveto(2, 4);
-        self.addstring(&format!("{:5}", "Track:"));
+        self.addstring(&format!("{:5}", tr("Title:")));
         self.moveto(3, 4);
-        self.addstring(&format!("{:5}", "Album:"));
+        self.addstring(&format!("{:5}", tr("Album:")));
         self.moveto(4, 4);
-        self.addstring(&format!("{:5}", "Artist(s):"));
+        self.addstring(&format!("{:5}", tr("Artist(s):")));
     }
 
     /// Draws the static parts of the progress bar and timestamp indicators
@@ -155,48 +543,93 @@ impl Display {
         addch(ACS_HLINE());
         self.addstr("[00:00]");
         addch(ACS_HLINE());
-        self.addchar('[');
-        self.moveto(LINES() - 5, COLS() - 11);
-        self.addchar(']');
+        self.moveto(LINES() - 5, COLS() - 10);
         addch(ACS_HLINE());
         self.addstr("[00:00]");
         addch(ACS_HLINE());
         addch(ACS_RTEE());
+        self.print_progress_brackets();
     }
 
-    /// Draws the keyboard shortcuts guide
-    fn print_controls(&self) {
-        const EXIT_CTL_TXT: &str = "[Q] Exit";
+    /// Draws the brackets around the progress bar, which are highlighted while the mouse pointer is
+    /// over the progress bar, and while it's being dragged.
+    fn print_progress_brackets(&self) {
+        let attr = self.mouse_attr(Clickable::ProgressBar);
 
-        self.moveto(LINES() - 3, 2);
-        //self.print_control('F', "Prev", true); // not implemented for now
-        self.print_control('G', "Play", true);
-        //self.print_control('H', "Next", false); // not implemented for now
+        attr_on(attr);
+        self.moveto(LINES() - 5, 16);
+        self.addchar('[');
+        self.moveto(LINES() - 5, COLS() - 11);
+        self.addchar(']');
+        attr_off(attr);
+    }
 
-        //self.moveto(LINES() - 2, 2);
-        self.print_control('B', "Pause", true);
-        self.print_control('Y', "Vol+", true);
-        self.print_control('X', "Vol-", true);
-        self.print_control('M', "Mute", false);
+    /// Returns the attributes of a clickable part of the TUI:
+    /// reversed while the mouse pointer is over it, and also bold while it's being clicked.
+    fn mouse_attr(&self, clickable: Clickable) -> attr_t {
+        let pressed = match clickable {
+            Clickable::ProgressBar => self.scrubbing,
+            Clickable::Control(_) => self.pressed == Some(clickable),
+        };
+        match (pressed, self.hovered == Some(clickable)) {
+            (true, _) => A_REVERSE() | A_BOLD(),
+            (false, true) => A_REVERSE(),
+            (false, false) => A_NORMAL(),
+        }
+    }
 
-        self.moveto(LINES() - 2, COLS() - 2 - EXIT_CTL_TXT.len() as i32);
-        self.addstr(EXIT_CTL_TXT);
+    /// Sets which key does what, and draws the keyboard shortcuts guide again with its keys.
+    /// Set the text between the artists of a track in the track info.
+    /// The track info isn't redrawn.
+    pub fn set_artist_separator(&mut self, separator: &str) {
+        separator.clone_into(&mut self.artist_separator);
     }
 
-    /// Draws a single keyboard shortcut guide
-    fn print_control(&self, ctl_symbol: char, desc: &str, _continue: bool) {
-        self.addstring(&format!("[{ctl_symbol}] {desc}"));
-        if _continue {
-            self.addchar(' ');
-            addch(ACS_VLINE());
-            self.addchar(' ');
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+        for ypos in [LINES() - 3, LINES() - 2] {
+            self.moveto(ypos, 1);
+            self.addnch(' ' as chtype, COLS() - 2);
         }
+        self.print_controls();
+    }
+
+    /// Draws the keyboard shortcuts guide
+    /// With mouse support, the label under the mouse pointer is highlighted.
+    fn print_controls(&self) {
+        for (index, label) in control_labels(&self.keymap).iter().enumerate() {
+            let attr = self.mouse_attr(Clickable::Control(index));
+
+            self.moveto(label.ypos, label.xpos);
+            attr_on(attr);
+            self.addstring(&label.text);
+            attr_off(attr);
+            if label.separated {
+                self.addchar(' ');
+                addch(ACS_VLINE());
+                self.addchar(' ');
+            }
+        }
+    }
+
+    /// Returns the clickable part of the TUI at a position, if there's one.
+    fn clickable_at(&self, ypos: i32, xpos: i32) -> Option<Clickable> {
+        /* Same as Display::set_progress() */
+        let (start, width) = (17, ((COLS() - 12) - 15) - 1);
+        if ypos == LINES() - 5 && (start..start + width).contains(&xpos) {
+            return Some(Clickable::ProgressBar);
+        }
+
+        control_labels(&self.keymap)
+            .iter()
+            .position(|label| label.contains(ypos, xpos))
+            .map(Clickable::Control)
     }
 
     /// Refreshes the TUI by applying any changes done before calling this function.
     pub fn refresh(&self) {
-        refresh();
-        wrefresh(self.infoview);
+        wnoutrefresh(stdscr());
+        self.refresh_infoview();
     }
 
     /// Destroys the `Lyrics` subwindow and the main one.  
@@ -204,19 +637,134 @@ impl Display {
     pub fn destroy(&self) {
         delwin(self.infoview);
         endwin();
+        /* Stop reporting mouse movement, see Display::enable_mouse() */
+        print!("\x1b[?1003l");
+        let _ = io::stdout().flush();
+    }
+
+    /// Enables mouse support, so the progress bar can be clicked or dragged to seek,
+    /// and the keyboard shortcuts can be clicked.
+    pub fn enable_mouse(&self) {
+        mousemask(
+            (BUTTON1_PRESSED | BUTTON1_RELEASED | REPORT_MOUSE_POSITION) as mmask_t,
+            None,
+        );
+        /* Presses and releases are reported right away, instead of being merged into clicks */
+        mouseinterval(0);
+        /* Also report the movement when no button is held, for highlighting what's under the
+        pointer (xterm's any event tracking) */
+        print!("\x1b[?1003h");
+        let _ = io::stdout().flush();
     }
 
     /// Tries to capture a keypress, converting it to a [`DisplayEvent`](DisplayEvent)
     /// if successfull.
     ///
     /// [`DisplayEvent::Invalid`](DisplayEvent::Invalid) is returned.
-    pub fn capture_event(&self) -> Option<DisplayEvent> {
+    pub fn capture_event(&mut self) -> Option<DisplayEvent> {
         match getch() {
             ERR => None,
-            key => Some(char::from_u32(key as u32).unwrap().into()),
+            KEY_MOUSE => self.capture_mouse(),
+            KEY_LEFT => Some(DisplayEvent::SeekBy(SeekDirection::Back)),
+            KEY_RIGHT => Some(DisplayEvent::SeekBy(SeekDirection::Forward)),
+            key => Some(self.keymap.event(char::from_u32(key as u32).unwrap())),
         }
     }
 
+    /// Converts a mouse event on the progress bar into a [`DisplayEvent`](DisplayEvent).
+    /// Pressing the left button on the progress bar starts dragging it, and releasing the button
+    /// (anywhere) seeks to where it was released. Other mouse events are ignored.
+    fn capture_mouse(&mut self) -> Option<DisplayEvent> {
+        let mut event = MEVENT {
+            id: 0,
+            x: 0,
+            y: 0,
+            z: 0,
+            bstate: 0,
+        };
+        if getmouse(&mut event) != OK {
+            return None;
+        }
+
+        /* Same as Display::set_progress() */
+        let (start, width) = (17, ((COLS() - 12) - 15) - 1);
+        let position = ((event.x - start) as f64 / width as f64).clamp(0.0, 1.0);
+        let target = self.clickable_at(event.y, event.x);
+
+        if event.bstate & BUTTON1_RELEASED as mmask_t != 0 {
+            let scrubbed = mem::take(&mut self.scrubbing);
+            let pressed = self.pressed.take();
+            self.set_hovered(target);
+            if scrubbed {
+                return Some(DisplayEvent::Seek(position));
+            }
+            /* A keyboard shortcut is clicked if the button is released over it */
+            return match (pressed, target) {
+                (Some(Clickable::Control(index)), Some(Clickable::Control(released)))
+                    if index == released =>
+                {
+                    let key = control_labels(&self.keymap)[index].key_at(event.x);
+                    Some(self.keymap.event(key))
+                }
+                _ => None,
+            };
+        }
+
+        if event.bstate & BUTTON1_PRESSED as mmask_t != 0 {
+            match target {
+                Some(Clickable::ProgressBar) => self.scrubbing = true,
+                Some(Clickable::Control(_)) => self.pressed = target,
+                None => (),
+            }
+        }
+        /* While the progress bar is dragged, it stays highlighted */
+        match self.scrubbing {
+            true => self.set_hovered(Some(Clickable::ProgressBar)),
+            false => self.set_hovered(target),
+        }
+        self.scrubbing.then_some(DisplayEvent::Scrub(position))
+    }
+
+    /// Highlights the clickable part of the TUI under the mouse pointer
+    /// (and redraws the highlighted parts, as they could also have been pressed or released).
+    fn set_hovered(&mut self, hovered: Option<Clickable>) {
+        self.hovered = hovered;
+        self.print_controls();
+        self.print_progress_brackets();
+    }
+
+    /// Tries to capture a keypress while a text prompt is open.  
+    /// Unlike [`Display::capture_event()`](Self::capture_event()), this supports Unicode characters.
+    pub fn capture_prompt_key(&self) -> Option<PromptKey> {
+        Some(match get_wch()? {
+            WchResult::Char(c) => match char::from_u32(c) {
+                Some('\n' | '\r') => PromptKey::Enter,
+                Some('\u{1b}') => PromptKey::Escape,
+                Some('\t') => PromptKey::Tab,
+                Some('\u{7f}' | '\u{8}') => PromptKey::Backspace,
+                Some(c) if !c.is_control() => PromptKey::Char(c),
+                _ => PromptKey::Other,
+            },
+            WchResult::KeyCode(KEY_BACKSPACE) => PromptKey::Backspace,
+            WchResult::KeyCode(KEY_ENTER) => PromptKey::Enter,
+            WchResult::KeyCode(KEY_UP) => PromptKey::Up,
+            WchResult::KeyCode(KEY_DOWN) => PromptKey::Down,
+            WchResult::KeyCode(KEY_SR) => PromptKey::ShiftUp,
+            WchResult::KeyCode(KEY_SF) => PromptKey::ShiftDown,
+            WchResult::KeyCode(_) => PromptKey::Other,
+        })
+    }
+
+    /// Sets whether waiting for a key blocks for up to [`IDLE_TIMEOUT`](IDLE_TIMEOUT) milliseconds,
+    /// instead of returning right away.
+    /// This is used while nothing on the screen changes, so an idle player doesn't use the CPU.
+    pub fn set_idle(&self, idle: bool) {
+        timeout(match idle {
+            true => IDLE_TIMEOUT,
+            false => 0,
+        });
+    }
+
     /// Alias for [`Display::waddchar()`](Self::waddchar()) with [`stdscr()`](ncurses::stdscr()) as the `win` argument.
     fn addchar(&self, c: char) {
         self.waddchar(c, stdscr());
@@ -278,6 +826,29 @@ impl Display {
     fn moveto(&self, ypos: i32, xpos: i32) {
         self.wmoveto(ypos, xpos, stdscr());
     }
+
+    /// Draws a [`Region`](Region) with `draw`, unless it already shows `content`.
+    /// This way only the parts of the TUI which changed are touched on every tick.
+    fn redraw<T: Hash>(&self, region: Region, content: T, draw: impl FnOnce()) {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.drawn.borrow().get(&region) == Some(&hash) {
+            return;
+        }
+
+        draw();
+        self.drawn.borrow_mut().insert(region, hash);
+    }
+
+    /// Forgets what's drawn in the given regions, e.g. after something else was drawn over them,
+    /// so they're redrawn by their next update.
+    fn invalidate(&self, regions: &[Region]) {
+        let mut drawn = self.drawn.borrow_mut();
+        for region in regions {
+            drawn.remove(region);
+        }
+    }
 }
 
 /// This implementation adds functions used to change dynamic parts of the TUI.
@@ -294,36 +865,354 @@ impl Display {
         }));
     }
 
+    /// Sets which segments the status bar shows, and in which order.
+    /// The status bar is hidden if there are none.
+    pub fn set_status_segments(&mut self, segments: Vec<StatusSegment>) {
+        self.status_segments = segments;
+    }
+
+    /// Shows the status bar over the bottom border, with the configured [segments](StatusSegment)
+    /// separated by `|`. Segments with nothing to show (like the repeat mode while it's off) are left out.
+    /// It's only redrawn if it changed, so it can be updated on every tick (which keeps the clock current).
+    /// While the sleep timer fades the audio out, the status bar flashes once per second as a warning.
+    pub fn set_status_bar(&mut self, status: &StatusBar) {
+        let segments: Vec<String> = self
+            .status_segments
+            .iter()
+            .filter_map(|segment| match segment {
+                StatusSegment::State => Some(match status.alarm {
+                    Some(remaining) if !status.playing => {
+                        /* Rounded up, so it doesn't show 0:00:00 before it goes off */
+                        let seconds = remaining.as_secs_f64().ceil() as u64;
+                        format!(
+                            "{} {}:{:02}:{:02}",
+                            tr("Alarm in"),
+                            seconds / 3600,
+                            seconds / 60 % 60,
+                            seconds % 60
+                        )
+                    }
+                    _ => match status.playing {
+                        true => tr("Playing"),
+                        false => tr("Paused"),
+                    }
+                    .to_owned(),
+                }),
+                StatusSegment::Volume => Some(match status.muted {
+                    true => tr("Muted").to_owned(),
+                    false => format!("{} {}%", tr("Volume"), status.volume),
+                }),
+                StatusSegment::Flags => {
+                    let flags: Vec<String> = status
+                        .bypass
+                        .then(|| "BYPASS".to_owned())
+                        .into_iter()
+                        .chain(
+                            (status.repeat != RepeatMode::Off)
+                                .then(|| format!("{} {}", tr("Repeat"), status.repeat.name())),
+                        )
+                        .chain(status.sleep.map(|remaining| {
+                            /* Rounded up, so it doesn't show 0:00 while still playing */
+                            let seconds = remaining.as_secs_f64().ceil() as u64;
+                            format!("{} {}:{:02}", tr("Sleep"), seconds / 60, seconds % 60)
+                        }))
+                        .collect();
+                    (!flags.is_empty()).then(|| flags.join(" | "))
+                }
+                StatusSegment::Format => Some(status.format.to_string()),
+                StatusSegment::Bitrate => status.bitrate.map(|bitrate| format!("{bitrate} kbps")),
+                StatusSegment::Clock => Some(local_clock()),
+            })
+            .collect();
+        let text: String = match segments.is_empty() {
+            true => String::new(),
+            false => format!("[ {} ]", segments.join(" | ")),
+        }
+        .chars()
+        .take((COLS() - 4) as usize)
+        .collect();
+        let flash = status.sleep_fading
+            && status
+                .sleep
+                .is_some_and(|remaining| remaining.as_secs() % 2 == 1);
+        if text == self.status_bar && flash == self.status_flash {
+            return;
+        }
+
+        /* Restore the border where the previous status bar was longer */
+        self.moveto(LINES() - 1, 2);
+        self.addnch(ACS_HLINE(), self.status_bar.chars().count() as i32);
+        self.moveto(LINES() - 1, 2);
+        if flash {
+            attr_on(A_STANDOUT());
+        }
+        self.addstring(&text);
+        if flash {
+            attr_off(A_STANDOUT());
+        }
+        self.status_bar = text;
+        self.status_flash = flash;
+    }
+
+    /// Set the file name which is scrolled across the UI.
+    pub fn set_file(&mut self, file: &str) {
+        let filename = Path::new(file).file_name().unwrap().to_string_lossy();
+
+        self.scrolledname = ScrolledBuf::new(filename, COLS() - 8, ScrollDirection::LeftToRight);
+        self.scroll_timer
+            .reset(Duration::from_millis(SCROLL_SHORT_TIME));
+    }
+
     /// Set the metadata display in the TUI.  
-    /// This updates the `Title`, `Album` and `Artist` fields.
+    /// This updates the `Title`, `Album` and `Artist` fields, with the position of the track on
+    /// the album (like `Track 4/9`) after the title, and the release year and genre after the album.
+    /// Any previously displayed values are cleared.
+    /// Values inferred from the path of the file (instead of tags) are shown in italics,
+    /// and the artists are split (see [`Artists`](Artists)) and joined by the artist separator.
+    /// The album artist of compilations is shown after the artists (like `Artist (Various Artists)`).
     pub fn set_track_info(&self, metadata: &AudioMeta) {
-        self.moveto(2, 15);
-        self.addstring(&metadata.title);
-        self.moveto(3, 15);
-        self.addstring(&metadata.album);
-        self.moveto(4, 15);
-        self.addstring(&metadata.artist);
+        let width = (COLS() - 19) as usize;
+        let artist = match metadata.artist.as_str() {
+            "Unknown" => metadata.artist.clone(),
+            tag => Artists::parse(tag).join(&self.artist_separator),
+        };
+        /* Compilations also show whose album it is, as each track has a different artist */
+        let artist = match metadata.album_artist() {
+            album_artist if metadata.is_compilation() && album_artist != metadata.artist => {
+                format!("{artist} ({album_artist})")
+            }
+            _ => artist,
+        };
+        let album = match (metadata.album.as_str(), metadata.year) {
+            (album, Some(year)) if album != "Unknown" => format!("{album} ({year})"),
+            (album, _) => album.to_owned(),
+        };
+        let position = match (metadata.track, metadata.track_total) {
+            (Some(track), Some(total)) => format!("{} {track}/{total}", tr("Track")),
+            (Some(track), None) => format!("{} {track}", tr("Track")),
+            (None, _) => String::new(),
+        };
+        let title_width = width.saturating_sub(8 + POSITION_WIDTH);
+        let album_width = width.saturating_sub(GENRE_WIDTH);
+        let attr = |name| match metadata.is_inferred(name) {
+            true => A_ITALIC(),
+            false => A_NORMAL(),
+        };
+
+        /* The favorite indicator and the rating are shown at the end of the title's line */
+        for (ypos, name, text) in [
+            (2, "title", format!("{:1$.1$}", metadata.title, title_width)),
+            (3, "album", format!("{album:album_width$.album_width$}")),
+            (4, "artist", format!("{artist:width$}")),
+        ] {
+            self.moveto(ypos, 15);
+            attr_on(attr(name));
+            self.addstring(&text);
+            attr_off(attr(name));
+        }
+
+        self.moveto(2, 15 + title_width as i32);
+        attr_on(attr("track") | A_DIM());
+        self.addstring(&format!("{position:>0$} ", POSITION_WIDTH - 1));
+        attr_off(attr("track") | A_DIM());
+
+        let genre = metadata.genre.as_deref().unwrap_or_default();
+        let genre_attr = self.genre_attr(metadata.genre.as_deref());
+        self.moveto(3, 15 + album_width as i32);
+        attr_on(genre_attr);
+        self.addstring(&format!("{genre:>0$.0$}", GENRE_WIDTH));
+        attr_off(genre_attr);
     }
 
-    /// Set the track length display in the TUI.
+    /// Display the rating of the track as stars, at the end of the title's line.
+    ///
+    /// # Arguments
+    /// * `rating` - The rating (from 1 to 5 stars), or `None` if the track isn't rated
+    pub fn set_rating(&self, rating: Option<u8>) {
+        self.moveto(2, COLS() - 9);
+        match rating {
+            Some(rating) => self.addstring(&stars(rating)),
+            None => self.addstr("     "),
+        }
+    }
+
+    /// Display a heart before the rating if the track is in the favorites.
+    pub fn set_favorite(&self, favorite: bool) {
+        self.moveto(2, COLS() - 11);
+        match favorite {
+            true => self.addstring("♥"),
+            false => self.addstr(" "),
+        }
+    }
+
+    /// Display suggested metadata (e.g. from MusicBrainz) below the metadata display,
+    /// along with the keys to accept or dismiss it.
+    pub fn set_suggestion(&self, meta: &AudioMeta) {
+        self.set_prompt(&format!(
+            "Suggested: {} / {} / {} - [C] Accept, [N] Dismiss",
+            meta.title, meta.album, meta.artist
+        ));
+    }
+
+    /// Display the position where playback stopped in the previous session,
+    /// along with the keys to resume or start over.
+    pub fn set_resume_prompt(&self, position: f64) {
+        let position = position as u64;
+        self.set_prompt(&format!(
+            "Resume at {:02}:{:02}? - [C] Resume, [N] Start over",
+            position / 60,
+            position % 60
+        ));
+    }
+
+    /// Display a preview of the changes that will be written to the file,
+    /// along with the keys to confirm or cancel writing.
+    pub fn set_tag_preview(&self, changes: &[TagChange]) {
+        let changes = changes
+            .iter()
+            .map(|change| format!("{}: {} -> {}", change.field, change.old, change.new))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        self.set_prompt(&format!("Write {changes}? - [C] Write, [N] Cancel"));
+    }
+
+    /// Display a preview of the missing tags that will be written to the files of the queue,
+    /// along with the keys to confirm or cancel writing (the changes of each file follow them).
+    pub fn set_tag_fix_preview(&self, fixes: &[TagFix]) {
+        let count: usize = fixes.iter().map(|fix| fix.writer.changes().len()).sum();
+        let details = fixes
+            .iter()
+            .map(|fix| {
+                let name = Path::new(&fix.file)
+                    .file_name()
+                    .map_or(fix.file.clone(), |name| name.to_string_lossy().into_owned());
+                let changes = fix
+                    .writer
+                    .changes()
+                    .iter()
+                    .map(|change| format!("{}: {}", change.field, change.new))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("{name} ({changes})")
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        self.set_prompt(&format!(
+            "Write {count} tags to {} files? - [C] Write, [N] Cancel - {details}",
+            fixes.len()
+        ));
+    }
+
+    /// Clears the suggestion or tag preview.
+    pub fn clear_prompt(&self) {
+        self.set_prompt("");
+    }
+
+    /// Draws a prompt below the metadata display.
+    fn set_prompt(&self, text: &str) {
+        let width = (COLS() - 8) as usize;
+
+        self.moveto(5, 4);
+        attr_on(A_ITALIC());
+        self.addstring(&format!("{:width$.width$}", text));
+        attr_off(A_ITALIC());
+    }
+
+    /// Set the track length display in the TUI.  
+    /// If the length is unknown (`0.0`), `--:--` is shown.
     pub fn set_track_length(&self, time: f64) {
+        /* The progress bar of the new track is drawn from scratch */
+        self.invalidate(&[Region::Playtime, Region::Progress]);
+        if time <= 0.0 {
+            self.moveto(LINES() - 5, COLS() - 8);
+            self.addstr("--:--");
+            return;
+        }
+
         self.print_pretty_time(LINES() - 5, COLS() - 8, time);
     }
 
     /// Update the current playback time and progress bar in the TUI.  
-    /// If you're looking for the progress bar implementation, check [`Display::set_progress()`](Self::set_progress()).
-    pub fn update_progress(&self, time: Duration, total_len: f64) {
+    /// If you're looking for the progress bar implementation, check [`Display::set_progress()`](Self::set_progress()).  
+    /// If the length is unknown (`0.0`), the progress bar is left untouched, so it can show
+    /// the health of the buffer instead (see [`Display::set_buffer_health()`](Self::set_buffer_health())).
+    /// If the loudness `envelope` of the track is known, the progress bar shows it.
+    pub fn update_progress(&self, time: Duration, total_len: f64, envelope: Option<&Envelope>) {
         self.set_playtime(time);
-        self.set_progress(time.as_secs_f64(), total_len);
+        if total_len > 0.0 {
+            self.set_progress(time.as_secs_f64(), total_len, envelope);
+        }
+    }
+
+    /// Shows how full the buffer of a stream is, in place of the progress bar.
+    ///
+    /// # Arguments
+    /// * `level` - How full the buffer is, from `0.0` to `1.0`
+    pub fn set_buffer_health(&self, level: f64) {
+        let label = format!("Buffer {:3.0}% ", level * 100.0);
+        let max_block_count = ((COLS() - 12) - 15) - 1 - label.len() as i32;
+        let use_blocks = ((level * max_block_count as f64) as i32).clamp(0, max_block_count);
+
+        self.redraw(Region::Progress, (&label, use_blocks), || {
+            self.moveto(LINES() - 5, 17);
+            self.addstring(&label);
+            for _ in 0..use_blocks {
+                self.addwchar(0x2587u32);
+            }
+            for _ in use_blocks..max_block_count {
+                self.addchar(' ');
+            }
+        });
     }
 
     /// Set the current playback time in the TUI.
     fn set_playtime(&self, time: Duration) {
-        self.print_pretty_time(LINES() - 5, 9, time.as_secs_f64());
+        self.redraw(Region::Playtime, time.as_secs(), || {
+            self.print_pretty_time(LINES() - 5, 9, time.as_secs_f64())
+        });
+    }
+
+    /// Shows the structure of the song (where the chorus is sung) below the progress bar.
+    /// The chorus is drawn as a thick line, the other sections aren't marked.
+    ///
+    /// # Arguments
+    /// * `sections` - Where the sections start, see [`LyricsTimeline::sections()`](crate::lyrics::LyricsTimeline::sections)
+    /// * `total_len` - Length of the track in seconds (nothing is marked if it's unknown)
+    pub fn set_structure(&self, sections: &[(Duration, Section)], total_len: f64) {
+        let max_block_count = ((COLS() - 12) - 15) - 1;
+        let sections = match total_len > 0.0 {
+            true => sections,
+            false => &[],
+        };
+
+        self.moveto(LINES() - 4, 17);
+        for block in 0..max_block_count {
+            let time = Duration::from_secs_f64(
+                (block as f64 + 0.5) / max_block_count as f64 * total_len.max(0.0),
+            );
+            let section = sections
+                .iter()
+                .rev()
+                .find(|(start, _)| *start <= time)
+                .map(|(_, section)| *section);
+
+            match section {
+                Some(Section::Chorus) => {
+                    attr_on(A_BOLD());
+                    self.addwchar(0x2501u32);
+                    attr_off(A_BOLD());
+                }
+                _ => {
+                    addch(ACS_HLINE());
+                }
+            }
+        }
     }
 
     /// Calculate the progress bar blocks and print them to the TUI.
-    pub fn set_progress(&self, played: f64, total_len: f64) {
+    pub fn set_progress(&self, played: f64, total_len: f64, envelope: Option<&Envelope>) {
         let max_block_count = ((COLS() - 12) - 15) - 1;
         let mut use_blocks =
             Display::map(played, 0.0, total_len, 0.0, max_block_count as f64) as i32;
@@ -331,36 +1220,212 @@ impl Display {
         // Constrain
         use_blocks = use_blocks.clamp(0, max_block_count);
 
-        self.print_progress_blocks(use_blocks, max_block_count);
+        self.redraw(
+            Region::Progress,
+            (use_blocks, max_block_count, envelope.is_some()),
+            || self.print_progress_blocks(use_blocks, max_block_count, envelope),
+        );
     }
 
-    /// Update the file quality display in the TUI.
-    pub fn set_file_quality(&self, fileinfo: &AudioFile) {
+    /// Update the file quality display in the TUI.  
+    /// If the file has a [`codec_mode`](AudioFile::codec_mode), it's shown in parentheses after the format.
+    /// Downmixing, the [channel mode](ChannelMode), summing to mono and night mode are shown
+    /// in parentheses after the layout.
+    ///
+    /// The `output` is the format the output plays at and the conversion for it (e.g. resampling).
+    /// It's shown at the end (after `->`), so it can be compared with the file's format.
+    pub fn set_file_quality(
+        &self,
+        fileinfo: &AudioFile,
+        downmixed: bool,
+        mode: ChannelMode,
+        mono: bool,
+        night: bool,
+        output: (Option<OutputFormat>, Option<&str>),
+    ) {
+        let width = (COLS() - 8) as usize;
+        let notes: Vec<&str> = downmixed
+            .then_some("Downmixed")
+            .into_iter()
+            .chain(mode.label())
+            .chain(mono.then_some("M0NO"))
+            .chain(night.then_some("Night"))
+            .collect();
+
         self.moveto(6, 4);
         self.addstring(&format!(
-            "{} Hz, {}, {} {}",
-            fileinfo.sample_rate,
-            match fileinfo.stereo {
-                true => "Stereo",
-                false => "Mono",
-            },
-            match fileinfo.lossless {
-                true => "Lossless",
-                false => "Lossy",
-            },
-            fileinfo.format,
+            "{:width$}",
+            format!(
+                "{} Hz, {}{}, {} {}{}{}",
+                fileinfo.sample_rate,
+                fileinfo.layout,
+                match notes.is_empty() {
+                    true => String::new(),
+                    false => format!(" ({})", notes.join(", ")),
+                },
+                match fileinfo.lossless {
+                    true => "Lossless",
+                    false => "Lossy",
+                },
+                fileinfo.format,
+                match &fileinfo.codec_mode {
+                    Some(mode) => format!(" ({mode})"),
+                    None => String::new(),
+                },
+                match output {
+                    (Some(output), Some(conversion)) => format!(" -> {output} ({conversion})"),
+                    (Some(output), None) => format!(" -> {output}"),
+                    (None, Some(conversion)) => format!(" -> {conversion}"),
+                    (None, None) => String::new(),
+                },
+            )
         ));
     }
 
+    /// Shows the gain applied to the audio, below the file quality line.
+    ///
+    /// # Arguments
+    /// * `gain_db` - The gain currently applied (in dB)
+    /// * `reduction_db` - How much of the gain is taken away by the limiter (in dB)
+    pub fn set_gain(&self, gain_db: f32, reduction_db: f32) {
+        let width = (COLS() - 8) as usize;
+        let text = match reduction_db >= 0.05 {
+            true => format!("Gain: {gain_db:+.1} dB (limiting {reduction_db:.1} dB)"),
+            false => format!("Gain: {gain_db:+.1} dB"),
+        };
+
+        self.redraw(Region::Gain, &text, || {
+            self.moveto(7, 4);
+            self.addstring(&format!("{text:width$}"));
+            /* The gain is padded over the rest of the line */
+            self.invalidate(&[Region::Levels, Region::Clipping, Region::TimeSaved]);
+        });
+    }
+
+    /// Shows the peak levels of the played audio, in the middle of the line below the file quality.
+    /// Levels below -99 dBFS are shown as `-inf`.
+    ///
+    /// # Arguments
+    /// * `current_db` - Peak level of the audio played right now (in dBFS)
+    /// * `session_db` - Highest peak level played in this session (in dBFS)
+    pub fn set_levels(&self, current_db: f32, session_db: f32) {
+        let format = |db: f32| match db > -99.0 {
+            true => format!("{db:.1}"),
+            false => "-inf".to_owned(),
+        };
+
+        let text = format!(
+            "Peak: {} dBFS (max {})",
+            format(current_db),
+            format(session_db)
+        );
+
+        self.redraw(Region::Levels, &text, || {
+            self.moveto(7, COLS() / 2 - 8);
+            self.addstring(&format!("{text:28}"));
+        });
+    }
+
+    /// Shows how many times the played audio clipped, after the peak levels.
+    /// Nothing is shown until the audio clips, and the indicator lights up while it's clipping.
+    ///
+    /// # Arguments
+    /// * `clips` - How many times the audio clipped in this session
+    /// * `clipping` - Whether the audio played right now clips
+    pub fn set_clipping(&self, clips: u64, clipping: bool) {
+        const WIDTH: usize = 10;
+        let (pair, _) = CLIP_COLOR;
+        let attr = match clipping {
+            true => self.color_attr(pair) | A_BOLD() | A_REVERSE(),
+            false => self.color_attr(pair) | A_BOLD(),
+        };
+
+        let text = match clips {
+            0 => String::new(),
+            clips => format!("CLIP {}", clips.min(9999)),
+        };
+
+        self.redraw(Region::Clipping, (&text, clipping), || {
+            self.moveto(7, COLS() / 2 + 21);
+            attr_on(attr);
+            self.addstring(&text);
+            attr_off(attr);
+            self.addstring(&" ".repeat(WIDTH - text.len()));
+        });
+    }
+
+    /// Shows how much silence was skipped, at the end of the line below the file quality.
+    /// If `saved` is `None`, it's cleared.
+    pub fn set_time_saved(&self, saved: Option<Duration>) {
+        const WIDTH: usize = 14;
+
+        let text = match saved {
+            Some(saved) => format!(
+                "Saved: {:02}:{:02}",
+                saved.as_secs() / 60,
+                saved.as_secs() % 60
+            ),
+            None => String::new(),
+        };
+
+        self.redraw(Region::TimeSaved, &text, || {
+            self.moveto(7, COLS() - 4 - WIDTH as i32);
+            self.addstring(&format!("{text:>WIDTH$}"));
+        });
+    }
+
+    /// Display how many times the track was played and skipped,
+    /// at the end of the file quality line.
+    pub fn set_play_count(&self, count: &PlayCount) {
+        let text = format!("Plays: {} | Skips: {}", count.plays, count.skips);
+
+        self.moveto(6, COLS() - 4 - text.len() as i32);
+        self.addstring(&text);
+    }
+
     /// Update the progress bar in the TUI.  
     /// Unicode character 0x2587 is used as the "block" character.
-    fn print_progress_blocks(&self, count: i32, total_space: i32) {
+    ///
+    /// If the loudness `envelope` of the track is known, the rest of the track is shown
+    /// with character 0x2591 (a light shade), and every block is dimmed or brightened
+    /// by the loudness of its part of the track.
+    fn print_progress_blocks(&self, count: i32, total_space: i32, envelope: Option<&Envelope>) {
+        /* The played blocks are colored along the gradient, if the terminal can show it */
+        let color = |block: i32| {
+            self.gradient_attr(block as f32 / total_space.max(1) as f32)
+                .filter(|_| block < count)
+                .unwrap_or(A_NORMAL())
+        };
+
         self.moveto(LINES() - 5, 17);
-        for _ in 0..count {
-            self.addwchar(0x2587u32);
-        }
-        for _ in count..total_space {
-            self.addchar(' ');
+        let Some(envelope) = envelope else {
+            for block in 0..count {
+                attr_on(color(block));
+                self.addwchar(0x2587u32);
+                attr_off(color(block));
+            }
+            for _ in count..total_space {
+                self.addchar(' ');
+            }
+            return;
+        };
+
+        for block in 0..total_space {
+            let attr = match envelope.loudness(
+                block as f64 / total_space as f64,
+                (block + 1) as f64 / total_space as f64,
+            ) {
+                Loudness::Quiet => A_DIM(),
+                Loudness::Normal => A_NORMAL(),
+                Loudness::Loud => A_BOLD(),
+            } | color(block);
+
+            attr_on(attr);
+            self.addwchar(match block < count {
+                true => 0x2587u32,
+                false => 0x2591u32,
+            });
+            attr_off(attr);
         }
     }
 
@@ -390,12 +1455,18 @@ impl Display {
         if self.message_timer.is_some() {
             self.clear_status_message();
         }
+        self.clear_osd();
 
         self.moveto(LINES() - STATUSMSG_OFFSET, xpos);
         attr_on(A_STANDOUT());
         self.addstring(&message);
         attr_off(A_STANDOUT());
-        self.message_timer = Some(Instant::now());
+        self.message_timer = Some(Timer::new(Duration::from_secs(STATUSMSG_DISPLAYTIME)));
+    }
+
+    /// Returns whether a status message is being displayed.
+    pub fn has_status_message(&self) -> bool {
+        self.message_timer.is_some()
     }
 
     /// Clears the currently displayed status message.
@@ -421,11 +1492,105 @@ impl Display {
     /// ## Note #2
     /// For good accuracy, this function should be called as often as possible.
     pub fn staus_message_tick(&mut self) {
-        if let Some(timer) = &self.message_timer {
-            if timer.elapsed().as_secs() >= STATUSMSG_DISPLAYTIME {
-                self.clear_status_message();
+        if self.message_timer.is_some_and(|timer| timer.expired()) {
+            self.clear_status_message();
+        }
+    }
+
+    /// Shows an on-screen display widget in place of the status message,
+    /// replacing the status message or the widget which is being shown.
+    pub fn show_osd(&mut self, osd: Osd) {
+        self.clear_status_message();
+        self.osd = Some(ShownOsd {
+            osd,
+            timer: Timer::new(Duration::from_millis(OSD_DISPLAY_TIME)),
+            faded: false,
+        });
+        self.draw_osd(osd, false);
+    }
+
+    /// Returns whether an on-screen display widget is being shown.
+    pub fn has_osd(&self) -> bool {
+        self.osd.is_some()
+    }
+
+    /// Fades the on-screen display widget once it's about to expire, and removes it
+    /// once it expired.
+    ///
+    /// ### Notes
+    /// Can be safely called even if no widget is being shown.
+    /// For good accuracy, this function should be called as often as possible.
+    pub fn osd_tick(&mut self) {
+        let Some(shown) = &mut self.osd else {
+            return;
+        };
+
+        if shown.timer.expired() {
+            self.clear_osd();
+        } else if !shown.faded && shown.timer.remaining() <= Duration::from_millis(OSD_FADE_TIME) {
+            shown.faded = true;
+            let osd = shown.osd;
+            self.draw_osd(osd, true);
+        }
+    }
+
+    /// Removes the on-screen display widget (if any).
+    fn clear_osd(&mut self) {
+        if self.osd.take().is_none() {
+            return;
+        }
+        self.moveto(LINES() - STATUSMSG_OFFSET, 1);
+        self.addnch(' ' as u32, COLS() - 4);
+    }
+
+    /// Draws an on-screen display widget, centered on the line of the status message.
+    fn draw_osd(&self, osd: Osd, faded: bool) {
+        let (text, attr) = match osd {
+            Osd::Volume { volume, muted } => {
+                let filled = (volume as usize * VOLUME_BAR_WIDTH).div_ceil(100);
+                let bar = format!(
+                    "{}{}",
+                    "█".repeat(filled),
+                    "░".repeat(VOLUME_BAR_WIDTH - filled)
+                );
+                match muted {
+                    true => (format!("[ {} {bar} {volume}% ]", tr("Muted")), A_DIM()),
+                    false => (format!("[ {} {bar} {volume}% ]", tr("Volume")), A_BOLD()),
+                }
             }
+        };
+        let attr = match faded {
+            true => A_DIM(),
+            false => attr,
+        };
+
+        let xpos = (COLS() / 2) - (text.chars().count() as i32 / 2);
+        self.moveto(LINES() - STATUSMSG_OFFSET, 1);
+        self.addnch(' ' as u32, COLS() - 4);
+        self.moveto(LINES() - STATUSMSG_OFFSET, xpos);
+        attr_on(attr);
+        self.addstring(&text);
+        attr_off(attr);
+    }
+
+    /// Shows the current time and how long was listened in this session (`listened`)
+    /// in the top right corner. Only the clock is redrawn, once per second,
+    /// so this function can be called on every tick.
+    pub fn handle_clock(&mut self, listened: Duration) {
+        if !self.clock_timer.tick() {
+            return;
         }
+
+        let listened = listened.as_secs();
+        let text = format!(
+            "[ {} | {}:{:02}:{:02} ]",
+            local_clock(),
+            listened / 3600,
+            listened / 60 % 60,
+            listened % 60
+        );
+        self.moveto(0, COLS() - 2 - text.len() as i32);
+        self.addstring(&text);
     }
 
     /// Handles scrolling the file name
@@ -439,18 +1604,166 @@ impl Display {
         self.addstr(&self.scrolledname.current_frame());
         if self.scrolledname.is_finished() {
             self.scrolledname.swap_direction();
-            self.scroll_timer = Timer::new(Duration::from_millis(SCROLL_PAUSE_TIME));
+            self.scroll_timer
+                .reset(Duration::from_millis(SCROLL_PAUSE_TIME));
         } else {
-            self.scroll_timer = Timer::new(Duration::from_millis(SCROLL_SHORT_TIME));
+            self.scroll_timer
+                .reset(Duration::from_millis(SCROLL_SHORT_TIME));
         }
         self.scrolledname.next_frame();
     }
 }
 
+/// This implementation adds functions to draw the visualizer, between the lyrics and the progress bar.
+impl Display {
+    /// Sets the [theme](Theme) of the TUI, and the color of the clipping indicator.
+    /// The colors of the visualizer are set separately, as they can be configured on their own.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        if has_colors() {
+            init_pair(CLIP_COLOR.0, curses_color(theme.clip_color()), -1);
+        }
+        /* Everything is drawn again, in the new colors */
+        self.drawn.borrow_mut().clear();
+    }
+
+    /// Sets the colors of genres, which color the genre in the track info, and the tracks
+    /// in the queue and the search results.
+    ///
+    /// # Arguments
+    /// * `colors` - Names of genres and their colors (e.g. `jazz` and blue). Genres which contain
+    ///   a name (__not__ case-sensitive) are in its color, like `Jazz Fusion` in the color of `jazz`.
+    pub fn set_genre_colors(&mut self, colors: &BTreeMap<String, Color>) {
+        self.genre_colors = colors
+            .iter()
+            .map(|(genre, color)| (genre.to_lowercase(), *color))
+            .collect();
+        /* The longest (most specific) names are matched first, like `death metal` before `metal` */
+        self.genre_colors
+            .sort_by_key(|(genre, _)| Reverse(genre.chars().count()));
+
+        if has_colors() {
+            for (_, color) in &self.genre_colors {
+                init_pair(GENRE_PAIR + *color as i16, curses_color(*color), -1);
+            }
+        }
+        self.drawn.borrow_mut().clear();
+    }
+
+    /// Returns the attributes to draw a genre (or a track of it) in, see
+    /// [`set_genre_colors()`](Display::set_genre_colors).
+    fn genre_attr(&self, genre: Option<&str>) -> attr_t {
+        let Some(genre) = genre.map(str::to_lowercase) else {
+            return A_NORMAL();
+        };
+
+        self.genre_colors
+            .iter()
+            .find(|(name, _)| genre.contains(name.as_str()))
+            .map_or(A_NORMAL(), |(_, color)| {
+                self.color_attr(GENRE_PAIR + *color as i16)
+            })
+    }
+
+    /// Returns the attributes to draw in the given color pair.
+    /// Colors are left out if the theme has none, and the high contrast theme also makes them bold.
+    fn color_attr(&self, pair: i16) -> attr_t {
+        match self.theme {
+            Theme::NoColor => A_NORMAL(),
+            Theme::HighContrast => COLOR_PAIR(pair) | A_BOLD(),
+            Theme::Default | Theme::Deuteranopia => COLOR_PAIR(pair),
+        }
+    }
+
+    /// Sets the colors of the visualizer (if the terminal supports colors).
+    /// On terminals with 256 colors (or truecolor), they're blended into a gradient,
+    /// which is also used by the progress bar.
+    ///
+    /// # Arguments
+    /// * `colors` - Colors from the quietest to the loudest parts of the audio
+    pub fn set_visualizer_colors(&mut self, colors: [Color; 3]) {
+        if !has_colors() {
+            return;
+        }
+
+        for ((pair, _), color) in VISUALIZER_COLORS.into_iter().zip(colors) {
+            init_pair(pair, curses_color(color), -1);
+        }
+
+        self.gradient = self.theme.has_colors() && self.color_depth != ColorDepth::Basic;
+        if self.gradient {
+            for (pair, rgb) in (GRADIENT_PAIR..).zip(theme::gradient(colors, GRADIENT_STEPS)) {
+                if let Some(color) = self.color_depth.color(rgb) {
+                    init_extended_pair(pair as i32, color, -1);
+                }
+            }
+        }
+        self.invalidate(&[Region::Progress]);
+    }
+
+    /// Returns the attributes to draw in the color of the gradient at `level`
+    /// (from `0.0` to `1.0`), or `None` if the terminal can't show the gradient.
+    fn gradient_attr(&self, level: f32) -> Option<attr_t> {
+        if !self.gradient {
+            return None;
+        }
+
+        let step = (level.clamp(0.0, 1.0) * (GRADIENT_STEPS - 1) as f32).round() as i16;
+        Some(self.color_attr(GRADIENT_PAIR + step))
+    }
+
+    /// Returns the size of the visualizer (in characters), as its width and height.
+    pub fn visualizer_size(&self) -> (usize, usize) {
+        (
+            (COLS() - 8) as usize,
+            (LINES() - STATUSMSG_OFFSET - VISUALIZER_OFFSET) as usize,
+        )
+    }
+
+    /// Draws a frame of the visualizer, colored by the heat of its characters.
+    ///
+    /// # Arguments
+    /// * `frame` - The frame, as large as [`visualizer_size()`](Self::visualizer_size)
+    pub fn set_visualizer(&self, frame: &Frame) {
+        for (ypos, row) in (VISUALIZER_OFFSET..LINES() - STATUSMSG_OFFSET).zip(&frame.cells) {
+            self.moveto(ypos, 4);
+            for cell in row {
+                let (pair, _) = VISUALIZER_COLORS[match cell.heat {
+                    Heat::Low => 0,
+                    Heat::Medium => 1,
+                    Heat::High => 2,
+                }];
+                /* Without colors, the heat is shown by the brightness */
+                let attr = match (self.gradient_attr(cell.level), self.theme.has_colors()) {
+                    (Some(attr), _) => attr,
+                    (None, true) => self.color_attr(pair),
+                    (None, false) => match cell.heat {
+                        Heat::Low => A_DIM(),
+                        Heat::Medium => A_NORMAL(),
+                        Heat::High => A_BOLD(),
+                    },
+                };
+                attr_on(attr);
+                self.addwchar(cell.symbol as u32);
+                attr_off(attr);
+            }
+        }
+    }
+
+    /// Clears the visualizer.
+    pub fn clear_visualizer(&self) {
+        for ypos in VISUALIZER_OFFSET..LINES() - STATUSMSG_OFFSET {
+            self.moveto(ypos, 4);
+            self.addnch(' ' as u32, COLS() - 8);
+        }
+    }
+}
+
 /// This implementation adds functions to use the `Lyrics` subwindow.
 impl Display {
     /// Clear all text inside the `Lyrics` subwindow.
     pub fn clear_infoview(&self) {
+        self.invalidate(&[Region::Lyrics]);
         for ypos in 1..5 {
             for xpos in 2..COLS() - 9 {
                 self.wmoveto(ypos, xpos, self.infoview);
@@ -459,67 +1772,554 @@ impl Display {
         }
     }
 
-    /// Refresh the Lyrics subwindow.
+    /// Refresh the Lyrics subwindow.  
+    /// It's drawn into an off-screen pad, which is copied to the screen here and applied at once
+    /// (with `doupdate()`), so it never shows up half-drawn (e.g. cleared, but without the new lyrics).
     pub fn refresh_infoview(&self) {
-        wrefresh(self.infoview);
+        pnoutrefresh(
+            self.infoview,
+            0,
+            0,
+            INFOVIEW_OFFSET,
+            4,
+            INFOVIEW_OFFSET + 5,
+            COLS() - 5,
+        );
+        doupdate();
+        self.redraws.set(self.redraws.get() + 1);
+    }
+
+    /// Returns how many times the screen was redrawn so far.
+    pub fn redraws(&self) -> u64 {
+        self.redraws.get()
     }
 
-    /// Set the `Lyrics` subwindow to display the "Unavailable" message.
-    pub fn set_unavailable(&self) {
+    /// Set the `Lyrics` subwindow to display why the lyrics couldn't be loaded,
+    /// with the details of the error (if any) below it.
+    pub fn set_lyrics_error(&self, error: &LyricsError) {
         self.clear_infoview();
         self.wmoveto(1, 2, self.infoview);
         wattron(self.infoview, A_ITALIC());
-        self.waddstr("Unavailable", self.infoview);
+        self.waddstr(error.summary(), self.infoview);
         wattroff(self.infoview, A_ITALIC());
+
+        if let Some(details) = error.details() {
+            let width = (COLS() - 13) as usize;
+            self.wmoveto(2, 2, self.infoview);
+            wattron(self.infoview, A_DIM());
+            self.waddstring(
+                &details.chars().take(width).collect::<String>(),
+                self.infoview,
+            );
+            wattroff(self.infoview, A_DIM());
+        }
     }
 
-    /// Display a [`LyricsBank`](LyricsBank).
-    pub fn set_lyrics_bank(&self, bank: &LyricsBank) {
+    /// Display a [`LyricsPage`](LyricsPage) with its active line highlighted,
+    /// and refresh the `Lyrics` subwindow. Nothing is redrawn if the same lines are already shown.
+    pub fn set_lyrics(&self, page: &LyricsPage) {
+        let lines: Vec<(&str, Option<&str>)> = page
+            .lines
+            .iter()
+            .map(|line| (line.words.as_str(), line.translation.as_deref()))
+            .collect();
+
+        self.redraw(Region::Lyrics, (lines, page.active), || {
+            self.set_lyrics_page(page);
+            self.refresh_infoview();
+        });
+    }
+
+    /// Display a [`LyricsPage`](LyricsPage) with its active line highlighted.
+    /// Translations are shown after the lines, as far as they fit.
+    /// Lines are styled by their section, see [`lyrics_attr()`](lyrics_attr).
+    pub fn set_lyrics_page(&self, page: &LyricsPage) {
         self.clear_infoview();
-        let mut ypos = 1;
+        let width = (COLS() - 13) as usize;
 
-        for line in bank.lines.iter().take(LYRICS_BANK_SIZE) {
+        for (ypos, line) in (1..).zip(page.lines.iter().take(LYRICS_PAGE_SIZE)) {
             self.wmoveto(ypos, 2, self.infoview);
             self.waddstr("   ", self.infoview);
+            let mut used = line.words.chars().count();
+            if let Some(singer) = &line.singer {
+                wattron(self.infoview, A_DIM());
+                self.waddstring(&format!("{singer}: "), self.infoview);
+                wattroff(self.infoview, A_DIM());
+                used += singer.chars().count() + 2;
+            }
+            wattron(self.infoview, lyrics_attr(line));
             self.waddstring(&line.words, self.infoview);
-            ypos += 1;
+            if let Some(translation) = &line.translation {
+                let room = width.saturating_sub(used + 3);
+                if room > 0 {
+                    let translation: String = translation.chars().take(room).collect();
+                    self.waddstring(&format!(" - {translation}"), self.infoview);
+                }
+            }
+            wattroff(self.infoview, lyrics_attr(line));
+        }
+
+        self.set_active_lyrics_line(page);
+    }
+
+    /// Display an overlay (like the debug statistics or the details of the track)
+    /// instead of the lyrics.
+    ///
+    /// # Arguments
+    /// * `rows` - Names and values of the shown rows, in two columns of 4
+    ///   (only the first 8 fit)
+    pub fn set_overlay(&self, rows: &[(&str, String)]) {
+        self.clear_infoview();
+        let columns = [2, (COLS() - 8) / 2];
+        for (index, (name, value)) in rows.iter().take(8).enumerate() {
+            self.wmoveto(1 + index as i32 % 4, columns[index / 4], self.infoview);
+            self.waddstring(&format!("   {name:16}{value}"), self.infoview);
         }
     }
 
-    /// Highlight a line of lyrics.
-    /// If `active` is `None`, none of the lines will be highlighted.
-    pub fn set_active_lyrics_line(&self, active: &Option<usize>) {
+    /// Highlight the active line of a [`LyricsPage`](LyricsPage).
+    /// If it has no active line, none of the lines will be highlighted.
+    fn set_active_lyrics_line(&self, page: &LyricsPage) {
         for ypos in 1..5 {
+            let attr = page.lines.get(ypos as usize - 1).map_or(0, lyrics_attr);
             self.wmoveto(ypos, 2, self.infoview);
             self.waddstr("   ", self.infoview);
-            wchgat(self.infoview, COLS() - 9 - 5, 0, COLOR_WHITE);
+            wchgat(self.infoview, COLS() - 9 - 5, attr, COLOR_WHITE);
         }
 
-        if active.is_none() {
+        let Some(active) = page.active else {
             return;
-        }
-        let active = active.unwrap();
+        };
 
+        let attr = match self.theme.reverse_highlights() {
+            true => A_BOLD() | A_REVERSE(),
+            false => A_BOLD(),
+        };
         self.wmoveto(1 + active as i32, 2, self.infoview);
         wattron(self.infoview, A_BOLD());
         self.waddstr("-> ", self.infoview);
-        wchgat(self.infoview, COLS() - 9 - 5, A_BOLD(), COLOR_WHITE);
+        wchgat(self.infoview, COLS() - 9 - 5, attr, COLOR_WHITE);
+        wattroff(self.infoview, A_BOLD());
+    }
+}
+
+/// This implementation adds functions to display the search prompt in the `Lyrics` subwindow.
+impl Display {
+    /// Display the search prompt and the results around the selected one.
+    pub fn set_search(&self, search: &Search) {
+        let width = (COLS() - 14) as usize;
+        self.clear_infoview();
+
+        self.wmoveto(1, 2, self.infoview);
+        wattron(self.infoview, A_BOLD());
+        self.waddstring(
+            &format!(
+                "{:width$.width$}",
+                format!(
+                    "Search {}: {}_",
+                    match search.scope() {
+                        SearchScope::Queue => "queue",
+                        SearchScope::Library => "library",
+                    },
+                    search.query()
+                )
+            ),
+            self.infoview,
+        );
         wattroff(self.infoview, A_BOLD());
+
+        /* Keep the selected result visible */
+        let visible = 3;
+        let first = search.selected().saturating_sub(visible - 1);
+        let results = search.results().enumerate().skip(first).take(visible);
+
+        for (ypos, (index, item)) in (2..).zip(results) {
+            let attr = if index == search.selected() {
+                A_REVERSE()
+            } else {
+                A_NORMAL()
+            } | self.genre_attr(item.genre.as_deref());
+
+            self.wmoveto(ypos, 2, self.infoview);
+            wattron(self.infoview, attr);
+            self.waddstring(
+                &format!(
+                    "{:width$.width$}",
+                    format!("{}{}", mark_symbol(search.is_marked(item)), item.label)
+                ),
+                self.infoview,
+            );
+            wattroff(self.infoview, attr);
+        }
+
+        self.refresh_infoview();
     }
 }
 
-impl From<char> for DisplayEvent {
-    fn from(value: char) -> Self {
-        match value {
-            'g' => DisplayEvent::MakePlay,
-            'f' => DisplayEvent::JumpBack,
-            'h' => DisplayEvent::JumpNext,
-            'b' => DisplayEvent::MakePause,
-            'm' => DisplayEvent::ToggleMute,
-            'q' => DisplayEvent::Quit,
-            'y' => DisplayEvent::VolUp,
-            'x' => DisplayEvent::VolDown,
-            c => DisplayEvent::Invalid(c),
+/// This implementation adds functions to display the queue in the `Lyrics` subwindow.
+impl Display {
+    /// Display the entries of the queue around the selected one.
+    /// The current entry is shown in bold.
+    pub fn set_queue(&self, queue: &Queue, view: &QueueView) {
+        let width = (COLS() - 14) as usize;
+        self.clear_infoview();
+
+        self.wmoveto(1, 2, self.infoview);
+        wattron(self.infoview, A_BOLD());
+        self.waddstring(
+            &format!(
+                "{:width$.width$}",
+                format!(
+                    "Queue ({}/{}{}) - [[/]] Move, [X] Remove, [N] Next, [C] Clear, [S] Shuffle, [U] Undo",
+                    view.selected() + 1,
+                    queue.entries().len(),
+                    match view.marked_count() {
+                        0 => String::new(),
+                        marked => format!(", {marked} marked"),
+                    }
+                )
+            ),
+            self.infoview,
+        );
+        wattroff(self.infoview, A_BOLD());
+
+        /* Keep the selected entry visible */
+        let visible = 3;
+        let first = view.selected().saturating_sub(visible - 1);
+        let entries = queue.entries().iter().enumerate().skip(first).take(visible);
+
+        for (ypos, (index, path)) in (2..).zip(entries) {
+            let mut attr = A_NORMAL();
+            if index == view.selected() {
+                attr |= A_REVERSE();
+            }
+            if index == queue.index() {
+                attr |= A_BOLD();
+            }
+            attr |= self.genre_attr(queue.metadata(path).and_then(|meta| meta.genre.as_deref()));
+
+            self.wmoveto(ypos, 2, self.infoview);
+            wattron(self.infoview, attr);
+            self.waddstring(
+                &format!(
+                    "{:width$.width$}",
+                    format!(
+                        "{}{:>3}. {}",
+                        mark_symbol(view.is_marked(index)),
+                        index + 1,
+                        view.label(path)
+                    )
+                ),
+                self.infoview,
+            );
+            wattroff(self.infoview, attr);
         }
+
+        self.refresh_infoview();
+    }
+}
+
+/// This implementation adds functions to display the add-files prompt in the `Lyrics` subwindow.
+impl Display {
+    /// Display the prompt for adding files to the queue, with the path typed so far.
+    pub fn set_add_prompt(&self, input: &str) {
+        let width = (COLS() - 14) as usize;
+        self.clear_infoview();
+
+        self.wmoveto(1, 2, self.infoview);
+        wattron(self.infoview, A_BOLD());
+        self.waddstring(
+            &format!("{:width$.width$}", format!("Add to queue: {input}_")),
+            self.infoview,
+        );
+        wattroff(self.infoview, A_BOLD());
+
+        self.wmoveto(2, 2, self.infoview);
+        wattron(self.infoview, A_ITALIC());
+        self.waddstr(
+            "A file, directory or playlist - [Tab] Complete, [Enter] Add, [Esc] Cancel",
+            self.infoview,
+        );
+        wattroff(self.infoview, A_ITALIC());
+
+        self.refresh_infoview();
+    }
+}
+
+/// This implementation adds functions to display the quick launcher in the `Lyrics` subwindow.
+impl Display {
+    /// Display the quick launcher, with the query typed so far and the matching tracks.
+    pub fn set_launcher(&self, launcher: &Launcher) {
+        let width = (COLS() - 14) as usize;
+        self.clear_infoview();
+
+        self.wmoveto(1, 2, self.infoview);
+        wattron(self.infoview, A_BOLD());
+        self.waddstring(
+            &format!(
+                "{:width$.width$}",
+                format!(
+                    "Play: {}_ ({} found) - [Enter] Play, [Tab] Enqueue, [Esc] Close",
+                    launcher.query(),
+                    launcher.results().len()
+                )
+            ),
+            self.infoview,
+        );
+        wattroff(self.infoview, A_BOLD());
+
+        /* Keep the selected result visible */
+        let visible = 3;
+        let first = launcher.selected().saturating_sub(visible - 1);
+        let results = launcher
+            .results()
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(visible);
+
+        for (ypos, (index, item)) in (2..).zip(results) {
+            let attr = if index == launcher.selected() {
+                A_REVERSE()
+            } else {
+                A_NORMAL()
+            };
+
+            self.wmoveto(ypos, 2, self.infoview);
+            wattron(self.infoview, attr);
+            self.waddstring(
+                &format!(
+                    "{:width$.width$}",
+                    format!("{}{}", mark_symbol(launcher.is_marked(item)), item.label)
+                ),
+                self.infoview,
+            );
+            wattroff(self.infoview, attr);
+        }
+
+        self.refresh_infoview();
+    }
+}
+
+/// This implementation adds functions to display the command prompt in the `Lyrics` subwindow.
+impl Display {
+    /// Display the command prompt, with the command typed so far.
+    pub fn set_command_prompt(&self, input: &str) {
+        let width = (COLS() - 14) as usize;
+        self.clear_infoview();
+
+        self.wmoveto(1, 2, self.infoview);
+        wattron(self.infoview, A_BOLD());
+        self.waddstring(
+            &format!("{:width$.width$}", format!(":{input}_")),
+            self.infoview,
+        );
+        wattroff(self.infoview, A_BOLD());
+
+        self.wmoveto(2, 2, self.infoview);
+        wattron(self.infoview, A_ITALIC());
+        self.waddstring(
+            &format!(
+                "{:width$.width$}",
+                format!("Commands: {}", command::COMMANDS.join(", "))
+            ),
+            self.infoview,
+        );
+        self.wmoveto(3, 2, self.infoview);
+        self.waddstr("[Enter] Run, [Esc] Cancel", self.infoview);
+        wattroff(self.infoview, A_ITALIC());
+
+        self.refresh_infoview();
+    }
+}
+
+/// This implementation adds functions to display the jump-to-time prompt in the `Lyrics` subwindow.
+impl Display {
+    /// Display the jump-to-time prompt, with the position typed so far.
+    pub fn set_jump_prompt(&self, input: &str) {
+        let width = (COLS() - 14) as usize;
+        self.clear_infoview();
+
+        self.wmoveto(1, 2, self.infoview);
+        wattron(self.infoview, A_BOLD());
+        self.waddstring(
+            &format!("{:width$.width$}", format!("Jump to: {input}_")),
+            self.infoview,
+        );
+        wattroff(self.infoview, A_BOLD());
+
+        self.wmoveto(2, 2, self.infoview);
+        wattron(self.infoview, A_ITALIC());
+        self.waddstr(
+            "mm:ss, h:mm:ss or a percentage (e.g. 1:30 or 50%) - [Enter] Jump, [Esc] Cancel",
+            self.infoview,
+        );
+        wattroff(self.infoview, A_ITALIC());
+
+        self.refresh_infoview();
+    }
+}
+
+/// This implementation adds functions to display the listening history in the `Lyrics` subwindow.
+impl Display {
+    /// Display the visible part of the listening history, newest first.
+    pub fn set_history(&self, history: &HistoryView) {
+        let width = (COLS() - 14) as usize;
+        let now = unix_time(SystemTime::now());
+        self.clear_infoview();
+
+        self.wmoveto(1, 2, self.infoview);
+        wattron(self.infoview, A_BOLD());
+        self.waddstring(
+            &format!(
+                "{:width$.width$}",
+                format!(
+                    "History ({} plays) - [Up/Down] Scroll, [Esc] Close",
+                    history.len()
+                )
+            ),
+            self.infoview,
+        );
+        wattroff(self.infoview, A_BOLD());
+
+        if history.is_empty() {
+            self.wmoveto(2, 2, self.infoview);
+            wattron(self.infoview, A_ITALIC());
+            self.waddstr("Nothing was played yet", self.infoview);
+            wattroff(self.infoview, A_ITALIC());
+        }
+
+        for (ypos, entry) in (2..).zip(history.visible()) {
+            let listened = entry.duration as u64;
+            let line = format!(
+                "{:>10}  [{:02}:{:02}]  {}",
+                history::format_ago(entry.started, now),
+                listened / 60,
+                listened % 60,
+                entry.label()
+            );
+
+            self.wmoveto(ypos, 2, self.infoview);
+            self.waddstring(&format!("{:width$.width$}", line), self.infoview);
+        }
+
+        self.refresh_infoview();
+    }
+}
+
+/// Formats a rating (from 1 to 5 stars) as 5 stars, of which `rating` are filled.
+pub fn stars(rating: u8) -> String {
+    let filled = rating.min(5) as usize;
+    format!(
+        "{}{}",
+        "\u{2605}".repeat(filled),
+        "\u{2606}".repeat(5 - filled)
+    )
+}
+
+/// Returns the symbol shown before marked entries of lists (and a space before other entries).
+fn mark_symbol(marked: bool) -> char {
+    match marked {
+        true => '+',
+        false => ' ',
+    }
+}
+
+/// Returns the local time, as `hh:mm`.
+fn local_clock() -> String {
+    match clock::local_time() {
+        Some(time) => format!("{:02}:{:02}", time.tm_hour, time.tm_min),
+        None => "--:--".to_owned(),
+    }
+}
+
+/// Converts a [`Color`](Color) to its `ncurses` color.
+fn curses_color(color: Color) -> i16 {
+    match color {
+        Color::Black => COLOR_BLACK,
+        Color::Red => COLOR_RED,
+        Color::Green => COLOR_GREEN,
+        Color::Yellow => COLOR_YELLOW,
+        Color::Blue => COLOR_BLUE,
+        Color::Magenta => COLOR_MAGENTA,
+        Color::Cyan => COLOR_CYAN,
+        Color::White => COLOR_WHITE,
+    }
+}
+
+/// Returns the keyboard shortcuts shown at the bottom of the TUI (with the keys of the
+/// [keymap](Keymap)), laid out in their rows.
+/// The exit shortcut is shown on its own at the end of the last row.
+fn control_labels(keymap: &Keymap) -> Vec<ControlLabel> {
+    let mut labels = Vec::new();
+    for (row, controls) in CONTROLS.iter().enumerate() {
+        let mut xpos = 2;
+        for (index, (events, desc)) in controls.iter().enumerate() {
+            let keys: Vec<char> = events
+                .iter()
+                .filter_map(|event| keymap.key(*event))
+                .collect();
+            let names: Vec<String> = keys.iter().map(|key| keymap::key_name(*key)).collect();
+            let text = format!("[{}] {}", names.join("/"), tr(desc));
+            let width = text.chars().count() as i32;
+            labels.push(ControlLabel {
+                ypos: LINES() - 3 + row as i32,
+                xpos,
+                keys,
+                text,
+                separated: index + 1 < controls.len(),
+            });
+            /* The labels are separated by " | " */
+            xpos += width + 3;
+        }
+    }
+
+    let quit = keymap.key(DisplayEvent::Quit).unwrap_or('q');
+    let text = format!("[{}] {}", keymap::key_name(quit), tr("Exit"));
+    labels.push(ControlLabel {
+        ypos: LINES() - 2,
+        xpos: COLS() - 2 - text.chars().count() as i32,
+        keys: vec![quit],
+        text,
+        separated: false,
+    });
+    labels
+}
+
+impl ControlLabel {
+    /// Returns whether a position is on the label.
+    fn contains(&self, ypos: i32, xpos: i32) -> bool {
+        ypos == self.ypos
+            && (self.xpos..self.xpos + self.text.chars().count() as i32).contains(&xpos)
+    }
+
+    /// Returns the key of the label at a column: the second key if the part after the `/` of the
+    /// keys or of the description is there (like `H` or `Next` of `[F/H] Prev/Next`),
+    /// otherwise the first key.
+    fn key_at(&self, xpos: i32) -> char {
+        let text: Vec<char> = self.text.chars().collect();
+        let offset = ((xpos - self.xpos).max(0) as usize).min(text.len());
+        /* The keys and the description are separated by the `]` */
+        let start = text[..offset]
+            .iter()
+            .rposition(|c| *c == ']')
+            .map_or(0, |index| index + 1);
+
+        let first = self.keys.first().copied().unwrap_or(' ');
+        match text[start..offset].contains(&'/') {
+            true => self.keys.get(1).copied().unwrap_or(first),
+            false => first,
+        }
+    }
+}
+
+/// Returns the attributes of a line of lyrics, by what it is: section markers (like `[Chorus]`)
+/// are dim and italic, ad-libs are dim, and the chorus is bold.
+fn lyrics_attr(line: &LyricsEntry) -> attr_t {
+    match (line.is_marker(), line.is_adlib(), line.section) {
+        (true, _, _) => A_DIM() | A_ITALIC(),
+        (false, true, _) => A_DIM(),
+        (false, false, Some(Section::Chorus)) => A_BOLD(),
+        (false, false, _) => A_NORMAL(),
     }
 }
@@ -0,0 +1,421 @@
+use crate::output;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{IpAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Multicast address of SSDP, used to discover the renderers.
+const SSDP_ADDRESS: &str = "239.255.255.250:1900";
+/// Type of the service which controls playback on a renderer.
+const AV_TRANSPORT: &str = "urn:schemas-upnp-org:service:AVTransport:1";
+/// How long to wait for the renderers to answer a search.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(2);
+/// Maximum time to wait for a renderer to answer a request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+/// How often the HTTP server checks whether it should stop.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+/// Size announced in the WAV header, as the length of the stream is unknown.
+const STREAM_SIZE: u32 = u32::MAX - 36;
+
+/// A DLNA (UPnP) media renderer, like a smart TV or a network speaker.
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    /// Name of the renderer, set by its owner
+    pub name: String,
+    /// URL of the AVTransport service, which controls playback
+    control_url: String,
+}
+
+/// Playback on a renderer.
+///
+/// The audio is served as a WAV stream by a small HTTP server, which the renderer is told
+/// to play. The renderer stops once the stream is dropped.
+pub struct RendererStream {
+    /// The renderer playing the stream
+    renderer: Renderer,
+    /// Stops the HTTP server
+    stop: Arc<AtomicBool>,
+}
+
+impl Renderer {
+    /// Tells the renderer to play (or resume).
+    pub fn play(&self) -> Result<(), String> {
+        self.call("Play", &[("InstanceID", "0"), ("Speed", "1")])
+    }
+
+    /// Tells the renderer to pause.
+    pub fn pause(&self) -> Result<(), String> {
+        self.call("Pause", &[("InstanceID", "0")])
+    }
+
+    /// Tells the renderer to stop.
+    pub fn stop(&self) -> Result<(), String> {
+        self.call("Stop", &[("InstanceID", "0")])
+    }
+
+    /// Tells the renderer which URL to play.
+    ///
+    /// # Arguments
+    /// * `url` - The URL of the audio
+    /// * `title` - Title shown by the renderer
+    fn set_uri(&self, url: &str, title: &str) -> Result<(), String> {
+        /* Many renderers refuse to play anything without a description of it */
+        let metadata = format!(
+            concat!(
+                r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" "#,
+                r#"xmlns:dc="http://purl.org/dc/elements/1.1/" "#,
+                r#"xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/">"#,
+                r#"<item id="0" parentID="-1" restricted="1">"#,
+                r#"<dc:title>{}</dc:title>"#,
+                r#"<upnp:class>object.item.audioItem.musicTrack</upnp:class>"#,
+                r#"<res protocolInfo="http-get:*:audio/wav:*">{}</res>"#,
+                r#"</item></DIDL-Lite>"#
+            ),
+            escape(title),
+            escape(url)
+        );
+
+        self.call(
+            "SetAVTransportURI",
+            &[
+                ("InstanceID", "0"),
+                ("CurrentURI", url),
+                ("CurrentURIMetaData", &metadata),
+            ],
+        )
+    }
+
+    /// Calls an action of the AVTransport service.
+    ///
+    /// # Arguments
+    /// * `action` - Name of the action
+    /// * `args` - Names and values of the arguments, in the order required by the action
+    fn call(&self, action: &str, args: &[(&str, &str)]) -> Result<(), String> {
+        let args: String = args
+            .iter()
+            .map(|(name, value)| format!("<{name}>{}</{name}>", escape(value)))
+            .collect();
+        let body = format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="utf-8"?>"#,
+                r#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" "#,
+                r#"s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">"#,
+                r#"<s:Body><u:{action} xmlns:u="{service}">{args}</u:{action}></s:Body>"#,
+                r#"</s:Envelope>"#
+            ),
+            action = action,
+            service = AV_TRANSPORT,
+            args = args
+        );
+
+        ureq::AgentBuilder::new()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .post(&self.control_url)
+            .set("Content-Type", r#"text/xml; charset="utf-8""#)
+            .set("SOAPAction", &format!(r#""{AV_TRANSPORT}#{action}""#))
+            .send_string(&body)
+            .map(|_| ())
+            .map_err(|e| format!("{} refused {action}: {e}", self.name))
+    }
+
+    /// Returns the address of this computer, as seen by the renderer.
+    fn local_address(&self) -> Result<IpAddr, String> {
+        let host = origin(&self.control_url)
+            .split_once("://")
+            .map(|(_, host)| host.to_owned())
+            .unwrap_or_default();
+        let host = match host.contains(':') {
+            true => host,
+            false => format!("{host}:80"),
+        };
+        let address = host
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or_else(|| format!("Invalid address of {}: {host}", self.name))?;
+
+        /* Nothing is sent, "connecting" only selects the network interface */
+        let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(|e| e.to_string())?;
+        socket.connect(address).map_err(|e| e.to_string())?;
+        socket
+            .local_addr()
+            .map(|address| address.ip())
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl RendererStream {
+    /// Starts streaming audio to a renderer.
+    /// *The playback is paused until [`play()`](Self::play) is called.*
+    ///
+    /// # Arguments
+    /// * `renderer` - The renderer to play on
+    /// * `title` - Title shown by the renderer
+    /// * `source` - The audio
+    /// * `channels` - Amount of channels of the audio
+    /// * `sample_rate` - Sample rate of the audio
+    ///
+    /// ## Errors
+    /// Fails if the HTTP server can't be started, or if the renderer can't be reached.
+    pub fn start(
+        renderer: &Renderer,
+        title: &str,
+        mut source: impl Iterator<Item = f32> + Send + 'static,
+        channels: u16,
+        sample_rate: u32,
+    ) -> Result<Self, String> {
+        let address = renderer.local_address()?;
+        let listener = TcpListener::bind((address, 0))
+            .map_err(|e| format!("Unable to start the HTTP server: {e}"))?;
+        let url = format!(
+            "http://{}/stream.wav",
+            listener.local_addr().map_err(|e| e.to_string())?
+        );
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Unable to start the HTTP server: {e}"))?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let server_stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            while !server_stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((connection, _)) => {
+                        /* If the renderer reconnects, the stream continues where it stopped */
+                        let _ = serve(connection, &mut source, channels, sample_rate, &server_stop);
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let stream = RendererStream {
+            renderer: renderer.clone(),
+            stop,
+        };
+        stream.renderer.set_uri(&url, title)?;
+        Ok(stream)
+    }
+
+    /// Resumes playback on the renderer.
+    pub fn play(&self) -> Result<(), String> {
+        self.renderer.play()
+    }
+
+    /// Pauses playback on the renderer.
+    pub fn pause(&self) -> Result<(), String> {
+        self.renderer.pause()
+    }
+}
+
+impl Drop for RendererStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        /* The renderer also stops by itself once the stream ends */
+        let _ = self.renderer.stop();
+    }
+}
+
+/// Discovers the renderers on the local network.
+/// *This takes a few seconds, as every renderer is given time to answer.*
+///
+/// ### Notes
+/// Renderers which answer but can't be queried afterwards are left out.
+pub fn discover() -> Result<Vec<Renderer>, String> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(|e| e.to_string())?;
+    let request = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {SSDP_ADDRESS}\r\nMAN: \"ssdp:discover\"\r\nMX: {}\r\nST: {AV_TRANSPORT}\r\n\r\n",
+        DISCOVERY_TIMEOUT.as_secs()
+    );
+    socket
+        .send_to(request.as_bytes(), SSDP_ADDRESS)
+        .map_err(|e| format!("Unable to search for renderers: {e}"))?;
+
+    let deadline = Instant::now() + DISCOVERY_TIMEOUT;
+    let mut locations: Vec<String> = Vec::new();
+    let mut buffer = [0; 2048];
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        if remaining.is_zero() {
+            break;
+        }
+        socket
+            .set_read_timeout(Some(remaining))
+            .map_err(|e| e.to_string())?;
+
+        let Ok(size) = socket.recv(&mut buffer) else {
+            break;
+        };
+        let response = String::from_utf8_lossy(&buffer[..size]);
+        let location = response.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("location")
+                .then(|| value.trim().to_owned())
+        });
+
+        if let Some(location) = location {
+            if !locations.contains(&location) {
+                locations.push(location);
+            }
+        }
+    }
+
+    Ok(locations
+        .iter()
+        .filter_map(|location| describe(location).ok())
+        .collect())
+}
+
+/// Finds a renderer on the local network by its name (ignoring case).
+///
+/// ## Errors
+/// Fails if no renderer with the name answered.
+pub fn find(name: &str) -> Result<Renderer, String> {
+    discover()?
+        .into_iter()
+        .find(|renderer| renderer.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("DLNA renderer \"{name}\" not found"))
+}
+
+/// Reads the description of a renderer, to find its name and how to control it.
+///
+/// # Arguments
+/// * `location` - URL of the description, announced by the renderer
+fn describe(location: &str) -> Result<Renderer, String> {
+    let text = ureq::AgentBuilder::new()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .get(location)
+        .call()
+        .map_err(|e| format!("Request failed: {e}"))?
+        .into_string()
+        .map_err(|e| format!("Invalid response: {e}"))?;
+
+    let mut reader = Reader::from_str(&text);
+    reader.trim_text(true);
+
+    let mut name = None;
+    let mut base = None;
+    let mut control_url = None;
+    let mut service_type = String::new();
+    let mut element = String::new();
+
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(start) => {
+                element = String::from_utf8_lossy(start.local_name().as_ref()).into_owned();
+                if element == "service" {
+                    service_type.clear();
+                }
+            }
+            Event::End(_) => element.clear(),
+            Event::Text(content) => {
+                let value = content.unescape().map_err(|e| e.to_string())?.into_owned();
+
+                match element.as_str() {
+                    /* Embedded devices have names too, the root device's one comes first */
+                    "friendlyName" if name.is_none() => name = Some(value),
+                    "URLBase" => base = Some(value),
+                    "serviceType" => service_type = value,
+                    /* The type always comes before the control URL */
+                    "controlURL" if service_type == AV_TRANSPORT && control_url.is_none() => {
+                        control_url = Some(value)
+                    }
+                    _ => (),
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    let control_url = control_url.ok_or("The device isn't a renderer")?;
+    let control_url = match control_url.contains("://") {
+        true => control_url,
+        false => {
+            let base = origin(base.as_deref().unwrap_or(location));
+            format!("{base}/{}", control_url.trim_start_matches('/'))
+        }
+    };
+
+    Ok(Renderer {
+        name: name.unwrap_or_else(|| location.to_owned()),
+        control_url,
+    })
+}
+
+/// Answers a request of the renderer, streaming the audio as a WAV file.
+fn serve(
+    connection: TcpStream,
+    source: &mut impl Iterator<Item = f32>,
+    channels: u16,
+    sample_rate: u32,
+    stop: &AtomicBool,
+) -> std::io::Result<()> {
+    connection.set_nonblocking(false)?;
+    let mut reader = BufReader::new(connection.try_clone()?);
+
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    /* The headers aren't needed, but have to be read */
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut writer = connection;
+    writer.write_all(
+        concat!(
+            "HTTP/1.1 200 OK\r\n",
+            "Content-Type: audio/wav\r\n",
+            "Connection: close\r\n",
+            "transferMode.dlna.org: Streaming\r\n",
+            "contentFeatures.dlna.org: DLNA.ORG_OP=00;DLNA.ORG_CI=0;",
+            "DLNA.ORG_FLAGS=01700000000000000000000000000000\r\n",
+            "\r\n"
+        )
+        .as_bytes(),
+    )?;
+    if request.starts_with("HEAD ") {
+        return Ok(());
+    }
+
+    writer.write_all(&wav_header(channels, sample_rate))?;
+    output::pump(source, channels, sample_rate, writer, stop)
+}
+
+/// Creates the header of a WAV stream (signed 16-bit PCM) with an unknown length.
+fn wav_header(channels: u16, sample_rate: u32) -> Vec<u8> {
+    let block_align = channels * 2;
+    let byte_rate = sample_rate * block_align as u32;
+
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(STREAM_SIZE + 36).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); /* PCM */
+    header.extend_from_slice(&channels.to_le_bytes());
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&byte_rate.to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&16u16.to_le_bytes()); /* Bits per sample */
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&STREAM_SIZE.to_le_bytes());
+    header
+}
+
+/// Returns the scheme, host and port of a URL (e.g. `http://192.168.1.2:8080`).
+fn origin(url: &str) -> &str {
+    let host_start = url.find("://").map(|i| i + 3).unwrap_or(0);
+    match url[host_start..].find('/') {
+        Some(i) => &url[..host_start + i],
+        None => url.trim_end_matches('/'),
+    }
+}
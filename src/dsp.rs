@@ -0,0 +1,1652 @@
+use crate::audioinfo::Speaker;
+use crate::tap::Taps;
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+use std::f64::consts::PI as PI64;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Peaks above this level (in dBFS) are limited.
+const LIMITER_THRESHOLD_DB: f32 = -1.0;
+/// Width of the soft knee of the limiter (in dB), centered on the threshold.
+const LIMITER_KNEE_DB: f32 = 6.0;
+/// How quickly the limiter lets the level recover after a peak.
+const LIMITER_RELEASE: Duration = Duration::from_millis(100);
+
+/// Night mode compresses audio above this level (in dBFS).
+const NIGHT_THRESHOLD_DB: f32 = -30.0;
+/// How much night mode compresses audio above the threshold (e.g. 3 dB louder input is 1 dB louder).
+const NIGHT_RATIO: f32 = 3.0;
+/// Gain (in dB) night mode adds after compressing, so quiet parts become louder.
+const NIGHT_MAKEUP_DB: f32 = 9.0;
+/// How quickly night mode turns the gain down when the audio gets louder.
+const NIGHT_ATTACK: Duration = Duration::from_millis(5);
+/// How quickly night mode turns the gain up again when the audio gets quieter.
+const NIGHT_RELEASE: Duration = Duration::from_millis(250);
+/// How long it takes the [fade](Fade) to follow a change of its level, so steps don't click.
+const FADE_SMOOTHING: Duration = Duration::from_millis(50);
+
+/// Corner frequency of the bass control (in Hz).
+const BASS_FREQUENCY: f32 = 100.0;
+/// Corner frequency of the treble control (in Hz).
+const TREBLE_FREQUENCY: f32 = 10000.0;
+/// Maximum boost (or cut) of the tone controls (in dB).
+pub const MAX_TONE_DB: i32 = 12;
+
+/// Audio below this level (in dBFS) is considered silent, unless configured otherwise.
+pub const DEFAULT_SILENCE_THRESHOLD_DB: f32 = -60.0;
+/// Silence shorter than this isn't skipped, unless configured otherwise.
+pub const DEFAULT_SILENCE_DURATION: Duration = Duration::from_secs(1);
+/// Length of the pauses left in the audio when pauses are skipped.
+const SHORTENED_PAUSE: Duration = Duration::from_millis(250);
+
+/// Half the length of the sinc resampler's filter (in input samples, when upsampling).
+const SINC_HALF_TAPS: usize = 16;
+/// Amount of precalculated fractional positions of the sinc resampler's filter.
+const SINC_PHASES: usize = 256;
+/// Cutoff of the sinc resampler's filter, relative to the lower Nyquist frequency.
+const SINC_CUTOFF: f64 = 0.95;
+
+/// Length of the blocks the current peak level is measured over.
+const PEAK_WINDOW: Duration = Duration::from_millis(200);
+
+/// Settings of the processing stages applied to the decoded audio.
+#[derive(Debug, Clone, Default)]
+pub struct Effects {
+    /// Gain (in dB) applied to every track, see [`Limiter`](Limiter)
+    pub preamp_db: f32,
+    /// Bass and treble levels, shared with the playing audio
+    pub tone: Arc<ToneControls>,
+    /// How the left and right channels are played, shared with the playing audio
+    pub channel_mode: Arc<SharedChannelMode>,
+    /// Whether the left and right channels are summed to mono, shared with the playing audio
+    pub mono: Arc<AtomicBool>,
+    /// Whether [night mode](NightMode) is on, shared with the playing audio
+    pub night: Arc<AtomicBool>,
+    /// Whether the effects which change the sound (the channel mode, mono, tone controls,
+    /// night mode and preamp) are bypassed, shared with the playing audio
+    pub bypass: Arc<AtomicBool>,
+    /// Level of the [fade](Fade) (e.g. of the sleep timer), shared with the playing audio
+    pub fade: Arc<FadeLevel>,
+    /// Which silence is skipped, see [`SkipSilence`](SkipSilence)
+    pub silence: Silence,
+    /// How the audio is resampled to the sample rate of the output
+    pub resampler: Resampler,
+    /// Whether [dither](Dither) is added when the output has a lower bit depth
+    pub dither: bool,
+    /// Listeners of the played audio (e.g. visualizers), see [`Tap`](crate::tap::Tap)
+    pub taps: Taps,
+    /// Peak levels of the played audio, see [`Peak`](Peak)
+    pub levels: Levels,
+}
+
+/// How the audio is resampled when the output plays at another sample rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resampler {
+    /// Linear interpolation by `rodio`: fast, but it dulls the treble and lets some aliasing through
+    #[default]
+    Linear,
+    /// A windowed sinc filter (see [`Resample`](Resample)): slower, but clean
+    Sinc,
+}
+
+/// What is considered silence by [`SkipSilence`](SkipSilence), and which of it is skipped.
+#[derive(Debug, Clone)]
+pub struct Silence {
+    /// Audio below this level (in dBFS) is silent
+    pub threshold_db: f32,
+    /// Silence shorter than this is kept
+    pub min_duration: Duration,
+    /// Whether silence at the start and the end of tracks is skipped
+    pub trim: bool,
+    /// Whether pauses in the middle of tracks are shortened, shared with the playing audio
+    pub pauses: Arc<AtomicBool>,
+}
+
+/// Meters showing what the [`Effects`](Effects) do to the playing audio.
+#[derive(Debug, Clone)]
+pub struct Meters {
+    /// The applied gain (if the gain is changed)
+    pub gain: Option<GainMeter>,
+    /// The skipped silence
+    pub silence: SilenceMeter,
+}
+
+/// Peak levels of the played audio, measured by [`Peak`](Peak).  
+/// It's kept for the whole session, so the session peak isn't reset when the track changes.
+#[derive(Debug, Clone, Default)]
+pub struct Levels {
+    /// Peak of the last measured block (linear, stored as `f32` bits)
+    current: Arc<AtomicU32>,
+    /// Highest peak of the session (linear, stored as `f32` bits)
+    session: Arc<AtomicU32>,
+    /// Amount of times the audio went above full scale in this session
+    clips: Arc<AtomicU64>,
+}
+
+/// How the left and right channels are played.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ChannelMode {
+    /// Unchanged
+    #[default]
+    Normal,
+    /// The left and right channels are swapped
+    Swapped,
+    /// The left channel is played on both sides
+    LeftOnly,
+    /// The right channel is played on both sides
+    RightOnly,
+}
+
+/// A [`ChannelMode`](ChannelMode) which can be changed while the audio is playing.
+#[derive(Debug, Default)]
+pub struct SharedChannelMode(AtomicU8);
+
+/// The level (from `0.0` to `1.0`) the audio is faded to, which can be changed while the
+/// audio is playing.
+#[derive(Debug)]
+pub struct FadeLevel(AtomicU32);
+
+/// Bass and treble levels (in dB), which can be changed while the audio is playing.
+#[derive(Debug, Default)]
+pub struct ToneControls {
+    /// Level of the bass (in dB)
+    bass: AtomicI32,
+    /// Level of the treble (in dB)
+    treble: AtomicI32,
+}
+
+/// Downmixes multi-channel audio to stereo.
+///
+/// The coefficients follow ITU-R BS.775: center and surround channels are mixed in
+/// at -3 dB and the LFE channel is dropped. The result is normalized, so the mix can't clip.
+pub struct Downmix<S: Source<Item = f32>> {
+    /// Input source
+    input: S,
+    /// `[left, right]` gain of each input channel
+    matrix: Vec<[f32; 2]>,
+    /// The right channel sample of the current frame (if it wasn't returned yet)
+    pending: Option<f32>,
+}
+
+impl<S: Source<Item = f32>> Downmix<S> {
+    /// Creates a new downmixer.
+    ///
+    /// # Arguments
+    /// * `input` - Source to downmix
+    /// * `speakers` - Speaker position of each channel in `input`
+    pub fn new(input: S, speakers: &[Speaker]) -> Self {
+        let mut matrix: Vec<[f32; 2]> = speakers
+            .iter()
+            .map(|speaker| match speaker {
+                Speaker::FrontLeft => [1.0, 0.0],
+                Speaker::FrontRight => [0.0, 1.0],
+                Speaker::FrontCenter => [FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+                Speaker::LowFrequency => [0.0, 0.0],
+                Speaker::BackLeft | Speaker::SideLeft => [FRAC_1_SQRT_2, 0.0],
+                Speaker::BackRight | Speaker::SideRight => [0.0, FRAC_1_SQRT_2],
+                Speaker::BackCenter => [0.5, 0.5],
+            })
+            .collect();
+
+        for side in 0..2 {
+            let total: f32 = matrix.iter().map(|gains| gains[side]).sum();
+            if total > 1.0 {
+                matrix.iter_mut().for_each(|gains| gains[side] /= total);
+            }
+        }
+
+        Self {
+            input,
+            matrix,
+            pending: None,
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Downmix<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.pending.take() {
+            return Some(right);
+        }
+
+        let (mut left, mut right) = (0.0, 0.0);
+        for gains in &self.matrix {
+            let sample = self.input.next()?;
+            left += sample * gains[0];
+            right += sample * gains[1];
+        }
+
+        self.pending = Some(right);
+        Some(left)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Downmix<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input
+            .current_frame_len()
+            .map(|len| len / self.matrix.len() * 2)
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// Amplifies audio by a fixed gain, with a soft-knee peak limiter so boosted audio can't clip.
+///
+/// The limiter reacts instantly to peaks above [`LIMITER_THRESHOLD_DB`](LIMITER_THRESHOLD_DB)
+/// (reducing every channel of the frame by the same amount), and recovers over
+/// [`LIMITER_RELEASE`](LIMITER_RELEASE).
+pub struct Limiter<S: Source<Item = f32>> {
+    /// Input source
+    input: S,
+    /// Gain applied before limiting (linear)
+    gain: f32,
+    /// The current frame, already processed
+    frame: Vec<f32>,
+    /// Index of the next sample of `frame` to return
+    position: usize,
+    /// Gain applied by the limiter (linear, at most `1.0`)
+    envelope: f32,
+    /// Gain reduction of the limiter in dB (stored as `f32` bits), see [`GainMeter`](GainMeter)
+    reduction: Arc<AtomicU32>,
+    /// Whether the gain is bypassed, read at the start of every frame
+    bypass: Arc<AtomicBool>,
+}
+
+/// Shows the gain applied by a [`Limiter`](Limiter) while it plays.
+#[derive(Debug, Clone)]
+pub struct GainMeter {
+    /// The gain applied before limiting (in dB)
+    gain_db: f32,
+    /// Gain reduction of the limiter in dB (stored as `f32` bits)
+    reduction: Arc<AtomicU32>,
+    /// Whether the gain is bypassed
+    bypass: Arc<AtomicBool>,
+}
+
+impl Effects {
+    /// Applies the effects to the audio.  
+    /// Returns the processed audio, and meters of what the effects do to it.
+    pub fn apply(
+        &self,
+        source: Box<dyn Source<Item = f32> + Send>,
+    ) -> (Box<dyn Source<Item = f32> + Send>, Meters) {
+        /* These can be changed at any time, so they're always applied */
+        /* (silence is detected first, before the audio is changed by the other effects) */
+        let source = SkipSilence::new(source, &self.silence);
+        let mut meters = Meters {
+            gain: None,
+            silence: source.meter(),
+        };
+        let source = ChannelMap::new(
+            source,
+            Arc::clone(&self.channel_mode),
+            Arc::clone(&self.mono),
+            Arc::clone(&self.bypass),
+        );
+        let source = Tone::new(source, Arc::clone(&self.tone), Arc::clone(&self.bypass));
+        let source = NightMode::new(source, Arc::clone(&self.night), Arc::clone(&self.bypass));
+        /* The fade is part of the volume, so it's applied last and never bypassed */
+        if self.preamp_db == 0.0 {
+            return (Box::new(Fade::new(source, Arc::clone(&self.fade))), meters);
+        }
+
+        /* Boosted bass or treble is limited too */
+        let limiter = Limiter::new(source, self.preamp_db, Arc::clone(&self.bypass));
+        meters.gain = Some(limiter.meter());
+        (Box::new(Fade::new(limiter, Arc::clone(&self.fade))), meters)
+    }
+
+    /// Converts the processed audio for the output (see [`resampler`](Self::resampler) and
+    /// [`dither`](Self::dither)).  
+    /// Returns the converted audio, and a description of the conversion (if there is one).
+    ///
+    /// # Arguments
+    /// * `source` - The processed audio
+    /// * `sample_rate` - Sample rate of the output, `None` if it plays any sample rate
+    /// * `bits` - Bit depth of the output, `None` if it takes floating point samples
+    pub fn convert(
+        &self,
+        source: Box<dyn Source<Item = f32> + Send>,
+        sample_rate: Option<u32>,
+        bits: Option<u16>,
+    ) -> (Box<dyn Source<Item = f32> + Send>, Option<String>) {
+        let mut conversion = Vec::new();
+
+        let source: Box<dyn Source<Item = f32> + Send> = match sample_rate {
+            Some(rate) if rate != source.sample_rate() => {
+                conversion.push(format!("{} resampling", self.resampler.label()));
+                match self.resampler {
+                    /* rodio resamples the audio itself */
+                    Resampler::Linear => source,
+                    Resampler::Sinc => Box::new(Resample::new(source, rate)),
+                }
+            }
+            _ => source,
+        };
+
+        /* The samples are rounded by rodio (or `output::pump()`) */
+        let source: Box<dyn Source<Item = f32> + Send> = match bits {
+            Some(bits) if self.dither => {
+                conversion.push("TPDF dither".to_owned());
+                Box::new(Dither::new(source, bits))
+            }
+            _ => source,
+        };
+
+        let conversion = match conversion.is_empty() {
+            true => None,
+            false => Some(conversion.join(", ")),
+        };
+        (source, conversion)
+    }
+}
+
+impl Resampler {
+    /// Returns the name of the resampler, shown in the file quality line.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Linear => "linear",
+            Self::Sinc => "sinc",
+        }
+    }
+}
+
+impl Default for Silence {
+    fn default() -> Self {
+        Self {
+            threshold_db: DEFAULT_SILENCE_THRESHOLD_DB,
+            min_duration: DEFAULT_SILENCE_DURATION,
+            trim: false,
+            pauses: Arc::default(),
+        }
+    }
+}
+
+impl ChannelMode {
+    /// All modes, in the order they're switched through.
+    const ALL: [Self; 4] = [Self::Normal, Self::Swapped, Self::LeftOnly, Self::RightOnly];
+
+    /// Returns the next mode.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|mode| *mode == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Returns the label shown in the file quality line, `None` if the channels are unchanged.
+    pub fn label(self) -> Option<&'static str> {
+        match self {
+            Self::Normal => None,
+            Self::Swapped => Some("Swapped"),
+            Self::LeftOnly => Some("Left only"),
+            Self::RightOnly => Some("Right only"),
+        }
+    }
+}
+
+impl SharedChannelMode {
+    /// Returns the current mode.
+    pub fn get(&self) -> ChannelMode {
+        let index = self.0.load(Ordering::Relaxed) as usize;
+        ChannelMode::ALL.get(index).copied().unwrap_or_default()
+    }
+
+    /// Changes the mode.
+    pub fn set(&self, mode: ChannelMode) {
+        let index = ChannelMode::ALL
+            .iter()
+            .position(|m| *m == mode)
+            .unwrap_or(0);
+        self.0.store(index as u8, Ordering::Relaxed);
+    }
+}
+
+impl FadeLevel {
+    /// Returns the current level.
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    /// Changes the level, which is clamped to `0.0..=1.0`.
+    pub fn set(&self, level: f32) {
+        self.0
+            .store(level.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+}
+
+impl Default for FadeLevel {
+    /// The audio isn't faded.
+    fn default() -> Self {
+        Self(AtomicU32::new(1.0f32.to_bits()))
+    }
+}
+
+impl ToneControls {
+    /// Creates tone controls with the given levels (in dB).
+    /// The levels are limited to [`MAX_TONE_DB`](MAX_TONE_DB).
+    pub fn new(bass: i32, treble: i32) -> Self {
+        Self {
+            bass: AtomicI32::new(bass.clamp(-MAX_TONE_DB, MAX_TONE_DB)),
+            treble: AtomicI32::new(treble.clamp(-MAX_TONE_DB, MAX_TONE_DB)),
+        }
+    }
+
+    /// Returns the level of the bass (in dB).
+    pub fn bass(&self) -> i32 {
+        self.bass.load(Ordering::Relaxed)
+    }
+
+    /// Returns the level of the treble (in dB).
+    pub fn treble(&self) -> i32 {
+        self.treble.load(Ordering::Relaxed)
+    }
+
+    /// Changes the levels of the bass and the treble (in dB).
+    /// The levels are limited to [`MAX_TONE_DB`](MAX_TONE_DB).
+    pub fn set(&self, bass: i32, treble: i32) {
+        self.bass
+            .store(bass.clamp(-MAX_TONE_DB, MAX_TONE_DB), Ordering::Relaxed);
+        self.treble
+            .store(treble.clamp(-MAX_TONE_DB, MAX_TONE_DB), Ordering::Relaxed);
+    }
+
+    /// Changes the level of the bass by `delta` dB, and returns the new level.
+    pub fn adjust_bass(&self, delta: i32) -> i32 {
+        Self::adjust(&self.bass, delta)
+    }
+
+    /// Changes the level of the treble by `delta` dB, and returns the new level.
+    pub fn adjust_treble(&self, delta: i32) -> i32 {
+        Self::adjust(&self.treble, delta)
+    }
+
+    /// Changes a level, keeping it within [`MAX_TONE_DB`](MAX_TONE_DB).
+    fn adjust(level: &AtomicI32, delta: i32) -> i32 {
+        let new = (level.load(Ordering::Relaxed) + delta).clamp(-MAX_TONE_DB, MAX_TONE_DB);
+        level.store(new, Ordering::Relaxed);
+        new
+    }
+}
+
+impl<S: Source<Item = f32>> Limiter<S> {
+    /// Creates a new limiter.
+    ///
+    /// # Arguments
+    /// * `input` - Source to amplify
+    /// * `gain_db` - Gain applied before limiting (in dB)
+    /// * `bypass` - Whether the audio is left untouched, which can be changed while playing
+    pub fn new(input: S, gain_db: f32, bypass: Arc<AtomicBool>) -> Self {
+        Self {
+            input,
+            gain: db_to_gain(gain_db),
+            frame: Vec::new(),
+            position: 0,
+            envelope: 1.0,
+            reduction: Arc::new(AtomicU32::new(0.0f32.to_bits())),
+            bypass,
+        }
+    }
+
+    /// Returns a meter showing the applied gain.
+    pub fn meter(&self) -> GainMeter {
+        GainMeter {
+            gain_db: gain_to_db(self.gain),
+            reduction: Arc::clone(&self.reduction),
+            bypass: Arc::clone(&self.bypass),
+        }
+    }
+
+    /// Reads and processes the next frame.
+    /// Returns `false` if the input has ended.
+    fn process_frame(&mut self) -> bool {
+        let channels = self.input.channels().max(1) as usize;
+        let gain = match self.bypass.load(Ordering::Relaxed) {
+            true => 1.0,
+            false => self.gain,
+        };
+        self.frame.clear();
+        self.position = 0;
+        self.frame.extend(
+            self.input
+                .by_ref()
+                .take(channels)
+                .map(|sample| sample * gain),
+        );
+        if self.frame.is_empty() {
+            return false;
+        }
+
+        let peak = self
+            .frame
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        let target = db_to_gain(-limiter_reduction(gain_to_db(peak)));
+        self.envelope = match target < self.envelope {
+            true => target,
+            false => {
+                let release = (-1.0
+                    / (LIMITER_RELEASE.as_secs_f32() * self.input.sample_rate() as f32))
+                    .exp();
+                target + (self.envelope - target) * release
+            }
+        };
+
+        self.frame
+            .iter_mut()
+            .for_each(|sample| *sample *= self.envelope);
+        self.reduction
+            .store(gain_to_db(self.envelope).abs().to_bits(), Ordering::Relaxed);
+        true
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Limiter<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position >= self.frame.len() && !self.process_frame() {
+            return None;
+        }
+
+        self.position += 1;
+        Some(self.frame[self.position - 1])
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Limiter<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input
+            .current_frame_len()
+            .map(|len| len + self.frame.len() - self.position)
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+impl GainMeter {
+    /// Returns the gain currently applied (in dB), including the reduction of the limiter.
+    pub fn gain_db(&self) -> f32 {
+        match self.bypass.load(Ordering::Relaxed) {
+            true => -self.reduction_db(),
+            false => self.gain_db - self.reduction_db(),
+        }
+    }
+
+    /// Returns how much the limiter currently reduces the gain (in dB).
+    pub fn reduction_db(&self) -> f32 {
+        f32::from_bits(self.reduction.load(Ordering::Relaxed))
+    }
+}
+
+/// Compresses the dynamic range while night mode is on, so quiet parts (like dialogue) stay
+/// audible at a low volume, without the loud parts getting much louder than them.
+///
+/// Levels above [`NIGHT_THRESHOLD_DB`](NIGHT_THRESHOLD_DB) are reduced by
+/// [`NIGHT_RATIO`](NIGHT_RATIO), everything is raised by [`NIGHT_MAKEUP_DB`](NIGHT_MAKEUP_DB),
+/// and peaks are limited at [`LIMITER_THRESHOLD_DB`](LIMITER_THRESHOLD_DB).
+/// The gain follows the level over [`NIGHT_ATTACK`](NIGHT_ATTACK) and
+/// [`NIGHT_RELEASE`](NIGHT_RELEASE), which also fades it when night mode is switched.
+pub struct NightMode<S: Source<Item = f32>> {
+    /// Input source
+    input: S,
+    /// Whether night mode is on, read at the start of every frame
+    enabled: Arc<AtomicBool>,
+    /// Whether the audio is left untouched, read at the start of every frame
+    bypass: Arc<AtomicBool>,
+    /// The current frame, already processed
+    frame: Vec<f32>,
+    /// Index of the next sample of `frame` to return
+    position: usize,
+    /// Gain currently applied (in dB, before limiting peaks)
+    gain_db: f32,
+}
+
+impl<S: Source<Item = f32>> NightMode<S> {
+    /// Creates a new compressor.
+    ///
+    /// # Arguments
+    /// * `input` - Source to compress
+    /// * `enabled` - Whether night mode is on, which can be changed while playing
+    /// * `bypass` - Whether the audio is left untouched (right away, instead of fading),
+    ///   which can be changed while playing
+    pub fn new(input: S, enabled: Arc<AtomicBool>, bypass: Arc<AtomicBool>) -> Self {
+        Self {
+            input,
+            enabled,
+            bypass,
+            frame: Vec::new(),
+            position: 0,
+            gain_db: 0.0,
+        }
+    }
+
+    /// Reads and processes the next frame.
+    /// Returns `false` if the input has ended.
+    fn process_frame(&mut self) -> bool {
+        let channels = self.input.channels().max(1) as usize;
+        self.frame.clear();
+        self.position = 0;
+        self.frame.extend(self.input.by_ref().take(channels));
+        if self.frame.is_empty() {
+            return false;
+        }
+        if self.bypass.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        let enabled = self.enabled.load(Ordering::Relaxed);
+        let peak_db = gain_to_db(
+            self.frame
+                .iter()
+                .fold(0.0f32, |peak, sample| peak.max(sample.abs())),
+        );
+        let target = match enabled {
+            true => night_gain(peak_db),
+            false => 0.0,
+        };
+        let time = match target < self.gain_db {
+            true => NIGHT_ATTACK,
+            false => NIGHT_RELEASE,
+        };
+        let coefficient = (-1.0 / (time.as_secs_f32() * self.input.sample_rate() as f32)).exp();
+        self.gain_db = target + (self.gain_db - target) * coefficient;
+        if self.gain_db.abs() < 0.001 {
+            return true;
+        }
+
+        /* The gain follows the level with a delay, so peaks are limited right away */
+        /* (while fading out, only the boost is limited) */
+        let ceiling = LIMITER_THRESHOLD_DB - peak_db;
+        let gain = db_to_gain(match enabled {
+            true => self.gain_db.min(ceiling),
+            false => self.gain_db.min(ceiling.max(0.0)),
+        });
+        self.frame.iter_mut().for_each(|sample| *sample *= gain);
+        true
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for NightMode<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position >= self.frame.len() && !self.process_frame() {
+            return None;
+        }
+
+        self.position += 1;
+        Some(self.frame[self.position - 1])
+    }
+}
+
+impl<S: Source<Item = f32>> Source for NightMode<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input
+            .current_frame_len()
+            .map(|len| len + self.frame.len() - self.position)
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// Fades the audio to a [level](FadeLevel) which can be changed while it plays
+/// (e.g. by the sleep timer).
+/// The gain rises to the level over [`FADE_SMOOTHING`](FADE_SMOOTHING), so it changes smoothly
+/// even if the level is only changed a few times per second. It falls right away, so audio
+/// which was faded out while paused (e.g. by the alarm) doesn't start loud.
+pub struct Fade<S: Source<Item = f32>> {
+    /// Input source
+    input: S,
+    /// The level to fade to, read at the start of every frame
+    level: Arc<FadeLevel>,
+    /// The current frame, already processed
+    frame: Vec<f32>,
+    /// Index of the next sample of `frame` to return
+    position: usize,
+    /// Gain currently applied
+    gain: f32,
+}
+
+impl<S: Source<Item = f32>> Fade<S> {
+    /// Creates a new fade, which starts at the current level.
+    pub fn new(input: S, level: Arc<FadeLevel>) -> Self {
+        Self {
+            input,
+            gain: level.get(),
+            level,
+            frame: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Reads and processes the next frame.
+    /// Returns `false` if the input has ended.
+    fn process_frame(&mut self) -> bool {
+        let channels = self.input.channels().max(1) as usize;
+        self.frame.clear();
+        self.position = 0;
+        self.frame.extend(self.input.by_ref().take(channels));
+        if self.frame.is_empty() {
+            return false;
+        }
+
+        let target = self.level.get();
+        if self.gain == target && target == 1.0 {
+            return true;
+        }
+        if target < self.gain {
+            self.gain = target;
+        } else {
+            let coefficient =
+                (-1.0 / (FADE_SMOOTHING.as_secs_f32() * self.input.sample_rate() as f32)).exp();
+            self.gain = target + (self.gain - target) * coefficient;
+            if target - self.gain < 0.0001 {
+                self.gain = target;
+            }
+        }
+
+        let gain = self.gain;
+        self.frame.iter_mut().for_each(|sample| *sample *= gain);
+        true
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Fade<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position >= self.frame.len() && !self.process_frame() {
+            return None;
+        }
+
+        self.position += 1;
+        Some(self.frame[self.position - 1])
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Fade<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input
+            .current_frame_len()
+            .map(|len| len + self.frame.len() - self.position)
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// Rearranges the left and right channels (see [`ChannelMode`](ChannelMode)),
+/// and sums them to mono if requested.
+/// Other channels (of multi-channel audio) are left untouched.
+pub struct ChannelMap<S: Source<Item = f32>> {
+    /// Input source
+    input: S,
+    /// The mode to apply, read at the start of every frame
+    mode: Arc<SharedChannelMode>,
+    /// Whether to sum the channels to mono, read at the start of every frame
+    mono: Arc<AtomicBool>,
+    /// Whether the channels are left untouched, read at the start of every frame
+    bypass: Arc<AtomicBool>,
+    /// The right channel sample of the current frame (if it wasn't returned yet)
+    pending: Option<f32>,
+    /// Channel of the next sample read from the input
+    channel: usize,
+}
+
+impl<S: Source<Item = f32>> ChannelMap<S> {
+    /// Creates a new channel mapper.
+    ///
+    /// # Arguments
+    /// * `input` - Source to rearrange
+    /// * `mode` - The mode, which can be changed while playing
+    /// * `mono` - Whether to sum the channels to mono, which can be changed while playing
+    /// * `bypass` - Whether the channels are left untouched, which can be changed while playing
+    pub fn new(
+        input: S,
+        mode: Arc<SharedChannelMode>,
+        mono: Arc<AtomicBool>,
+        bypass: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            input,
+            mode,
+            mono,
+            bypass,
+            pending: None,
+            channel: 0,
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for ChannelMap<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.pending.take() {
+            return Some(right);
+        }
+
+        let channels = self.input.channels() as usize;
+        if self.channel >= channels {
+            self.channel = 0;
+        }
+
+        let sample = self.input.next()?;
+        if channels < 2 || self.channel != 0 {
+            self.channel += 1;
+            return Some(sample);
+        }
+
+        let (left, right) = (sample, self.input.next()?);
+        self.channel += 2;
+        if self.bypass.load(Ordering::Relaxed) {
+            self.pending = Some(right);
+            return Some(left);
+        }
+        let (left, right) = match self.mode.get() {
+            ChannelMode::Normal => (left, right),
+            ChannelMode::Swapped => (right, left),
+            ChannelMode::LeftOnly => (left, left),
+            ChannelMode::RightOnly => (right, right),
+        };
+        let (left, right) = match self.mono.load(Ordering::Relaxed) {
+            /* Halved, so the sum can't clip */
+            true => ((left + right) * 0.5, (left + right) * 0.5),
+            false => (left, right),
+        };
+
+        self.pending = Some(right);
+        Some(left)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for ChannelMap<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input
+            .current_frame_len()
+            .map(|len| len + self.pending.is_some() as usize)
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// Skips silence at the start and the end of the audio, and shortens pauses in the middle
+/// of it (each of them if enabled, see [`Silence`](Silence)).
+///
+/// Frames where every channel is below the threshold are silent. A silent run at the start
+/// (or the end) is skipped if it lasts at least the minimum duration, and such a run in the
+/// middle is shortened to [`SHORTENED_PAUSE`](SHORTENED_PAUSE). Other silence is kept.
+///
+/// ### Notes
+/// Silence is read ahead until the audio continues, but only the first part of it is kept
+/// in memory: the rest of a long silence that's played is replaced by digital silence.
+pub struct SkipSilence<S: Source<Item = f32>> {
+    /// Input source
+    input: S,
+    /// Samples below this level are silent (linear)
+    threshold: f32,
+    /// Length of the shortest skipped silence (in frames)
+    min_frames: u64,
+    /// Length of shortened pauses (in frames)
+    pause_frames: u64,
+    /// Whether silence at the start and the end is skipped
+    trim: bool,
+    /// Whether pauses in the middle are shortened
+    pauses: Arc<AtomicBool>,
+    /// Whether anything but silence was read yet
+    started: bool,
+    /// The start of the silence before `frame`, played before it
+    held: VecDeque<f32>,
+    /// Amount of zero samples played after `held`
+    zeros: u64,
+    /// The current frame
+    frame: Vec<f32>,
+    /// Index of the next sample of `frame` to return
+    position: usize,
+    /// Total duration of the skipped silence in microseconds, see [`SilenceMeter`](SilenceMeter)
+    skipped: Arc<AtomicU64>,
+}
+
+/// Shows how much silence a [`SkipSilence`](SkipSilence) skipped.
+#[derive(Debug, Clone)]
+pub struct SilenceMeter {
+    /// Total duration of the skipped silence in microseconds
+    skipped: Arc<AtomicU64>,
+}
+
+impl<S: Source<Item = f32>> SkipSilence<S> {
+    /// Creates a new silence skipper.
+    ///
+    /// # Arguments
+    /// * `input` - Source to skip the silence of
+    /// * `silence` - What is considered silence, and which of it is skipped
+    pub fn new(input: S, silence: &Silence) -> Self {
+        let frames = |duration: Duration| duration.as_secs_f64() * input.sample_rate() as f64;
+        let min_frames = (frames(silence.min_duration) as u64).max(1);
+        Self {
+            threshold: db_to_gain(silence.threshold_db),
+            min_frames,
+            pause_frames: (frames(SHORTENED_PAUSE) as u64).min(min_frames),
+            trim: silence.trim,
+            pauses: Arc::clone(&silence.pauses),
+            input,
+            started: false,
+            held: VecDeque::new(),
+            zeros: 0,
+            frame: Vec::new(),
+            position: 0,
+            skipped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Returns a meter showing the skipped silence.
+    pub fn meter(&self) -> SilenceMeter {
+        SilenceMeter {
+            skipped: Arc::clone(&self.skipped),
+        }
+    }
+
+    /// Reads frames until one isn't silent (or the input ends), and decides whether
+    /// the silence before it is played, shortened or skipped.
+    /// Returns `false` if the input has ended and nothing is left to play.
+    fn read_frame(&mut self) -> bool {
+        let channels = self.input.channels().max(1) as usize;
+        let mut silent_frames: u64 = 0;
+
+        loop {
+            self.frame.clear();
+            self.position = 0;
+            self.frame.extend(self.input.by_ref().take(channels));
+
+            /* Without anything to skip, the audio isn't read ahead */
+            let detect = self.trim || self.pauses.load(Ordering::Relaxed);
+            let ended = self.frame.is_empty();
+            if detect
+                && !ended
+                && self
+                    .frame
+                    .iter()
+                    .all(|sample| sample.abs() < self.threshold)
+            {
+                if silent_frames < self.min_frames {
+                    self.held.extend(&self.frame);
+                }
+                silent_frames += 1;
+                continue;
+            }
+
+            let edge = !self.started || ended;
+            let kept_frames = match silent_frames >= self.min_frames {
+                true if edge && self.trim => 0,
+                true if !edge && self.pauses.load(Ordering::Relaxed) => self.pause_frames,
+                _ => silent_frames,
+            };
+
+            self.held.truncate(kept_frames as usize * channels);
+            let held_frames = silent_frames.min(self.min_frames).min(kept_frames);
+            self.zeros = (kept_frames - held_frames) * channels as u64;
+            let skipped = (silent_frames - kept_frames) * 1_000_000;
+            self.skipped.fetch_add(
+                skipped / self.input.sample_rate().max(1) as u64,
+                Ordering::Relaxed,
+            );
+
+            self.started = true;
+            return !(ended && self.held.is_empty() && self.zeros == 0);
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for SkipSilence<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        loop {
+            if let Some(sample) = self.held.pop_front() {
+                return Some(sample);
+            }
+            if self.zeros > 0 {
+                self.zeros -= 1;
+                return Some(0.0);
+            }
+            if self.position < self.frame.len() {
+                self.position += 1;
+                return Some(self.frame[self.position - 1]);
+            }
+            if !self.read_frame() {
+                return None;
+            }
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Source for SkipSilence<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        let pending = self.held.len() + self.zeros as usize + self.frame.len() - self.position;
+        self.input.current_frame_len().map(|len| len + pending)
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+impl SilenceMeter {
+    /// Returns the total duration of the skipped silence so far.
+    pub fn skipped(&self) -> Duration {
+        Duration::from_micros(self.skipped.load(Ordering::Relaxed))
+    }
+}
+
+/// Resamples audio with a windowed sinc filter (Blackman window), which keeps the treble
+/// and removes (almost) all aliasing, unlike linear interpolation.
+///
+/// The filter is precalculated for [`SINC_PHASES`](SINC_PHASES) fractional positions,
+/// positions in between are interpolated.
+///
+/// ### Notes
+/// The amount of channels and the sample rate of the input are expected not to change.
+pub struct Resample<S: Source<Item = f32>> {
+    /// Input source
+    input: S,
+    /// Amount of channels
+    channels: usize,
+    /// Sample rate of the output
+    sample_rate: u32,
+    /// Amount of input frames per output frame
+    step: f64,
+    /// Half the length of the filter (in input frames)
+    half: usize,
+    /// The filter at every fractional position, `2 * half` taps each
+    table: Vec<f32>,
+    /// Input frames around the current position (interleaved)
+    history: VecDeque<f32>,
+    /// Position of the next output frame, relative to the first frame of `history`
+    position: f64,
+    /// Amount of frames of `history` which come from the input, once it ended
+    end: Option<usize>,
+    /// The current output frame
+    frame: Vec<f32>,
+    /// Index of the next sample of `frame` to return
+    index: usize,
+}
+
+impl<S: Source<Item = f32>> Resample<S> {
+    /// Creates a new resampler.
+    ///
+    /// # Arguments
+    /// * `input` - Source to resample
+    /// * `sample_rate` - Sample rate to resample to
+    pub fn new(input: S, sample_rate: u32) -> Self {
+        let channels = input.channels().max(1) as usize;
+        let step = input.sample_rate() as f64 / sample_rate as f64;
+
+        /* When downsampling, the cutoff is lowered (and the filter gets longer) */
+        let cutoff = SINC_CUTOFF * (1.0 / step).min(1.0);
+        let half = (SINC_HALF_TAPS as f64 / cutoff).ceil() as usize;
+        let taps = 2 * half;
+
+        let mut table = Vec::with_capacity((SINC_PHASES + 1) * taps);
+        for phase in 0..=SINC_PHASES {
+            let fraction = phase as f64 / SINC_PHASES as f64;
+            let row: Vec<f64> = (0..taps)
+                .map(|tap| {
+                    let x = tap as f64 - (half - 1) as f64 - fraction;
+                    let n = x / half as f64;
+                    let window = match n.abs() < 1.0 {
+                        true => 0.42 + 0.5 * (PI64 * n).cos() + 0.08 * (2.0 * PI64 * n).cos(),
+                        false => 0.0,
+                    };
+                    cutoff * sinc(cutoff * x) * window
+                })
+                .collect();
+            /* Normalized, so the level doesn't change */
+            let sum: f64 = row.iter().sum();
+            table.extend(row.iter().map(|weight| (weight / sum) as f32));
+        }
+
+        /* The first output frame is at the first input frame, with silence before it */
+        let history = VecDeque::from(vec![0.0; (half - 1) * channels]);
+        Self {
+            input,
+            channels,
+            sample_rate,
+            step,
+            half,
+            table,
+            history,
+            position: (half - 1) as f64,
+            end: None,
+            frame: Vec::new(),
+            index: 0,
+        }
+    }
+
+    /// Calculates the next output frame.
+    /// Returns `false` if the input has ended.
+    fn process_frame(&mut self) -> bool {
+        let base = self.position as usize;
+        let needed = (base + self.half + 1) * self.channels;
+        while self.history.len() < needed {
+            match self.end {
+                Some(_) => self.history.push_back(0.0),
+                None => match self.input.next() {
+                    Some(sample) => self.history.push_back(sample),
+                    None => self.end = Some(self.history.len() / self.channels),
+                },
+            }
+        }
+        if self.end.is_some_and(|end| base >= end) {
+            return false;
+        }
+
+        let taps = 2 * self.half;
+        let phase = (self.position - base as f64) * SINC_PHASES as f64;
+        let (row, blend) = (phase as usize, phase.fract() as f32);
+        /* The fraction is below 1, so there's always a next row */
+        let current = &self.table[row * taps..(row + 1) * taps];
+        let next = &self.table[(row + 1) * taps..(row + 2) * taps];
+
+        let first = base + 1 - self.half;
+        self.frame.clear();
+        self.index = 0;
+        for channel in 0..self.channels {
+            let mut sum = 0.0;
+            for tap in 0..taps {
+                let weight = current[tap] + (next[tap] - current[tap]) * blend;
+                sum += self.history[(first + tap) * self.channels + channel] * weight;
+            }
+            self.frame.push(sum);
+        }
+
+        /* Frames which aren't needed anymore are dropped */
+        self.position += self.step;
+        let unused = (self.position as usize).saturating_sub(self.half - 1);
+        self.history.drain(..unused * self.channels);
+        self.position -= unused as f64;
+        self.end = self.end.map(|end| end.saturating_sub(unused));
+        true
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Resample<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.index >= self.frame.len() && !self.process_frame() {
+            return None;
+        }
+
+        self.index += 1;
+        Some(self.frame[self.index - 1])
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Resample<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// Adds TPDF (triangular) dither before the audio is rounded to a lower bit depth, which turns
+/// the rounding distortion of quiet passages into a constant, very quiet noise.
+pub struct Dither<S: Source<Item = f32>> {
+    /// Input source
+    input: S,
+    /// Size of the least significant bit of the output
+    lsb: f32,
+    /// State of the random number generator (xorshift)
+    random: u32,
+}
+
+impl<S: Source<Item = f32>> Dither<S> {
+    /// Creates a new ditherer.
+    ///
+    /// # Arguments
+    /// * `input` - Source to dither
+    /// * `bits` - Bit depth the audio is rounded to
+    pub fn new(input: S, bits: u16) -> Self {
+        Self {
+            input,
+            lsb: 2f32.powi(1 - bits.clamp(2, 32) as i32),
+            random: 0x9E37_79B9,
+        }
+    }
+
+    /// Returns a random number from `0.0` to `1.0`.
+    fn random(&mut self) -> f32 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        (self.random >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Dither<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        /* The difference of two uniform random numbers has a triangular distribution */
+        Some(sample + (self.random() - self.random()) * self.lsb)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Dither<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// Bass and treble tone controls: a low-shelf and a high-shelf filter.
+/// The levels are read from the shared [`ToneControls`](ToneControls) at the start of
+/// every frame, so changes are heard immediately.
+pub struct Tone<S: Source<Item = f32>> {
+    /// Input source
+    input: S,
+    /// The levels to apply
+    controls: Arc<ToneControls>,
+    /// Whether the audio is left untouched (as if the levels were flat)
+    bypass: Arc<AtomicBool>,
+    /// Bass, treble and sample rate the filters were made for
+    current: (i32, i32, u32),
+    /// The bass filter
+    bass: Biquad,
+    /// The treble filter
+    treble: Biquad,
+    /// Channel of the next sample
+    channel: usize,
+}
+
+/// A second-order IIR filter, with separate state for every channel.
+struct Biquad {
+    /// Normalized coefficients `[b0, b1, b2, a1, a2]`
+    coefficients: [f32; 5],
+    /// `[x1, x2, y1, y2]` of every channel
+    state: Vec<[f32; 4]>,
+}
+
+impl<S: Source<Item = f32>> Tone<S> {
+    /// Creates new tone controls.
+    ///
+    /// # Arguments
+    /// * `input` - Source to filter
+    /// * `controls` - The levels, which can be changed while playing
+    /// * `bypass` - Whether the audio is left untouched, which can be changed while playing
+    pub fn new(input: S, controls: Arc<ToneControls>, bypass: Arc<AtomicBool>) -> Self {
+        Self {
+            input,
+            controls,
+            bypass,
+            current: (0, 0, 0),
+            bass: Biquad::new(),
+            treble: Biquad::new(),
+            channel: 0,
+        }
+    }
+
+    /// Makes the filters again if the levels (or the sample rate) changed.
+    fn update_filters(&mut self) {
+        let (bass, treble) = match self.bypass.load(Ordering::Relaxed) {
+            true => (0, 0),
+            false => (self.controls.bass(), self.controls.treble()),
+        };
+        let sample_rate = self.input.sample_rate();
+        let levels = (bass, treble, sample_rate);
+        if levels == self.current {
+            return;
+        }
+
+        self.bass.coefficients = shelf(false, BASS_FREQUENCY, bass, sample_rate);
+        self.treble.coefficients = shelf(true, TREBLE_FREQUENCY, treble, sample_rate);
+        self.current = levels;
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Tone<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let channels = self.input.channels().max(1) as usize;
+        if self.channel >= channels {
+            self.channel = 0;
+        }
+        if self.channel == 0 {
+            self.update_filters();
+        }
+
+        let sample = self.input.next()?;
+        let channel = self.channel;
+        self.channel += 1;
+
+        /* Flat, the audio is left untouched */
+        if self.current.0 == 0 && self.current.1 == 0 {
+            return Some(sample);
+        }
+
+        let sample = self.bass.process(channel, sample);
+        Some(self.treble.process(channel, sample))
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Tone<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+impl Biquad {
+    /// Creates a filter which leaves the audio untouched.
+    fn new() -> Self {
+        Self {
+            coefficients: [1.0, 0.0, 0.0, 0.0, 0.0],
+            state: Vec::new(),
+        }
+    }
+
+    /// Filters a sample of a channel.
+    fn process(&mut self, channel: usize, x: f32) -> f32 {
+        if channel >= self.state.len() {
+            self.state.resize(channel + 1, [0.0; 4]);
+        }
+
+        let [b0, b1, b2, a1, a2] = self.coefficients;
+        let [x1, x2, y1, y2] = self.state[channel];
+        let y = b0 * x + b1 * x1 + b2 * x2 - a1 * y1 - a2 * y2;
+        self.state[channel] = [x, x1, y, y1];
+        y
+    }
+}
+
+/// Calculates the coefficients of a shelf filter, using the formulas of the
+/// [Audio EQ Cookbook](https://www.w3.org/TR/audio-eq-cookbook/) (with a slope of 1).
+///
+/// # Arguments
+/// * `high` - Whether to make a high-shelf filter, instead of a low-shelf one
+/// * `frequency` - Corner frequency (in Hz)
+/// * `gain_db` - Gain of the shelf (in dB)
+/// * `sample_rate` - Sample rate of the audio
+fn shelf(high: bool, frequency: f32, gain_db: i32, sample_rate: u32) -> [f32; 5] {
+    /* The frequency has to stay below the Nyquist frequency */
+    let frequency = frequency.min(sample_rate as f32 * 0.45);
+    let a = 10f32.powf(gain_db as f32 / 40.0);
+    let w0 = 2.0 * PI * frequency / sample_rate as f32;
+    let (sin, cos) = w0.sin_cos();
+    let alpha = sin / 2.0 * 2f32.sqrt();
+    let k = 2.0 * a.sqrt() * alpha;
+
+    let (b0, b1, b2, a0, a1, a2) = match high {
+        false => (
+            a * ((a + 1.0) - (a - 1.0) * cos + k),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            a * ((a + 1.0) - (a - 1.0) * cos - k),
+            (a + 1.0) + (a - 1.0) * cos + k,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+            (a + 1.0) + (a - 1.0) * cos - k,
+        ),
+        true => (
+            a * ((a + 1.0) + (a - 1.0) * cos + k),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - k),
+            (a + 1.0) - (a - 1.0) * cos + k,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - k,
+        ),
+    };
+
+    [b0 / a0, b1 / a0, b2 / a0, a1 / a0, a2 / a0]
+}
+
+/// Returns how much the limiter reduces a peak (in dB).
+/// Below the knee nothing is reduced, above it the peak is reduced to the threshold.
+fn limiter_reduction(peak_db: f32) -> f32 {
+    let over = peak_db - LIMITER_THRESHOLD_DB;
+    let half_knee = LIMITER_KNEE_DB / 2.0;
+
+    match over {
+        _ if over <= -half_knee => 0.0,
+        _ if over >= half_knee => over,
+        /* Inside the knee, the reduction grows smoothly */
+        _ => (over + half_knee).powi(2) / (2.0 * LIMITER_KNEE_DB),
+    }
+}
+
+/// Returns the gain (in dB) night mode applies to audio peaking at `peak_db`, before limiting.
+fn night_gain(peak_db: f32) -> f32 {
+    let over = (peak_db - NIGHT_THRESHOLD_DB).max(0.0);
+    NIGHT_MAKEUP_DB - over * (1.0 - 1.0 / NIGHT_RATIO)
+}
+
+/// The normalized sinc function, `sin(πx) / πx`.
+fn sinc(x: f64) -> f64 {
+    match x == 0.0 {
+        true => 1.0,
+        false => (PI64 * x).sin() / (PI64 * x),
+    }
+}
+
+/// Measures the peak level of the audio passing through it, without changing it.
+///
+/// The current level is the highest sample of each block of [`PEAK_WINDOW`](PEAK_WINDOW),
+/// so it changes slowly enough to be read.  
+/// Every run of samples above full scale (which clip when they're played) counts as one clip.
+pub struct Peak<S: Source<Item = f32>> {
+    /// Input source
+    input: S,
+    /// Highest absolute sample of the current block
+    peak: f32,
+    /// Amount of samples left in the current block
+    remaining: usize,
+    /// Whether the last sample was above full scale
+    clipping: bool,
+    /// Where the levels are stored
+    levels: Levels,
+}
+
+impl Levels {
+    /// Returns the peak level of the last measured block of audio (in dBFS).
+    pub fn current_db(&self) -> f32 {
+        gain_to_db(f32::from_bits(self.current.load(Ordering::Relaxed)))
+    }
+
+    /// Returns the highest peak level of the session (in dBFS).
+    pub fn session_db(&self) -> f32 {
+        gain_to_db(f32::from_bits(self.session.load(Ordering::Relaxed)))
+    }
+
+    /// Returns how many times the audio clipped in this session.
+    pub fn clips(&self) -> u64 {
+        self.clips.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether the last measured block of audio clipped.
+    pub fn is_clipping(&self) -> bool {
+        f32::from_bits(self.current.load(Ordering::Relaxed)) > 1.0
+    }
+}
+
+impl<S: Source<Item = f32>> Peak<S> {
+    /// Creates a new peak meter.
+    ///
+    /// # Arguments
+    /// * `input` - The measured audio
+    /// * `levels` - Where the levels are stored
+    pub fn new(input: S, levels: Levels) -> Self {
+        let mut peak = Self {
+            input,
+            peak: 0.0,
+            remaining: 0,
+            clipping: false,
+            levels,
+        };
+        peak.remaining = peak.window();
+        peak
+    }
+
+    /// Returns the amount of samples in a block.
+    fn window(&self) -> usize {
+        let samples = PEAK_WINDOW.as_secs_f64()
+            * self.input.sample_rate() as f64
+            * self.input.channels() as f64;
+        (samples as usize).max(1)
+    }
+
+    /// Stores the peak of the finished block.
+    fn flush(&mut self) {
+        let bits = self.peak.to_bits();
+        self.levels.current.store(bits, Ordering::Relaxed);
+        /* The bits of positive floats are ordered like the floats */
+        self.levels.session.fetch_max(bits, Ordering::Relaxed);
+        self.peak = 0.0;
+        self.remaining = self.window();
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Peak<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+
+        self.peak = self.peak.max(sample.abs());
+        let clipping = sample.abs() > 1.0;
+        if clipping && !self.clipping {
+            self.levels.clips.fetch_add(1, Ordering::Relaxed);
+        }
+        self.clipping = clipping;
+        self.remaining = self.remaining.saturating_sub(1);
+        if self.remaining == 0 {
+            self.flush();
+        }
+
+        Some(sample)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Peak<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// Converts decibels into a linear gain.
+fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Converts a linear gain into decibels.
+fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(f32::MIN_POSITIVE).log10()
+}
@@ -0,0 +1,143 @@
+use crate::audioinfo::AudioFormat;
+use crate::player::{DecodeOptions, Player};
+use rodio::Source;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Length of the windows the loudness is measured over.
+const WINDOW: Duration = Duration::from_millis(250);
+/// Parts of a track within this many dB of its loudest window are loud.
+const LOUD_DB: f32 = 6.0;
+/// Parts of a track more than this many dB below its loudest window are quiet.
+const QUIET_DB: f32 = 18.0;
+
+/// The loudness of a whole track over time, measured by decoding it ahead of playback.
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    /// Mean power of every window (linear)
+    power: Vec<f32>,
+    /// Mean power of the loudest window (linear)
+    loudest: f32,
+}
+
+/// Measuring the loudness of a track in the background, see [`Envelope::scan()`](Envelope::scan).
+/// The measuring stops once this is dropped (e.g. when the track is skipped).
+pub struct EnvelopeScan {
+    /// Receives the envelope once the whole track is measured
+    envelope: Receiver<Envelope>,
+    /// Stops the measuring
+    cancelled: Arc<AtomicBool>,
+}
+
+/// How loud a part of a track is, compared to the rest of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Loudness {
+    /// Much quieter than the loudest part (e.g. an intro or a break)
+    Quiet,
+    /// In between
+    Normal,
+    /// About as loud as the loudest part
+    Loud,
+}
+
+impl Envelope {
+    /// Starts measuring the loudness of a local file, in a background thread.
+    /// The envelope can be taken from the returned scan once the whole file is decoded.
+    /// If the file can't be decoded, or the scan is dropped before, there's no envelope.
+    ///
+    /// # Arguments
+    /// * `file` - The file to measure
+    /// * `options` - How the file is decoded (the same way as it's played)
+    pub fn scan(file: &str, options: &DecodeOptions) -> EnvelopeScan {
+        let (sender, envelope) = channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let file = file.to_owned();
+        let options = options.clone();
+
+        let thread_cancelled = Arc::clone(&cancelled);
+        thread::spawn(move || {
+            let Ok(format) = AudioFormat::from_path(&file) else {
+                return;
+            };
+            let Ok(source) = Player::decode_file(&file, format, &options) else {
+                return;
+            };
+            if let Some(measured) = Self::measure(source, &thread_cancelled) {
+                let _ = sender.send(measured);
+            }
+        });
+
+        EnvelopeScan {
+            envelope,
+            cancelled,
+        }
+    }
+
+    /// Measures the loudness of decoded audio.
+    /// Returns `None` if it was cancelled before the end.
+    fn measure(source: Box<dyn Source<Item = f32> + Send>, cancelled: &AtomicBool) -> Option<Self> {
+        let samples = WINDOW.as_secs_f64() * source.sample_rate() as f64 * source.channels() as f64;
+        let window = (samples as usize).max(1);
+
+        let mut power = Vec::new();
+        let (mut sum, mut count) = (0.0, 0);
+        for sample in source {
+            sum += sample * sample;
+            count += 1;
+            if count == window {
+                power.push(sum / count as f32);
+                (sum, count) = (0.0, 0);
+                if cancelled.load(Ordering::Relaxed) {
+                    return None;
+                }
+            }
+        }
+        if count > 0 {
+            power.push(sum / count as f32);
+        }
+
+        let loudest = power.iter().copied().fold(0.0, f32::max);
+        Some(Self { power, loudest })
+    }
+
+    /// Returns how loud a part of the track is, compared to the rest of it.
+    ///
+    /// # Arguments
+    /// * `start` - Where the part starts (as a fraction of the track, from `0.0` to `1.0`)
+    /// * `end` - Where the part ends (as a fraction of the track, from `0.0` to `1.0`)
+    pub fn loudness(&self, start: f64, end: f64) -> Loudness {
+        let len = self.power.len();
+        let first = ((start * len as f64) as usize).min(len.saturating_sub(1));
+        let last = ((end * len as f64).ceil() as usize).clamp(first + 1, len.max(1));
+
+        let part = &self.power[first..last.min(len)];
+        if part.is_empty() || self.loudest <= 0.0 {
+            return Loudness::Quiet;
+        }
+
+        /* Power ratio in dB, below the loudest window */
+        let mean = part.iter().sum::<f32>() / part.len() as f32;
+        let below_db = 10.0 * (self.loudest / mean.max(f32::MIN_POSITIVE)).log10();
+        match below_db {
+            _ if below_db <= LOUD_DB => Loudness::Loud,
+            _ if below_db > QUIET_DB => Loudness::Quiet,
+            _ => Loudness::Normal,
+        }
+    }
+}
+
+impl EnvelopeScan {
+    /// Returns the envelope, once the whole track was measured.
+    pub fn try_recv(&self) -> Option<Envelope> {
+        self.envelope.try_recv().ok()
+    }
+}
+
+impl Drop for EnvelopeScan {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,104 @@
+use rodio::Source;
+use std::io::{BufReader, Read};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::time::Duration;
+
+/// A [`Source`](rodio::Source) which decodes audio using an external `ffmpeg` process.
+/// This is used for formats that neither `rodio` nor `sndfile` can decode.
+///
+/// The audio is decoded into 32-bit floating point samples and read from `ffmpeg`'s
+/// standard output as it's being played, so the whole file is never kept in memory.
+pub struct FfmpegDecoder {
+    /// The `ffmpeg` process
+    child: Child,
+    /// Standard output of the `ffmpeg` process
+    output: BufReader<ChildStdout>,
+    /// Number of channels
+    channels: u16,
+    /// Sample rate
+    sample_rate: u32,
+    /// Total length of the stream
+    duration: Duration,
+}
+
+impl FfmpegDecoder {
+    /// Starts decoding a file with `ffmpeg`.
+    ///
+    /// # Arguments
+    /// * `file` - A [`String`](String) containing the path to the audio file.
+    /// * `channels` - Number of channels to decode
+    /// * `sample_rate` - Sample rate to decode at
+    /// * `duration` - Length of the track
+    /// * `args` - Extra options of the decoder (e.g. `-drc_scale 0`), given before the input
+    ///
+    /// ### Notes
+    /// This fails if `ffmpeg` is not installed.
+    pub fn new(
+        file: &str,
+        channels: u16,
+        sample_rate: u32,
+        duration: Duration,
+        args: &[String],
+    ) -> Result<Self, String> {
+        let mut child = Command::new("ffmpeg")
+            .args(["-v", "quiet", "-nostdin"])
+            .args(args)
+            .args(["-i", file, "-f", "f32le"])
+            .args([
+                "-ac",
+                &channels.to_string(),
+                "-ar",
+                &sample_rate.to_string(),
+            ])
+            .arg("-")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Unable to start ffmpeg: {e}"))?;
+        let output = BufReader::new(child.stdout.take().unwrap());
+
+        Ok(Self {
+            child,
+            output,
+            channels,
+            sample_rate,
+            duration,
+        })
+    }
+}
+
+impl Iterator for FfmpegDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let mut sample = [0u8; 4];
+        self.output.read_exact(&mut sample).ok()?;
+        Some(f32::from_le_bytes(sample))
+    }
+}
+
+impl Source for FfmpegDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(self.duration)
+    }
+}
+
+impl Drop for FfmpegDecoder {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
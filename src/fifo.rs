@@ -0,0 +1,105 @@
+use crate::tap::{TapChunk, Taps};
+use std::ffi::CString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time to wait before trying to open the pipe again, while nothing reads it.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Starts writing the played audio into a named pipe (which is created if needed),
+/// for external visualizers like [cava](https://github.com/karlstav/cava).
+///
+/// The audio is written as signed 16-bit little-endian stereo PCM at the sample rate it's
+/// played at, which is the format of cava's `fifo` input (and of MPD's FIFO output).
+/// It's written in a background thread for the whole session. While nothing reads the pipe,
+/// the audio is thrown away.
+///
+/// ## Errors
+/// Fails if the pipe can't be created, or if the path exists but isn't a pipe.
+pub fn start(path: &Path, taps: &Taps) -> Result<(), String> {
+    create(path)?;
+
+    let path = path.to_path_buf();
+    let chunks = taps.subscribe();
+    thread::spawn(move || {
+        let mut pipe: Option<File> = None;
+        let mut attempted: Option<Instant> = None;
+
+        for chunk in chunks {
+            if pipe.is_none() && attempted.is_none_or(|time| time.elapsed() >= RETRY_INTERVAL) {
+                attempted = Some(Instant::now());
+                pipe = open(&path).ok();
+            }
+
+            let Some(writer) = pipe.as_mut() else {
+                continue;
+            };
+            if writer.write_all(&encode(&chunk)).is_err() {
+                /* The visualizer was closed, the pipe is opened again once it's back */
+                pipe = None;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Creates the named pipe, unless it already exists.
+fn create(path: &Path) -> Result<(), String> {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.file_type().is_fifo() => return Ok(()),
+        Ok(_) => return Err(format!("{} is not a named pipe", path.display())),
+        Err(e) if e.kind() == ErrorKind::NotFound => (),
+        Err(e) => return Err(format!("Unable to access {}: {e}", path.display())),
+    }
+
+    let invalid = || format!("Invalid path: {}", path.display());
+    let name = CString::new(path.as_os_str().as_bytes()).map_err(|_| invalid())?;
+    if unsafe { libc::mkfifo(name.as_ptr(), 0o644) } < 0 {
+        let e = io::Error::last_os_error();
+        return Err(format!("Unable to create {}: {e}", path.display()));
+    }
+
+    Ok(())
+}
+
+/// Opens the pipe for writing.
+/// Fails if nothing reads the pipe, instead of waiting for a reader.
+fn open(path: &Path) -> io::Result<File> {
+    let pipe = OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)?;
+
+    /* Writing waits for the reader, so no audio is lost when it's a bit slow */
+    let fd = pipe.as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(pipe)
+}
+
+/// Converts a chunk into signed 16-bit little-endian stereo PCM.
+/// Mono audio is played on both channels, and channels other than the first two are dropped.
+fn encode(chunk: &TapChunk) -> Vec<u8> {
+    let channels = chunk.channels.max(1) as usize;
+    let mut data = Vec::with_capacity(chunk.samples.len() / channels * 4);
+
+    for frame in chunk.samples.chunks(channels) {
+        let (left, right) = (frame[0], *frame.get(1).unwrap_or(&frame[0]));
+        for sample in [left, right] {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+            data.extend_from_slice(&sample.to_le_bytes());
+        }
+    }
+
+    data
+}
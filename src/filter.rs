@@ -0,0 +1,558 @@
+use rusqlite::types::Value;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A field of a track which can be used in a filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Path,
+    Title,
+    Album,
+    Artist,
+    Genre,
+    Year,
+    Track,
+    Length,
+    Plays,
+    Skips,
+    Rating,
+}
+
+/// A comparison operator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    /// `=`
+    Equal,
+    /// `!=`
+    NotEqual,
+    /// `<`
+    Less,
+    /// `<=`
+    LessOrEqual,
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterOrEqual,
+    /// `~` (contains)
+    Contains,
+}
+
+/// A value to compare a field to.
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Text(String),
+    Number(f64),
+}
+
+/// A parsed filter expression.
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Compare(Field, Operator, Literal),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+/// A token of a filter expression.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A field name or a keyword (`AND`, `OR`, `NOT`)
+    Word(String),
+    /// A quoted string
+    Text(String),
+    Number(f64),
+    Operator(Operator),
+    Open,
+    Close,
+}
+
+/// A filter which selects tracks from the library, for example
+/// `genre = "jazz" AND year >= 1970`, `plays = 0` (never played) or `rating >= 4`.
+///
+/// A filter is made of comparisons (`FIELD OPERATOR VALUE`), which can be combined using
+/// `AND`, `OR`, `NOT` and parentheses. Keywords and field names are __not__ case-sensitive.
+///
+/// | Fields | Values |
+/// |--------|--------|
+/// | `path`, `title`, `album`, `artist`, `genre` | Text in double (or single) quotes |
+/// | `year`, `track`, `length` (in seconds), `plays`, `skips`, `rating` (`0` if not rated) | Numbers |
+///
+/// Supported operators are `=`, `!=`, `<`, `<=`, `>`, `>=` and `~` (contains, only for text).
+///
+/// ### Notes
+/// Text is compared case-insensitively. Missing text tags are equal to `"Unknown"`,
+/// tracks with a missing year or track number never match comparisons of these fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    /// Parses a filter expression.
+    ///
+    /// ## Errors
+    /// Returns a description of the problem if the expression is invalid.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+        };
+
+        let expr = parser.or()?;
+        if let Some(token) = parser.next() {
+            return Err(format!("Unexpected {token}"));
+        }
+
+        Ok(Self { expr })
+    }
+
+    /// Converts the filter into a condition on the `tracks` table of the library.
+    /// Returns the SQL condition, and the values of its (anonymous) parameters.
+    pub fn to_sql(&self) -> (String, Vec<Value>) {
+        let mut params = Vec::new();
+        let sql = self.expr.to_sql(&mut params);
+        (sql, params)
+    }
+}
+
+impl Field {
+    /// Finds a field by its name (case-insensitively).
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "path" => Self::Path,
+            "title" => Self::Title,
+            "album" => Self::Album,
+            "artist" => Self::Artist,
+            "genre" => Self::Genre,
+            "year" => Self::Year,
+            "track" => Self::Track,
+            "length" => Self::Length,
+            "plays" => Self::Plays,
+            "skips" => Self::Skips,
+            "rating" => Self::Rating,
+            _ => return None,
+        })
+    }
+
+    /// Returns the SQL expression of the field.
+    fn column(self) -> &'static str {
+        match self {
+            Self::Path => "path",
+            Self::Title => "title",
+            Self::Album => "album",
+            Self::Artist => "artist",
+            Self::Genre => "IFNULL(genre, 'Unknown')",
+            Self::Year => "year",
+            Self::Track => "track",
+            Self::Length => "length",
+            Self::Plays => "IFNULL(plays, 0)",
+            Self::Skips => "IFNULL(skips, 0)",
+            Self::Rating => "IFNULL(rating, 0)",
+        }
+    }
+
+    /// Returns whether the field is compared to numbers.
+    fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            Self::Year | Self::Track | Self::Length | Self::Plays | Self::Skips | Self::Rating
+        )
+    }
+}
+
+impl Operator {
+    /// Returns the SQL operator.
+    fn sql(self) -> &'static str {
+        match self {
+            Self::Equal => "=",
+            Self::NotEqual => "<>",
+            Self::Less => "<",
+            Self::LessOrEqual => "<=",
+            Self::Greater => ">",
+            Self::GreaterOrEqual => ">=",
+            Self::Contains => "~",
+        }
+    }
+}
+
+impl Expr {
+    /// Converts the expression into SQL, appending the values of its parameters to `params`.
+    fn to_sql(&self, params: &mut Vec<Value>) -> String {
+        match self {
+            Self::Compare(field, Operator::Contains, Literal::Text(text)) => {
+                params.push(Value::Text(text.clone()));
+                format!("instr(lower({}), lower(?)) > 0", field.column())
+            }
+            Self::Compare(field, operator, literal) => {
+                let collation = match literal {
+                    Literal::Text(text) => {
+                        params.push(Value::Text(text.clone()));
+                        " COLLATE NOCASE"
+                    }
+                    Literal::Number(number) => {
+                        params.push(Value::Real(*number));
+                        ""
+                    }
+                };
+                format!("{} {} ?{collation}", field.column(), operator.sql())
+            }
+            Self::And(a, b) => format!("({} AND {})", a.to_sql(params), b.to_sql(params)),
+            Self::Or(a, b) => format!("({} OR {})", a.to_sql(params), b.to_sql(params)),
+            Self::Not(a) => format!("(NOT {})", a.to_sql(params)),
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Word(word) => write!(f, "'{word}'"),
+            Self::Text(text) => write!(f, "\"{text}\""),
+            Self::Number(number) => write!(f, "'{number}'"),
+            Self::Operator(Operator::NotEqual) => write!(f, "'!='"),
+            Self::Operator(operator) => write!(f, "'{}'", operator.sql()),
+            Self::Open => write!(f, "'('"),
+            Self::Close => write!(f, "')'"),
+        }
+    }
+}
+
+/// A recursive descent parser for filter expressions.
+///
+/// ```text
+/// or         = and ("OR" and)*
+/// and        = not ("AND" not)*
+/// not        = "NOT" not | primary
+/// primary    = "(" or ")" | comparison
+/// comparison = FIELD OPERATOR VALUE
+/// ```
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    /// Returns the next token without consuming it.
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    /// Consumes the next token.
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Consumes the next token if it's the given keyword.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found =
+            matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.keyword("OR") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.keyword("AND") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.keyword("NOT") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    Some(token) => Err(format!("Expected ')', found {token}")),
+                    None => Err("Missing ')'".to_owned()),
+                }
+            }
+            Some(Token::Word(name)) => self.comparison(&name),
+            Some(token) => Err(format!("Expected a field name, found {token}")),
+            None => Err("Unexpected end of the filter".to_owned()),
+        }
+    }
+
+    fn comparison(&mut self, name: &str) -> Result<Expr, String> {
+        let field = Field::from_name(name).ok_or_else(|| format!("Unknown field '{name}'"))?;
+
+        let operator = match self.next() {
+            Some(Token::Operator(operator)) => operator,
+            Some(token) => {
+                return Err(format!(
+                    "Expected an operator after '{name}', found {token}"
+                ))
+            }
+            None => return Err(format!("Expected an operator after '{name}'")),
+        };
+
+        let literal = match (self.next(), field.is_numeric()) {
+            (Some(Token::Number(number)), true) => Literal::Number(number),
+            (Some(Token::Text(text)), false) => Literal::Text(text),
+            (_, true) => return Err(format!("'{name}' must be compared to a number")),
+            (_, false) => return Err(format!("'{name}' must be compared to quoted text")),
+        };
+
+        if operator == Operator::Contains && field.is_numeric() {
+            return Err(format!("'~' can't be used with '{name}'"));
+        }
+
+        Ok(Expr::Compare(field, operator, literal))
+    }
+}
+
+/// Splits a filter expression into tokens.
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' => {
+                chars.next();
+                Token::Open
+            }
+            ')' => {
+                chars.next();
+                Token::Close
+            }
+            '"' | '\'' => Token::Text(read_quoted(&mut chars)?),
+            '=' | '!' | '<' | '>' | '~' => Token::Operator(read_operator(&mut chars)?),
+            c if c.is_ascii_digit() => {
+                let number = read_while(&mut chars, |c| c.is_ascii_digit() || c == '.');
+                Token::Number(
+                    number
+                        .parse()
+                        .map_err(|_| format!("Invalid number '{number}'"))?,
+                )
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                Token::Word(read_while(&mut chars, |c| c.is_alphanumeric() || c == '_'))
+            }
+            c => return Err(format!("Unexpected character '{c}'")),
+        };
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// Reads characters as long as they match `predicate`.
+fn read_while(chars: &mut Peekable<Chars>, predicate: impl Fn(char) -> bool) -> String {
+    let mut result = String::new();
+    while let Some(c) = chars.next_if(|c| predicate(*c)) {
+        result.push(c);
+    }
+    result
+}
+
+/// Reads a quoted string. A backslash escapes the next character.
+fn read_quoted(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let quote = chars.next().unwrap();
+    let mut result = String::new();
+
+    loop {
+        match chars.next() {
+            Some('\\') => result.push(chars.next().ok_or("Unterminated text")?),
+            Some(c) if c == quote => return Ok(result),
+            Some(c) => result.push(c),
+            None => return Err("Unterminated text".to_owned()),
+        }
+    }
+}
+
+/// Reads a comparison operator.
+fn read_operator(chars: &mut Peekable<Chars>) -> Result<Operator, String> {
+    let first = chars.next().unwrap();
+    let second = chars.next_if_eq(&'=').or_else(|| {
+        /* `<>` is accepted as well, like in SQL */
+        (first == '<').then(|| chars.next_if_eq(&'>')).flatten()
+    });
+
+    Ok(match (first, second) {
+        ('=', None) | ('=', Some('=')) => Operator::Equal,
+        ('!', Some('=')) | ('<', Some('>')) => Operator::NotEqual,
+        ('<', None) => Operator::Less,
+        ('<', Some('=')) => Operator::LessOrEqual,
+        ('>', None) => Operator::Greater,
+        ('>', Some('=')) => Operator::GreaterOrEqual,
+        ('~', None) => Operator::Contains,
+        _ => return Err(format!("Invalid operator '{first}'")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compare(field: Field, operator: Operator, literal: Literal) -> Expr {
+        Expr::Compare(field, operator, literal)
+    }
+
+    fn text(text: &str) -> Literal {
+        Literal::Text(text.to_owned())
+    }
+
+    #[test]
+    fn tokenize_splits_words_operators_and_values() {
+        assert_eq!(
+            tokenize(r#"(genre~"ja\"zz") AND year>=1970.5"#).unwrap(),
+            vec![
+                Token::Open,
+                Token::Word("genre".to_owned()),
+                Token::Operator(Operator::Contains),
+                Token::Text("ja\"zz".to_owned()),
+                Token::Close,
+                Token::Word("AND".to_owned()),
+                Token::Word("year".to_owned()),
+                Token::Operator(Operator::GreaterOrEqual),
+                Token::Number(1970.5),
+            ]
+        );
+        assert_eq!(
+            tokenize("title = 'it''s'").unwrap()[2..],
+            [Token::Text("it".to_owned()), Token::Text("s".to_owned())]
+        );
+    }
+
+    #[test]
+    fn tokenize_rejects_invalid_input() {
+        assert_eq!(tokenize("title = \"open").unwrap_err(), "Unterminated text");
+        assert_eq!(
+            tokenize("plays = 1.2.3").unwrap_err(),
+            "Invalid number '1.2.3'"
+        );
+        assert_eq!(
+            tokenize("plays # 1").unwrap_err(),
+            "Unexpected character '#'"
+        );
+    }
+
+    #[test]
+    fn read_operator_reads_every_operator() {
+        let read = |text: &str| read_operator(&mut text.chars().peekable());
+
+        assert_eq!(read("="), Ok(Operator::Equal));
+        assert_eq!(read("=="), Ok(Operator::Equal));
+        assert_eq!(read("!="), Ok(Operator::NotEqual));
+        assert_eq!(read("<>"), Ok(Operator::NotEqual));
+        assert_eq!(read("<"), Ok(Operator::Less));
+        assert_eq!(read("<="), Ok(Operator::LessOrEqual));
+        assert_eq!(read(">"), Ok(Operator::Greater));
+        assert_eq!(read(">="), Ok(Operator::GreaterOrEqual));
+        assert_eq!(read("~"), Ok(Operator::Contains));
+        assert_eq!(read("!"), Err("Invalid operator '!'".to_owned()));
+        assert_eq!(read("~="), Err("Invalid operator '~'".to_owned()));
+    }
+
+    #[test]
+    fn read_operator_leaves_the_rest() {
+        let mut chars = "<5".chars().peekable();
+        assert_eq!(read_operator(&mut chars), Ok(Operator::Less));
+        assert_eq!(chars.collect::<String>(), "5");
+    }
+
+    #[test]
+    fn parse_gives_and_precedence_over_or() {
+        let filter = Filter::parse("genre = 'jazz' OR genre = 'blues' and rating >= 4").unwrap();
+        assert_eq!(
+            filter.expr,
+            Expr::Or(
+                Box::new(compare(Field::Genre, Operator::Equal, text("jazz"))),
+                Box::new(Expr::And(
+                    Box::new(compare(Field::Genre, Operator::Equal, text("blues"))),
+                    Box::new(compare(
+                        Field::Rating,
+                        Operator::GreaterOrEqual,
+                        Literal::Number(4.0)
+                    )),
+                )),
+            )
+        );
+    }
+
+    #[test]
+    fn parse_applies_not_and_parentheses_first() {
+        let filter = Filter::parse("NOT (plays = 0 OR skips > 2) AND Title ~ \"live\"").unwrap();
+        assert_eq!(
+            filter.expr,
+            Expr::And(
+                Box::new(Expr::Not(Box::new(Expr::Or(
+                    Box::new(compare(Field::Plays, Operator::Equal, Literal::Number(0.0))),
+                    Box::new(compare(
+                        Field::Skips,
+                        Operator::Greater,
+                        Literal::Number(2.0)
+                    )),
+                )))),
+                Box::new(compare(Field::Title, Operator::Contains, text("live"))),
+            )
+        );
+    }
+
+    #[test]
+    fn parse_reports_errors() {
+        let error = |text: &str| Filter::parse(text).unwrap_err();
+
+        assert_eq!(error(""), "Unexpected end of the filter");
+        assert_eq!(error("mood = 'calm'"), "Unknown field 'mood'");
+        assert_eq!(
+            error("year 1970"),
+            "Expected an operator after 'year', found '1970'"
+        );
+        assert_eq!(error("year ="), "'year' must be compared to a number");
+        assert_eq!(
+            error("title = 5"),
+            "'title' must be compared to quoted text"
+        );
+        assert_eq!(error("year ~ 19"), "'~' can't be used with 'year'");
+        assert_eq!(error("(plays = 0"), "Missing ')'");
+        assert_eq!(error("plays = 0)"), "Unexpected ')'");
+        assert_eq!(error("= 0"), "Expected a field name, found '='");
+    }
+
+    #[test]
+    fn to_sql_uses_parameters() {
+        let filter =
+            Filter::parse("artist ~ 'Davis' AND NOT (year < 1960 OR genre != 'jazz')").unwrap();
+        assert_eq!(
+            filter.to_sql(),
+            (
+                "(instr(lower(artist), lower(?)) > 0 AND (NOT (year < ? OR \
+                 IFNULL(genre, 'Unknown') <> ? COLLATE NOCASE)))"
+                    .to_owned(),
+                vec![
+                    Value::Text("Davis".to_owned()),
+                    Value::Real(1960.0),
+                    Value::Text("jazz".to_owned()),
+                ]
+            )
+        );
+    }
+}
@@ -0,0 +1,67 @@
+/// Score of each matched character.
+const SCORE_MATCH: i64 = 16;
+/// Bonus for a character matching right after the previous one.
+const BONUS_CONSECUTIVE: i64 = 12;
+/// Bonus for a character matching at the start of a word.
+const BONUS_WORD_START: i64 = 8;
+/// Penalty for each skipped character between two matches.
+const PENALTY_GAP: i64 = 1;
+
+/// Scores how well `pattern` fuzzy-matches `text`.
+///
+/// The pattern is split into space-separated terms, and every term must match.
+/// A term matches if all of its characters appear in `text` in the same order, but not
+/// necessarily next to each other. Matches at the start of words and consecutive matches
+/// are scored higher.
+///
+/// ### Notes
+/// Matching is __not__ case-sensitive.
+/// Returns `None` if `text` doesn't match, otherwise higher scores mean better matches.
+pub fn score(pattern: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    pattern
+        .to_lowercase()
+        .split_whitespace()
+        .map(|term| score_term(&term.chars().collect::<Vec<_>>(), &text))
+        .sum()
+}
+
+/// Scores a single term of the pattern.  
+/// Every occurrence of the first character is tried as a starting point, and the best
+/// alignment is used.
+fn score_term(term: &[char], text: &[char]) -> Option<i64> {
+    let first = *term.first()?;
+
+    text.iter()
+        .enumerate()
+        .filter(|(_, c)| **c == first)
+        .filter_map(|(start, _)| score_from(term, text, start))
+        .max()
+}
+
+/// Scores a term, matching its characters greedily starting at `start`.
+fn score_from(term: &[char], text: &[char], start: usize) -> Option<i64> {
+    let mut score = 0;
+    let mut pos = start;
+    let mut last_match: Option<usize> = None;
+
+    for c in term {
+        let found = pos + text[pos..].iter().position(|t| t == c)?;
+
+        score += SCORE_MATCH;
+        match last_match {
+            Some(last) if found == last + 1 => score += BONUS_CONSECUTIVE,
+            Some(last) => score -= (found - last - 1) as i64 * PENALTY_GAP,
+            None => (),
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += BONUS_WORD_START;
+        }
+
+        last_match = Some(found);
+        pos = found + 1;
+    }
+
+    Some(score)
+}
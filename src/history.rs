@@ -0,0 +1,287 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Number of entries visible in the history pane.
+const VISIBLE_ENTRIES: usize = 3;
+/// Number of most played artists shown in the statistics overlay.
+const TOP_ARTISTS: usize = 2;
+/// Number of most played tracks shown in the statistics overlay.
+const TOP_TRACKS: usize = 3;
+
+/// A track which was listened to.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    /// Path to the audio file
+    pub path: String,
+    /// Title of the track (at the time it was played)
+    pub title: String,
+    /// Album of the track (at the time it was played)
+    pub album: String,
+    /// Artist of the track (at the time it was played)
+    pub artist: String,
+    /// When playback started (UNIX timestamp)
+    pub started: u64,
+    /// How long the track was listened to, in seconds (pauses are not counted)
+    pub duration: f64,
+}
+
+/// Statistics of the whole listening history, shown in the statistics overlay.
+#[derive(Debug, Clone, Default)]
+pub struct ListeningStats {
+    /// How long was listened in total (pauses are not counted)
+    pub listened: Duration,
+    /// Number of plays (entries of the history)
+    pub plays: usize,
+    /// The most played artists, and how often they were played
+    pub top_artists: Vec<(String, usize)>,
+    /// The most played tracks (as shown in the history pane), and how often they were played
+    pub top_tracks: Vec<(String, usize)>,
+}
+
+/// Format of an exported history.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// The listening history, shown in the `Lyrics` subwindow.
+/// The newest entry is shown first.
+pub struct HistoryView {
+    /// The entries, newest first
+    entries: Vec<HistoryEntry>,
+    /// Index of the first visible entry
+    scroll: usize,
+}
+
+impl ExportFormat {
+    /// Finds a format by its name (`csv` or `json`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+impl HistoryView {
+    /// Creates a new view of the history.
+    ///
+    /// # Arguments
+    /// * `entries` - The history, in chronological order
+    pub fn new(mut entries: Vec<HistoryEntry>) -> Self {
+        entries.reverse();
+        Self { entries, scroll: 0 }
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the history is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the visible entries.
+    pub fn visible(&self) -> &[HistoryEntry] {
+        let end = (self.scroll + VISIBLE_ENTRIES).min(self.entries.len());
+        &self.entries[self.scroll..end]
+    }
+
+    /// Scrolls to older entries.
+    pub fn scroll_down(&mut self) {
+        if self.scroll + VISIBLE_ENTRIES < self.entries.len() {
+            self.scroll += 1;
+        }
+    }
+
+    /// Scrolls to newer entries.
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+}
+
+impl HistoryEntry {
+    /// Returns the text shown in the history pane.
+    /// If the title is unknown, the path is shown instead.
+    pub fn label(&self) -> String {
+        match (self.title.as_str(), self.artist.as_str()) {
+            ("Unknown", _) => self.path.clone(),
+            (title, "Unknown") => title.to_owned(),
+            (title, artist) => format!("{artist} - {title}"),
+        }
+    }
+}
+
+impl ListeningStats {
+    /// Sums up the history.
+    /// Tracks without a known artist don't count for the most played artists.
+    pub fn new(entries: &[HistoryEntry]) -> Self {
+        Self {
+            listened: Duration::from_secs_f64(entries.iter().map(|entry| entry.duration).sum()),
+            plays: entries.len(),
+            top_artists: most_played(
+                entries
+                    .iter()
+                    .filter(|entry| entry.artist != "Unknown")
+                    .map(|entry| entry.artist.clone()),
+                TOP_ARTISTS,
+            ),
+            top_tracks: most_played(entries.iter().map(HistoryEntry::label), TOP_TRACKS),
+        }
+    }
+
+    /// Returns the statistics as names and values, as shown in the overlay:
+    /// the listening times in the first column, and the most played tracks in the second.
+    ///
+    /// # Arguments
+    /// * `session` - How long was listened in this session
+    /// * `current` - How long the current track was listened to (it's not in the history yet)
+    pub fn rows(&self, session: Duration, current: Duration) -> Vec<(&'static str, String)> {
+        let mut rows = vec![
+            ("Session:", format_listened(session)),
+            (
+                "All time:",
+                format!(
+                    "{} ({} plays)",
+                    format_listened(self.listened + current),
+                    self.plays
+                ),
+            ),
+        ];
+        rows.extend(ranking("Top artists:", &self.top_artists, TOP_ARTISTS));
+        rows.extend(ranking("Top tracks:", &self.top_tracks, TOP_TRACKS));
+        rows
+    }
+}
+
+/// Counts how often each name occurs, and returns the `count` most frequent ones
+/// (by name if they're tied).
+fn most_played(names: impl Iterator<Item = String>, count: usize) -> Vec<(String, usize)> {
+    let mut plays: HashMap<String, usize> = HashMap::new();
+    for name in names {
+        *plays.entry(name).or_default() += 1;
+    }
+
+    let mut plays: Vec<(String, usize)> = plays.into_iter().collect();
+    plays.sort_by(|(a, a_plays), (b, b_plays)| b_plays.cmp(a_plays).then_with(|| a.cmp(b)));
+    plays.truncate(count);
+    plays
+}
+
+/// Returns the rows of a ranking, like `1. Artist (12)`.
+/// It always has `slots` rows (empty ones if there are fewer entries), so the rows after it
+/// don't move. Only the first row is named.
+fn ranking<'a>(
+    name: &'static str,
+    entries: &'a [(String, usize)],
+    slots: usize,
+) -> impl Iterator<Item = (&'static str, String)> + 'a {
+    (0..slots).map(move |index| {
+        let name = match index {
+            0 => name,
+            _ => "",
+        };
+        match entries.get(index) {
+            Some((entry, plays)) => (name, format!("{}. {entry} ({plays})", index + 1)),
+            None if index == 0 => (name, "none yet".to_owned()),
+            None => (name, String::new()),
+        }
+    })
+}
+
+/// Formats a listening time, like `12:34:56`.
+fn format_listened(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Exports the history.
+///
+/// # Arguments
+/// * `entries` - The history, in chronological order
+/// * `format` - Format of the output
+///
+/// ### Notes
+/// In CSV files, the start time is written in the ISO 8601 format (in UTC).
+/// In JSON files, it's a UNIX timestamp.
+pub fn export(entries: &[HistoryEntry], format: ExportFormat) -> Result<String, String> {
+    match format {
+        ExportFormat::Json => serde_json::to_string_pretty(entries)
+            .map_err(|e| format!("Unable to export the history: {e}")),
+        ExportFormat::Csv => {
+            let mut csv = String::from("started,path,title,album,artist,duration\n");
+            for entry in entries {
+                let fields = [
+                    format_timestamp(entry.started),
+                    csv_field(&entry.path),
+                    csv_field(&entry.title),
+                    csv_field(&entry.album),
+                    csv_field(&entry.artist),
+                    format!("{:.1}", entry.duration),
+                ];
+                csv.push_str(&fields.join(","));
+                csv.push('\n');
+            }
+            Ok(csv)
+        }
+    }
+}
+
+/// Formats how long ago something happened, like `5 min ago`.
+///
+/// # Arguments
+/// * `time` - When it happened (UNIX timestamp)
+/// * `now` - The current time (UNIX timestamp)
+pub fn format_ago(time: u64, now: u64) -> String {
+    let seconds = now.saturating_sub(time);
+
+    match seconds {
+        0..=59 => "just now".to_owned(),
+        60..=3599 => format!("{} min ago", seconds / 60),
+        3600..=86399 => format!("{} h ago", seconds / 3600),
+        _ => format!("{} d ago", seconds / 86400),
+    }
+}
+
+/// Quotes a CSV field if needed.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
+
+/// Formats a UNIX timestamp as an ISO 8601 date and time in UTC, like `2023-04-01T12:30:00Z`.
+pub fn format_timestamp(time: u64) -> String {
+    let days = (time / 86400) as i64;
+    let seconds = time % 86400;
+
+    /* Converts days since 1970-01-01 to a date (http://howardhinnant.github.io/date_algorithms.html) */
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
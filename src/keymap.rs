@@ -0,0 +1,195 @@
+use crate::display::DisplayEvent;
+use std::collections::{BTreeMap, HashMap};
+
+/// The actions which can be bound to keys: their names (as used in the configuration),
+/// and their default keys.
+///
+/// Rating keys (`0` to `5`) and the arrow keys can't be rebound.
+const ACTIONS: [(&str, DisplayEvent, char); 40] = [
+    ("play", DisplayEvent::MakePlay, 'g'),
+    ("pause", DisplayEvent::MakePause, 'b'),
+    ("previous", DisplayEvent::JumpBack, 'f'),
+    ("next", DisplayEvent::JumpNext, 'h'),
+    ("mute", DisplayEvent::ToggleMute, 'm'),
+    ("volume_up", DisplayEvent::VolUp, 'y'),
+    ("volume_down", DisplayEvent::VolDown, 'x'),
+    ("accept", DisplayEvent::Accept, 'c'),
+    ("dismiss", DisplayEvent::Dismiss, 'n'),
+    ("write_tags", DisplayEvent::WriteTags, 'w'),
+    ("search", DisplayEvent::Search, '/'),
+    ("sort", DisplayEvent::CycleSort, 'o'),
+    ("history", DisplayEvent::History, 'l'),
+    ("bass_up", DisplayEvent::BassUp, 'u'),
+    ("bass_down", DisplayEvent::BassDown, 'j'),
+    ("treble_up", DisplayEvent::TrebleUp, 'i'),
+    ("treble_down", DisplayEvent::TrebleDown, 'k'),
+    ("channels", DisplayEvent::CycleChannels, 'e'),
+    ("downmix", DisplayEvent::CycleDownmix, '\u{18}'),
+    ("pauses", DisplayEvent::TogglePauses, 'p'),
+    ("mono", DisplayEvent::ToggleMono, 'd'),
+    ("night_mode", DisplayEvent::ToggleNight, '\u{e}'),
+    ("bypass", DisplayEvent::ToggleBypass, '\u{2}'),
+    ("visualizer", DisplayEvent::CycleVisualizer, 'v'),
+    ("profile", DisplayEvent::CycleProfile, '\u{f}'),
+    ("jump_to", DisplayEvent::JumpTo, 't'),
+    ("add_files", DisplayEvent::AddFiles, 'a'),
+    ("favorite", DisplayEvent::ToggleFavorite, 'z'),
+    ("play_favorites", DisplayEvent::PlayFavorites, 's'),
+    ("queue", DisplayEvent::EditQueue, 'r'),
+    ("debug", DisplayEvent::ToggleDebug, '`'),
+    ("details", DisplayEvent::ToggleDetails, '\u{5}'),
+    ("stats", DisplayEvent::ToggleStats, '\u{b}'),
+    ("reload_lyrics", DisplayEvent::ReloadLyrics, '\u{12}'),
+    ("refresh_metadata", DisplayEvent::RefreshMetadata, '\u{15}'),
+    ("translate", DisplayEvent::ToggleTranslation, '\u{14}'),
+    ("rescale_lyrics", DisplayEvent::RescaleLyrics, '\u{4}'),
+    ("launcher", DisplayEvent::Launch, '\u{10}'),
+    ("command", DisplayEvent::CommandPrompt, ':'),
+    ("quit", DisplayEvent::Quit, 'q'),
+];
+
+/// Keys which can't be bound, and why.
+const RESERVED_KEYS: [(char, &str); 9] = [
+    ('\t', "it's Tab"),
+    ('\n', "it's Enter"),
+    ('\r', "it's Enter"),
+    ('\u{1b}', "it's Escape"),
+    ('\u{7f}', "it's Backspace"),
+    ('\u{3}', "the terminal uses it to interrupt programs"),
+    ('\u{13}', "the terminal uses it to pause the output"),
+    ('\u{11}', "the terminal uses it to resume the output"),
+    ('\u{1a}', "the terminal uses it to suspend programs"),
+];
+
+/// Which key does what, after the user's key bindings are applied to the default ones.
+#[derive(Clone)]
+pub struct Keymap {
+    /// The action of each bound key
+    keys: HashMap<char, DisplayEvent>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            keys: ACTIONS
+                .iter()
+                .map(|(_, event, key)| (*key, *event))
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Applies the user's key bindings to the default ones.
+    ///
+    /// # Arguments
+    /// * `bindings` - Names of actions, and the keys they're bound to: a character
+    ///   (like `g` or `/`) or a control key (like `ctrl+p`)
+    ///
+    /// ## Errors
+    /// Fails with a report of every problem: unknown actions, keys which can't be bound
+    /// (like Enter or the rating keys), and keys which are bound to several actions.
+    pub fn new(bindings: &BTreeMap<String, String>) -> Result<Self, String> {
+        let mut problems = Vec::new();
+
+        let mut bound: Vec<(&str, DisplayEvent, char)> = ACTIONS.to_vec();
+        for (action, key) in bindings {
+            let Some(binding) = bound.iter_mut().find(|(name, _, _)| name == action) else {
+                problems.push(format!("unknown action {action:?}"));
+                continue;
+            };
+            match parse_key(key) {
+                Ok(key) => binding.2 = key,
+                Err(e) => problems.push(format!("{action}: {e}")),
+            }
+        }
+
+        let mut keys: HashMap<char, DisplayEvent> = HashMap::new();
+        for (name, event, key) in &bound {
+            if let Some(other) = keys.insert(*key, *event) {
+                let (other, _, _) = bound.iter().find(|(_, e, _)| *e == other).unwrap();
+                problems.push(format!(
+                    "{} is bound to both {other} and {name}",
+                    key_name(*key)
+                ));
+            }
+        }
+
+        match problems.is_empty() {
+            true => Ok(Self { keys }),
+            false => Err(format!(
+                "Invalid key bindings:\n{}",
+                problems
+                    .iter()
+                    .map(|problem| format!("  - {problem}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )),
+        }
+    }
+
+    /// Returns the action of a pressed key.
+    /// Keys which are not bound are returned as [`DisplayEvent::Invalid`](DisplayEvent::Invalid).
+    pub fn event(&self, key: char) -> DisplayEvent {
+        match key {
+            '0'..='5' => DisplayEvent::Rate(key as u8 - b'0'),
+            _ => self
+                .keys
+                .get(&key)
+                .copied()
+                .unwrap_or(DisplayEvent::Invalid(key)),
+        }
+    }
+
+    /// Returns the key an action is bound to.
+    pub fn key(&self, event: DisplayEvent) -> Option<char> {
+        self.keys
+            .iter()
+            .find(|(_, bound)| **bound == event)
+            .map(|(key, _)| *key)
+    }
+}
+
+/// Parses a key of a key binding: a single character (like `g` or `/`),
+/// or a control key (like `ctrl+p`, which is __not__ case-sensitive).
+///
+/// ## Errors
+/// Fails if the key isn't a single key, or if it can't be bound.
+fn parse_key(input: &str) -> Result<char, String> {
+    let lowercase = input.to_ascii_lowercase();
+    let key = match lowercase.strip_prefix("ctrl+") {
+        Some(letter) => match letter.as_bytes() {
+            [letter @ b'a'..=b'z'] => (letter - b'a' + 1) as char,
+            _ => return Err(format!("{input:?} isn't a control key (ctrl+a to ctrl+z)")),
+        },
+        None => {
+            let mut chars = input.chars();
+            match (chars.next(), chars.next()) {
+                (Some(key), None) => key,
+                _ => return Err(format!("{input:?} isn't a single key")),
+            }
+        }
+    };
+
+    if let Some((_, reason)) = RESERVED_KEYS.iter().find(|(reserved, _)| *reserved == key) {
+        return Err(format!("{} can't be bound, as {reason}", key_name(key)));
+    }
+    if ('0'..='5').contains(&key) {
+        return Err(format!("{key} can't be bound, as it rates the track"));
+    }
+    if key.is_whitespace() && key != ' ' {
+        return Err(format!("{input:?} can't be bound"));
+    }
+    Ok(key)
+}
+
+/// Returns the name of a key, as shown in the keyboard shortcuts guide
+/// (like `G`, `Ctrl+P` or `Space`).
+pub fn key_name(key: char) -> String {
+    match key {
+        ' ' => "Space".to_owned(),
+        '\u{1}'..='\u{1a}' => format!("Ctrl+{}", (key as u8 - 1 + b'A') as char),
+        key if key.is_ascii_uppercase() => format!("Shift+{key}"),
+        key => key.to_uppercase().to_string(),
+    }
+}
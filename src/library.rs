@@ -0,0 +1,753 @@
+use crate::audioinfo::{AudioFile, AudioFormat, AudioMeta};
+use crate::filter::Filter;
+use crate::history::HistoryEntry;
+use crate::paths;
+use crate::playlist::PlaylistEntry;
+use crate::sort::SortMode;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Name of the library database file.
+const DATABASE_FILE_NAME: &str = "library.db";
+/// How long to wait for the database to be unlocked by another connection.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Version of the database schema, stored in `PRAGMA user_version`.
+const SCHEMA_VERSION: i64 = 3;
+
+/// A track in the library.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Track {
+    /// Path to the audio file
+    pub path: String,
+    /// Metadata
+    pub metadata: AudioMeta,
+    /// Length of the track in seconds
+    pub length: f64,
+    /// The time when the track was added to the library (UNIX timestamp)
+    pub added: u64,
+    /// How many times the track was played
+    pub plays: u32,
+}
+
+/// How many times a track was played and skipped.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlayCount {
+    /// Number of times the track was played
+    pub plays: u32,
+    /// Number of times the track was skipped
+    pub skips: u32,
+    /// The last time the track was played (UNIX timestamp)
+    pub last_played: Option<u64>,
+    /// The last time the track was skipped (UNIX timestamp)
+    pub last_skipped: Option<u64>,
+}
+
+impl Track {
+    /// Reads a track which is not in the library.  
+    /// The modification time of the file is used as the time it was added.
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let afile = AudioFile::open(path)?;
+        let added = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map(unix_time)
+            .unwrap_or(0);
+
+        Ok(Self {
+            path: path.to_owned(),
+            metadata: afile.metadata,
+            length: afile.length,
+            added,
+            plays: 0,
+        })
+    }
+
+    /// A track whose file isn't read, so its tags and length are unknown
+    /// (e.g. to sort it by its path, where they aren't needed).
+    pub fn unread(path: String, added: u64) -> Self {
+        Self {
+            path,
+            metadata: AudioMeta::unknown(),
+            length: 0.0,
+            added,
+            plays: 0,
+        }
+    }
+}
+
+/// Result of a library scan or update.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScanSummary {
+    /// Number of new tracks
+    pub added: usize,
+    /// Number of tracks which were modified since the last scan
+    pub updated: usize,
+    /// Number of tracks which no longer exist
+    pub removed: usize,
+    /// Number of files which couldn't be read
+    pub failed: usize,
+}
+
+impl ScanSummary {
+    /// Returns `true` if any track was added, updated or removed.
+    pub fn has_changes(&self) -> bool {
+        self.added + self.updated + self.removed > 0
+    }
+}
+
+impl fmt::Display for ScanSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Added {}, updated {}, removed {} track(s)",
+            self.added, self.updated, self.removed
+        )
+    }
+}
+
+/// A persistent music library.
+///
+/// The tags and lengths of all audio files in the library roots are stored in an SQLite
+/// database, so they don't have to be read again on every launch. Scanning is incremental,
+/// only new and modified files are read.
+pub struct Library {
+    /// Connection to the database
+    db: Connection,
+    /// Whether untagged files take their album and artist from their directories
+    folder_metadata: bool,
+}
+
+impl Library {
+    /// Opens (or creates) the library database.
+    pub fn open() -> Result<Self, String> {
+        let path = Self::path().ok_or("Unable to find the data directory")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Unable to create {}: {e}", dir.display()))?;
+        }
+
+        let db = Connection::open(&path)
+            .map_err(|e| format!("Unable to open {}: {e}", path.display()))?;
+        /* The library can be updated by the watcher thread while it's being read */
+        db.busy_timeout(BUSY_TIMEOUT)
+            .map_err(|e| format!("Unable to open {}: {e}", path.display()))?;
+        db.execute_batch(
+            "CREATE TABLE IF NOT EXISTS tracks (
+                path     TEXT PRIMARY KEY,
+                title    TEXT NOT NULL,
+                album    TEXT NOT NULL,
+                artist   TEXT NOT NULL,
+                track    INTEGER,
+                genre    TEXT,
+                year     INTEGER,
+                album_artist TEXT,
+                compilation  INTEGER NOT NULL DEFAULT 0,
+                length   REAL NOT NULL,
+                modified INTEGER NOT NULL,
+                added    INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS play_counts (
+                path         TEXT PRIMARY KEY,
+                plays        INTEGER NOT NULL DEFAULT 0,
+                skips        INTEGER NOT NULL DEFAULT 0,
+                last_played  INTEGER,
+                last_skipped INTEGER
+            );
+            CREATE TABLE IF NOT EXISTS ratings (
+                path   TEXT PRIMARY KEY,
+                rating INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS favorites (
+                path  TEXT PRIMARY KEY,
+                added INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS history (
+                id       INTEGER PRIMARY KEY,
+                path     TEXT NOT NULL,
+                title    TEXT NOT NULL,
+                album    TEXT NOT NULL,
+                artist   TEXT NOT NULL,
+                started  INTEGER NOT NULL,
+                duration REAL NOT NULL
+            );",
+        )
+        .map_err(|e| format!("Unable to initialize the library: {e}"))?;
+
+        migrate(&db).map_err(|e| format!("Unable to upgrade the library: {e}"))?;
+
+        Ok(Self {
+            db,
+            folder_metadata: false,
+        })
+    }
+
+    /// Sets whether untagged files take their album and artist from their directories
+    /// (`Artist/Album/Track`) when they're read, so they're still grouped correctly.
+    /// See [`AudioMeta::infer_missing()`](AudioMeta::infer_missing).
+    ///
+    /// ### Notes
+    /// Disabled by default. Tracks without an album or artist are read again by the next scan
+    /// while it's enabled, as they were probably stored before.
+    pub fn set_folder_metadata(&mut self, enabled: bool) {
+        self.folder_metadata = enabled;
+    }
+
+    /// Returns the path to the library database (in the [data directory](paths::data_dir)).
+    /// Returns `None` if the home directory is unknown.
+    pub fn path() -> Option<PathBuf> {
+        Some(paths::data_dir()?.join(DATABASE_FILE_NAME))
+    }
+
+    /// Scans the library roots for audio files and updates the database.
+    ///
+    /// # Arguments
+    /// * `roots` - Directories to scan (recursively)
+    ///
+    /// ### Notes
+    /// Tracks which are no longer in any of the roots (or were deleted) are removed.
+    pub fn scan(&mut self, roots: &[PathBuf]) -> Result<ScanSummary, String> {
+        let db_error = |e: rusqlite::Error| format!("Library database error: {e}");
+        let mut summary = ScanSummary::default();
+
+        let known: HashMap<String, i64> = {
+            /* Tracks which may have been stored without their inferred tags are read again */
+            let mut stmt = self
+                .db
+                .prepare(
+                    "SELECT path, CASE WHEN ?1 AND (album = 'Unknown' OR artist = 'Unknown')
+                        THEN 0 ELSE modified END
+                     FROM tracks",
+                )
+                .map_err(db_error)?;
+            let rows = stmt
+                .query_map([self.folder_metadata], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(db_error)?;
+            rows.collect::<Result<_, _>>().map_err(db_error)?
+        };
+
+        let mut files = Vec::new();
+        for root in roots {
+            find_audio_files(root, &mut files);
+        }
+
+        let now = unix_time(SystemTime::now());
+        let tx = self.db.transaction().map_err(db_error)?;
+        let mut seen = HashSet::new();
+
+        for (path, modified) in files {
+            let Some(path) = path.to_str().map(str::to_owned) else {
+                continue;
+            };
+            let modified = modified as i64;
+            let previous = known.get(&path).copied();
+            seen.insert(path.clone());
+
+            if previous == Some(modified) {
+                continue;
+            }
+
+            if !store_track(&tx, &path, modified, now, self.folder_metadata).map_err(db_error)? {
+                summary.failed += 1;
+            } else if previous.is_some() {
+                summary.updated += 1;
+            } else {
+                summary.added += 1;
+            }
+        }
+
+        for path in known.keys().filter(|path| !seen.contains(*path)) {
+            tx.execute("DELETE FROM tracks WHERE path = ?1", [path])
+                .map_err(db_error)?;
+            summary.removed += 1;
+        }
+
+        tx.commit().map_err(db_error)?;
+        Ok(summary)
+    }
+
+    /// Updates the tracks affected by changes to the given files or directories.
+    /// Only files which were modified since they were last read are read again.
+    ///
+    /// ### Notes
+    /// Paths which no longer exist are removed from the library. If a removed path was a
+    /// directory, every track in it is removed as well.
+    pub fn update(&mut self, paths: &[PathBuf]) -> Result<ScanSummary, String> {
+        let db_error = |e: rusqlite::Error| format!("Library database error: {e}");
+        let mut summary = ScanSummary::default();
+
+        let now = unix_time(SystemTime::now());
+        let tx = self.db.transaction().map_err(db_error)?;
+
+        for path in paths {
+            let Some(path_str) = path.to_str() else {
+                continue;
+            };
+
+            if !path.exists() {
+                let children = format!("{}/%", escape_like(path_str));
+                summary.removed += tx
+                    .execute(
+                        "DELETE FROM tracks WHERE path = ?1 OR path LIKE ?2 ESCAPE '\\'",
+                        params![path_str, children],
+                    )
+                    .map_err(db_error)?;
+                continue;
+            }
+
+            let mut files = Vec::new();
+            if path.is_dir() {
+                find_audio_files(path, &mut files);
+            } else if let Some(modified) = audio_file_modified(path) {
+                files.push((path.clone(), modified));
+            }
+
+            for (file, modified) in files {
+                let Some(file) = file.to_str() else {
+                    continue;
+                };
+                let modified = modified as i64;
+                let previous: Option<i64> = tx
+                    .query_row(
+                        "SELECT modified FROM tracks WHERE path = ?1",
+                        [file],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(db_error)?;
+
+                if previous == Some(modified) {
+                    continue;
+                }
+
+                if !store_track(&tx, file, modified, now, self.folder_metadata).map_err(db_error)? {
+                    summary.failed += 1;
+                } else if previous.is_some() {
+                    summary.updated += 1;
+                } else {
+                    summary.added += 1;
+                }
+            }
+        }
+
+        tx.commit().map_err(db_error)?;
+        Ok(summary)
+    }
+
+    /// Returns all tracks in the library.
+    ///
+    /// # Arguments
+    /// * `sort` - How to order the tracks
+    pub fn tracks(&self, sort: SortMode) -> Result<Vec<Track>, String> {
+        let mut tracks = self.query("TRUE", [])?;
+        sort.sort(&mut tracks);
+        Ok(tracks)
+    }
+
+    /// Searches for tracks whose title, album, artist, album artist or path contains `query`.
+    ///
+    /// # Arguments
+    /// * `query` - Text to search for
+    /// * `sort` - How to order the matching tracks
+    ///
+    /// ### Notes
+    /// The search is __not__ case-sensitive.
+    pub fn search(&self, query: &str, sort: SortMode) -> Result<Vec<Track>, String> {
+        let pattern = format!("%{}%", escape_like(query));
+
+        let mut tracks = self.query(
+            "title LIKE ?1 ESCAPE '\\' OR album LIKE ?1 ESCAPE '\\'
+             OR artist LIKE ?1 ESCAPE '\\' OR album_artist LIKE ?1 ESCAPE '\\'
+             OR path LIKE ?1 ESCAPE '\\'",
+            [pattern],
+        )?;
+        sort.sort(&mut tracks);
+        Ok(tracks)
+    }
+
+    /// Returns the tracks matching a filter (like a smart playlist).
+    ///
+    /// # Arguments
+    /// * `filter` - The filter to match the tracks against
+    /// * `sort` - How to order the matching tracks
+    pub fn filter(&self, filter: &Filter, sort: SortMode) -> Result<Vec<Track>, String> {
+        let (condition, params) = filter.to_sql();
+        let mut tracks = self.query(&condition, params_from_iter(params))?;
+        sort.sort(&mut tracks);
+        Ok(tracks)
+    }
+
+    /// Records that a track was played.
+    ///
+    /// ### Notes
+    /// Play counts are stored by path, so tracks which are not in the library
+    /// (or were removed from it) are counted as well.
+    pub fn record_play(&self, path: &str) -> Result<(), String> {
+        self.db
+            .execute(
+                "INSERT INTO play_counts (path, plays, last_played) VALUES (?1, 1, ?2)
+                 ON CONFLICT(path) DO UPDATE SET
+                    plays = plays + 1, last_played = excluded.last_played",
+                params![path, unix_time(SystemTime::now()) as i64],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("Unable to record the play: {e}"))
+    }
+
+    /// Records that a track was skipped.
+    pub fn record_skip(&self, path: &str) -> Result<(), String> {
+        self.db
+            .execute(
+                "INSERT INTO play_counts (path, skips, last_skipped) VALUES (?1, 1, ?2)
+                 ON CONFLICT(path) DO UPDATE SET
+                    skips = skips + 1, last_skipped = excluded.last_skipped",
+                params![path, unix_time(SystemTime::now()) as i64],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("Unable to record the skip: {e}"))
+    }
+
+    /// Returns how many times a track was played and skipped.
+    pub fn play_count(&self, path: &str) -> Result<PlayCount, String> {
+        self.db
+            .query_row(
+                "SELECT plays, skips, last_played, last_skipped FROM play_counts WHERE path = ?1",
+                [path],
+                |row| {
+                    Ok(PlayCount {
+                        plays: row.get(0)?,
+                        skips: row.get(1)?,
+                        last_played: row.get::<_, Option<i64>>(2)?.map(|time| time as u64),
+                        last_skipped: row.get::<_, Option<i64>>(3)?.map(|time| time as u64),
+                    })
+                },
+            )
+            .optional()
+            .map(Option::unwrap_or_default)
+            .map_err(|e| format!("Library database error: {e}"))
+    }
+
+    /// Rates a track (from 1 to 5 stars), or removes its rating if `rating` is `None`.
+    ///
+    /// ### Notes
+    /// Like play counts, ratings are stored by path.
+    pub fn set_rating(&self, path: &str, rating: Option<u8>) -> Result<(), String> {
+        let result = match rating {
+            Some(rating) => self.db.execute(
+                "INSERT INTO ratings (path, rating) VALUES (?1, ?2)
+                 ON CONFLICT(path) DO UPDATE SET rating = excluded.rating",
+                params![path, rating],
+            ),
+            None => self
+                .db
+                .execute("DELETE FROM ratings WHERE path = ?1", [path]),
+        };
+
+        result
+            .map(|_| ())
+            .map_err(|e| format!("Unable to save the rating: {e}"))
+    }
+
+    /// Returns the rating of a track (from 1 to 5 stars), or `None` if it's not rated.
+    pub fn rating(&self, path: &str) -> Result<Option<u8>, String> {
+        self.db
+            .query_row(
+                "SELECT rating FROM ratings WHERE path = ?1",
+                [path],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Library database error: {e}"))
+    }
+
+    /// Adds a track to the favorites, or removes it from them.
+    ///
+    /// ### Notes
+    /// Like ratings, favorites are stored by path, so streams can be added too.
+    pub fn set_favorite(&self, path: &str, favorite: bool) -> Result<(), String> {
+        let result = match favorite {
+            true => self.db.execute(
+                "INSERT OR IGNORE INTO favorites (path, added) VALUES (?1, ?2)",
+                params![path, unix_time(SystemTime::now()) as i64],
+            ),
+            false => self
+                .db
+                .execute("DELETE FROM favorites WHERE path = ?1", [path]),
+        };
+
+        result
+            .map(|_| ())
+            .map_err(|e| format!("Unable to save the favorites: {e}"))
+    }
+
+    /// Returns whether a track is in the favorites.
+    pub fn is_favorite(&self, path: &str) -> Result<bool, String> {
+        self.db
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM favorites WHERE path = ?1)",
+                [path],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Library database error: {e}"))
+    }
+
+    /// Returns the favorites, in the order they were added.
+    /// The metadata of the tracks which are in the library is included.
+    pub fn favorites(&self) -> Result<Vec<PlaylistEntry>, String> {
+        let db_error = |e: rusqlite::Error| format!("Library database error: {e}");
+        let mut stmt = self
+            .db
+            .prepare(
+                "SELECT path, title, album, artist, track, genre, year, album_artist, compilation
+                 FROM favorites LEFT JOIN tracks USING (path)
+                 ORDER BY favorites.added, favorites.rowid",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map([], |row| {
+                let location: String = row.get("path")?;
+                let metadata = match row.get::<_, Option<String>>("title")? {
+                    Some(title) => AudioMeta {
+                        title,
+                        album: row.get("album")?,
+                        artist: row.get("artist")?,
+                        track: row.get("track")?,
+                        track_total: None,
+                        genre: row.get("genre")?,
+                        year: row.get("year")?,
+                        album_artist: row.get("album_artist")?,
+                        compilation: row.get("compilation")?,
+                        inferred: Vec::new(),
+                    },
+                    None => AudioMeta::unknown(),
+                };
+                Ok(PlaylistEntry { location, metadata })
+            })
+            .map_err(db_error)?;
+
+        rows.collect::<Result<_, _>>().map_err(db_error)
+    }
+
+    /// Adds an entry to the listening history.
+    pub fn record_history(&self, entry: &HistoryEntry) -> Result<(), String> {
+        self.db
+            .execute(
+                "INSERT INTO history (path, title, album, artist, started, duration)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    entry.path,
+                    entry.title,
+                    entry.album,
+                    entry.artist,
+                    entry.started as i64,
+                    entry.duration
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("Unable to record the history: {e}"))
+    }
+
+    /// Returns the listening history, in chronological order.
+    pub fn history(&self) -> Result<Vec<HistoryEntry>, String> {
+        let db_error = |e: rusqlite::Error| format!("Library database error: {e}");
+        let mut stmt = self
+            .db
+            .prepare("SELECT * FROM history ORDER BY started, id")
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(HistoryEntry {
+                    path: row.get("path")?,
+                    title: row.get("title")?,
+                    album: row.get("album")?,
+                    artist: row.get("artist")?,
+                    started: row.get::<_, i64>("started")? as u64,
+                    duration: row.get("duration")?,
+                })
+            })
+            .map_err(db_error)?;
+
+        rows.collect::<Result<_, _>>().map_err(db_error)
+    }
+
+    /// Returns the tracks matching an SQL condition.
+    /// The condition can use the columns of the `tracks` table, the play counts
+    /// (`plays` and `skips`) and the `rating`.
+    fn query<P: rusqlite::Params>(&self, condition: &str, params: P) -> Result<Vec<Track>, String> {
+        let db_error = |e: rusqlite::Error| format!("Library database error: {e}");
+        let sql = format!(
+            "SELECT tracks.*, IFNULL(plays, 0) AS plays
+             FROM tracks LEFT JOIN play_counts USING (path) LEFT JOIN ratings USING (path)
+             WHERE {condition}"
+        );
+        let mut stmt = self.db.prepare(&sql).map_err(db_error)?;
+        let rows = stmt
+            .query_map(params, |row| {
+                Ok(Track {
+                    path: row.get("path")?,
+                    metadata: AudioMeta {
+                        title: row.get("title")?,
+                        album: row.get("album")?,
+                        artist: row.get("artist")?,
+                        track: row.get("track")?,
+                        track_total: None,
+                        genre: row.get("genre")?,
+                        year: row.get("year")?,
+                        album_artist: row.get("album_artist")?,
+                        compilation: row.get("compilation")?,
+                        inferred: Vec::new(),
+                    },
+                    length: row.get("length")?,
+                    added: row.get::<_, i64>("added")? as u64,
+                    plays: row.get("plays")?,
+                })
+            })
+            .map_err(db_error)?;
+
+        rows.collect::<Result<_, _>>().map_err(db_error)
+    }
+}
+
+/// Reads an audio file and stores it in the library, replacing the previous version
+/// of the track (if any).
+///
+/// # Arguments
+/// * `db` - Connection to the database (or a transaction)
+/// * `path` - Path to the audio file
+/// * `modified` - Modification time of the file (UNIX timestamp)
+/// * `now` - Current time, used as the time the track was added if it's new
+/// * `folder_metadata` - Whether missing tags are inferred from the path of the file,
+///   see [`Library::set_folder_metadata()`](Library::set_folder_metadata)
+///
+/// Returns `false` if the file couldn't be read.
+fn store_track(
+    db: &Connection,
+    path: &str,
+    modified: i64,
+    now: u64,
+    folder_metadata: bool,
+) -> rusqlite::Result<bool> {
+    let Ok(mut afile) = AudioFile::open(path) else {
+        return Ok(false);
+    };
+    if folder_metadata {
+        afile.metadata.infer_missing(path, true);
+    }
+
+    db.execute(
+        "INSERT INTO tracks
+            (path, title, album, artist, track, genre, year, album_artist, compilation,
+             length, modified, added)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+         ON CONFLICT(path) DO UPDATE SET
+            title = excluded.title, album = excluded.album, artist = excluded.artist,
+            track = excluded.track, genre = excluded.genre, year = excluded.year,
+            album_artist = excluded.album_artist, compilation = excluded.compilation,
+            length = excluded.length, modified = excluded.modified",
+        params![
+            path,
+            afile.metadata.title,
+            afile.metadata.album,
+            afile.metadata.artist,
+            afile.metadata.track,
+            afile.metadata.genre,
+            afile.metadata.year,
+            afile.metadata.album_artist,
+            afile.metadata.compilation,
+            afile.length,
+            modified,
+            now as i64
+        ],
+    )?;
+
+    Ok(true)
+}
+
+/// Escapes the wildcards of a `LIKE` pattern, using `\` as the escape character.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Upgrades a database created by an older version.
+fn migrate(db: &Connection) -> rusqlite::Result<()> {
+    let version: i64 = db.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version >= SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    /* Version 1 added track numbers, version 2 added genres and years,
+    version 3 added album artists and compilations */
+    for (column, kind) in [
+        ("track", "INTEGER"),
+        ("genre", "TEXT"),
+        ("year", "INTEGER"),
+        ("album_artist", "TEXT"),
+        ("compilation", "INTEGER NOT NULL DEFAULT 0"),
+    ] {
+        if db
+            .prepare(&format!("SELECT {column} FROM tracks LIMIT 0"))
+            .is_err()
+        {
+            db.execute_batch(&format!("ALTER TABLE tracks ADD COLUMN {column} {kind}"))?;
+        }
+    }
+
+    /* The new columns are empty, so existing tracks have to be read again */
+    db.execute("UPDATE tracks SET modified = 0", [])?;
+    db.execute_batch(&format!("PRAGMA user_version = {SCHEMA_VERSION}"))
+}
+
+/// Recursively finds supported audio files in a directory.
+/// The path and modification time of each file is added to `files`.
+///
+/// ### Notes
+/// Unreadable directories and symbolic links to directories are skipped.
+pub fn find_audio_files(dir: &Path, files: &mut Vec<(PathBuf, u64)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_dir() {
+            find_audio_files(&path, files);
+            continue;
+        }
+
+        if let Some(modified) = audio_file_modified(&path) {
+            files.push((path, modified));
+        }
+    }
+}
+
+/// Returns the modification time of a file if it's a supported audio file.
+/// Paths which aren't valid UTF-8 are included, so callers can report them.
+fn audio_file_modified(path: &Path) -> Option<u64> {
+    let is_audio = AudioFormat::from_path(&path.to_string_lossy()).is_ok();
+    if !is_audio {
+        return None;
+    }
+
+    /* Follows symbolic links, unlike `DirEntry::metadata()` */
+    let metadata = fs::metadata(path).ok()?;
+    metadata
+        .is_file()
+        .then(|| metadata.modified().map(unix_time).unwrap_or(0))
+}
+
+/// Converts a [`SystemTime`](SystemTime) into a UNIX timestamp.
+pub fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
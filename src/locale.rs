@@ -0,0 +1,213 @@
+use std::env;
+use std::sync::OnceLock;
+
+/// The language of the UI, detected from the environment when it's first needed.
+static LANGUAGE: OnceLock<Language> = OnceLock::new();
+
+/// A language the UI is translated to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    German,
+}
+
+impl Language {
+    /// Detects the language from the environment, from the first of `LC_ALL`, `LC_MESSAGES`
+    /// and `LANG` which is set (e.g. `de_DE.UTF-8`).
+    /// Languages without a translation fall back to English.
+    pub fn detect() -> Self {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+
+        match locale.split(['_', '.', '@']).next() {
+            Some("de") => Self::German,
+            _ => Self::English,
+        }
+    }
+
+    /// Returns the translations, as pairs of the English text and its translation.
+    fn table(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::English => &[],
+            Self::German => GERMAN,
+        }
+    }
+}
+
+/// Translates a text of the UI (written in English) to the language of the environment.
+/// Texts without a translation are returned as they are.
+pub fn tr(text: &'static str) -> &'static str {
+    LANGUAGE
+        .get_or_init(Language::detect)
+        .table()
+        .iter()
+        .find(|(english, _)| *english == text)
+        .map_or(text, |(_, translation)| translation)
+}
+
+/// German translations (texts which are the same in German are left out).
+const GERMAN: &[(&str, &str)] = &[
+    /* Controls */
+    ("Prev/Next", "Titel"),
+    ("Play/Pause", "Wiedergabe"),
+    ("Vol", "Laut"),
+    ("Mute", "Stumm"),
+    ("Visual", "Anzeige"),
+    ("Search", "Suche"),
+    ("Sort", "Sortieren"),
+    ("History", "Verlauf"),
+    ("Treble", "Höhen"),
+    ("Channels", "Kanäle"),
+    ("Downmix", "Heruntermischen"),
+    ("Pauses", "Pausen"),
+    ("Exit", "Beenden"),
+    /* Track info and lyrics */
+    ("Title:", "Titel:"),
+    ("Artist(s):", "Künstler:"),
+    ("Lyrics", "Liedtext"),
+    ("Unavailable", "Nicht verfügbar"),
+    (
+        "Lyrics file can't be read",
+        "Liedtextdatei kann nicht gelesen werden",
+    ),
+    ("Lyrics file malformed", "Liedtextdatei fehlerhaft"),
+    ("Lyrics weren't fetched", "Liedtext wurde nicht abgerufen"),
+    (
+        "Unsupported lyrics format",
+        "Nicht unterstütztes Liedtextformat",
+    ),
+    (
+        "Unsupported lyrics sync type",
+        "Nicht unterstützte Liedtext-Synchronisierung",
+    ),
+    /* Status bar */
+    ("Playing", "Wiedergabe"),
+    ("Paused", "Pausiert"),
+    ("Volume", "Lautstärke"),
+    ("Muted", "Stumm"),
+    ("Repeat", "Wiederholen"),
+    /* Status messages */
+    ("Resumed", "Fortgesetzt"),
+    ("Track", "Titel"),
+    ("Skipped", "Übersprungen"),
+    ("This is the last track", "Das ist der letzte Titel"),
+    ("This is the first track", "Das ist der erste Titel"),
+    ("Unknown command", "Unbekannter Befehl"),
+    ("Configuration reloaded", "Konfiguration neu geladen"),
+    ("Profile", "Profil"),
+    ("Lyrics reloaded", "Liedtext neu geladen"),
+    ("Metadata refreshed", "Metadaten neu gelesen"),
+    ("Streams have no tags", "Streams haben keine Tags"),
+    ("No lyrics found", "Kein Liedtext gefunden"),
+    ("Lyrics timing is off", "Liedtext ist nicht synchron"),
+    (
+        "Lyrics aren't synced, timing is estimated",
+        "Liedtext ist nicht synchronisiert, Timing ist geschätzt",
+    ),
+    (
+        "Lyrics stretched to the track",
+        "Liedtext an den Titel angepasst",
+    ),
+    ("Original lyrics timing", "Ursprüngliches Liedtext-Timing"),
+    (
+        "Lyrics already match the track",
+        "Liedtext passt bereits zum Titel",
+    ),
+    ("Night mode on", "Nachtmodus an"),
+    ("Night mode off", "Nachtmodus aus"),
+    ("Effects bypassed", "Effekte umgangen"),
+    ("Effects applied", "Effekte aktiv"),
+    ("Audio device failed", "Audiogerät ausgefallen"),
+    ("Audio device plays again", "Audiogerät spielt wieder"),
+    ("Sleep timer", "Schlaftimer"),
+    ("Sleep timer off", "Schlaftimer aus"),
+    ("Sleep timer expired", "Schlaftimer abgelaufen"),
+    ("Sleep", "Schlaf"),
+    ("Alarm", "Wecker"),
+    ("Alarm set for", "Wecker gestellt auf"),
+    ("Alarm off", "Wecker aus"),
+    ("Alarm in", "Wecker in"),
+    ("Scheduled pause", "Geplante Pause"),
+    ("Scheduled playback", "Geplante Wiedergabe"),
+    ("Translating lyrics", "Liedtext wird übersetzt"),
+    ("Translation off", "Übersetzung aus"),
+    ("Translation failed", "Übersetzung fehlgeschlagen"),
+    (
+        "Translation isn't set up",
+        "Übersetzung ist nicht eingerichtet",
+    ),
+    (
+        "This track can't be seeked",
+        "In diesem Titel kann nicht gespult werden",
+    ),
+    ("Looking up tags", "Tags werden gesucht"),
+    ("No missing tags found", "Keine fehlenden Tags gefunden"),
+    ("Lookups failed", "Suchen fehlgeschlagen"),
+    ("Tags written", "Tags geschrieben"),
+    ("No match found", "Kein Treffer gefunden"),
+    ("Lookup failed", "Suche fehlgeschlagen"),
+    (
+        "The library is unavailable",
+        "Die Bibliothek ist nicht verfügbar",
+    ),
+    ("Library updated", "Bibliothek aktualisiert"),
+    ("No profiles configured", "Keine Profile eingerichtet"),
+    ("Skipping pauses", "Pausen werden übersprungen"),
+    ("Playing pauses", "Pausen werden gespielt"),
+    ("Rated", "Bewertet mit"),
+    ("Rating removed", "Bewertung entfernt"),
+    (
+        "Unable to write the rating",
+        "Bewertung konnte nicht geschrieben werden",
+    ),
+    ("Added to favorites", "Zu den Favoriten hinzugefügt"),
+    ("Removed from favorites", "Aus den Favoriten entfernt"),
+    ("There are no favorites", "Es gibt keine Favoriten"),
+    ("Seek to", "Springen zu"),
+    ("Visualizer", "Visualisierung"),
+    ("Visualizer hidden", "Visualisierung ausgeblendet"),
+    ("Added to queue", "Zur Warteschlange hinzugefügt"),
+    ("Unreadable", "Nicht lesbar"),
+    ("Unsupported file", "Nicht unterstützte Datei"),
+    (
+        "No such file or directory",
+        "Datei oder Verzeichnis nicht gefunden",
+    ),
+    ("No audio files found in", "Keine Audiodateien gefunden in"),
+    (
+        "The current track can't be removed",
+        "Der aktuelle Titel kann nicht entfernt werden",
+    ),
+    (
+        "This track is already playing",
+        "Dieser Titel läuft bereits",
+    ),
+    ("Playing next", "Wird als Nächstes gespielt"),
+    (
+        "The queue is already empty",
+        "Die Warteschlange ist bereits leer",
+    ),
+    ("Queue cleared", "Warteschlange geleert"),
+    ("Queue shuffled", "Warteschlange gemischt"),
+    ("Undone", "Rückgängig gemacht"),
+    ("Nothing to undo", "Nichts rückgängig zu machen"),
+    ("Saved the queue to", "Warteschlange gespeichert unter"),
+    (
+        "Only M3U playlists can be saved",
+        "Nur M3U-Playlists können gespeichert werden",
+    ),
+    ("Nothing to write", "Nichts zu schreiben"),
+    (
+        "Nothing to accept or dismiss",
+        "Nichts anzunehmen oder abzulehnen",
+    ),
+    (
+        "Suggestion accepted, press [W] to save it",
+        "Vorschlag angenommen, [W] zum Speichern drücken",
+    ),
+    ("Suggestion dismissed", "Vorschlag verworfen"),
+    ("Writing cancelled", "Schreiben abgebrochen"),
+];
@@ -1,134 +1,511 @@
-use crate::lyrics_parse::{Lyrics, LyricsEntry};
+use crate::locale::tr;
+use crate::lyrics_parse::{Credit, Lyrics, LyricsEntry, SyncType};
+use std::collections::HashMap;
+use std::fmt;
 use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
 use std::time::Duration;
 
 /// Defines the amount of lines that can be displayed by the TUI.
-///
-/// ## Note
-/// The actual amount of [`LyricsLine`](LyricsLine)s stored in
-/// [`LyricsBank`](LyricsBank) is [`LYRICS_BANK_SIZE`](LYRICS_BANK_SIZE) + 1.
-/// The extra one is used to prevent the TUI from switching to another bank
-/// too early.
-pub const LYRICS_BANK_SIZE: usize = 4;
-
-/// Represents a small set of lines from the lyrics.  
-#[derive(Debug, Clone)]
-pub struct LyricsBank {
-    /// Internal stack  
-    /// The length of this vector should be no more than [`LYRICS_BANK_SIZE`](LYRICS_BANK_SIZE) + 1
-    pub lines: Vec<LyricsEntry>,
+pub const LYRICS_PAGE_SIZE: usize = 4;
+/// How much the end of the lyrics may differ from the length of the track (as a fraction of it)
+/// before they're considered to be synced to a different edit of the song.
+const DRIFT_TOLERANCE: f64 = 0.1;
+/// How many of the following entries of the queue have their lyrics read ahead.
+pub const PREFETCH_AHEAD: usize = 3;
+
+/// A section of a song, recognized from the markers in the lyrics (like `[Chorus]`),
+/// or from repeated lines if there are no markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Section {
+    Intro,
+    Verse,
+    PreChorus,
+    Chorus,
+    Bridge,
+    Outro,
+}
+
+impl Section {
+    /// Parses a section marker, like `[Chorus]`, `[Verse 2]` or `[Bridge: Artist]`
+    /// (__not__ case-sensitive).
+    /// Returns `None` if the line isn't a marker, or the section is unknown.
+    pub fn from_marker(line: &str) -> Option<Self> {
+        Self::from_label(line.trim().strip_prefix('[')?.strip_suffix(']')?)
+    }
+
+    /// Parses the name of a section, like `Chorus`, `Verse 2` or `Bridge: Artist`
+    /// (__not__ case-sensitive).
+    /// Returns `None` if the section is unknown.
+    pub fn from_label(label: &str) -> Option<Self> {
+        let name = label.split([':', '(']).next()?.trim().to_lowercase();
+        let name = name.trim_end_matches(|c: char| c.is_ascii_digit() || c == ' ');
+
+        match name {
+            "intro" => Some(Self::Intro),
+            "verse" => Some(Self::Verse),
+            "pre-chorus" | "prechorus" | "pre chorus" => Some(Self::PreChorus),
+            "chorus" | "refrain" | "hook" => Some(Self::Chorus),
+            "bridge" => Some(Self::Bridge),
+            "outro" => Some(Self::Outro),
+            _ => None,
+        }
+    }
+}
+
+/// Why the lyrics of a track couldn't be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LyricsError {
+    /// The track is a stream, which has no lyrics
+    Stream,
+    /// There's no lyrics file next to the track
+    NotFound,
+    /// The lyrics file can't be read, with the reason
+    Unreadable(String),
+    /// The lyrics file isn't valid JSON or misses fields, with the reason
+    Malformed(String),
+    /// The lyrics file says fetching the lyrics failed (`"error": true`), with its message
+    Failed(Option<String>),
+    /// The lyrics are synced in an unsupported way, with the `syncType`
+    UnsupportedSync(String),
+    /// The lyrics file is of a newer format, with its version
+    UnsupportedVersion(u32),
+}
+
+impl LyricsError {
+    /// Returns a short description of the error, shown in place of the lyrics.
+    pub fn summary(&self) -> &'static str {
+        match self {
+            Self::Stream => tr("Unavailable"),
+            Self::NotFound => tr("No lyrics found"),
+            Self::Unreadable(_) => tr("Lyrics file can't be read"),
+            Self::Malformed(_) => tr("Lyrics file malformed"),
+            Self::Failed(_) => tr("Lyrics weren't fetched"),
+            Self::UnsupportedSync(_) => tr("Unsupported lyrics sync type"),
+            Self::UnsupportedVersion(_) => tr("Unsupported lyrics format"),
+        }
+    }
+
+    /// Returns the details of the error (like where the file is malformed), if there are any.
+    pub fn details(&self) -> Option<&str> {
+        match self {
+            Self::Stream | Self::NotFound | Self::UnsupportedVersion(_) => None,
+            Self::Unreadable(details)
+            | Self::Malformed(details)
+            | Self::UnsupportedSync(details) => Some(details),
+            Self::Failed(message) => message.as_deref(),
+        }
+    }
 }
 
-/// This structure represents a lyrics file.
+impl fmt::Display for LyricsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stream => write!(f, "Streams have no lyrics"),
+            Self::NotFound => write!(f, "No lyrics file found"),
+            Self::Unreadable(e) => write!(f, "Unable to read the lyrics file: {e}"),
+            Self::Malformed(e) => write!(f, "Lyrics file malformed: {e}"),
+            Self::Failed(Some(message)) => write!(f, "Lyrics weren't fetched: {message}"),
+            Self::Failed(None) => write!(f, "Lyrics weren't fetched"),
+            Self::UnsupportedSync(sync) => write!(f, "Unsupported lyrics sync type: {sync}"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "Unsupported lyrics format (version {version})")
+            }
+        }
+    }
+}
+
+/// The lines of lyrics shown at a time: a page of [`LYRICS_PAGE_SIZE`](LYRICS_PAGE_SIZE) lines.
+/// The lyrics are shown page by page, and the next page is shown once its first line starts.
+#[derive(Debug, Clone, Copy)]
+pub struct LyricsPage<'a> {
+    /// The lines of the page (the last page may have fewer lines)
+    pub lines: &'a [LyricsEntry],
+    /// Index of the line to highlight in [`lines`](LyricsPage::lines), if any
+    pub active: Option<usize>,
+}
+
+/// The lines of a lyrics file, ordered by their start times, so the line sung at any time
+/// (also after seeking) is found with a binary search.
 #[derive(Debug)]
-pub struct LyricsProcessor {
+pub struct LyricsTimeline {
     /// Lines of lyrics.
     lines: Vec<LyricsEntry>,
+    /// How much the timestamps are stretched, see [`set_scale()`](LyricsTimeline::set_scale)
+    scale: f64,
+    /// How the lines are synced, unsynced lyrics have estimated timestamps
+    sync: SyncType,
+    /// Code of the language the lyrics are in, if it's known
+    language: Option<String>,
+    /// The people credited for the lyrics
+    credits: Vec<Credit>,
 }
 
-impl LyricsProcessor {
+impl LyricsTimeline {
     /// Loads a lyrics file from a given path.
     /// The lyrics file is a JSON file who's contents can be generated by:
     /// - [`akashrchandran/spotify-lyrics-api`](https://github.com/akashrchandran/spotify-lyrics-api)
     /// - [`br0kenpixel/spotify-lyrics-api-rust`](https://github.com/br0kenpixel/spotify-lyrics-api-rust)  
     /// `LRC`'s are not supported and likely never will be.
-    pub fn load_file(file: String) -> Result<LyricsProcessor, String> {
-        let mut lyrics = Lyrics::parse_file(&PathBuf::from(file))
-            .map_err(|e| format!("File parse error: {e}"))?;
+    ///
+    /// # Arguments
+    /// * `file` - Path of the lyrics file
+    /// * `length` - Length of the track, which unsynced lyrics are spread across
+    ///   (see [`spread()`](spread))
+    ///
+    /// ## Errors
+    /// Fails with the reason, see [`LyricsError`](LyricsError).
+    pub fn load_file(file: String, length: Duration) -> Result<LyricsTimeline, LyricsError> {
+        Self::from_parsed(Lyrics::parse_file(&PathBuf::from(file))?, length)
+    }
+
+    /// Builds the timeline of a parsed lyrics file (e.g. one read ahead by [`LyricsPrefetch`](LyricsPrefetch)).
+    ///
+    /// # Arguments
+    /// * `lyrics` - The parsed lyrics file
+    /// * `length` - Length of the track, which unsynced lyrics are spread across
+    ///
+    /// ## Errors
+    /// Fails if the lyrics aren't synced in a supported way.
+    pub fn from_parsed(
+        mut lyrics: Lyrics,
+        length: Duration,
+    ) -> Result<LyricsTimeline, LyricsError> {
+        let sync = lyrics.sync_type()?;
+        /* Lines are searched by their start times */
+        lyrics.lines.sort_by_key(|line| line.startTimeMs.get());
         lyrics.fix_end_times();
+        mark_sections(&mut lyrics.lines);
+        if sync == SyncType::Unsynced {
+            spread(&lyrics.lines, length);
+        }
 
-        Ok(LyricsProcessor {
+        Ok(LyricsTimeline {
             lines: lyrics.lines,
+            scale: 1.0,
+            sync,
+            language: lyrics.language,
+            credits: lyrics.credits,
         })
     }
 
-    /// Gets the (next) [`LyricsBank`](LyricsBank).
-    /// If `prev_bank` is `None`, the returned [`LyricsBank`](LyricsBank)
-    /// will contain the first [`LYRICS_BANK_SIZE`](LYRICS_BANK_SIZE) + 1 lines
-    /// from the lyrics.
+    /// Returns the code of the language the lyrics are in, if the lyrics file tells it.
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// Returns the people credited for the lyrics (with what they did, if it's known),
+    /// or `None` if nobody is credited.
+    pub fn credits(&self) -> Option<String> {
+        let credits: Vec<String> = self
+            .credits
+            .iter()
+            .map(|credit| match &credit.role {
+                Some(role) => format!("{} ({role})", credit.name),
+                None => credit.name.clone(),
+            })
+            .collect();
+
+        (!credits.is_empty()).then(|| credits.join(", "))
+    }
+
+    /// Returns whether the lyrics are synced, or their timestamps were estimated
+    /// (see [`spread()`](spread)).
+    pub fn is_synced(&self) -> bool {
+        self.sync == SyncType::LineSynced
+    }
+
+    /// Returns when the lyrics end (without rescaling): the end time of the last line,
+    /// or its start time if it has no end time.
+    fn end(&self) -> Option<Duration> {
+        let last = self.lines.last()?;
+        Some(match last.is_endtime_valid() {
+            true => last.endTimeMs.get(),
+            false => last.startTimeMs.get(),
+        })
+    }
+
+    /// Checks whether the lyrics were synced to a different edit of the song, which happens
+    /// when they end noticeably before or after the track.
+    /// Returns the scale which stretches the lyrics to the length of the track if so.
     ///
-    /// ## Panics
-    /// This function may panic if `prev_bank` is a malformed [`LyricsBank`](LyricsBank).
-    pub fn get_bank(&self, prev_bank: Option<LyricsBank>) -> LyricsBank {
-        if prev_bank.is_none() {
-            return LyricsBank {
-                lines: self
-                    .lines
-                    .iter()
-                    .take(LYRICS_BANK_SIZE + 1)
-                    .cloned()
-                    .collect(),
-            };
+    /// ### Notes
+    /// Lyrics of a song with a long instrumental outro also end early, which is why
+    /// the scale is only suggested.
+    pub fn drift(&self, length: Duration) -> Option<f64> {
+        let end = self.end().filter(|end| !end.is_zero())?;
+        /* Estimated timestamps are spread across the track already */
+        if length.is_zero() || !self.is_synced() {
+            return None;
         }
 
-        let prev_bank = prev_bank.unwrap();
-        let end = self
-            .lines
-            .iter()
-            .position(|entry| prev_bank.lines.last().unwrap().startTimeMs == entry.startTimeMs)
-            .unwrap();
-
-        let mut result = Vec::new();
-        for i in end..=(end + LYRICS_BANK_SIZE) {
-            match self.lines.get(i) {
-                None => break,
-                Some(val) => result.push(val.clone()),
+        let scale = length.as_secs_f64() / end.as_secs_f64();
+        ((scale - 1.0).abs() > DRIFT_TOLERANCE).then_some(scale)
+    }
+
+    /// Stretches (or squeezes) the timestamps of the lines by a factor,
+    /// `1.0` restores the original timestamps.
+    pub fn set_scale(&mut self, scale: f64) {
+        self.scale = scale;
+    }
+
+    /// Returns whether the timestamps are stretched, see [`set_scale()`](LyricsTimeline::set_scale).
+    pub fn is_rescaled(&self) -> bool {
+        self.scale != 1.0
+    }
+
+    /// Converts a playtime into the time of the lyrics, which differs if they're rescaled.
+    fn lyrics_time(&self, time: Duration) -> Duration {
+        time.div_f64(self.scale)
+    }
+
+    /// Converts a time of the lyrics into a playtime, see [`lyrics_time()`](LyricsTimeline::lyrics_time).
+    fn playtime(&self, time: Duration) -> Duration {
+        Duration::try_from_secs_f64(time.as_secs_f64() * self.scale).unwrap_or(Duration::MAX)
+    }
+
+    /// Returns where the sections of the song start (in playtime), in order.
+    /// Returns nothing if no sections were recognized.
+    pub fn sections(&self) -> Vec<(Duration, Section)> {
+        let mut sections: Vec<(Duration, Section)> = Vec::new();
+        for line in &self.lines {
+            let Some(section) = line.section else {
+                continue;
+            };
+            if sections.last().map(|(_, last)| *last) != Some(section) {
+                sections.push((self.playtime(line.startTimeMs.get()), section));
             }
         }
 
-        LyricsBank { lines: result }
+        sections
     }
-}
 
-impl LyricsBank {
-    /// Alias to the [`lines`](LyricsBank::lines) attribute's [`last()`](Iterator::last).
-    ///
-    /// ## Panics
-    /// This function will panic if the aliased function returns `None`.
-    pub fn last(&self) -> &LyricsEntry {
-        self.lines.last().unwrap()
+    /// Returns the words of every line.
+    pub fn words(&self) -> Vec<String> {
+        self.lines.iter().map(|line| line.words.clone()).collect()
     }
 
-    /// Alias to the [`lines`](LyricsBank::lines) attribute's [`len()`](Vec::len).
-    pub fn len(&self) -> usize {
-        self.lines.len()
+    /// Sets the translations of the lines (in the same order as [`words()`](LyricsTimeline::words)),
+    /// or removes them if `translations` is `None`.
+    /// Empty translations, and translations which are the same as the line, aren't shown.
+    pub fn set_translations(&mut self, translations: Option<&[String]>) {
+        for (index, line) in self.lines.iter_mut().enumerate() {
+            line.translation = translations
+                .and_then(|translations| translations.get(index))
+                .filter(|translation| !translation.is_empty() && **translation != line.words)
+                .cloned();
+        }
+    }
+
+    /// Returns the index of the line which started last at a playtime,
+    /// or `None` before the first line.
+    pub fn current(&self, time: Duration) -> Option<usize> {
+        let time = self.lyrics_time(time);
+        self.lines
+            .partition_point(|line| line.startTimeMs.get() <= time)
+            .checked_sub(1)
+    }
+
+    /// Returns the index of the line which is sung at a playtime.
+    /// Returns `None` before the first line, and after a line ended if it has an end time
+    /// (until the next line starts).
+    pub fn active(&self, time: Duration) -> Option<usize> {
+        self.current(time).filter(|index| {
+            let line = &self.lines[*index];
+            !line.is_endtime_valid() || self.lyrics_time(time) < line.endTimeMs.get()
+        })
     }
 
-    /// Get the index of a line in [`LyricsBank`](LyricsBank) which
-    /// should be highlighted according to the given playtime.
+    /// Returns the line before the one which started last at a playtime.
+    #[allow(dead_code)]
+    pub fn previous(&self, time: Duration) -> Option<&LyricsEntry> {
+        self.lines.get(self.current(time)?.checked_sub(1)?)
+    }
+
+    /// Returns the line which starts next after a playtime.
+    #[allow(dead_code)]
+    pub fn next(&self, time: Duration) -> Option<&LyricsEntry> {
+        self.lines
+            .get(self.current(time).map_or(0, |index| index + 1))
+    }
+
+    /// Returns the page of lines shown at a playtime.
     ///
-    /// ## Notes
-    /// Returns `None` if no line should be highlighted at the given playtime.
-    pub fn get_active(&self, time: Duration) -> Option<usize> {
-        let potential = self
-            .lines
-            .iter()
-            .take(LYRICS_BANK_SIZE)
-            .rposition(|entry| time >= entry.startTimeMs.get());
-        if let Some(index) = potential {
-            let entry = &self.lines[index];
+    /// Synced lyrics are shown page by page, with the active line highlighted.
+    /// Unsynced lyrics scroll by one line at a time instead, with the current line second
+    /// (as their timing is only estimated, no line is highlighted).
+    pub fn page(&self, time: Duration) -> LyricsPage<'_> {
+        let current = self.current(time).unwrap_or(0);
+        let first = match self.sync {
+            SyncType::LineSynced => current - current % LYRICS_PAGE_SIZE,
+            SyncType::Unsynced => current
+                .saturating_sub(1)
+                .min(self.lines.len().saturating_sub(LYRICS_PAGE_SIZE)),
+        };
+        let last = (first + LYRICS_PAGE_SIZE).min(self.lines.len());
+        let active = match self.sync {
+            SyncType::LineSynced => self.active(time).map(|index| index - first),
+            SyncType::Unsynced => None,
+        };
 
-            if entry.is_endtime_valid() && time >= entry.endTimeMs.get() {
-                return None;
-            }
-            return Some(index);
+        LyricsPage {
+            lines: &self.lines[first..last],
+            active,
+        }
+    }
+}
+
+/// Estimates when the lines of unsynced lyrics are sung, by spreading them across the track.
+/// Each line takes time by its length, so the lyrics roughly follow along.
+/// Section markers take no time, they start together with the line after them.
+///
+/// If the length of the track is unknown, only the first line starts (at the beginning),
+/// so the lyrics stay at the top.
+fn spread(lines: &[LyricsEntry], length: Duration) {
+    let weight = |line: &LyricsEntry| match line.is_marker() {
+        true => 0,
+        false => line.words.chars().count().max(1),
+    };
+    let total: usize = lines.iter().map(weight).sum();
+
+    let mut elapsed = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let start = match (length.is_zero() || total == 0, index) {
+            (true, 0) => Duration::ZERO,
+            (true, _) => Duration::MAX,
+            (false, _) => length.mul_f64(elapsed as f64 / total as f64),
+        };
+        line.startTimeMs.set(start);
+        line.endTimeMs.set(Duration::ZERO);
+        elapsed += weight(line);
+    }
+}
+
+/// Finds out which section each line belongs to.  
+/// If the lyrics have section labels or markers (like `[Chorus]`), the lines belong to the section
+/// of the label or marker before them (unknown labels end the section). Otherwise, lines which are repeated together with a neighbouring line
+/// are taken as the chorus, and the other lines as verses (if nothing is repeated,
+/// no sections are marked).
+fn mark_sections(lines: &mut [LyricsEntry]) {
+    if lines
+        .iter()
+        .any(|line| line.label.is_some() || Section::from_marker(&line.words).is_some())
+    {
+        let mut current = None;
+        for line in lines.iter_mut() {
+            current = match &line.label {
+                Some(label) => Section::from_label(label),
+                None => Section::from_marker(&line.words).or(current),
+            };
+            line.section = current;
         }
-        None
+        return;
     }
 
-    /// Returns whether the bank should no longer be used and the
-    /// next one should be requested using [`get_bank()`](LyricsProcessor::get_bank).
-    pub fn is_expired(&self, playtime: Duration) -> bool {
-        playtime >= self.last().startTimeMs.get()
+    let normalize = |line: &LyricsEntry| line.words.trim().to_lowercase();
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for line in lines.iter().filter(|line| !line.is_adlib()) {
+        *counts.entry(normalize(line)).or_default() += 1;
     }
+    let repeated: Vec<bool> = lines
+        .iter()
+        .map(|line| {
+            !line.is_adlib()
+                && !line.words.trim().is_empty()
+                && counts.get(&normalize(line)).is_some_and(|count| *count > 1)
+        })
+        .collect();
 
-    /// Returns whether this bank is the last or the next one can be requested
-    /// using [`get_bank()`](LyricsProcessor::get_bank).
+    let chorus: Vec<bool> = (0..lines.len())
+        .map(|index| {
+            repeated[index]
+                && ((index > 0 && repeated[index - 1])
+                    || repeated.get(index + 1).copied().unwrap_or(false))
+        })
+        .collect();
+    if !chorus.contains(&true) {
+        return;
+    }
+    /* Ad-libs belong to the section they're sung in */
+    let mut current = None;
+    for (line, chorus) in lines.iter_mut().zip(chorus) {
+        current = match (line.is_adlib(), chorus) {
+            (true, _) => current.or(Some(Section::Verse)),
+            (false, true) => Some(Section::Chorus),
+            (false, false) => Some(Section::Verse),
+        };
+        line.section = current;
+    }
+}
+
+/// A parsed lyrics file, or why it couldn't be read.
+type ParsedLyrics = Result<Lyrics, LyricsError>;
+
+/// Reads the lyrics files of the following entries of the queue in a background thread,
+/// so their lyrics are ready once they start playing.
+///
+/// ### Notes
+/// The thread stops once this is dropped.
+pub struct LyricsPrefetch {
+    /// Lyrics files to read
+    requests: Sender<Vec<String>>,
+    /// Lyrics files which were read
+    loaded: Receiver<(String, ParsedLyrics)>,
+    /// Lyrics files which were read, and are still ahead in the queue
+    cache: HashMap<String, ParsedLyrics>,
+}
+
+impl LyricsPrefetch {
+    /// Starts the background thread.
+    pub fn start() -> Self {
+        let (requests, pending) = channel::<Vec<String>>();
+        let (sender, loaded) = channel();
+
+        thread::spawn(move || {
+            for files in pending {
+                for file in files {
+                    let parsed = Lyrics::parse_file(&PathBuf::from(&file));
+                    if sender.send((file, parsed)).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Self {
+            requests,
+            loaded,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Reads the lyrics files ahead (if they weren't read yet), and forgets the ones which
+    /// aren't ahead anymore.
     ///
-    /// ## Notes
-    /// [`get_bank()`](LyricsProcessor::get_bank) should not be called if
-    /// this function returns `false`.
-    pub fn next_available(&self) -> bool {
-        self.len() > LYRICS_BANK_SIZE
+    /// # Arguments
+    /// * `files` - Paths of the lyrics files of the following entries
+    pub fn prefetch(&mut self, files: Vec<String>) {
+        self.receive();
+        self.cache.retain(|file, _| files.contains(file));
+        let missing: Vec<String> = files
+            .into_iter()
+            .filter(|file| !self.cache.contains_key(file))
+            .collect();
+        if !missing.is_empty() {
+            let _ = self.requests.send(missing);
+        }
+    }
+
+    /// Takes the lyrics of a file which was read ahead.
+    /// Returns `None` if it wasn't read (yet).
+    pub fn take(&mut self, file: &str) -> Option<ParsedLyrics> {
+        self.receive();
+        self.cache.remove(file)
+    }
+
+    /// Stores the lyrics files the thread has read so far.
+    fn receive(&mut self) {
+        self.cache.extend(self.loaded.try_iter());
     }
 }
@@ -1,26 +1,95 @@
+use crate::lyrics::{LyricsError, Section};
 use serde::{
     de::{self, Deserialize as DeserializeTrait, Deserializer},
     Deserialize,
 };
-use serde_json::Result;
-use std::{cell::Cell, fs::File, path::PathBuf, result::Result as StdResult, time::Duration};
+use std::{
+    cell::Cell, fs::File, io::ErrorKind, path::Path, result::Result as StdResult, time::Duration,
+};
+
+/// How the lines of lyrics are synced to the track, given by `syncType`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncType {
+    /// Each line has a start time (`LINE_SYNCED`), and optionally an end time
+    LineSynced,
+    /// The lines have no timestamps (`UNSYNCED`)
+    Unsynced,
+}
+
+impl SyncType {
+    /// Parses a `syncType`, returns `None` if it's unknown.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "LINE_SYNCED" => Some(Self::LineSynced),
+            "UNSYNCED" => Some(Self::Unsynced),
+            _ => None,
+        }
+    }
+}
+
+/// The newest version of the lyrics format which is supported.
+const FORMAT_VERSION: u32 = 2;
 
 #[derive(Debug, Deserialize)]
 #[allow(non_snake_case)]
 pub struct Lyrics {
+    /// Version of the format: `1` is the format of the Spotify lyrics tools, `2` adds
+    /// the language, credits, singers and section labels (which are all optional)
+    #[serde(default = "original_version")]
+    pub version: u32,
+    #[serde(default)]
     pub error: bool,
-    pub syncType: String,
+    /// Why the lyrics couldn't be fetched, if `error` is set
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub syncType: Option<String>,
+    #[serde(default)]
     pub lines: Vec<LyricsEntry>,
+    /// Code of the language the lyrics are in (e.g. `en`)
+    #[serde(default)]
+    pub language: Option<String>,
+    /// The people who wrote (or transcribed) the lyrics
+    #[serde(default)]
+    pub credits: Vec<Credit>,
+}
+
+/// Someone credited for the lyrics.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Credit {
+    pub name: String,
+    /// What they did (e.g. `writer`)
+    #[serde(default)]
+    pub role: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 #[allow(non_snake_case)]
 pub struct LyricsEntry {
-    #[serde(deserialize_with = "parse_duration")]
+    /// Start time of the line (missing in unsynced lyrics)
+    #[serde(default, deserialize_with = "parse_duration")]
     pub startTimeMs: Cell<Duration>,
     pub words: String,
-    #[serde(deserialize_with = "parse_duration")]
+    /// End time of the line, `0` (or missing) if it has none
+    #[serde(default, deserialize_with = "parse_duration")]
     pub endTimeMs: Cell<Duration>,
+    /// Translation of the line, shown next to it (see [`Translator`](crate::translate::Translator))
+    #[serde(skip)]
+    pub translation: Option<String>,
+    /// Section of the song the line belongs to, see [`Section`](crate::lyrics::Section)
+    #[serde(skip)]
+    pub section: Option<Section>,
+    /// Who sings the line (e.g. in duets)
+    #[serde(default)]
+    pub singer: Option<String>,
+    /// Name of the section the line starts (like `Chorus`), the following lines belong to it too
+    #[serde(default, rename = "section")]
+    pub label: Option<String>,
+}
+
+/// Returns the version of lyrics files which don't have one.
+fn original_version() -> u32 {
+    1
 }
 
 fn parse_duration<'de, D>(deserializer: D) -> StdResult<Cell<Duration>, D::Error>
@@ -33,8 +102,41 @@ where
 }
 
 impl Lyrics {
-    pub fn parse_file(file: &PathBuf) -> Result<Self> {
-        serde_json::from_reader(File::open(file).unwrap())
+    /// Reads and parses a lyrics file.
+    ///
+    /// ## Errors
+    /// Fails with the reason, see [`LyricsError`](LyricsError).
+    pub fn parse_file(file: &Path) -> StdResult<Self, LyricsError> {
+        let file = File::open(file).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => LyricsError::NotFound,
+            _ => LyricsError::Unreadable(e.to_string()),
+        })?;
+        let lyrics: Self =
+            serde_json::from_reader(file).map_err(|e| LyricsError::Malformed(e.to_string()))?;
+
+        if lyrics.version > FORMAT_VERSION {
+            return Err(LyricsError::UnsupportedVersion(lyrics.version));
+        }
+        if lyrics.error {
+            return Err(LyricsError::Failed(lyrics.message));
+        }
+        lyrics.sync_type()?;
+        Ok(lyrics)
+    }
+
+    /// Returns how the lyrics are synced.
+    ///
+    /// ## Errors
+    /// Fails if `syncType` is missing or unknown.
+    pub fn sync_type(&self) -> StdResult<SyncType, LyricsError> {
+        match self.syncType.as_deref() {
+            Some(name) => {
+                SyncType::parse(name).ok_or_else(|| LyricsError::UnsupportedSync(name.to_owned()))
+            }
+            None => Err(LyricsError::Malformed(
+                "missing field `syncType`".to_owned(),
+            )),
+        }
     }
 
     pub fn fix_end_times(&mut self) {
@@ -63,7 +165,21 @@ impl Lyrics {
 }
 
 impl LyricsEntry {
+    /// Returns whether the line has an end time (after its start time).
     pub fn is_endtime_valid(&self) -> bool {
-        self.endTimeMs.get().as_secs() > 0
+        self.endTimeMs.get() > self.startTimeMs.get()
+    }
+
+    /// Returns whether the line is a section marker (like `[Chorus]`), instead of sung words.
+    pub fn is_marker(&self) -> bool {
+        let words = self.words.trim();
+        words.starts_with('[') && words.ends_with(']')
+    }
+
+    /// Returns whether the line is an ad-lib (sung in the background), which is written
+    /// in parentheses (like `(Oh yeah)`).
+    pub fn is_adlib(&self) -> bool {
+        let words = self.words.trim();
+        words.starts_with('(') && words.ends_with(')')
     }
 }
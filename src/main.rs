@@ -1,154 +1,3217 @@
+use serde_json::Value;
+use std::collections::HashMap;
 use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::Receiver;
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
+mod acoustid;
+mod alarm;
+mod ape;
+mod artists;
+mod artwork;
 mod audioinfo;
+mod clock;
+mod codec;
+mod command;
+mod comments;
+mod completion;
+mod config;
+mod diagnostics;
 mod display;
+mod dlna;
+mod dsp;
+mod envelope;
+mod ffmpeg;
+mod fifo;
+mod filter;
+mod fuzzy;
+mod history;
+mod keymap;
+mod library;
+mod locale;
 mod lyrics;
 mod lyrics_parse;
+mod mixer;
+mod musicbrainz;
+mod opus;
+mod output;
+mod pathmeta;
+mod paths;
 mod player;
+mod playlist;
+mod prefetch;
+mod queue;
+mod recorder;
+mod resume;
+mod schedule;
 mod scrolledbuf;
+mod search;
+mod seek;
+mod settings;
+mod sleep;
+mod snapcast;
+mod sort;
+mod stream;
+mod tagfix;
+mod tagwriter;
+mod tap;
+mod theme;
 mod timer;
+mod translate;
+mod visualizer;
+mod watcher;
+mod wavpack;
+mod ytdlp;
 
+use crate::alarm::Alarm;
 use crate::audioinfo::*;
+use crate::codec::CodecDetails;
+use crate::command::Command;
+use crate::config::Config;
+use crate::diagnostics::Diagnostics;
 use crate::display::*;
+use crate::dsp::Effects;
+use crate::envelope::{Envelope, EnvelopeScan};
+use crate::filter::Filter;
+use crate::history::{ExportFormat, HistoryEntry, HistoryView, ListeningStats};
+use crate::library::{Library, ScanSummary, Track};
+use crate::locale::tr;
 use crate::lyrics::*;
+use crate::mixer::Mixer;
+use crate::output::{Output, StreamEvent};
 use crate::player::*;
+use crate::playlist::PlaylistEntry;
+use crate::queue::{Queue, QueueView, RepeatMode, UndoStack};
+use crate::recorder::OutputFormat;
+use crate::resume::SavedQueue;
+use crate::schedule::{ScheduledAction, ScheduledFade, Scheduler};
+use crate::search::{Launcher, Search, SearchAction};
+use crate::seek::{HeldSeek, SeekDirection};
+use crate::settings::Settings;
+use crate::sleep::SleepTimer;
+use crate::snapcast::Snapcast;
+use crate::sort::SortMode;
+use crate::tagfix::{FixProgress, TagFix};
+use crate::tagwriter::TagWriter;
+use crate::timer::Timer;
+use crate::visualizer::{Slot, VisualizerKind};
+
+/// Receives the result of a background metadata lookup.
+type MetadataLookup = Receiver<Result<Option<AudioMeta>, String>>;
+/// Receives the translation of the lyrics, made in the background.
+type Translation = Receiver<Result<Vec<String>, String>>;
+/// Receives the updates made by the library watcher.
+type LibraryUpdates = Receiver<Result<ScanSummary, String>>;
+
+/// A prompt shown below the metadata, waiting to be accepted or dismissed.
+enum Prompt {
+    /// Metadata suggested by a lookup
+    Suggestion(AudioMeta),
+    /// Tag changes waiting to be written to the file
+    WriteTags(TagWriter),
+    /// Missing tags of the queue, waiting to be written to the files
+    FixTags(Vec<TagFix>),
+    /// Resuming playback where it stopped in the previous session
+    Resume,
+}
+
+/// A pane shown in the `Lyrics` subwindow instead of the lyrics.
+/// While a pane is open, it receives every key press.
+enum Pane {
+    /// The search prompt
+    Search(Search),
+    /// The listening history
+    History(HistoryView),
+    /// The queue, where its entries can be rearranged
+    Queue(QueueView),
+    /// The jump-to-time prompt, with the position typed so far
+    JumpTo(String),
+    /// The prompt for adding files to the queue, with the path typed so far
+    AddFiles(String),
+    /// The quick launcher
+    Launcher(Launcher),
+    /// The command prompt, with the command typed so far
+    Command(String),
+}
+
+/// An overlay shown in the `Lyrics` subwindow instead of the lyrics.
+/// Unlike a [`Pane`](Pane), it stays open between tracks and doesn't take key presses.
+#[derive(Clone, Copy, PartialEq)]
+enum Overlay {
+    /// Memory usage, buffers, the output and the statistics of the main loop
+    Debug,
+    /// How the current track was encoded (see [`CodecDetails`](CodecDetails))
+    Details,
+    /// How long was listened, and the most played artists and tracks
+    /// (see [`ListeningStats`](ListeningStats))
+    Stats,
+}
+
+/// The reason why playback of a track ended.
+enum TrackEnd {
+    /// The track finished playing
+    Finished,
+    /// The user skipped to the next track
+    Next,
+    /// The user went back to the previous track
+    Previous,
+    /// The user selected an entry of the queue
+    Jump(usize),
+    /// The user quit the program, at the given position (in seconds)
+    Quit(f64),
+    /// The track couldn't be played, with the reason
+    Failed(String),
+}
+
+/// State kept between tracks.
+struct Session {
+    /// The play queue
+    queue: Queue,
+    /// The music library (if it could be opened)
+    library: Option<Library>,
+    /// Updates of the library made by the watcher (if the library is watched)
+    library_updates: Option<LibraryUpdates>,
+    /// Changes of the configuration file (if it's watched)
+    config_updates: Option<Receiver<()>>,
+    /// The user's configuration
+    config: Config,
+    /// Playback volume, so it doesn't reset when the track changes
+    volume: u8,
+    /// Controls the volume, in the player or the system's mixer
+    mixer: Mixer,
+    /// Position (in seconds) to offer resuming the first track at
+    resume: Option<f64>,
+    /// Path to record the first track into
+    record: Option<PathBuf>,
+    /// Where the audio is played
+    output: Output,
+    /// Latency of the output
+    latency: Duration,
+    /// Effects applied to the audio
+    effects: Effects,
+    /// The overlay shown instead of the lyrics (if any)
+    overlay: Option<Overlay>,
+    /// Statistics of the main loop, shown in the debug overlay
+    diagnostics: Diagnostics,
+    /// Whether the lyrics are translated (see [`Translator`](translate::Translator))
+    translate: bool,
+    /// The visualizer (if it's shown)
+    visualizer: Option<Slot>,
+    /// What to play after the current track finishes
+    repeat: RepeatMode,
+    /// Previous states of the queue, to undo destructive changes
+    undo: UndoStack,
+    /// How long was listened in this session, following the clock of the player
+    /// (so time spent paused, or while the audio device failed, doesn't count)
+    listened: Duration,
+    /// The sleep timer (if it's armed)
+    sleep: Option<SleepTimer>,
+    /// The alarm (if it's set), until the volume ramped up after it went off
+    alarm: Option<Alarm>,
+    /// Runs the schedule set in the configuration
+    scheduler: Scheduler,
+    /// The fade of a scheduled pause or playback (while it fades)
+    scheduled_fade: Option<ScheduledFade>,
+    /// A message shown when the next track starts (e.g. why an entry was skipped)
+    notice: Option<String>,
+    /// Reads the lyrics of the following entries ahead
+    lyrics_prefetch: LyricsPrefetch,
+    /// Looks up the missing tags of the queue (while it's looking them up)
+    tag_fix: Option<Receiver<FixProgress>>,
+}
+
+/// A track is counted as played after half of it (or this many seconds) was played.
+const PLAY_THRESHOLD: f64 = 240.0;
+/// After being paused for this long, the UI stops polling and waits for keys instead
+/// (so the visualizer and the status message can settle first).
+const IDLE_DELAY: Duration = Duration::from_secs(3);
+/// Minimum time between seeks while the progress bar is dragged (if it seeks live).
+const SCRUB_INTERVAL: Duration = Duration::from_millis(250);
 
 /// A list of supported audio formats.
-const SUPPORTED_FORMATS: [&str; 3] = ["wav", "flac", "ogg"];
+const SUPPORTED_FORMATS: [&str; 6] = ["wav", "flac", "ogg", "opus", "wv", "ape"];
+
+fn main() {
+    let mut args: Vec<String> = env::args().collect();
+
+    /* Options can be set for a single run, before the other arguments */
+    let mut flags: Vec<(String, Value)> = Vec::new();
+    let mut alarm = None;
+    while let Some(option @ ("--profile" | "--backend" | "--set" | "--alarm")) =
+        args.get(1).map(String::as_str)
+    {
+        let Some(value) = args.get(2).cloned() else {
+            usage(&args[0]);
+        };
+        match option {
+            "--alarm" => {
+                alarm = Some(alarm::parse_time(&value).unwrap_or_else(|e| {
+                    eprintln!("{e}");
+                    exit(1);
+                }));
+            }
+            "--profile" => flags.push(("profile".to_owned(), Value::String(value))),
+            "--backend" => {
+                let (host, device) = match value.split_once(':') {
+                    Some((host, device)) => (host.to_owned(), Value::String(device.to_owned())),
+                    None => (value.clone(), Value::Null),
+                };
+                flags.push(("audio_backend".to_owned(), Value::String(host)));
+                flags.push(("audio_device".to_owned(), device));
+            }
+            _ => {
+                let Some((name, value)) = value.split_once('=') else {
+                    usage(&args[0]);
+                };
+                /* Values which aren't JSON are taken as strings, so they don't need to be quoted */
+                let value = serde_json::from_str(value).unwrap_or(Value::String(value.to_owned()));
+                flags.push((name.to_owned(), value));
+            }
+        }
+        args.drain(1..3);
+    }
+
+    /* Settings are moved as they're written, so a broken configuration can still be replaced */
+    match args.get(1).map(String::as_str) {
+        Some("--export-settings") if args.len() == 3 => {
+            return export_settings(Path::new(&args[2]))
+        }
+        Some("--import-settings") if args.len() == 3 => {
+            return import_settings(Path::new(&args[2]))
+        }
+        _ => {}
+    }
+
+    let config = Config::resolve(flags).unwrap_or_else(|e| {
+        match args.get(1).map(String::as_str) {
+            Some("--check-config") => eprintln!("error: {e}"),
+            _ => eprintln!("{e}"),
+        }
+        exit(1);
+    });
+
+    match args.get(1).map(String::as_str) {
+        Some("--scan") if args.len() == 2 => scan_library(&config),
+        Some("--print-config") if args.len() == 2 => print_config(&config),
+        Some("--check-config") if args.len() == 2 => check_config(&config),
+        Some("--search") if args.len() == 3 => search_library(&args[2], config.sort),
+        Some("--history") if args.len() == 3 => export_history(&args[2]),
+        Some("--favorites") if args.len() == 2 => export_favorites(),
+        Some("--list-renderers") if args.len() == 2 => list_renderers(),
+        Some("--list-backends") if args.len() == 2 => list_backends(),
+        Some("--convert") if args.len() == 4 => {
+            convert_file(&args[3], Path::new(&args[2]), &config)
+        }
+        Some("--record") if args.len() == 4 => {
+            let output = PathBuf::from(&args[2]);
+            if OutputFormat::from_path(&output).is_none() {
+                eprintln!("Only WAV and FLAC files can be written");
+                exit(1);
+            }
+
+            println!("Launching...");
+            run(
+                Queue::new(vec![args[3].clone()]),
+                config,
+                None,
+                Some(output),
+                alarm,
+            );
+        }
+        Some("--playlist") if args.len() == 3 => {
+            let entries = load_playlist(&args[2], &config);
+            println!("Launching...");
+            run(Queue::new(entries), config, None, None, alarm);
+        }
+        Some(file) if !file.starts_with("--") => {
+            let entries = load_entries(&args[1..], &config);
+            if entries.is_empty() {
+                eprintln!("No supported audio files found");
+                exit(1);
+            }
+
+            if entries.iter().any(|entry| entry.location == stream::STDIN) {
+                stream::capture_stdin().unwrap_or_else(|e| {
+                    eprintln!("{e}");
+                    exit(1);
+                });
+            }
+
+            println!("Launching...");
+            run(queue_from_entries(entries), config, None, None, alarm);
+        }
+        None => match SavedQueue::load() {
+            Ok(Some(saved)) => {
+                println!("Launching...");
+                run(saved.queue, config, Some(saved.position), None, alarm);
+            }
+            Ok(None) => usage(&args[0]),
+            Err(e) => {
+                eprintln!("{e}");
+                exit(1);
+            }
+        },
+        _ => usage(&args[0]),
+    }
+}
+
+/// Prints how to use the program, and exits.
+fn usage(program: &str) -> ! {
+    eprintln!("Invalid arguments:");
+    eprintln!("Usage:\n {program} [FILE|DIRECTORY|PLAYLIST|URL|-]...");
+    eprintln!(" {program}                    Resume the previous session");
+    eprintln!(" {program} --scan             Scan the library roots");
+    eprintln!(" {program} --search [QUERY]   Search the library");
+    eprintln!(" {program} --playlist [NAME]  Play a smart playlist");
+    eprintln!(" {program} --history csv|json Export the listening history");
+    eprintln!(" {program} --favorites        Export the favorites as an M3U playlist");
+    eprintln!(" {program} --convert OUT FILE Convert a file to WAV or FLAC");
+    eprintln!(" {program} --record OUT FILE  Play a file while recording it to WAV or FLAC");
+    eprintln!(" {program} --list-renderers   List the DLNA renderers on the network");
+    eprintln!(" {program} --list-backends    List the audio backends and their devices");
+    eprintln!(" {program} --backend HOST[:DEVICE] ...  Use another audio backend (and device)");
+    eprintln!(" {program} --profile NAME ...  Use a profile of the configuration");
+    eprintln!(" {program} --set OPTION=VALUE ...  Set an option of the configuration");
+    eprintln!(" {program} --alarm HH:MM ...  Start playing at a time, like an alarm clock");
+    eprintln!(" {program} --print-config     Print the options and where they come from");
+    eprintln!(" {program} --check-config     Check the configuration for errors and mistakes");
+    eprintln!(" {program} --export-settings FILE  Export the configuration and favorites");
+    eprintln!(" {program} --import-settings FILE  Import settings exported on another machine");
+    eprintln!(
+        "Supported formats: {}",
+        SUPPORTED_FORMATS.map(str::to_ascii_uppercase).join(", ")
+    );
+    exit(1);
+}
+
+/// Turns the command line arguments into entries of the queue.  
+/// Files and URLs are kept in the given order, playlists are expanded, and directories are searched
+/// recursively with the audio files found ordered by the configured sort mode.
+///
+/// ## Panics
+/// Exits the program if a playlist can't be read.
+fn load_entries(args: &[String], config: &Config) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+
+    for arg in args {
+        let path = Path::new(arg);
+
+        let entry = PlaylistEntry::new(arg.clone());
+        if !entry.is_local() {
+            entries.extend(load_url(entry, config));
+            continue;
+        }
+
+        if playlist::is_playlist(path) {
+            let loaded = playlist::load(path).unwrap_or_else(|e| {
+                eprintln!("{e}");
+                exit(1);
+            });
+            for entry in loaded {
+                match entry.is_local() {
+                    true => entries.push(entry),
+                    false => entries.extend(load_url(entry, config)),
+                }
+            }
+            continue;
+        }
+
+        if !path.is_dir() {
+            entries.push(entry);
+            continue;
+        }
+
+        let (loaded, problems) = load_directory(path, config);
+        for problem in problems {
+            eprintln!("{problem}");
+        }
+        entries.extend(loaded);
+    }
+
+    entries
+}
+
+/// Searches a directory recursively for audio files, ordered by the configured sort mode.
+/// Returns the entries, and why files were skipped or placed at the end.
+///
+/// ### Notes
+/// The tags are only read if the sort mode needs them. Files whose tags can't be read are
+/// placed at the end (ordered by their paths).
+fn load_directory(dir: &Path, config: &Config) -> (Vec<PlaylistEntry>, Vec<String>) {
+    let mut files = Vec::new();
+    library::find_audio_files(dir, &mut files);
+    files.sort();
+
+    let mut tracks = Vec::new();
+    let mut unreadable = Vec::new();
+    let mut problems = Vec::new();
+    for (path, modified) in files {
+        /* Entries of the queue are strings */
+        let Some(path) = path.to_str().map(str::to_owned) else {
+            problems.push(format!(
+                "Skipping {}: the path isn't valid UTF-8",
+                path.display()
+            ));
+            continue;
+        };
+
+        let track = match config.sort.reads_tags() {
+            true => Track::from_file(&path),
+            false => Ok(Track::unread(path.clone(), modified)),
+        };
+        match track {
+            Ok(track) => tracks.push(track),
+            Err(e) => {
+                problems.push(format!(
+                    "Unable to read {path} ({e}), it's added at the end"
+                ));
+                unreadable.push(path);
+            }
+        }
+    }
+
+    config.sort.sort(&mut tracks);
+    let entries = tracks
+        .into_iter()
+        .map(|track| track.path)
+        .chain(unreadable)
+        .map(PlaylistEntry::new)
+        .collect();
+    (entries, problems)
+}
+
+/// Prepares an entry pointing to a URL for playback.  
+/// YouTube and SoundCloud URLs are downloaded using `yt-dlp` (if it's enabled), other
+/// HTTP(S) URLs are streamed.
+///
+/// Returns `None` (after printing why) if the URL can't be played.
+fn load_url(entry: PlaylistEntry, config: &Config) -> Option<PlaylistEntry> {
+    if ytdlp::is_supported(&entry.location) {
+        if !config.ytdlp {
+            eprintln!(
+                "Skipping {}: enable 'ytdlp' in the configuration to play it",
+                entry.location
+            );
+            return None;
+        }
+
+        println!("Downloading {}...", entry.location);
+        return match ytdlp::download(&entry.location) {
+            Ok(download) => {
+                let mut metadata = download.metadata;
+                metadata.fill_missing(&entry.metadata);
+                Some(PlaylistEntry {
+                    location: download.path,
+                    metadata,
+                })
+            }
+            Err(e) => {
+                eprintln!("Skipping {}: {e}", entry.location);
+                None
+            }
+        };
+    }
+
+    if stream::is_url(&entry.location) {
+        return Some(entry);
+    }
+
+    eprintln!("Skipping {}: unsupported URL", entry.location);
+    None
+}
+
+/// Creates a queue from playlist entries, keeping their metadata.
+///
+/// ## Panics
+/// Panics if `entries` is empty.
+fn queue_from_entries(entries: Vec<PlaylistEntry>) -> Queue {
+    let mut queue = Queue::new(entries.iter().map(|entry| entry.location.clone()).collect());
+    for entry in entries {
+        if entry.metadata != AudioMeta::unknown() {
+            queue.set_metadata(entry.location, entry.metadata);
+        }
+    }
+
+    queue
+}
+
+/// Selects the tracks of a smart playlist from the library.
+///
+/// ## Panics
+/// Exits the program if the playlist doesn't exist, is invalid or empty.
+fn load_playlist(name: &str, config: &Config) -> Vec<String> {
+    Library::open()
+        .and_then(|library| playlist_tracks(name, config, &library))
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(1);
+        })
+}
+
+/// Selects the tracks of a smart playlist from `library`.
+///
+/// ## Errors
+/// Fails if the playlist doesn't exist, is invalid or empty, or if the library can't be read.
+fn playlist_tracks(name: &str, config: &Config, library: &Library) -> Result<Vec<String>, String> {
+    let rule = config
+        .playlists
+        .get(name)
+        .ok_or_else(|| format!("No playlist named '{name}'"))?;
+    let filter =
+        Filter::parse(rule).map_err(|e| format!("Invalid filter of playlist '{name}': {e}"))?;
+    let tracks = library.filter(&filter, config.sort)?;
+
+    if tracks.is_empty() {
+        return Err(format!("No tracks match the playlist '{name}'"));
+    }
+    Ok(tracks.into_iter().map(|track| track.path).collect())
+}
+
+/// Scans the library roots set in the configuration, and prints a summary.
+fn scan_library(config: &Config) {
+    if config.library_roots.is_empty() {
+        eprintln!("No library roots are configured");
+        exit(1);
+    }
+
+    let summary = Library::open()
+        .and_then(|mut library| {
+            library.set_folder_metadata(config.folder_metadata);
+            library.scan(&config.library_roots)
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(1);
+        });
+
+    println!("{summary}");
+    if summary.failed > 0 {
+        println!("{} file(s) couldn't be read", summary.failed);
+    }
+}
+
+/// Prints the names of the DLNA renderers on the local network.
+fn list_renderers() {
+    println!("Searching for DLNA renderers...");
+    let renderers = dlna::discover().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        exit(1);
+    });
+
+    if renderers.is_empty() {
+        println!("No renderers found");
+    }
+    for renderer in renderers {
+        println!("{}", renderer.name);
+    }
+}
+
+/// Prints the available audio backends, and their output devices.
+fn list_backends() {
+    for (host, devices) in output::backends() {
+        println!("{host}");
+        for device in devices {
+            println!("  {host}:{device}");
+        }
+    }
+}
+
+/// Searches the library, and prints the paths of the matching tracks.
+fn search_library(query: &str, sort: SortMode) {
+    let tracks = Library::open()
+        .and_then(|library| library.search(query, sort))
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(1);
+        });
+
+    for track in tracks {
+        println!("{}", track.path);
+    }
+}
+
+/// Prints the listening history in the given format (`csv` or `json`).
+fn export_history(format: &str) {
+    let Some(format) = ExportFormat::from_name(format) else {
+        eprintln!("Unknown format '{format}', use 'csv' or 'json'");
+        exit(1);
+    };
+
+    let exported = Library::open()
+        .and_then(|library| library.history())
+        .and_then(|entries| history::export(&entries, format))
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(1);
+        });
+
+    print!("{exported}");
+}
+
+/// Prints the favorites as an M3U playlist.
+fn export_favorites() {
+    let favorites = Library::open()
+        .and_then(|library| library.favorites())
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(1);
+        });
+
+    print!("{}", playlist::to_m3u(&favorites));
+}
+
+/// Exports the configuration file and the favorites into a single file.
+fn export_settings(path: &Path) {
+    Settings::collect()
+        .and_then(|settings| settings.export(path))
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(1);
+        });
+
+    println!("Settings exported to {}", path.display());
+}
+
+/// Imports settings exported by [`export_settings()`](export_settings), replacing the configuration
+/// file and adding to the favorites.
+fn import_settings(path: &Path) {
+    let favorites = Settings::read(path)
+        .and_then(Settings::import)
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(1);
+        });
+
+    println!("Imported the configuration and {favorites} favorites");
+}
+
+/// Prints the value of every option of the configuration, and where it comes from.
+fn print_config(config: &Config) {
+    let Ok(Value::Object(options)) = serde_json::to_value(config) else {
+        return;
+    };
+
+    let width = options.keys().map(String::len).max().unwrap_or(0);
+    for (option, value) in options {
+        println!("{option:width$} = {value}  ({})", config.origin(&option));
+    }
+}
+
+/// Prints the likely mistakes in the configuration (it's valid, as it was loaded), and exits.
+/// Exits with `2` if there are any, so scripts can check the configuration before it's deployed.
+fn check_config(config: &Config) {
+    let warnings = config.warnings();
+    for warning in &warnings {
+        eprintln!("warning: {warning}");
+    }
+
+    match warnings.is_empty() {
+        true => println!("The configuration is valid"),
+        false => {
+            println!(
+                "The configuration is valid, with {} warnings",
+                warnings.len()
+            );
+            exit(2);
+        }
+    }
+}
+
+/// Converts a file to WAV or FLAC, and prints the progress.
+fn convert_file(input: &str, output: &Path, config: &Config) {
+    let length = AudioFile::open(input)
+        .map(|afile| afile.length)
+        .unwrap_or(0.0);
+
+    let max_channels = config.backend().channels();
+    let result = recorder::convert(
+        input,
+        output,
+        max_channels,
+        &config.effects(),
+        &config.decode_options(),
+        |converted| {
+            let converted = converted.as_secs_f64();
+            match length > 0.0 {
+                true => print!(
+                    "\rConverting... {:3.0}% ({} / {})",
+                    (converted / length * 100.0).min(100.0),
+                    format_time(converted),
+                    format_time(length)
+                ),
+                false => print!("\rConverting... {}", format_time(converted)),
+            }
+            let _ = io::stdout().flush();
+        },
+    );
+    println!();
+
+    if let Err(e) = result {
+        eprintln!("{e}");
+        exit(1);
+    }
+    println!("Saved {}", output.display());
+}
+
+/// Formats a time in the format `mm:ss`.
+fn format_time(seconds: f64) -> String {
+    format!(
+        "{:02}:{:02}",
+        (seconds / 60.0) as u64,
+        (seconds % 60.0) as u64
+    )
+}
+
+/// Runs the program.
+///
+/// # Arguments
+/// * `queue` - The play queue
+/// * `config` - The user's configuration
+/// * `resume` - Position (in seconds) in the current entry to offer resuming at
+/// * `record` - Path to record the first track into
+/// * `alarm` - Time (hour and minute) to start playback at, see [`Alarm`](Alarm)
+///
+/// ### Notes
+/// When the user quits, the queue and the playback position are saved, so they can be resumed
+/// on the next start. If the whole queue was played, the saved queue is removed.
+fn run(
+    queue: Queue,
+    config: Config,
+    resume: Option<f64>,
+    record: Option<PathBuf>,
+    alarm: Option<(u8, u8)>,
+) {
+    let output = match (&config.dlna_renderer, &config.snapcast) {
+        (Some(_), Some(_)) => Err("Only one of dlna_renderer and snapcast can be set".to_owned()),
+        (Some(name), None) => dlna::find(name).map(Output::Dlna),
+        (None, Some(location)) => Snapcast::parse(location).map(Output::Snapcast),
+        (None, None) => config
+            .backend()
+            .device()
+            .map(|_| Output::Device(config.backend())),
+    }
+    .unwrap_or_else(|e| {
+        eprintln!("{e}");
+        exit(1);
+    });
+
+    let library = Library::open().ok();
+    let library_updates = (library.is_some() && !config.library_roots.is_empty()).then(|| {
+        watcher::watch_in_background(config.library_roots.clone(), config.folder_metadata)
+    });
+
+    let latency = config
+        .output_latency_ms
+        .map(Duration::from_millis)
+        .unwrap_or_else(|| output.latency());
+    let effects = config.effects();
+    if let Some(path) = &config.visualizer_fifo {
+        fifo::start(path, &effects.taps).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(1);
+        });
+    }
+
+    let visualizer = config
+        .visualizer
+        .then(|| config.visualizer(&effects.taps, latency));
+    let volume = config
+        .profile
+        .as_ref()
+        .and_then(|name| config.profiles.get(name))
+        .and_then(|profile| profile.volume)
+        .unwrap_or(100);
+    let mixer = Mixer::new(config.mixer);
+    let scheduler = Scheduler::start(config.schedule.clone());
+
+    let mut session = Session {
+        queue,
+        library,
+        library_updates,
+        config_updates: Config::path().map(watcher::watch_config),
+        config,
+        volume,
+        mixer,
+        resume,
+        record,
+        output,
+        latency,
+        effects,
+        overlay: None,
+        diagnostics: Diagnostics::new(0),
+        translate: false,
+        visualizer,
+        repeat: RepeatMode::default(),
+        undo: UndoStack::default(),
+        listened: Duration::ZERO,
+        sleep: None,
+        alarm: alarm.map(Alarm::new),
+        scheduler,
+        scheduled_fade: None,
+        notice: None,
+        lyrics_prefetch: LyricsPrefetch::start(),
+        tag_fix: None,
+    };
+
+    /* Start UI */
+    let mut display = Display::new(session.queue.current());
+    display.set_theme(session.config.theme);
+    display.set_visualizer_colors(session.config.visualizer_colors());
+    display.set_status_segments(session.config.status_bar());
+    display.set_keymap(session.config.keymap());
+    display.set_artist_separator(session.config.artist_separator());
+    display.set_genre_colors(&session.config.genre_colors);
+    if session.config.mouse {
+        display.enable_mouse();
+    }
+
+    display.init();
+
+    if !display.sizecheck() {
+        display.destroy();
+        eprintln!("Terminal is too small!");
+        eprintln!("The minimum required size is 100x28");
+        exit(1);
+    }
+
+    /* Entries which failed in a row, so a queue where nothing plays isn't repeated forever */
+    let mut failed = 0;
+    let position = loop {
+        let end = play_track(&mut session, &mut display);
+        failed = match &end {
+            TrackEnd::Failed(e) => {
+                session.notice = Some(format!("{}: {e}", tr("Skipped")));
+                failed + 1
+            }
+            _ => 0,
+        };
+        match end {
+            TrackEnd::Finished if session.repeat == RepeatMode::One => (),
+            TrackEnd::Failed(_) if failed >= session.queue.entries().len() => break None,
+            TrackEnd::Finished | TrackEnd::Next | TrackEnd::Failed(_) => {
+                if !session.queue.next() {
+                    match session.repeat {
+                        RepeatMode::All => session.queue.jump(0),
+                        _ => break None,
+                    }
+                }
+            }
+            TrackEnd::Previous => {
+                session.queue.previous();
+            }
+            TrackEnd::Jump(index) => session.queue.jump(index),
+            TrackEnd::Quit(position) => break Some(position),
+        }
+    };
+
+    display.destroy();
+    /* e.g. why the last entry couldn't be played */
+    if let Some(notice) = session.notice.take() {
+        eprintln!("{notice}");
+    }
+
+    let saved = match position {
+        /* The standard input can't be read again */
+        Some(_)
+            if session
+                .queue
+                .entries()
+                .iter()
+                .any(|entry| entry == stream::STDIN) =>
+        {
+            SavedQueue::remove()
+        }
+        Some(position) => SavedQueue {
+            queue: session.queue,
+            position,
+        }
+        .save(),
+        None => SavedQueue::remove(),
+    };
+    if let Err(e) = saved {
+        eprintln!("{e}");
+    }
+}
+
+/// Plays the current entry of the queue.
+fn play_track(session: &mut Session, display: &mut Display) -> TrackEnd {
+    let file = session.queue.current().to_owned();
+
+    /* Initialize everything first, so the UI doesn't appear laggy/frozen for too long */
+    /* Streams can't be resumed, as they can't be seeked */
+    let resume_at = session
+        .resume
+        .take()
+        .filter(|position| *position >= 1.0 && !stream::is_stream(&file));
+    let player = match session.record.take() {
+        Some(recording) => Player::new_recording(
+            &file,
+            &recording,
+            &session.output,
+            &session.effects,
+            &session.config.decode_options(),
+        )
+        .or_else(|e| {
+            display.set_status_message(&e);
+            Player::new(
+                &file,
+                &session.output,
+                &session.effects,
+                &session.config.decode_options(),
+            )
+        }),
+        None => Player::new_at(
+            &file,
+            Duration::from_secs_f64(resume_at.unwrap_or(0.0)),
+            &session.output,
+            &session.effects,
+            &session.config.decode_options(),
+        ),
+    };
+    let opened = player.and_then(|player| {
+        let afile = match stream::is_stream(&file) {
+            true => Ok(AudioFile::new_stream(
+                &file,
+                player.format(),
+                player.sample_rate(),
+                player.channels(),
+            )),
+            false => AudioFile::open(&file),
+        };
+        afile.map(|afile| (player, afile))
+    });
+    /* e.g. a URL which is down, or a file which was removed */
+    let (mut player, mut afile) = match opened {
+        Ok(opened) => opened,
+        Err(e) => return TrackEnd::Failed(e),
+    };
+    complete_metadata(session, &file, &mut afile.metadata);
+    let mut lyrics = take_prefetched_lyrics(session, &file, afile.length);
+    prefetch_lyrics(session);
+    let mut translation = start_translation(session, &file, &lyrics);
+    let mut lookup = start_lookup(&file, &afile, &session.config);
+    let mut scan = (!stream::is_stream(&file))
+        .then(|| Envelope::scan(&file, &session.config.decode_options()));
+    let mut envelope: Option<Envelope> = None;
+    let mut details: Option<Vec<(&str, String)>> = None;
+    let mut stats: Option<ListeningStats> = None;
+    let mut prompt: Option<Prompt> = None;
+    let mut pane: Option<Pane> = None;
+    /* The metadata stored in the file, used to find out what needs to be written */
+    let mut file_meta = afile.metadata.clone();
+
+    display.set_file(&file);
+    display.clear_prompt();
+    display.set_track_info(&afile.metadata);
+    display.set_rating(
+        session
+            .library
+            .as_ref()
+            .and_then(|library| library.rating(&file).ok().flatten()),
+    );
+    display.set_favorite(
+        session
+            .library
+            .as_ref()
+            .is_some_and(|library| library.is_favorite(&file).unwrap_or(false)),
+    );
+    display.set_track_length(afile.length);
+    show_structure(display, &lyrics, afile.length);
+    show_file_quality(session, display, &file, &afile, &player);
+    let play_threshold = match afile.length > 0.0 {
+        true => (afile.length / 2.0).min(PLAY_THRESHOLD),
+        false => PLAY_THRESHOLD,
+    };
+    let mut counted = false;
+    /* Streams can't be seeked, and neither can recorded tracks (the recording would be cut) */
+    let seekable = !stream::is_stream(&file) && afile.length > 0.0 && !player.is_recording();
+    /* Where the progress bar is being dragged to, and when it last seeked while dragging */
+    let mut scrub: Option<f64> = None;
+    let mut scrubbed: Option<Instant> = None;
+    /* Seeking with a key which is being held */
+    let mut held: Option<HeldSeek> = None;
+    let mut bitrate = afile.bitrate();
+    /* How much of the player's clock was counted as listened */
+    let mut clocked = Duration::ZERO;
+    let mut paused_since: Option<Instant> = None;
+    let mut progress_timer = Timer::new(Duration::ZERO);
+
+    if let Err(e) = &lyrics {
+        display.set_lyrics_error(e);
+        display.refresh();
+    }
+
+    if let Some(notice) = session.notice.take() {
+        display.set_status_message(&notice);
+    } else if session.queue.entries().len() > 1 {
+        display.set_status_message(&format!(
+            "{} {}/{}",
+            tr("Track"),
+            session.queue.index() + 1,
+            session.queue.entries().len()
+        ));
+    }
+    report_lyrics_timing(session, display, &lyrics, afile.length);
+
+    player.set_volume(session.volume);
+    player.set_latency(session.latency);
+    let started = library::unix_time(SystemTime::now());
+    if let Some(position) = resume_at {
+        display.set_resume_prompt(position);
+        display.set_playback_status(false);
+        prompt = Some(Prompt::Resume);
+    } else if session
+        .alarm
+        .as_ref()
+        .is_some_and(|alarm| alarm.remaining().is_some())
+    {
+        /* The alarm starts playback when it goes off */
+        display.set_playback_status(false);
+    } else {
+        display.set_playback_status(true);
+        player.play();
+    }
+
+    let end = loop {
+        if player.is_finished() {
+            break TrackEnd::Finished;
+        }
+
+        /* A new player (after seeking) starts its clock at zero */
+        let clock = player.listened();
+        session.listened += clock.checked_sub(clocked).unwrap_or(clock);
+        clocked = clock;
+        let ticked = Instant::now();
+        paused_since = match player.is_paused() {
+            true => paused_since.or(Some(ticked)),
+            false => None,
+        };
+        if session.config.header_clock {
+            display.handle_clock(session.listened);
+        }
+
+        /* Streams keep buffering while paused */
+        if let Some(level) = player.buffer_health() {
+            display.set_buffer_health(level);
+        }
+
+        match player.poll_output() {
+            Some(StreamEvent::Failed(e)) => {
+                display.set_status_message(&format!("{}: {e}", tr("Audio device failed")));
+                show_file_quality(session, display, &file, &afile, &player);
+            }
+            Some(StreamEvent::Recovered(_)) => {
+                display.set_status_message(tr("Audio device plays again"));
+                /* The device may have been opened in another format */
+                show_file_quality(session, display, &file, &afile, &player);
+            }
+            None => (),
+        }
+        /* Audio devices report their latency while they play, and it changes with their buffer */
+        if session.config.output_latency_ms.is_none() {
+            if let Some(latency) = player.device_buffer().map(|meter| meter.latency()) {
+                player.set_latency(latency);
+            }
+        }
+        if let Some(end) = poll_schedule(session, &player, display) {
+            break end;
+        }
+        /* The sleep timer, the alarm and the schedule all fade the audio */
+        let fade = tick_sleep_timer(session, &player, display)
+            * tick_alarm(session, &player, display)
+            * tick_scheduled_fade(session, &player, display);
+        session.effects.fade.set(fade);
+
+        if !player.is_paused() {
+            if progress_timer.expired() {
+                let shown = scrub.map_or(player.playtime(), |position| {
+                    Duration::from_secs_f64(position * afile.length)
+                });
+                display.update_progress(shown, afile.length, envelope.as_ref());
+                /* The interval can change when the configuration is reloaded */
+                progress_timer.reset(session.config.progress_interval());
+            }
+            if let Some(gain) = player.gain() {
+                display.set_gain(gain.gain_db(), gain.reduction_db());
+            }
+            let levels = &session.effects.levels;
+            display.set_levels(levels.current_db(), levels.session_db());
+            display.set_clipping(levels.clips(), levels.is_clipping());
+            let saved = player.skipped();
+            let skipping = session.effects.silence.pauses.load(Ordering::Relaxed);
+            display.set_time_saved((skipping || !saved.is_zero()).then_some(saved));
+            display.handle_scroll();
+
+            if !counted && player.playtime().as_secs_f64() >= play_threshold {
+                record_play_count(session.library.as_ref(), &file, false, display);
+                counted = true;
+            }
+        }
+
+        /* The lyrics follow the playtime, also while paused (e.g. after seeking) */
+        if let (Ok(timeline), None, None) = (&lyrics, &pane, session.overlay) {
+            display.set_lyrics(&timeline.page(player.playtime()));
+        }
+
+        match (session.overlay, &pane) {
+            (Some(Overlay::Debug), None) => show_debug_overlay(session, display, &player),
+            (Some(Overlay::Details), None) => {
+                /* The details are only read once the overlay is shown */
+                let rows = details.get_or_insert_with(|| CodecDetails::read(&afile).rows(&afile));
+                display.set_overlay(rows);
+                display.refresh_infoview();
+            }
+            (Some(Overlay::Stats), None) => {
+                /* The history is only read once the overlay is shown */
+                let stats = stats.get_or_insert_with(|| read_stats(session.library.as_ref()));
+                display.set_overlay(&stats.rows(session.listened, player.listened()));
+                display.refresh_infoview();
+            }
+            _ => (),
+        }
+
+        draw_visualizer(session, display);
+        display.set_status_bar(&StatusBar {
+            playing: !player.is_paused(),
+            volume: session.mixer.volume(&player),
+            muted: session.mixer.is_muted(&player),
+            repeat: session.repeat,
+            bypass: session.effects.bypass.load(Ordering::Relaxed),
+            sleep: session.sleep.as_ref().map(SleepTimer::remaining),
+            sleep_fading: session.sleep.as_ref().is_some_and(SleepTimer::is_fading),
+            alarm: session.alarm.as_ref().and_then(Alarm::remaining),
+            format: afile.format,
+            bitrate,
+        });
+
+        if let Some(released) = held.filter(HeldSeek::is_released) {
+            if let Some(target) = released.pending() {
+                seek(&mut player, &file, target, session);
+            }
+            (held, scrub) = (None, None);
+        }
+
+        if let Some(scanned) = scan.as_ref().and_then(EnvelopeScan::try_recv) {
+            envelope = Some(scanned);
+            scan = None;
+        }
+
+        if let Some((service, result)) = lookup
+            .as_ref()
+            .and_then(|(service, rx)| rx.try_recv().ok().map(|result| (*service, result)))
+        {
+            match result {
+                Ok(Some(meta)) => {
+                    display.set_suggestion(&meta);
+                    prompt = Some(Prompt::Suggestion(meta));
+                }
+                Ok(None) => {
+                    display.set_status_message(&format!("{} ({service})", tr("No match found")))
+                }
+                Err(e) => {
+                    display.set_status_message(&format!("{} ({service}): {e}", tr("Lookup failed")))
+                }
+            }
+            lookup = None;
+        }
+
+        if let Some(progress) = session.tag_fix.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            match progress {
+                FixProgress::Checked(checked, total) => display
+                    .set_status_message(&format!("{}: {checked}/{total}", tr("Looking up tags"))),
+                FixProgress::Done(fixes, failed) => {
+                    match (fixes.is_empty(), failed) {
+                        (true, 0) => display.set_status_message(tr("No missing tags found")),
+                        (true, failed) => display
+                            .set_status_message(&format!("{}: {failed}", tr("Lookups failed"))),
+                        (false, _) => {
+                            display.set_tag_fix_preview(&fixes);
+                            prompt = Some(Prompt::FixTags(fixes));
+                        }
+                    }
+                    session.tag_fix = None;
+                }
+            }
+        }
+
+        if let Some(result) = translation.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            match (result, lyrics.as_mut()) {
+                (Ok(translated), Ok(lp)) => {
+                    lp.set_translations(Some(&translated));
+                    if pane.is_none() && session.overlay.is_none() {
+                        restore_infoview(display, &lyrics, player.playtime());
+                    }
+                }
+                (Err(e), _) => {
+                    display.set_status_message(&format!("{}: {e}", tr("Translation failed")))
+                }
+                (Ok(_), Err(_)) => (),
+            }
+            translation = None;
+        }
+
+        let search = match pane.as_mut() {
+            Some(Pane::Search(search)) => Some(search),
+            _ => None,
+        };
+        poll_library_updates(session, search, display);
+        poll_config_updates(session, display);
+        display.staus_message_tick();
+        display.osd_tick();
+
+        if let Some(active) = pane.as_mut() {
+            let Some(key) = display.capture_prompt_key() else {
+                session.diagnostics.tick(ticked.elapsed());
+                sleep(session.config.refresh_interval());
+                continue;
+            };
+            session.diagnostics.event();
+
+            let (close, end) = match active {
+                Pane::Search(search) => {
+                    process_search_key(key, search, &mut session.queue, display)
+                }
+                Pane::History(history) => (process_history_key(key, history, display), None),
+                Pane::Queue(view) => process_queue_key(key, view, session, display),
+                Pane::JumpTo(input) => {
+                    let length = Duration::from_secs_f64(afile.length);
+                    let (close, target) = process_jump_key(key, input, length, display);
+                    if let Some(target) = target {
+                        seek(&mut player, &file, target, session);
+                        display.update_progress(target, afile.length, envelope.as_ref());
+                    }
+                    (close, None)
+                }
+                Pane::AddFiles(input) => (process_add_key(key, input, session, display), None),
+                Pane::Launcher(launcher) => process_launcher_key(key, launcher, session, display),
+                Pane::Command(input) => match process_command_key(key, input, display) {
+                    (close, Some(Command::Seek(_))) if !seekable => {
+                        display.set_status_message(tr("This track can't be seeked"));
+                        (close, None)
+                    }
+                    (close, Some(Command::Seek(position))) => {
+                        let length = Duration::from_secs_f64(afile.length);
+                        match seek::parse_position(&position, length) {
+                            Ok(target) => {
+                                seek(&mut player, &file, target, session);
+                                display.update_progress(target, afile.length, envelope.as_ref());
+                            }
+                            Err(e) => display.set_status_message(&e),
+                        }
+                        (close, None)
+                    }
+                    (close, Some(Command::Profile(name))) => {
+                        switch_profile(&name, &mut player, &file, seekable, session, display);
+                        (close, None)
+                    }
+                    (close, Some(command)) => {
+                        (close, run_command(command, &player, session, display))
+                    }
+                    (close, None) => (close, None),
+                },
+            };
+            if close {
+                pane = None;
+                restore_infoview(display, &lyrics, player.playtime());
+            }
+            if let Some(end) = end {
+                break end;
+            }
+            continue;
+        }
+
+        // Getch will also refresh the display
+        let event = display.capture_event();
+        if event.is_some() {
+            session.diagnostics.event();
+        }
+        match event {
+            None => (), /* no key was pressed */
+            Some(DisplayEvent::Search) => {
+                let new_search = Search::new(
+                    &session.queue,
+                    session.library.as_ref(),
+                    session.config.sort,
+                );
+                display.set_search(&new_search);
+                pane = Some(Pane::Search(new_search));
+            }
+            Some(DisplayEvent::History) => match session.library.as_ref().map(Library::history) {
+                Some(Ok(entries)) => {
+                    let view = HistoryView::new(entries);
+                    display.set_history(&view);
+                    pane = Some(Pane::History(view));
+                }
+                Some(Err(e)) => display.set_status_message(&e),
+                None => display.set_status_message(tr("The library is unavailable")),
+            },
+            Some(DisplayEvent::EditQueue) => {
+                let view = QueueView::new(&session.queue, session.library.as_ref());
+                display.set_queue(&session.queue, &view);
+                pane = Some(Pane::Queue(view));
+            }
+            Some(DisplayEvent::AddFiles) => {
+                display.set_add_prompt("");
+                pane = Some(Pane::AddFiles(String::new()));
+            }
+            Some(DisplayEvent::Launch) if session.library.is_none() => {
+                display.set_status_message(tr("The library is unavailable"));
+            }
+            Some(DisplayEvent::Launch) => {
+                let launcher = Launcher::new();
+                display.set_launcher(&launcher);
+                pane = Some(Pane::Launcher(launcher));
+            }
+            Some(DisplayEvent::CommandPrompt) => {
+                display.set_command_prompt("");
+                pane = Some(Pane::Command(String::new()));
+            }
+            Some(DisplayEvent::CycleSort) => cycle_sort(session, display),
+            Some(DisplayEvent::CycleChannels) => {
+                let mode = session.effects.channel_mode.get().next();
+                session.effects.channel_mode.set(mode);
+                show_file_quality(session, display, &file, &afile, &player);
+                display.set_status_message(&format!(
+                    "{}: {}",
+                    tr("Channels"),
+                    mode.label().unwrap_or(tr("Normal"))
+                ));
+            }
+            Some(DisplayEvent::CycleDownmix) => {
+                cycle_downmix(&mut player, &file, seekable, session, display);
+                show_file_quality(session, display, &file, &afile, &player);
+            }
+            Some(
+                event @ (DisplayEvent::BassUp
+                | DisplayEvent::BassDown
+                | DisplayEvent::TrebleUp
+                | DisplayEvent::TrebleDown),
+            ) => adjust_tone(event, session, display),
+            Some(DisplayEvent::TogglePauses) => toggle_pauses(session, display),
+            Some(DisplayEvent::ToggleBypass) => {
+                let bypass = !session.effects.bypass.load(Ordering::Relaxed);
+                session.effects.bypass.store(bypass, Ordering::Relaxed);
+                display.set_status_message(match bypass {
+                    true => tr("Effects bypassed"),
+                    false => tr("Effects applied"),
+                });
+            }
+            Some(DisplayEvent::ToggleNight) => {
+                toggle_night(session, display);
+                show_file_quality(session, display, &file, &afile, &player);
+            }
+            Some(DisplayEvent::CycleVisualizer) => cycle_visualizer(session, display),
+            Some(DisplayEvent::CycleProfile) => match session.config.next_profile() {
+                Some(name) => {
+                    let name = name.to_owned();
+                    switch_profile(&name, &mut player, &file, seekable, session, display);
+                }
+                None => display.set_status_message(tr("No profiles configured")),
+            },
+            Some(
+                DisplayEvent::Scrub(_)
+                | DisplayEvent::Seek(_)
+                | DisplayEvent::SeekBy(_)
+                | DisplayEvent::JumpTo,
+            ) if !seekable => {
+                display.set_status_message(tr("This track can't be seeked"));
+            }
+            Some(DisplayEvent::Rate(rating)) => rate_track(session, &file, &afile, rating, display),
+            Some(DisplayEvent::ToggleFavorite) => toggle_favorite(session, &file, display),
+            Some(DisplayEvent::PlayFavorites) => match favorites_queue(session) {
+                Ok(queue) => {
+                    session.queue = queue;
+                    break TrackEnd::Jump(0);
+                }
+                Err(e) => display.set_status_message(&e),
+            },
+            Some(DisplayEvent::JumpTo) => {
+                display.set_jump_prompt("");
+                pane = Some(Pane::JumpTo(String::new()));
+            }
+            Some(DisplayEvent::Scrub(position)) => {
+                let target = Duration::from_secs_f64(position * afile.length);
+                scrub = Some(position);
+                show_seek_target(display, target, afile.length, envelope.as_ref(), "");
+
+                if session.config.scrub_live
+                    && scrubbed.is_none_or(|time| time.elapsed() >= SCRUB_INTERVAL)
+                {
+                    seek(&mut player, &file, target, session);
+                    scrubbed = Some(Instant::now());
+                }
+            }
+            Some(DisplayEvent::Seek(position)) => {
+                let target = Duration::from_secs_f64(position * afile.length);
+                (scrub, scrubbed) = (None, None);
+                seek(&mut player, &file, target, session);
+                display.update_progress(target, afile.length, envelope.as_ref());
+            }
+            Some(DisplayEvent::SeekBy(direction)) => {
+                let moved = match held.as_mut() {
+                    Some(seeking) if seeking.direction() == direction => seeking.repeat(),
+                    _ => {
+                        let from = held.map_or(player.playtime(), |seeking| seeking.target());
+                        let length = Duration::from_secs_f64(afile.length);
+                        let seeking = HeldSeek::start(direction, from, length);
+                        seek(&mut player, &file, seeking.target(), session);
+                        held = Some(seeking);
+                        true
+                    }
+                };
+
+                if let Some(seeking) = held.filter(|_| moved) {
+                    let sign = match direction {
+                        SeekDirection::Back => '-',
+                        SeekDirection::Forward => '+',
+                    };
+                    let step = format!(" ({sign}{}s)", seeking.step_size().as_secs());
+                    scrub = Some(seeking.target().as_secs_f64() / afile.length);
+                    show_seek_target(
+                        display,
+                        seeking.target(),
+                        afile.length,
+                        envelope.as_ref(),
+                        &step,
+                    );
+                }
+            }
+            Some(DisplayEvent::ToggleDebug) => {
+                toggle_overlay(session, display, Overlay::Debug, &lyrics, player.playtime());
+            }
+            Some(DisplayEvent::ToggleDetails) => {
+                toggle_overlay(
+                    session,
+                    display,
+                    Overlay::Details,
+                    &lyrics,
+                    player.playtime(),
+                );
+            }
+            Some(DisplayEvent::ToggleStats) => {
+                toggle_overlay(session, display, Overlay::Stats, &lyrics, player.playtime());
+                /* The history is read again, in case it changed */
+                stats = None;
+            }
+            Some(DisplayEvent::ReloadLyrics) => {
+                let rescaled = lyrics.as_ref().is_ok_and(LyricsTimeline::is_rescaled);
+                lyrics = load_lyrics(&file, afile.length);
+                if let (true, Ok(timeline)) = (rescaled, lyrics.as_mut()) {
+                    /* The lyrics may have been fixed, so the scale is checked again */
+                    let length = Duration::from_secs_f64(afile.length.max(0.0));
+                    timeline.set_scale(timeline.drift(length).unwrap_or(1.0));
+                }
+                translation = start_translation(session, &file, &lyrics);
+                show_structure(display, &lyrics, afile.length);
+                if session.overlay.is_none() {
+                    restore_infoview(display, &lyrics, player.playtime());
+                }
+                display.set_status_message(match &lyrics {
+                    Ok(_) => tr("Lyrics reloaded"),
+                    Err(e) => e.summary(),
+                });
+            }
+            Some(DisplayEvent::RefreshMetadata) if stream::is_stream(&file) => {
+                display.set_status_message(tr("Streams have no tags"));
+            }
+            Some(DisplayEvent::RefreshMetadata) => match AudioFile::open(&file) {
+                Ok(refreshed) => {
+                    /* Playback isn't restarted, so the length of the playing audio is kept */
+                    afile = AudioFile {
+                        length: afile.length,
+                        ..refreshed
+                    };
+                    complete_metadata(session, &file, &mut afile.metadata);
+                    file_meta = afile.metadata.clone();
+                    bitrate = afile.bitrate();
+                    details = None;
+                    display.set_track_info(&afile.metadata);
+                    show_file_quality(session, display, &file, &afile, &player);
+                    display.set_status_message(tr("Metadata refreshed"));
+                }
+                Err(e) => display.set_status_message(&e),
+            },
+            Some(DisplayEvent::RescaleLyrics) => {
+                let length = Duration::from_secs_f64(afile.length.max(0.0));
+                let message = match lyrics.as_mut() {
+                    Ok(timeline) if timeline.is_rescaled() => {
+                        timeline.set_scale(1.0);
+                        tr("Original lyrics timing")
+                    }
+                    Ok(timeline) => match timeline.drift(length) {
+                        Some(scale) => {
+                            timeline.set_scale(scale);
+                            tr("Lyrics stretched to the track")
+                        }
+                        None => tr("Lyrics already match the track"),
+                    },
+                    Err(e) => e.summary(),
+                };
+                show_structure(display, &lyrics, afile.length);
+                if pane.is_none() && session.overlay.is_none() {
+                    restore_infoview(display, &lyrics, player.playtime());
+                }
+                display.set_status_message(message);
+            }
+            Some(DisplayEvent::ToggleTranslation) if session.config.translator().is_none() => {
+                display.set_status_message(tr("Translation isn't set up"));
+            }
+            Some(DisplayEvent::ToggleTranslation) => {
+                session.translate = !session.translate;
+                translation = start_translation(session, &file, &lyrics);
+                if !session.translate {
+                    if let Ok(lp) = lyrics.as_mut() {
+                        lp.set_translations(None);
+                    }
+                    if session.overlay.is_none() {
+                        restore_infoview(display, &lyrics, player.playtime());
+                    }
+                }
+                display.set_status_message(match session.translate {
+                    true => tr("Translating lyrics"),
+                    false => tr("Translation off"),
+                });
+            }
+            Some(DisplayEvent::ToggleMono) => {
+                let mono = !session.effects.mono.load(Ordering::Relaxed);
+                session.effects.mono.store(mono, Ordering::Relaxed);
+                show_file_quality(session, display, &file, &afile, &player);
+                display.set_status_message(match mono {
+                    true => tr("Mono"),
+                    false => tr("Stereo"),
+                });
+            }
+            Some(event @ (DisplayEvent::Accept | DisplayEvent::Dismiss))
+                if matches!(prompt, Some(Prompt::Resume)) =>
+            {
+                prompt = None;
+                display.clear_prompt();
+                if event == DisplayEvent::Dismiss {
+                    /* Players can't seek, so a new one is created */
+                    player.destroy();
+                    player = match Player::new(
+                        &file,
+                        &session.output,
+                        &session.effects,
+                        &session.config.decode_options(),
+                    ) {
+                        Ok(player) => player,
+                        Err(e) => break TrackEnd::Failed(e),
+                    };
+                    player.set_volume(session.volume);
+                    player.set_latency(session.latency);
+                }
+                player.play();
+                display.set_playback_status(true);
+            }
+            Some(
+                event @ (DisplayEvent::Accept | DisplayEvent::Dismiss | DisplayEvent::WriteTags),
+            ) => process_prompt_event(
+                event,
+                &mut prompt,
+                (&mut afile, &mut file_meta),
+                &mut session.queue,
+                display,
+            ),
+            Some(event) => {
+                if let Some(end) =
+                    process_display_event(event, &player, &session.queue, &session.mixer, display)
+                {
+                    break end;
+                }
+            }
+        }
+
+        /* While paused and nothing moves, block on input instead of polling
+        (unless the debug overlay is shown, which keeps measuring the loop) */
+        let idle = pane.is_none()
+            && session.overlay != Some(Overlay::Debug)
+            && held.is_none()
+            && scrub.is_none()
+            && !display.has_status_message()
+            && !display.has_osd()
+            && !player.is_output_failed()
+            && session.alarm.is_none()
+            && paused_since.is_some_and(|since| since.elapsed() >= IDLE_DELAY);
+        display.set_idle(idle);
+        session.diagnostics.tick(ticked.elapsed());
+        if !idle {
+            sleep(session.config.refresh_interval());
+        }
+    };
+    display.set_idle(false);
+
+    match end {
+        TrackEnd::Finished if !counted => {
+            record_play_count(session.library.as_ref(), &file, false, display)
+        }
+        TrackEnd::Next | TrackEnd::Jump(_) if !counted => {
+            record_play_count(session.library.as_ref(), &file, true, display)
+        }
+        _ => (),
+    }
+
+    let listened = player.listened().as_secs_f64();
+    if let (Some(library), true) = (session.library.as_ref(), listened >= 1.0) {
+        let entry = HistoryEntry {
+            path: file.clone(),
+            title: afile.metadata.title.clone(),
+            album: afile.metadata.album.clone(),
+            artist: afile.metadata.artist.clone(),
+            started,
+            duration: listened,
+        };
+        if let Err(e) = library.record_history(&entry) {
+            display.set_status_message(&e);
+        }
+    }
+
+    session.volume = player.get_volume();
+    player.destroy();
+    if let Err(e) = player.finish_recording() {
+        display.set_status_message(&e);
+    }
+    end
+}
+
+/// Completes the metadata read from a file: missing tags are taken from the queue
+/// (e.g. a playlist or the library), then inferred from the path of the file.
+fn complete_metadata(session: &Session, file: &str, metadata: &mut AudioMeta) {
+    if let Some(meta) = session.queue.metadata(file) {
+        metadata.fill_missing(meta);
+    }
+    if !stream::is_stream(file) {
+        metadata.infer_missing(file, session.config.folder_metadata);
+    }
+}
+
+/// Shows the volume bar, after the volume was changed (or the audio was muted or unmuted).
+/// If the mixer couldn't be changed, the error is shown instead.
+fn show_volume(display: &mut Display, player: &Player, mixer: &Mixer, result: Result<(), String>) {
+    match result {
+        Ok(()) => display.show_osd(Osd::Volume {
+            volume: mixer.volume(player),
+            muted: mixer.is_muted(player),
+        }),
+        Err(e) => display.set_status_message(&e),
+    }
+}
+
+/// Shows the file quality line, along with the play counts of the track.
+fn show_file_quality(
+    session: &Session,
+    display: &Display,
+    file: &str,
+    afile: &AudioFile,
+    player: &Player,
+) {
+    display.set_file_quality(
+        afile,
+        player.is_downmixed(),
+        session.effects.channel_mode.get(),
+        session.effects.mono.load(Ordering::Relaxed),
+        session.effects.night.load(Ordering::Relaxed),
+        (player.output_format(), player.conversion()),
+    );
+    if let Some(count) = session
+        .library
+        .as_ref()
+        .and_then(|library| library.play_count(file).ok())
+    {
+        display.set_play_count(&count);
+    }
+}
+
+/// Records a play (or a skip) of a track in the library, and shows the new counts.
+fn record_play_count(library: Option<&Library>, file: &str, skipped: bool, display: &mut Display) {
+    let Some(library) = library else {
+        return;
+    };
+
+    let result = match skipped {
+        true => library.record_skip(file),
+        false => library.record_play(file),
+    };
+    match result.and_then(|()| library.play_count(file)) {
+        Ok(count) => display.set_play_count(&count),
+        Err(e) => display.set_status_message(&e),
+    }
+}
+
+/// Process a key pressed while the search prompt is open.
+///
+/// Returns whether the prompt should be closed, and how playback of the current track
+/// should end (if it should).
+fn process_search_key(
+    key: PromptKey,
+    search: &mut Search,
+    queue: &mut Queue,
+    display: &mut Display,
+) -> (bool, Option<TrackEnd>) {
+    match key {
+        PromptKey::Char(c) => search.push(c),
+        PromptKey::Backspace => search.pop(),
+        PromptKey::Tab => search.toggle_scope(),
+        PromptKey::Up => search.select_previous(),
+        PromptKey::Down => search.select_next(),
+        PromptKey::ShiftUp => search.mark_previous(),
+        PromptKey::ShiftDown => search.mark_next(),
+        PromptKey::Escape => return (true, None),
+        PromptKey::Enter => {
+            return match search.action() {
+                Some(SearchAction::Jump(index)) => (true, Some(TrackEnd::Jump(index))),
+                Some(SearchAction::Enqueue(paths)) => {
+                    display.set_status_message(&match paths.len() {
+                        1 => tr("Added to queue").to_owned(),
+                        added => format!("{}: {added}", tr("Added to queue")),
+                    });
+                    for path in paths {
+                        queue.push(path);
+                    }
+                    (true, None)
+                }
+                None => (false, None),
+            };
+        }
+        PromptKey::Other => return (false, None),
+    }
+
+    display.set_search(search);
+    (false, None)
+}
+
+/// Checks whether the library watcher updated the library.  
+/// The library results of an open search prompt are refreshed.
+fn poll_library_updates(session: &mut Session, search: Option<&mut Search>, display: &mut Display) {
+    let Some(result) = session
+        .library_updates
+        .as_ref()
+        .and_then(|rx| rx.try_recv().ok())
+    else {
+        return;
+    };
+
+    match result {
+        Ok(summary) => {
+            if let (Some(search), Some(library)) = (search, session.library.as_ref()) {
+                search.reload_library(library, session.config.sort);
+                display.set_search(search);
+            }
+            display.set_status_message(&format!("{}: {summary}", tr("Library updated")));
+        }
+        Err(e) => {
+            display.set_status_message(&e);
+            session.library_updates = None;
+        }
+    }
+}
+
+/// Reloads the configuration file if it changed, and applies the settings which can be changed
+/// while the player is running (see [`Config::apply_live()`](Config::apply_live)).
+///
+/// ### Notes
+/// Changes made by the player itself (like switching the visualizer) are saved to the file too,
+/// but they don't change any setting, so they're not reported.
+fn poll_config_updates(session: &mut Session, display: &mut Display) {
+    let Some(rx) = session.config_updates.as_ref() else {
+        return;
+    };
+    if rx.try_iter().count() == 0 {
+        return;
+    }
+
+    let config = match session.config.reload() {
+        Ok(config) => config,
+        Err(e) => {
+            /* Reports of several problems are shown on one line */
+            let e: Vec<&str> = e.lines().map(str::trim).collect();
+            display.set_status_message(&e.join(" "));
+            return;
+        }
+    };
+    if !session.config.apply_live(config) {
+        return;
+    }
+
+    display.set_theme(session.config.theme);
+    display.set_visualizer_colors(session.config.visualizer_colors());
+    display.set_status_segments(session.config.status_bar());
+    display.set_keymap(session.config.keymap());
+    display.set_artist_separator(session.config.artist_separator());
+    display.set_genre_colors(&session.config.genre_colors);
+    display.clear_visualizer();
+    session
+        .scheduler
+        .set_entries(session.config.schedule.clone());
+    session.visualizer = session.config.visualizer.then(|| {
+        session
+            .config
+            .visualizer(&session.effects.taps, session.latency)
+    });
+    display.set_status_message(tr("Configuration reloaded"));
+}
+
+/// Switches to the next sort mode and re-sorts the queue, keeping the current track.  
+/// The new mode is saved in the configuration.
+///
+/// ### Notes
+/// Tags are taken from the library, entries which are not in the library are read again.
+/// Entries which can't be read are moved to the end of the queue.
+fn cycle_sort(session: &mut Session, display: &mut Display) {
+    let sort = session.config.sort.next();
+    session.config.sort = sort;
+
+    let known: HashMap<String, Track> = session
+        .library
+        .as_ref()
+        .and_then(|library| library.tracks(SortMode::Path).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|track| (track.path.clone(), track))
+        .collect();
+
+    let mut tracks = Vec::new();
+    let mut unreadable = Vec::new();
+    for path in session.queue.entries() {
+        let track = match known.get(path) {
+            Some(track) => Ok(track.clone()),
+            None => Track::from_file(path),
+        };
+
+        match track {
+            Ok(track) => tracks.push(track),
+            Err(_) => unreadable.push(path.clone()),
+        }
+    }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() != 2 {
-        eprintln!("Invalid arguments:");
-        eprintln!("Usage:\n {} [FILE]", args[0]);
-        eprintln!(
-            "Supported formats: {}",
-            SUPPORTED_FORMATS.map(str::to_ascii_uppercase).join(", ")
-        );
-        exit(1);
+    sort.sort(&mut tracks);
+    let mut entries: Vec<String> = tracks.into_iter().map(|track| track.path).collect();
+    entries.append(&mut unreadable);
+    session.queue.reorder(entries);
+
+    match session.config.save() {
+        Ok(()) => display.set_status_message(&format!("{}: {sort}", tr("Sort"))),
+        Err(e) => display.set_status_message(&e),
+    }
+}
+
+/// Switches to a [profile](config::Profile), and saves it in the configuration.
+///
+/// Its volume, tone levels and theme apply right away. If it uses another audio device,
+/// the current track is restarted on it at the same position (tracks which can't be seeked
+/// keep playing, and the device is used from the next track on).
+fn switch_profile(
+    name: &str,
+    player: &mut Player,
+    file: &str,
+    seekable: bool,
+    session: &mut Session,
+    display: &mut Display,
+) {
+    let previous = session.config.backend();
+    let profile = match session.config.apply_profile(name) {
+        Ok(profile) => profile,
+        Err(e) => {
+            display.set_status_message(&e);
+            return;
+        }
+    };
+
+    if let Some(volume) = profile.volume {
+        if let Err(e) = session.mixer.set_volume(player, volume) {
+            display.set_status_message(&e);
+        }
+        session.volume = player.get_volume();
+    }
+    session.effects.preamp_db = session.config.preamp_db;
+    session
+        .effects
+        .tone
+        .set(session.config.bass_db, session.config.treble_db);
+    display.set_theme(session.config.theme);
+    display.set_visualizer_colors(session.config.visualizer_colors());
+
+    let backend = session.config.backend();
+    let moved = backend.host != previous.host || backend.device != previous.device;
+    if moved && matches!(session.output, Output::Device(_)) {
+        if let Err(e) = backend.device() {
+            display.set_status_message(&e);
+            return;
+        }
+        session.output = Output::Device(backend);
+        session.latency = session
+            .config
+            .output_latency_ms
+            .map(Duration::from_millis)
+            .unwrap_or_else(|| session.output.latency());
+        if seekable {
+            let position = player.playtime();
+            seek(player, file, position, session);
+        }
     }
 
-    println!("Launching...");
-    run(args[1].clone());
+    match session.config.save() {
+        Ok(()) => display.set_status_message(&format!("{}: {name}", tr("Profile"))),
+        Err(e) => display.set_status_message(&e),
+    }
 }
 
-/// Runs the program.
-fn run(file: String) {
-    /* Initialize everything first, so the UI doesn't appear laggy/frozen for too long */
-    let afile = AudioFile::new(&file);
-    let player = Player::new(&file);
-    let lyrics = LyricsProcessor::load_file(generate_lyrics_file_name(&file));
-    let mut lyrics_bank: Option<LyricsBank> = None;
+/// Changes the bass or treble level of the playing audio (by 1 dB),
+/// and saves it in the configuration.
+fn adjust_tone(event: DisplayEvent, session: &mut Session, display: &mut Display) {
+    let tone = &session.effects.tone;
+    let message = match event {
+        DisplayEvent::BassUp | DisplayEvent::BassDown => {
+            let delta = match event == DisplayEvent::BassUp {
+                true => 1,
+                false => -1,
+            };
+            session.config.bass_db = tone.adjust_bass(delta);
+            format!("{}: {:+} dB", tr("Bass"), session.config.bass_db)
+        }
+        _ => {
+            let delta = match event == DisplayEvent::TrebleUp {
+                true => 1,
+                false => -1,
+            };
+            session.config.treble_db = tone.adjust_treble(delta);
+            format!("{}: {:+} dB", tr("Treble"), session.config.treble_db)
+        }
+    };
 
-    /* Start UI */
-    let mut display = Display::new(&file);
+    match session.config.save() {
+        Ok(()) => display.set_status_message(&message),
+        Err(e) => display.set_status_message(&e),
+    }
+}
 
-    display.init();
+/// Turns shortening the pauses of the playing audio on or off,
+/// and saves it in the configuration.
+fn toggle_pauses(session: &mut Session, display: &mut Display) {
+    let pauses = !session.effects.silence.pauses.load(Ordering::Relaxed);
+    session
+        .effects
+        .silence
+        .pauses
+        .store(pauses, Ordering::Relaxed);
+    session.config.skip_pauses = pauses;
 
-    if !display.sizecheck() {
-        display.destroy();
-        eprintln!("Terminal is too small!");
-        eprintln!("The minimum required size is 100x28");
-        exit(1);
+    match session.config.save() {
+        Ok(()) => display.set_status_message(match pauses {
+            true => tr("Skipping pauses"),
+            false => tr("Playing pauses"),
+        }),
+        Err(e) => display.set_status_message(&e),
     }
+}
 
-    display.set_track_info(&afile.metadata);
-    display.set_track_length(afile.length);
-    display.set_file_quality(&afile);
+/// Turns night mode on or off, and saves it in the configuration.
+fn toggle_night(session: &mut Session, display: &mut Display) {
+    let night = !session.effects.night.load(Ordering::Relaxed);
+    session.effects.night.store(night, Ordering::Relaxed);
+    session.config.night_mode = night;
 
-    if lyrics.is_err() {
-        display.set_unavailable();
-        display.refresh();
+    match session.config.save() {
+        Ok(()) => display.set_status_message(match night {
+            true => tr("Night mode on"),
+            false => tr("Night mode off"),
+        }),
+        Err(e) => display.set_status_message(&e),
+    }
+}
+
+/// Switches to the next [downmix mode](DownmixMode), and saves it in the configuration.
+/// The playing track is decoded again at the same position, so the switch is heard
+/// immediately, unless it can't be seeked (then it applies from the next track).
+fn cycle_downmix(
+    player: &mut Player,
+    file: &str,
+    seekable: bool,
+    session: &mut Session,
+    display: &mut Display,
+) {
+    let mode = session.config.downmix.next();
+    session.config.downmix = mode;
+    if seekable {
+        let position = player.playtime();
+        seek(player, file, position, session);
+    }
+
+    match session.config.save() {
+        Ok(()) => display.set_status_message(&format!("{}: {}", tr("Downmix"), mode.name())),
+        Err(e) => display.set_status_message(&e),
+    }
+}
+
+/// Seeks to `position` in the playing track.
+/// Players can't seek, so the player is replaced by one starting at `position`,
+/// which keeps the volume (or muting) and whether playback is paused.
+///
+/// ### Notes
+/// If the file can't be decoded again (e.g. it was removed), or the DLNA renderer or the
+/// Snapcast server can't be reached, the track ends and the reason is shown when the next
+/// one starts.
+fn seek(player: &mut Player, file: &str, position: Duration, session: &mut Session) {
+    let (paused, volume) = (player.is_paused(), player.get_volume());
+    player.destroy();
+
+    *player = match Player::new_at(
+        file,
+        position,
+        &session.output,
+        &session.effects,
+        &session.config.decode_options(),
+    ) {
+        Ok(player) => player,
+        Err(e) => {
+            session.notice = Some(e);
+            return;
+        }
+    };
+    player.set_volume(volume);
+    player.set_latency(session.latency);
+    if !paused {
+        player.play();
+    }
+}
+
+/// Rates the current track (from 1 to 5 stars), or removes its rating if `rating` is `0`.
+/// The rating is stored in the library, and written to the file if `write_ratings` is enabled.
+fn rate_track(session: &Session, file: &str, afile: &AudioFile, rating: u8, display: &mut Display) {
+    let Some(library) = session.library.as_ref() else {
+        display.set_status_message(tr("The library is unavailable"));
+        return;
+    };
+
+    let rating = (rating > 0).then_some(rating);
+    if let Err(e) = library.set_rating(file, rating) {
+        display.set_status_message(&e);
+        return;
+    }
+    display.set_rating(rating);
+
+    let written = match rating {
+        Some(rating) if session.config.write_ratings && !stream::is_stream(file) => {
+            TagWriter::rating(file, afile.format, rating).apply()
+        }
+        _ => Ok(()),
+    };
+    match (written, rating) {
+        (Err(e), _) => {
+            display.set_status_message(&format!("{}: {e}", tr("Unable to write the rating")))
+        }
+        (Ok(()), Some(rating)) => {
+            display.set_status_message(&format!("{} {}", tr("Rated"), stars(rating)))
+        }
+        (Ok(()), None) => display.set_status_message(tr("Rating removed")),
+    }
+}
+
+/// Adds the current track to the favorites, or removes it from them.
+fn toggle_favorite(session: &Session, file: &str, display: &mut Display) {
+    let Some(library) = session.library.as_ref() else {
+        display.set_status_message(tr("The library is unavailable"));
+        return;
+    };
+
+    let favorite = !library.is_favorite(file).unwrap_or(false);
+    match library.set_favorite(file, favorite) {
+        Ok(()) => {
+            display.set_favorite(favorite);
+            display.set_status_message(match favorite {
+                true => tr("Added to favorites"),
+                false => tr("Removed from favorites"),
+            });
+        }
+        Err(e) => display.set_status_message(&e),
+    }
+}
+
+/// Creates a queue of the favorites, to replace the current queue.
+///
+/// ## Errors
+/// Fails if the library is unavailable, or if there are no favorites.
+fn favorites_queue(session: &Session) -> Result<Queue, String> {
+    let library = session
+        .library
+        .as_ref()
+        .ok_or(tr("The library is unavailable"))?;
+    let entries = library.favorites()?;
+    if entries.is_empty() {
+        return Err(tr("There are no favorites").to_owned());
+    }
+
+    Ok(queue_from_entries(entries))
+}
+
+/// Shows where a seek jumps to, on the progress bar and in a status message.
+///
+/// # Arguments
+/// * `target` - Where it jumps to
+/// * `length` - Length of the track (in seconds)
+/// * `envelope` - The loudness of the track, shown in the progress bar (if it's known)
+/// * `note` - Appended to the status message
+fn show_seek_target(
+    display: &mut Display,
+    target: Duration,
+    length: f64,
+    envelope: Option<&Envelope>,
+    note: &str,
+) {
+    display.update_progress(target, length, envelope);
+    display.set_status_message(&format!(
+        "{} {} / {}{note}",
+        tr("Seek to"),
+        format_time(target.as_secs_f64()),
+        format_time(length)
+    ));
+}
+
+/// Updates the visualizer (if it's shown).
+fn draw_visualizer(session: &mut Session, display: &Display) {
+    let Some(slot) = session.visualizer.as_mut() else {
+        return;
+    };
+
+    let (width, height) = display.visualizer_size();
+    if let Some(frame) = slot.render(width, height) {
+        display.set_visualizer(&frame);
+    }
+}
+
+/// Switches to the next visualizer (the spectrum, the VU meter, the scope, then none),
+/// and saves the choice.
+fn cycle_visualizer(session: &mut Session, display: &mut Display) {
+    let next = match &session.visualizer {
+        None => Some(VisualizerKind::Spectrum),
+        Some(slot) => slot.kind().next(),
+    };
+
+    display.clear_visualizer();
+    if let Some(kind) = next {
+        session.config.visualizer_kind = kind;
+    }
+    session.config.visualizer = next.is_some();
+    session.visualizer = next.map(|_| {
+        session
+            .config
+            .visualizer(&session.effects.taps, session.latency)
+    });
+
+    match session.config.save() {
+        Ok(()) => display.set_status_message(&match next {
+            Some(kind) => format!("{}: {}", tr("Visualizer"), kind.label()),
+            None => tr("Visualizer hidden").to_owned(),
+        }),
+        Err(e) => display.set_status_message(&e),
+    }
+}
+
+/// Process a key pressed while the queue is shown.
+///
+/// Returns whether the queue should be closed, and how playback of the current track should end
+/// (if an entry was chosen to be played). Rearranging the queue doesn't interrupt the current track.
+fn process_queue_key(
+    key: PromptKey,
+    view: &mut QueueView,
+    session: &mut Session,
+    display: &mut Display,
+) -> (bool, Option<TrackEnd>) {
+    let selected = view.selected();
+    let group = view.group();
+    let queue = &mut session.queue;
+    match key {
+        PromptKey::Up => view.select(selected.saturating_sub(1), queue),
+        PromptKey::Down => view.select(selected + 1, queue),
+        PromptKey::ShiftUp => view.mark_to(selected.saturating_sub(1), queue),
+        PromptKey::ShiftDown => view.mark_to(selected + 1, queue),
+        PromptKey::Char(' ') => view.toggle_mark(),
+        PromptKey::Char('[') => {
+            if queue.move_up(&group) {
+                view.follow_move(true, queue);
+            }
+        }
+        PromptKey::Char(']') => {
+            if queue.move_down(&group) {
+                view.follow_move(false, queue);
+            }
+        }
+        PromptKey::Char('x') => {
+            let previous = queue.clone();
+            let removed = queue.remove(&group);
+            if removed > 0 {
+                session.undo.push(&previous);
+                view.clear_marks();
+                view.select(group[0], queue);
+            }
+            if removed < group.len() {
+                display.set_status_message(tr("The current track can't be removed"));
+            }
+        }
+        PromptKey::Char('n') => match queue.play_next(&group) {
+            0 => display.set_status_message(tr("This track is already playing")),
+            moved => {
+                view.clear_marks();
+                view.select(queue.index() + moved, queue);
+                display.set_status_message(tr("Playing next"));
+            }
+        },
+        PromptKey::Char('c') => {
+            clear_queue(session, display);
+            view.clear_marks();
+            view.select(session.queue.index(), &session.queue);
+        }
+        PromptKey::Char('s') => {
+            shuffle_queue(session, display);
+            view.clear_marks();
+        }
+        PromptKey::Char('u') => {
+            undo_queue(session, display);
+            view.clear_marks();
+            view.select(selected, &session.queue);
+        }
+        PromptKey::Enter => return (true, Some(TrackEnd::Jump(selected))),
+        PromptKey::Escape | PromptKey::Char('r') => return (true, None),
+        _ => return (false, None),
+    }
+
+    display.set_queue(&session.queue, view);
+    (false, None)
+}
+
+/// Removes every entry of the queue except the current one.
+/// The previous queue can be restored by undoing.
+fn clear_queue(session: &mut Session, display: &mut Display) {
+    if session.queue.entries().len() == 1 {
+        display.set_status_message(tr("The queue is already empty"));
+        return;
+    }
+
+    session.undo.push(&session.queue);
+    session.queue.clear();
+    display.set_status_message(tr("Queue cleared"));
+}
+
+/// Shuffles the entries of the queue after the current one.
+/// The previous order can be restored by undoing.
+fn shuffle_queue(session: &mut Session, display: &mut Display) {
+    session.undo.push(&session.queue);
+    session.queue.shuffle();
+    display.set_status_message(tr("Queue shuffled"));
+}
+
+/// Restores the queue to how it was before the last destructive change.
+/// The current track keeps playing.
+fn undo_queue(session: &mut Session, display: &mut Display) {
+    match session.undo.pop() {
+        Some(previous) => {
+            session.queue.restore(previous);
+            display.set_status_message(tr("Undone"));
+        }
+        None => display.set_status_message(tr("Nothing to undo")),
+    }
+}
+
+/// Process a key pressed while the command prompt is open.
+///
+/// Returns whether the prompt should be closed, and the command to run (once a valid command
+/// is entered). If the command is invalid, the prompt stays open.
+fn process_command_key(
+    key: PromptKey,
+    input: &mut String,
+    display: &mut Display,
+) -> (bool, Option<Command>) {
+    match key {
+        PromptKey::Char(c) => input.push(c),
+        PromptKey::Backspace => {
+            input.pop();
+        }
+        PromptKey::Escape => return (true, None),
+        PromptKey::Enter => match Command::parse(input) {
+            Ok(command) => return (true, Some(command)),
+            Err(e) => display.set_status_message(&e),
+        },
+        _ => return (false, None),
+    }
+
+    display.set_command_prompt(input);
+    (false, None)
+}
+
+/// Runs a command typed into the command prompt.
+/// Returns how playback of the current track should end, if the command ends it.
+fn run_command(
+    command: Command,
+    player: &Player,
+    session: &mut Session,
+    display: &mut Display,
+) -> Option<TrackEnd> {
+    match command {
+        Command::Seek(_) => unreachable!("Seeking is handled in play_track()"),
+        Command::Profile(_) => unreachable!("Profiles are switched in play_track()"),
+        Command::Volume(volume) => {
+            let result = session.mixer.set_volume(player, volume);
+            show_volume(display, player, &session.mixer, result);
+        }
+        Command::Repeat(mode) => {
+            session.repeat = mode;
+            display.set_status_message(&format!("{}: {}", tr("Repeat"), mode.name()));
+        }
+        Command::Sleep(minutes) => {
+            session.sleep = minutes.map(SleepTimer::new);
+            match minutes {
+                Some(minutes) => {
+                    display.set_status_message(&format!("{}: {minutes} min", tr("Sleep timer")))
+                }
+                None => display.set_status_message(tr("Sleep timer off")),
+            }
+        }
+        Command::Alarm(Some(time)) => {
+            let alarm = Alarm::new(time);
+            player.pause();
+            display.set_playback_status(false);
+            display.set_status_message(&format!("{} {}", tr("Alarm set for"), alarm.label()));
+            session.alarm = Some(alarm);
+        }
+        Command::Alarm(None) => {
+            session.alarm = None;
+            display.set_status_message(tr("Alarm off"));
+        }
+        Command::SavePlaylist(name) => match save_queue(&session.queue, &name) {
+            Ok(path) => display.set_status_message(&format!(
+                "{} {}",
+                tr("Saved the queue to"),
+                path.display()
+            )),
+            Err(e) => display.set_status_message(&e),
+        },
+        Command::Clear => clear_queue(session, display),
+        Command::Shuffle => shuffle_queue(session, display),
+        Command::Undo => undo_queue(session, display),
+        Command::FixTags => {
+            let files = session
+                .queue
+                .entries()
+                .iter()
+                .map(|entry| (entry.clone(), session.queue.metadata(entry).cloned()))
+                .collect();
+            session.tag_fix = Some(tagfix::fix_in_background(
+                files,
+                session.config.folder_metadata,
+                session.config.acoustid_key.clone(),
+            ));
+            display.set_status_message(tr("Looking up tags"));
+        }
+        Command::Event(event) => {
+            return process_display_event(event, player, &session.queue, &session.mixer, display);
+        }
+    }
+
+    None
+}
+
+/// Counts down the sleep timer (if it's armed) and fades the audio out at its end.
+/// Once it expired, playback is paused and the sleep timer is turned off.
+///
+/// Returns the level the audio is faded to.
+fn tick_sleep_timer(session: &mut Session, player: &Player, display: &mut Display) -> f32 {
+    let Some(timer) = &mut session.sleep else {
+        return 1.0;
+    };
+
+    timer.set_playing(!player.is_paused());
+    if !timer.expired() {
+        return timer.fade_level();
     }
 
-    display.set_playback_status(true);
-    player.play();
+    player.pause();
+    display.set_playback_status(false);
+    display.set_status_message(tr("Sleep timer expired"));
+    session.sleep = None;
+    /* The audio is paused before it's turned up again */
+    1.0
+}
+
+/// Waits for the alarm (if it's set), and starts playback when it goes off.
+/// The alarm is removed once the volume ramped up.
+///
+/// Returns the level the audio is faded to.
+fn tick_alarm(session: &mut Session, player: &Player, display: &mut Display) -> f32 {
+    let Some(alarm) = &mut session.alarm else {
+        return 1.0;
+    };
 
-    while !player.is_finished() {
+    if alarm.is_due() {
+        /* If playback was already started by hand, it's left as it is */
         if !player.is_paused() {
-            display.update_progress(player.playtime(), afile.length);
-            display.handle_scroll();
+            session.alarm = None;
+            return 1.0;
+        }
+        alarm.ring();
+        player.play();
+        display.set_playback_status(true);
+        display.set_status_message(&format!("{} {}", tr("Alarm"), alarm.label()));
+    }
 
-            if lyrics.is_ok() {
-                let lp = lyrics.as_ref().unwrap();
-                let playtime = player.playtime();
-                let mut bank = lyrics_bank.unwrap_or(lp.get_bank(None));
+    alarm.set_playing(!player.is_paused());
+    if alarm.is_finished() {
+        session.alarm = None;
+        return 1.0;
+    }
+    alarm.fade_level()
+}
 
-                if bank.is_expired(playtime) && bank.next_available() {
-                    bank = lp.get_bank(Some(bank));
+/// Handles the entries of the schedule which are due: a scheduled pause fades the audio out
+/// before pausing, and scheduled playback starts (or plays a smart playlist) and fades it in.
+///
+/// Returns how the current track ends, if a playlist replaced the queue.
+fn poll_schedule(
+    session: &mut Session,
+    player: &Player,
+    display: &mut Display,
+) -> Option<TrackEnd> {
+    let entry = session.scheduler.poll()?;
+    let fade = ScheduledFade::new(entry.action, entry.fade());
+
+    match (entry.action, &entry.playlist) {
+        (ScheduledAction::Pause, _) => {
+            if !player.is_paused() {
+                display.set_status_message(tr("Scheduled pause"));
+                session.scheduled_fade = Some(fade);
+            }
+            None
+        }
+        (ScheduledAction::Play, Some(name)) => {
+            let tracks = session
+                .library
+                .as_ref()
+                .ok_or_else(|| tr("The library is unavailable").to_owned())
+                .and_then(|library| playlist_tracks(name, &session.config, library));
+            match tracks {
+                Ok(tracks) => {
+                    display.set_status_message(&format!("{} {name}", tr("Scheduled playback")));
+                    /* The next track starts silent */
+                    session.effects.fade.set(0.0);
+                    session.scheduled_fade = Some(fade);
+                    session.queue = Queue::new(tracks);
+                    Some(TrackEnd::Jump(0))
+                }
+                Err(e) => {
+                    display.set_status_message(&e);
+                    None
                 }
+            }
+        }
+        (ScheduledAction::Play, None) => {
+            /* If it's already playing, only a scheduled pause which is fading is stopped */
+            if !player.is_paused() {
+                session
+                    .scheduled_fade
+                    .take_if(|fade| fade.action() == ScheduledAction::Pause);
+                return None;
+            }
+            display.set_status_message(tr("Scheduled playback"));
+            session.effects.fade.set(0.0);
+            session.scheduled_fade = Some(fade);
+            player.play();
+            display.set_playback_status(true);
+            None
+        }
+    }
+}
 
-                let active = bank.get_active(playtime);
-                display.set_lyrics_bank(&bank);
-                display.set_active_lyrics_line(&active);
-                display.refresh_infoview();
+/// Fades the audio before a scheduled pause (and pauses playback once it faded out),
+/// or after scheduled playback started.
+///
+/// Returns the level the audio is faded to.
+fn tick_scheduled_fade(session: &mut Session, player: &Player, display: &mut Display) -> f32 {
+    let Some(fade) = &mut session.scheduled_fade else {
+        return 1.0;
+    };
+
+    fade.set_playing(!player.is_paused());
+    if !fade.expired() {
+        return fade.fade_level();
+    }
+
+    if fade.action() == ScheduledAction::Pause {
+        player.pause();
+        display.set_playback_status(false);
+    }
+    session.scheduled_fade = None;
+    /* The audio is paused before it's turned up again */
+    1.0
+}
+
+/// Saves the queue as an M3U playlist, along with the metadata known from other playlists.
+/// If `name` has no extension, `.m3u` is appended.
+///
+/// Returns the path of the playlist.
+///
+/// ## Errors
+/// Fails if `name` has another extension, or if the playlist can't be written.
+fn save_queue(queue: &Queue, name: &str) -> Result<PathBuf, String> {
+    let mut path = completion::expand_home(name);
+    match path.extension().and_then(|ext| ext.to_str()) {
+        None => {
+            path.set_extension("m3u");
+        }
+        Some(ext) if ext.eq_ignore_ascii_case("m3u") || ext.eq_ignore_ascii_case("m3u8") => (),
+        Some(_) => return Err(tr("Only M3U playlists can be saved").to_owned()),
+    }
+
+    let entries: Vec<PlaylistEntry> = queue
+        .entries()
+        .iter()
+        .map(|location| PlaylistEntry {
+            location: location.clone(),
+            metadata: queue
+                .metadata(location)
+                .cloned()
+                .unwrap_or_else(AudioMeta::unknown),
+        })
+        .collect();
+
+    fs::write(&path, playlist::to_m3u(&entries))
+        .map_err(|e| format!("Unable to save {}: {e}", path.display()))?;
+    Ok(path)
+}
+
+/// Process a key pressed while the quick launcher is open.
+///
+/// Returns whether the launcher should be closed, and how playback of the current track should end
+/// (if a track was chosen to be played). The chosen track is inserted after the current one, and
+/// enqueued tracks are appended to the end of the queue.
+fn process_launcher_key(
+    key: PromptKey,
+    launcher: &mut Launcher,
+    session: &mut Session,
+    display: &mut Display,
+) -> (bool, Option<TrackEnd>) {
+    let Some(library) = session.library.as_ref() else {
+        return (true, None);
+    };
+    let sort = session.config.sort;
+
+    let searched = match key {
+        PromptKey::Char(c) => launcher.push(c, library, sort),
+        PromptKey::Backspace => launcher.pop(library, sort),
+        PromptKey::Up => {
+            launcher.select_previous();
+            Ok(())
+        }
+        PromptKey::Down => {
+            launcher.select_next();
+            Ok(())
+        }
+        PromptKey::ShiftUp => {
+            launcher.mark_previous();
+            Ok(())
+        }
+        PromptKey::ShiftDown => {
+            launcher.mark_next();
+            Ok(())
+        }
+        PromptKey::Tab => {
+            let group = launcher.group();
+            match (group.len(), launcher.selected_item()) {
+                (0, _) => (),
+                (1, Some(item)) if item.path == group[0] => {
+                    display.set_status_message(&format!("{}: {}", tr("Added to queue"), item.label))
+                }
+                (added, _) => {
+                    display.set_status_message(&format!("{}: {added}", tr("Added to queue")))
+                }
+            }
+            for path in group {
+                session.queue.push(path);
+            }
+            launcher.clear_marks();
+            Ok(())
+        }
+        PromptKey::Enter => {
+            let group = launcher.group();
+            if group.is_empty() {
+                return (false, None);
+            }
+            /* The marked tracks are played in the order they were marked */
+            session.queue.insert_next(group);
+            return (true, Some(TrackEnd::Jump(session.queue.index() + 1)));
+        }
+        PromptKey::Escape => return (true, None),
+        PromptKey::Other => return (false, None),
+    };
+
+    if let Err(e) = searched {
+        display.set_status_message(&e);
+    }
+    display.set_launcher(launcher);
+    (false, None)
+}
 
-                lyrics_bank = Some(bank);
+/// Process a key pressed while the prompt for adding files to the queue is open.
+///
+/// Returns whether the prompt should be closed. If the path can't be added, the prompt stays open.
+fn process_add_key(
+    key: PromptKey,
+    input: &mut String,
+    session: &mut Session,
+    display: &mut Display,
+) -> bool {
+    match key {
+        PromptKey::Char(c) => input.push(c),
+        PromptKey::Backspace => {
+            input.pop();
+        }
+        PromptKey::Tab => *input = completion::complete_path(input),
+        PromptKey::Escape => return true,
+        PromptKey::Enter => match add_to_queue(input, session) {
+            Ok((added, 0)) => {
+                display.set_status_message(&format!("{}: {added}", tr("Added to queue")));
+                return true;
+            }
+            Ok((added, problems)) => {
+                display.set_status_message(&format!(
+                    "{}: {added}, {}: {problems}",
+                    tr("Added to queue"),
+                    tr("Unreadable")
+                ));
+                return true;
             }
+            Err(e) => display.set_status_message(&e),
+        },
+        _ => return false,
+    }
+
+    display.set_add_prompt(input);
+    false
+}
+
+/// Appends a file, a directory (searched recursively) or a playlist to the end of the queue.
+/// Returns the number of entries added, and the number of files in a directory which were
+/// skipped or can't be read (see [`load_directory()`](load_directory)).
+///
+/// ## Errors
+/// Fails if the path doesn't exist, isn't a supported audio file or playlist,
+/// or if no audio files were found.
+///
+/// ### Notes
+/// Only the local files of playlists are added, as URLs may need to be downloaded first.
+fn add_to_queue(input: &str, session: &mut Session) -> Result<(usize, usize), String> {
+    let path = completion::expand_home(input.trim());
+
+    let (entries, problems) = if playlist::is_playlist(&path) {
+        let entries = playlist::load(&path)?
+            .into_iter()
+            .filter(PlaylistEntry::is_local)
+            .collect();
+        (entries, Vec::new())
+    } else if path.is_dir() {
+        load_directory(&path, &session.config)
+    } else if path.is_file() {
+        match path.to_str().filter(|p| AudioFormat::from_path(p).is_ok()) {
+            Some(file) => (vec![PlaylistEntry::new(file.to_owned())], Vec::new()),
+            None => return Err(format!("{}: {}", tr("Unsupported file"), path.display())),
         }
+    } else {
+        return Err(format!(
+            "{}: {}",
+            tr("No such file or directory"),
+            path.display()
+        ));
+    };
 
-        display.staus_message_tick();
+    if entries.is_empty() {
+        return Err(format!(
+            "{} {}",
+            tr("No audio files found in"),
+            path.display()
+        ));
+    }
 
-        // Getch will also refresh the display
-        match display.capture_event() {
-            None => (), /* no key was pressed */
-            Some(event) => process_display_event(event, &player, &mut display),
+    let added = entries.len();
+    for entry in entries {
+        session.queue.push(entry.location.clone());
+        if entry.metadata != AudioMeta::unknown() {
+            session.queue.set_metadata(entry.location, entry.metadata);
         }
+    }
+    Ok((added, problems.len()))
+}
 
-        sleep(Duration::from_millis(10));
+/// Process a key pressed while the listening history is shown.
+/// Returns whether the history should be closed.
+fn process_history_key(key: PromptKey, history: &mut HistoryView, display: &Display) -> bool {
+    match key {
+        PromptKey::Up => history.scroll_up(),
+        PromptKey::Down => history.scroll_down(),
+        PromptKey::Escape | PromptKey::Enter | PromptKey::Char('l') => return true,
+        _ => return false,
     }
 
-    player.destroy();
-    display.destroy();
+    display.set_history(history);
+    false
+}
+
+/// Process a key pressed while the jump-to-time prompt is open.
+///
+/// Returns whether the prompt should be closed, and where to seek to (once a valid position
+/// is entered). If the position is invalid, the prompt stays open.
+fn process_jump_key(
+    key: PromptKey,
+    input: &mut String,
+    length: Duration,
+    display: &mut Display,
+) -> (bool, Option<Duration>) {
+    match key {
+        PromptKey::Char(c) if c.is_ascii_digit() || matches!(c, ':' | '.' | '%') => input.push(c),
+        PromptKey::Backspace => {
+            input.pop();
+        }
+        PromptKey::Escape => return (true, None),
+        PromptKey::Enter => match seek::parse_position(input, length) {
+            Ok(target) => return (true, Some(target)),
+            Err(e) => display.set_status_message(&e),
+        },
+        _ => return (false, None),
+    }
+
+    display.set_jump_prompt(input);
+    (false, None)
+}
+
+/// Shows an overlay instead of the lyrics, or hides it (showing the lyrics again)
+/// if it's already shown. Another overlay is replaced.
+fn toggle_overlay(
+    session: &mut Session,
+    display: &Display,
+    overlay: Overlay,
+    lyrics: &Result<LyricsTimeline, LyricsError>,
+    time: Duration,
+) {
+    session.overlay = match session.overlay == Some(overlay) {
+        true => None,
+        false => Some(overlay),
+    };
+    match session.overlay {
+        Some(Overlay::Debug) => session.diagnostics = Diagnostics::new(display.redraws()),
+        Some(Overlay::Details | Overlay::Stats) => display.clear_infoview(),
+        None => restore_infoview(display, lyrics, time),
+    }
+}
+
+/// Reads the statistics of the listening history.
+/// If the library is unavailable (or the history can't be read), they're empty.
+fn read_stats(library: Option<&Library>) -> ListeningStats {
+    library
+        .and_then(|library| library.history().ok())
+        .map(|history| ListeningStats::new(&history))
+        .unwrap_or_default()
+}
+
+/// Shows the debug overlay: memory usage, how much decoded audio is buffered, the output,
+/// and the statistics of the main loop.
+fn show_debug_overlay(session: &mut Session, display: &Display, player: &Player) {
+    let memory = match resident_memory() {
+        Some(bytes) => format_size(bytes),
+        None => "unknown".to_owned(),
+    };
+    let decode_buffer = match player.decode_buffer() {
+        Some(meter) => format!(
+            "{} / {}",
+            format_size(meter.buffered_bytes() as u64),
+            format_size(meter.capacity_bytes() as u64)
+        ),
+        None => "off".to_owned(),
+    };
+    let stream_buffer = match player.buffer_health() {
+        Some(level) => format!("{:.0}%", level * 100.0),
+        None => "off".to_owned(),
+    };
+    let device_buffer = match player.device_buffer() {
+        Some(_) if player.is_output_failed() => "failed, retrying...".to_owned(),
+        Some(meter) => format!(
+            "{} frames ({} ms), {} ms latency",
+            meter.buffer_frames(),
+            meter.buffer().as_millis(),
+            meter.latency().as_millis()
+        ),
+        None => "off".to_owned(),
+    };
+    let mut rows = vec![
+        ("Memory:", memory),
+        ("Decode buffer:", decode_buffer),
+        ("Stream buffer:", stream_buffer),
+        ("Device buffer:", device_buffer),
+        (
+            "Output:",
+            match player.output_format() {
+                Some(format) => format!("{format}, {} ms", player.latency().as_millis()),
+                None => "none".to_owned(),
+            },
+        ),
+    ];
+
+    /* The statistics of the loop are shown once the first second was measured */
+    let measuring = || "measuring...".to_owned();
+    let stats = session.diagnostics.stats(display.redraws());
+    rows.extend([
+        (
+            "Ticks:",
+            stats.map_or_else(measuring, |stats| format!("{:.0}/s", stats.tick_rate)),
+        ),
+        (
+            "Tick time:",
+            stats.map_or_else(measuring, |stats| {
+                format!(
+                    "{:.1} ms avg, {:.1} max",
+                    stats.average_tick.as_secs_f64() * 1000.0,
+                    stats.slowest_tick.as_secs_f64() * 1000.0
+                )
+            }),
+        ),
+        (
+            "Redraws:",
+            stats.map_or_else(measuring, |stats| format!("{:.0}/s", stats.redraw_rate)),
+        ),
+        (
+            "Events:",
+            stats.map_or_else(measuring, |stats| {
+                format!("{} ({:.1}/s)", stats.events, stats.event_rate)
+            }),
+        ),
+    ]);
+
+    display.set_overlay(&rows);
+    display.refresh_infoview();
+}
+
+/// Returns how much memory the player uses (its resident set size, in bytes).
+/// Returns `None` if it can't be found out (it's read from `/proc`, which only Linux has).
+fn resident_memory() -> Option<u64> {
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * u64::try_from(page_size).ok()?)
+}
+
+/// Formats a size in bytes, using the largest fitting unit (e.g. `1.5 MiB`).
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", UNITS[unit]),
+    }
+}
+
+/// Redraws the `Lyrics` subwindow after it was used by a pane.
+fn restore_infoview(
+    display: &Display,
+    lyrics: &Result<LyricsTimeline, LyricsError>,
+    time: Duration,
+) {
+    display.clear_infoview();
+    match lyrics {
+        Ok(timeline) => display.set_lyrics_page(&timeline.page(time)),
+        Err(e) => display.set_lyrics_error(e),
+    }
+    display.refresh_infoview();
+}
+
+/// Starts looking up the metadata of a file with missing tags in the background.  
+/// Completely untagged files are identified by their fingerprint (if an AcoustID key is set),
+/// otherwise MusicBrainz is searched using the known tags.
+///
+/// Returns the name of the service and the channel which receives the result,
+/// or `None` if no lookup is needed or enabled.
+fn start_lookup(
+    file: &str,
+    afile: &AudioFile,
+    config: &Config,
+) -> Option<(&'static str, MetadataLookup)> {
+    /* Streams can't be fingerprinted, and their length is unknown */
+    if stream::is_stream(file) {
+        return None;
+    }
+
+    if let Some(key) = config.acoustid_key.as_ref() {
+        if afile.metadata.is_untagged() {
+            return Some((
+                "AcoustID",
+                acoustid::lookup_in_background(file.to_owned(), key.clone()),
+            ));
+        }
+    }
+
+    if config.musicbrainz_lookup && afile.metadata.is_incomplete() {
+        return Some((
+            "MusicBrainz",
+            musicbrainz::lookup_in_background(afile.metadata.clone(), afile.length),
+        ));
+    }
+
+    None
 }
 
-/// Process the current [`DisplayEvent`](DisplayEvent).
-fn process_display_event(event: DisplayEvent, player: &Player, display: &mut Display) {
+/// Process the current [`DisplayEvent`](DisplayEvent).  
+/// Returns how playback of the current track should end, if the event ends it.
+fn process_display_event(
+    event: DisplayEvent,
+    player: &Player,
+    queue: &Queue,
+    mixer: &Mixer,
+    display: &mut Display,
+) -> Option<TrackEnd> {
     use DisplayEvent::*;
 
     match event {
         MakePlay => {
             player.play();
             display.set_playback_status(true);
-            display.set_status_message("Resumed");
+            display.set_status_message(tr("Resumed"));
         }
         MakePause => {
             player.pause();
             display.set_playback_status(false);
-            display.set_status_message("Paused");
+            display.set_status_message(tr("Paused"));
         }
         ToggleMute => {
-            if player.is_muted() {
-                player.unmute();
-                display.set_status_message("Unmuted");
-            } else {
-                player.mute();
-                display.set_status_message("Muted");
+            let result = mixer.toggle_mute(player);
+            show_volume(display, player, mixer, result);
+        }
+        JumpNext => {
+            if queue.index() + 1 < queue.entries().len() {
+                return Some(TrackEnd::Next);
+            }
+            display.set_status_message(tr("This is the last track"));
+        }
+        JumpBack => {
+            if queue.index() > 0 {
+                return Some(TrackEnd::Previous);
             }
+            display.set_status_message(tr("This is the first track"));
         }
-        JumpNext => (), //TODO: Implement
-        JumpBack => (), //TODO: Implement
         VolUp => {
-            player.inc_volume();
-            display.set_status_message(&format!("+ Volume ({}%)", player.get_volume()));
+            let result = mixer.step_volume(player, true);
+            show_volume(display, player, mixer, result);
         }
         VolDown => {
-            player.dec_volume();
-            display.set_status_message(&format!("- Volume ({}%)", player.get_volume()));
+            let result = mixer.step_volume(player, false);
+            show_volume(display, player, mixer, result);
         }
         Invalid(c) => {
             if !c.is_ascii_alphanumeric() {
-                display.set_status_message("Unknown command");
+                display.set_status_message(tr("Unknown command"));
             } else {
-                display.set_status_message(&format!("Unknown command '{c}'"));
+                display.set_status_message(&format!("{} '{c}'", tr("Unknown command")));
+            }
+        }
+        Accept | Dismiss | WriteTags | Search | CycleSort | History | BassUp | BassDown
+        | TrebleUp | TrebleDown | CycleChannels | CycleDownmix | TogglePauses | ToggleMono
+        | ToggleNight | ToggleBypass | ToggleDebug | ToggleDetails | ToggleStats
+        | CycleVisualizer | CycleProfile | ReloadLyrics | RefreshMetadata | ToggleTranslation
+        | RescaleLyrics | Rate(_) | ToggleFavorite | PlayFavorites | EditQueue | AddFiles
+        | Launch | CommandPrompt | JumpTo | Scrub(_) | Seek(_) | SeekBy(_) => (), /* handled in play_track() */
+        Quit => return Some(TrackEnd::Quit(player.playtime().as_secs_f64())),
+    }
+
+    None
+}
+
+/// Process a [`DisplayEvent`](DisplayEvent) related to the suggested metadata or writing tags.  
+/// Accepting a suggestion replaces the metadata of `afile`, which can then be written
+/// to the file after previewing the changes.
+///
+/// # Arguments
+/// * `current` - The playing track, and the metadata stored in its file
+fn process_prompt_event(
+    event: DisplayEvent,
+    prompt: &mut Option<Prompt>,
+    current: (&mut AudioFile, &mut AudioMeta),
+    queue: &mut Queue,
+    display: &mut Display,
+) {
+    let (afile, file_meta) = current;
+    if event == DisplayEvent::WriteTags {
+        let writer = TagWriter::new(&afile.file_name, afile.format, file_meta, &afile.metadata);
+        if writer.is_empty() {
+            display.set_status_message(tr("Nothing to write"));
+        } else {
+            display.set_tag_preview(writer.changes());
+            *prompt = Some(Prompt::WriteTags(writer));
+        }
+        return;
+    }
+
+    let Some(current) = prompt.take() else {
+        display.set_status_message(tr("Nothing to accept or dismiss"));
+        return;
+    };
+    display.clear_prompt();
+
+    match (current, event) {
+        (Prompt::Suggestion(meta), DisplayEvent::Accept) => {
+            afile.metadata = meta;
+            display.set_track_info(&afile.metadata);
+            display.set_status_message(tr("Suggestion accepted, press [W] to save it"));
+        }
+        (Prompt::Suggestion(_), _) => display.set_status_message(tr("Suggestion dismissed")),
+        (Prompt::WriteTags(writer), DisplayEvent::Accept) => match writer.apply() {
+            Ok(()) => {
+                *file_meta = afile.metadata.clone();
+                display.set_status_message(tr("Tags written"));
+            }
+            Err(e) => display.set_status_message(&e),
+        },
+        (Prompt::FixTags(fixes), DisplayEvent::Accept) => {
+            let total = fixes.len();
+            let mut written = 0;
+            let mut error = None;
+            for fix in fixes {
+                if let Err(e) = fix.writer.apply() {
+                    error = Some(e);
+                    continue;
+                }
+                written += 1;
+                if fix.file == afile.file_name {
+                    afile.metadata.fill_missing(&fix.metadata);
+                    file_meta.fill_missing(&fix.metadata);
+                    display.set_track_info(&afile.metadata);
+                }
+                queue.set_metadata(fix.file, fix.metadata);
+            }
+            let message = format!("{}: {written}/{total}", tr("Tags written"));
+            match error {
+                Some(e) => display.set_status_message(&format!("{message} ({e})")),
+                None => display.set_status_message(&message),
             }
         }
-        Quit => player.destroy(),
+        (Prompt::WriteTags(_) | Prompt::FixTags(_), _) => {
+            display.set_status_message(tr("Writing cancelled"))
+        }
+        (Prompt::Resume, _) => unreachable!("Resuming is handled in play_track()"),
+    }
+}
+
+/// Loads the lyrics of a track, from the lyrics file next to it.
+///
+/// # Arguments
+/// * `file` - Path of the track
+/// * `length` - Length of the track (in seconds)
+fn load_lyrics(file: &str, length: f64) -> Result<LyricsTimeline, LyricsError> {
+    match stream::is_stream(file) {
+        true => Err(LyricsError::Stream),
+        false => LyricsTimeline::load_file(
+            generate_lyrics_file_name(file),
+            Duration::from_secs_f64(length.max(0.0)),
+        ),
+    }
+}
+
+/// Loads the lyrics of a track, using the ones which were read ahead (if they were).
+fn take_prefetched_lyrics(
+    session: &mut Session,
+    file: &str,
+    length: f64,
+) -> Result<LyricsTimeline, LyricsError> {
+    match stream::is_stream(file) {
+        true => Err(LyricsError::Stream),
+        false => match session
+            .lyrics_prefetch
+            .take(&generate_lyrics_file_name(file))
+        {
+            Some(parsed) => parsed.and_then(|parsed| {
+                LyricsTimeline::from_parsed(parsed, Duration::from_secs_f64(length.max(0.0)))
+            }),
+            None => load_lyrics(file, length),
+        },
+    }
+}
+
+/// Reads the lyrics of the following entries of the queue ahead (see [`LyricsPrefetch`](LyricsPrefetch)).
+fn prefetch_lyrics(session: &mut Session) {
+    let files = session
+        .queue
+        .entries()
+        .iter()
+        .skip(session.queue.index() + 1)
+        .filter(|entry| !stream::is_stream(entry) && Path::new(entry).extension().is_some())
+        .take(PREFETCH_AHEAD)
+        .map(|entry| generate_lyrics_file_name(entry))
+        .collect();
+    session.lyrics_prefetch.prefetch(files);
+}
+
+/// Shows where the chorus is sung below the progress bar, and who's credited for the lyrics,
+/// if the lyrics were loaded.
+fn show_structure(display: &Display, lyrics: &Result<LyricsTimeline, LyricsError>, length: f64) {
+    display.set_lyrics_credits(lyrics.as_ref().ok().and_then(LyricsTimeline::credits));
+    let sections = lyrics
+        .as_ref()
+        .map(LyricsTimeline::sections)
+        .unwrap_or_default();
+    display.set_structure(&sections, length);
+}
+
+/// Points out when the timing of the lyrics is off: suggests stretching them to the track if they
+/// end noticeably before or after it (see [`LyricsTimeline::drift()`](LyricsTimeline::drift)),
+/// and tells that the timing of unsynced lyrics is only estimated.
+fn report_lyrics_timing(
+    session: &Session,
+    display: &mut Display,
+    lyrics: &Result<LyricsTimeline, LyricsError>,
+    length: f64,
+) {
+    let Ok(timeline) = lyrics else {
+        return;
+    };
+    if !timeline.is_synced() {
+        display.set_status_message(tr("Lyrics aren't synced, timing is estimated"));
+        return;
     }
+    let key = session.config.keymap().key(DisplayEvent::RescaleLyrics);
+    if let (Some(_), Some(key)) = (
+        timeline.drift(Duration::from_secs_f64(length.max(0.0))),
+        key,
+    ) {
+        display.set_status_message(&format!(
+            "{} ({})",
+            tr("Lyrics timing is off"),
+            keymap::key_name(key)
+        ));
+    }
+}
+
+/// Starts translating the lyrics in the background, if they're translated (and were loaded).
+fn start_translation(
+    session: &Session,
+    file: &str,
+    lyrics: &Result<LyricsTimeline, LyricsError>,
+) -> Option<Translation> {
+    let (true, Ok(lyrics)) = (session.translate, lyrics) else {
+        return None;
+    };
+
+    Some(translate::translate_in_background(
+        session.config.translator()?,
+        file.to_owned(),
+        lyrics.words(),
+        lyrics.language().map(str::to_owned),
+    ))
 }
 
 /// Generates a file name for the lyrics file.  
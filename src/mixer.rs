@@ -0,0 +1,265 @@
+use crate::player::Player;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// How long the volume read from the system mixer is used, before it's read again
+/// (it can also be changed in the desktop's mixer).
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How much the volume keys change the volume.
+const VOL_CHANGE_AMOUNT: u8 = 10;
+/// The lowest volume the volume keys go down to.
+const MIN_VOLUME: u8 = 10;
+
+/// The volume and mute state of the system mixer (`None` if they can't be read).
+type SystemState = Option<(u8, bool)>;
+
+/// Which volume the volume keys (and the `volume` command) control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MixerMode {
+    /// The player's own (software) gain
+    #[default]
+    Software,
+    /// The mixer of the operating system, so the volume matches what the desktop's mixer shows:
+    /// the player's stream in PulseAudio or PipeWire (using `pactl`),
+    /// or the output volume on macOS (using `osascript`).
+    /// The software gain is left as it is.
+    System,
+    /// Both the software gain and the system mixer, set to the same volume
+    Both,
+}
+
+/// Controls the playback volume, using the [mixers](MixerMode) chosen in the configuration.
+pub struct Mixer {
+    /// Which mixers are used
+    mode: MixerMode,
+    /// The state last read from the system mixer, and when it was read
+    system: Cell<Option<(SystemState, Instant)>>,
+}
+
+impl Mixer {
+    /// Creates a mixer.
+    pub fn new(mode: MixerMode) -> Self {
+        Self {
+            mode,
+            system: Cell::new(None),
+        }
+    }
+
+    /// Returns whether the system mixer is used.
+    fn uses_system(&self) -> bool {
+        self.mode != MixerMode::Software
+    }
+
+    /// Returns the volume and mute state of the system mixer, which are read again once
+    /// they're older than [`POLL_INTERVAL`](POLL_INTERVAL).
+    /// Returns `None` if it can't be read.
+    ///
+    /// ### Notes
+    /// Failures are remembered for as long, so a missing mixer isn't asked on every call.
+    fn system_state(&self) -> SystemState {
+        match self.system.get() {
+            Some((state, read)) if read.elapsed() < POLL_INTERVAL => state,
+            _ => {
+                let state = read_system().ok();
+                self.system.set(Some((state, Instant::now())));
+                state
+            }
+        }
+    }
+
+    /// Returns the playback volume (from 0 to 100%): the volume of the system mixer if only
+    /// it is used (and it can be read), otherwise the software gain.
+    pub fn volume(&self, player: &Player) -> u8 {
+        match self.mode {
+            MixerMode::System => self
+                .system_state()
+                .map_or_else(|| player.get_volume(), |(volume, _)| volume),
+            MixerMode::Software | MixerMode::Both => player.get_volume(),
+        }
+    }
+
+    /// Returns whether the playback is muted, in any of the used mixers.
+    pub fn is_muted(&self, player: &Player) -> bool {
+        match self.mode {
+            MixerMode::Software => player.is_muted(),
+            MixerMode::System => self.system_state().is_some_and(|(_, muted)| muted),
+            MixerMode::Both => {
+                player.is_muted() || self.system_state().is_some_and(|(_, muted)| muted)
+            }
+        }
+    }
+
+    /// Sets the playback volume (from 0 to 100%) in the used mixers.
+    ///
+    /// ## Errors
+    /// Fails if the system mixer can't be set (the software gain is still set).
+    pub fn set_volume(&self, player: &Player, volume: u8) -> Result<(), String> {
+        if self.mode != MixerMode::System {
+            player.set_volume(volume);
+        }
+        if self.uses_system() {
+            write_system_volume(volume)?;
+            self.system.set(None);
+        }
+        Ok(())
+    }
+
+    /// Turns the volume up (or down) by a step, but not below 10%.
+    /// The volume can be set to any value (e.g. with the `volume` command), so it's clamped.
+    ///
+    /// ## Errors
+    /// Fails if the system mixer can't be set.
+    pub fn step_volume(&self, player: &Player, up: bool) -> Result<(), String> {
+        let current = self.volume(player);
+        let volume = match up {
+            true => current.saturating_add(VOL_CHANGE_AMOUNT).min(100),
+            false if current <= MIN_VOLUME => return Ok(()),
+            false => current.saturating_sub(VOL_CHANGE_AMOUNT).max(MIN_VOLUME),
+        };
+        self.set_volume(player, volume)
+    }
+
+    /// Mutes the playback in the used mixers, or unmutes it if it's muted.
+    ///
+    /// ## Errors
+    /// Fails if the system mixer can't be set.
+    pub fn toggle_mute(&self, player: &Player) -> Result<(), String> {
+        let muted = self.is_muted(player);
+        if self.mode != MixerMode::System {
+            match muted {
+                true => player.unmute(),
+                false => player.mute(),
+            }
+        }
+        if self.uses_system() {
+            write_system_mute(!muted)?;
+            self.system.set(None);
+        }
+        Ok(())
+    }
+}
+
+/// Runs a command of the system mixer, and returns its output.
+/// The command runs in the C locale, since its output is parsed.
+fn run(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(args)
+        .env("LC_ALL", "C")
+        .output()
+        .map_err(|e| format!("Unable to start {program}: {e}"))?;
+    match output.status.success() {
+        true => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+        false => Err(format!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+/// Finds the player's stream (its sink input) in PulseAudio or PipeWire, by its process ID.
+/// Returns its index, volume and mute state.
+#[cfg(not(target_os = "macos"))]
+fn find_sink_input() -> Result<(u32, u8, bool), String> {
+    let output = run("pactl", &["list", "sink-inputs"])?;
+    let pid = format!("application.process.id = \"{}\"", std::process::id());
+
+    output
+        .split("Sink Input #")
+        .skip(1)
+        .find(|block| block.lines().any(|line| line.trim() == pid))
+        .and_then(|block| {
+            let index = block.lines().next()?.trim().parse().ok()?;
+            let mut volume = None;
+            let mut muted = false;
+            for line in block.lines().map(str::trim) {
+                if let Some(value) = line.strip_prefix("Volume:") {
+                    /* Like `front-left: 52428 /  80% / -5.81 dB, ...`, the first channel is used */
+                    volume = value
+                        .split('/')
+                        .nth(1)
+                        .and_then(|percent| percent.trim().trim_end_matches('%').parse().ok());
+                } else if let Some(value) = line.strip_prefix("Mute:") {
+                    muted = value.trim() == "yes";
+                }
+            }
+            Some((index, volume?, muted))
+        })
+        .ok_or_else(|| "The player's stream isn't in the system mixer".to_owned())
+}
+
+/// Reads the volume and mute state of the player's stream.
+#[cfg(not(target_os = "macos"))]
+fn read_system() -> Result<(u8, bool), String> {
+    find_sink_input().map(|(_, volume, muted)| (volume.min(100), muted))
+}
+
+/// Sets the volume of the player's stream.
+#[cfg(not(target_os = "macos"))]
+fn write_system_volume(volume: u8) -> Result<(), String> {
+    let (index, _, _) = find_sink_input()?;
+    run(
+        "pactl",
+        &[
+            "set-sink-input-volume",
+            &index.to_string(),
+            &format!("{volume}%"),
+        ],
+    )
+    .map(|_| ())
+}
+
+/// Mutes or unmutes the player's stream.
+#[cfg(not(target_os = "macos"))]
+fn write_system_mute(muted: bool) -> Result<(), String> {
+    let (index, _, _) = find_sink_input()?;
+    let muted = match muted {
+        true => "1",
+        false => "0",
+    };
+    run("pactl", &["set-sink-input-mute", &index.to_string(), muted]).map(|_| ())
+}
+
+/// Reads the output volume and mute state.
+/// CoreAudio has no volume per application, so the output volume is used.
+#[cfg(target_os = "macos")]
+fn read_system() -> Result<(u8, bool), String> {
+    let output = run(
+        "osascript",
+        &[
+            "-e",
+            "get {output volume, output muted} of (get volume settings)",
+        ],
+    )?;
+    /* Like `80, false` */
+    let (volume, muted) = output
+        .trim()
+        .split_once(", ")
+        .ok_or("Unable to read the output volume")?;
+    let volume = volume
+        .parse()
+        .map_err(|_| "Unable to read the output volume")?;
+    Ok((volume, muted == "true"))
+}
+
+/// Sets the output volume.
+#[cfg(target_os = "macos")]
+fn write_system_volume(volume: u8) -> Result<(), String> {
+    run(
+        "osascript",
+        &["-e", &format!("set volume output volume {volume}")],
+    )
+    .map(|_| ())
+}
+
+/// Mutes or unmutes the output.
+#[cfg(target_os = "macos")]
+fn write_system_mute(muted: bool) -> Result<(), String> {
+    run(
+        "osascript",
+        &["-e", &format!("set volume output muted {muted}")],
+    )
+    .map(|_| ())
+}
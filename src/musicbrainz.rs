@@ -0,0 +1,203 @@
+use crate::artists::Artists;
+use crate::audioinfo::AudioMeta;
+use serde::Deserialize;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+use std::thread::{self, sleep};
+use std::time::{Duration, Instant};
+
+/// MusicBrainz endpoint used to search for recordings.
+const SEARCH_URL: &str = "https://musicbrainz.org/ws/2/recording";
+/// MusicBrainz requires every client to identify itself.
+const USER_AGENT: &str = concat!(
+    "rustyplay/",
+    env!("CARGO_PKG_VERSION"),
+    " ( https://github.com/br0kenpixel/rustyplay )"
+);
+/// Minimum time between two requests, as MusicBrainz allows 1 request per second.
+const REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+/// Maximum time to wait for a response.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Search results with a lower score are ignored.
+const MIN_SCORE: u8 = 90;
+/// Tracks are considered the same if their lengths differ by less than this many milliseconds.
+const LENGTH_TOLERANCE_MS: u64 = 5000;
+
+/// The time of the last request, shared by every client, so lookups of several tracks
+/// (e.g. while skipping through the queue) don't exceed the rate limit together.
+static LAST_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// A rate-limited MusicBrainz client.
+pub struct MusicBrainz {
+    /// HTTP client
+    agent: ureq::Agent,
+}
+
+/// The result of a recording search.
+#[derive(Debug, Deserialize)]
+struct SearchResult {
+    recordings: Vec<Recording>,
+}
+
+/// A recording in the search result.
+#[derive(Debug, Deserialize)]
+struct Recording {
+    score: u8,
+    title: String,
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<ArtistCredit>,
+    #[serde(default)]
+    releases: Vec<Release>,
+}
+
+/// An artist of a recording.
+#[derive(Debug, Deserialize)]
+struct ArtistCredit {
+    name: String,
+    #[serde(default)]
+    joinphrase: String,
+}
+
+/// A release which contains a recording.
+#[derive(Debug, Deserialize)]
+struct Release {
+    title: String,
+}
+
+impl MusicBrainz {
+    /// Creates a new client.
+    pub fn new() -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .user_agent(USER_AGENT)
+                .timeout(REQUEST_TIMEOUT)
+                .build(),
+        }
+    }
+
+    /// Blocks until the next request can be sent without exceeding the rate limit.
+    ///
+    /// ### Notes
+    /// The lock is held while waiting, so clients in other threads wait in turn.
+    fn throttle(&self) {
+        let mut last_request = LAST_REQUEST.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(last) = *last_request {
+            let elapsed = last.elapsed();
+            if elapsed < REQUEST_INTERVAL {
+                sleep(REQUEST_INTERVAL - elapsed);
+            }
+        }
+        *last_request = Some(Instant::now());
+    }
+
+    /// Searches for a recording using the known tags of a track, and returns
+    /// the corrected metadata of the best match.
+    ///
+    /// # Arguments
+    /// * `meta` - Metadata of the track (`"Unknown"` fields are not used for searching)
+    /// * `length` - Length of the track in seconds
+    ///
+    /// ### Notes
+    /// Returns `Ok(None)` if the title is unknown or there is no good match.
+    pub fn search(&self, meta: &AudioMeta, length: f64) -> Result<Option<AudioMeta>, String> {
+        let Some(title) = known(&meta.title) else {
+            return Ok(None);
+        };
+
+        let mut query = format!("recording:{}", quote(title));
+        /* Only the main artist is searched, as the others are often credited differently */
+        let artists = known(&meta.artist).map(Artists::parse);
+        if let Some(artist) = artists.as_ref().and_then(Artists::primary) {
+            query.push_str(&format!(" AND artist:{}", quote(artist)));
+        }
+        if let Some(album) = known(&meta.album) {
+            query.push_str(&format!(" AND release:{}", quote(album)));
+        }
+        let length_ms = (length * 1000.0) as u64;
+        query.push_str(&format!(
+            " AND dur:[{} TO {}]",
+            length_ms.saturating_sub(LENGTH_TOLERANCE_MS),
+            length_ms + LENGTH_TOLERANCE_MS
+        ));
+
+        self.throttle();
+        let result: SearchResult = self
+            .agent
+            .get(SEARCH_URL)
+            .query("query", &query)
+            .query("fmt", "json")
+            .query("limit", "5")
+            .call()
+            .map_err(|e| format!("Request failed: {e}"))?
+            .into_json()
+            .map_err(|e| format!("Invalid response: {e}"))?;
+
+        let Some(recording) = result.recordings.into_iter().find(|r| r.score >= MIN_SCORE) else {
+            return Ok(None);
+        };
+
+        let artist = recording
+            .artist_credit
+            .iter()
+            .map(|credit| format!("{}{}", credit.name, credit.joinphrase))
+            .collect::<String>();
+        /* Prefer the release matching the current album tag */
+        let album = recording
+            .releases
+            .iter()
+            .find(|release| known(&meta.album) == Some(release.title.as_str()))
+            .or(recording.releases.first())
+            .map(|release| release.title.clone());
+
+        Ok(Some(AudioMeta {
+            title: recording.title,
+            album: album.unwrap_or(meta.album.clone()),
+            artist: if artist.is_empty() {
+                meta.artist.clone()
+            } else {
+                artist
+            },
+            track: meta.track,
+            track_total: meta.track_total,
+            genre: meta.genre.clone(),
+            year: meta.year,
+            album_artist: meta.album_artist.clone(),
+            compilation: meta.compilation,
+            inferred: Vec::new(),
+        }))
+    }
+}
+
+/// Looks up the metadata of a track on MusicBrainz in a background thread,
+/// so playback is not blocked.
+///
+/// The returned channel receives a single message: the corrected metadata (if it differs
+/// from `meta`), `None` if there's nothing to correct, or an error message.
+pub fn lookup_in_background(
+    meta: AudioMeta,
+    length: f64,
+) -> Receiver<Result<Option<AudioMeta>, String>> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let result = MusicBrainz::new()
+            .search(&meta, length)
+            .map(|found| found.filter(|found| *found != meta));
+        let _ = tx.send(result);
+    });
+
+    rx
+}
+
+/// Returns the value of a tag, or `None` if it's unknown.
+fn known(tag: &str) -> Option<&str> {
+    match tag {
+        "Unknown" | "" => None,
+        tag => Some(tag),
+    }
+}
+
+/// Quotes a value for use in a Lucene search query.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,435 @@
+use audiopus::ffi;
+use ogg::PacketReader;
+use rodio::Source;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::time::Duration;
+
+/// Opus always decodes at 48 kHz, regardless of the original input sample rate.
+pub const OPUS_SAMPLE_RATE: u32 = 48000;
+/// The largest possible Opus packet is 120ms long (per channel).
+const MAX_FRAME_SIZE: usize = 5760;
+/// The largest possible Ogg page is 65307 bytes long.
+const MAX_PAGE_SIZE: u64 = 65307;
+/// Size of the fixed part of an Ogg page header (before the segment table).
+const PAGE_HEADER_SIZE: usize = 27;
+/// Channel mapping family of mono and stereo streams.
+const MAPPING_RTP: u8 = 0;
+/// Channel mapping family of surround streams (up to 8 channels, in the Vorbis channel order).
+const MAPPING_VORBIS: u8 = 1;
+
+/// The identification header (`OpusHead`) of an Ogg Opus stream.
+#[derive(Debug, Clone, Copy)]
+pub struct OpusHeader {
+    /// Number of output channels
+    pub channels: u8,
+    /// Number of samples (at 48 kHz) to discard from the decoder output when starting playback
+    pub pre_skip: u16,
+    /// Gain to apply to the decoder output in Q7.8 dB
+    pub output_gain: i16,
+    /// Number of Opus streams in each packet
+    pub streams: u8,
+    /// Number of streams which are coupled (stereo)
+    pub coupled_streams: u8,
+    /// Which decoded channel each output channel is taken from (only the first
+    /// `channels` entries are used)
+    pub mapping: [u8; 8],
+}
+
+/// Information about an Ogg Opus file.
+#[derive(Debug, Clone)]
+pub struct OpusInfo {
+    /// Identification header
+    pub header: OpusHeader,
+    /// Vendor string from the `OpusTags` header, which names the encoder
+    pub vendor: String,
+    /// User comments from the `OpusTags` header as `(KEY, value)` pairs
+    pub comments: Vec<(String, String)>,
+    /// Amount of playable samples (per channel), with the pre-skip already subtracted
+    pub frames: u64,
+}
+
+/// A [`Source`](rodio::Source) which decodes an Ogg Opus stream.
+pub struct OpusDecoder<R: Read + Seek> {
+    /// Ogg packet reader
+    reader: PacketReader<R>,
+    /// `libopus` decoder
+    decoder: MultistreamDecoder,
+    /// Number of channels
+    channels: u16,
+    /// Decoded (interleaved) samples of the current packet
+    buffer: Vec<f32>,
+    /// Position of the next sample in `buffer`
+    pos: usize,
+    /// Amount of valid samples in `buffer`
+    len: usize,
+    /// Amount of samples (per channel) which still need to be discarded because of the pre-skip
+    skip: usize,
+    /// Amount of samples (per channel) which can still be played
+    remaining: u64,
+    /// Total length of the stream
+    duration: Duration,
+}
+
+/// A `libopus` multistream decoder, which decodes every [channel mapping family](OpusHeader::parse)
+/// the player supports (a mono or stereo stream is a single stream).
+struct MultistreamDecoder(*mut ffi::OpusMSDecoder);
+
+/* The decoder state is only used by one thread at a time */
+unsafe impl Send for MultistreamDecoder {}
+
+impl OpusHeader {
+    /// Parses an `OpusHead` packet.
+    ///
+    /// ## Errors
+    /// Fails if the packet isn't a valid `OpusHead`, or uses a channel mapping family other than
+    /// `0` (mono or stereo) and `1` (surround, up to 8 channels).
+    pub fn parse(packet: &[u8]) -> Result<Self, String> {
+        if packet.len() < 19 || &packet[0..8] != b"OpusHead" {
+            return Err("Missing OpusHead header".to_owned());
+        }
+        if packet[8] >> 4 != 0 {
+            return Err(format!("Unsupported Opus version {}", packet[8]));
+        }
+
+        let channels = packet[9];
+        let (streams, coupled_streams, mapping) = match (packet[18], channels) {
+            (MAPPING_RTP, 1 | 2) => (1, channels - 1, [0, 1, 0, 0, 0, 0, 0, 0]),
+            (MAPPING_RTP, _) => {
+                return Err(format!("Invalid Opus channel count: {channels}"));
+            }
+            (MAPPING_VORBIS, 1..=8) => {
+                let table = packet
+                    .get(19..21 + channels as usize)
+                    .ok_or("Malformed OpusHead header")?;
+                let (streams, coupled_streams) = (table[0], table[1]);
+                let coded = streams as u16 + coupled_streams as u16;
+                /* 255 marks a silent channel */
+                let invalid = |&channel: &u8| channel != 255 && channel as u16 >= coded;
+                if streams == 0 || coupled_streams > streams || table[2..].iter().any(invalid) {
+                    return Err("Malformed OpusHead header".to_owned());
+                }
+                let mut mapping = [0; 8];
+                mapping[..channels as usize].copy_from_slice(&table[2..]);
+                (streams, coupled_streams, mapping)
+            }
+            (MAPPING_VORBIS, _) => {
+                return Err(format!("Unsupported Opus channel count: {channels}"));
+            }
+            (family, _) => {
+                return Err(format!("Unsupported Opus channel mapping family {family}"));
+            }
+        };
+
+        Ok(Self {
+            channels,
+            pre_skip: u16::from_le_bytes([packet[10], packet[11]]),
+            output_gain: i16::from_le_bytes([packet[16], packet[17]]),
+            streams,
+            coupled_streams,
+            mapping,
+        })
+    }
+}
+
+impl MultistreamDecoder {
+    /// Creates a decoder for the streams described by the header.
+    fn new(header: &OpusHeader) -> Result<Self, String> {
+        let mut error = ffi::OPUS_OK;
+        let decoder = unsafe {
+            ffi::opus_multistream_decoder_create(
+                OPUS_SAMPLE_RATE as i32,
+                header.channels as i32,
+                header.streams as i32,
+                header.coupled_streams as i32,
+                header.mapping.as_ptr(),
+                &mut error,
+            )
+        };
+        if decoder.is_null() || error != ffi::OPUS_OK {
+            return Err(format!("Unable to create Opus decoder (error {error})"));
+        }
+        let decoder = Self(decoder);
+
+        let result = unsafe {
+            ffi::opus_multistream_decoder_ctl(
+                decoder.0,
+                ffi::OPUS_SET_GAIN_REQUEST,
+                header.output_gain as i32,
+            )
+        };
+        match result {
+            ffi::OPUS_OK => Ok(decoder),
+            e => Err(format!("Unable to set output gain (error {e})")),
+        }
+    }
+
+    /// Decodes a packet into `output` (interleaved), which has room for `frames` samples
+    /// per channel.
+    /// Returns the amount of decoded samples (per channel), or `None` if the packet is invalid.
+    fn decode(&mut self, packet: &[u8], output: &mut [f32], frames: usize) -> Option<usize> {
+        let decoded = unsafe {
+            ffi::opus_multistream_decode_float(
+                self.0,
+                packet.as_ptr(),
+                packet.len() as i32,
+                output.as_mut_ptr(),
+                frames as i32,
+                0,
+            )
+        };
+        usize::try_from(decoded).ok()
+    }
+}
+
+impl Drop for MultistreamDecoder {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_multistream_decoder_destroy(self.0) };
+    }
+}
+
+impl OpusInfo {
+    /// Reads the headers and the length of an Ogg Opus file.
+    ///
+    /// # Arguments
+    /// * `file` - A [`String`](String) containing the path to the audio file.
+    ///
+    /// ### Notes
+    /// The length is calculated from the granule position of the last Ogg page,
+    /// so the file doesn't need to be decoded.
+    pub fn probe(file: &str) -> Result<Self, String> {
+        let mut reader = PacketReader::new(BufReader::new(
+            File::open(file).map_err(|e| format!("Unable to open file: {e}"))?,
+        ));
+
+        let header = OpusHeader::parse(&read_packet(&mut reader)?.data)?;
+        let (vendor, comments) = parse_comments(&read_packet(&mut reader)?.data)?;
+        let last_granule = last_granule_position(reader.into_inner().get_mut())
+            .map_err(|e| format!("Unable to read file: {e}"))?
+            .ok_or("Unable to find the last Ogg page")?;
+
+        Ok(Self {
+            header,
+            vendor,
+            comments,
+            frames: last_granule.saturating_sub(header.pre_skip as u64),
+        })
+    }
+
+    /// Returns the length of the track in seconds.
+    pub fn length(&self) -> f64 {
+        self.frames as f64 / OPUS_SAMPLE_RATE as f64
+    }
+
+    /// Gets the value of a user comment.
+    ///
+    /// ### Notes
+    /// Comment keys are __not__ case-sensitive.
+    pub fn comment(&self, key: &str) -> Option<String> {
+        self.comments
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.clone())
+    }
+}
+
+impl<R: Read + Seek> OpusDecoder<R> {
+    /// Creates a new decoder from an Ogg Opus stream.
+    ///
+    /// # Arguments
+    /// * `data` - The Ogg Opus stream
+    /// * `frames` - Amount of playable samples (per channel), see [`OpusInfo::frames`](OpusInfo::frames)
+    pub fn new(data: R, frames: u64) -> Result<Self, String> {
+        let mut reader = PacketReader::new(data);
+        let header = OpusHeader::parse(&read_packet(&mut reader)?.data)?;
+        /* OpusTags - already handled by OpusInfo::probe() */
+        read_packet(&mut reader)?;
+
+        let decoder = MultistreamDecoder::new(&header)?;
+
+        Ok(Self {
+            reader,
+            decoder,
+            channels: header.channels as u16,
+            buffer: vec![0.0; MAX_FRAME_SIZE * header.channels as usize],
+            pos: 0,
+            len: 0,
+            skip: header.pre_skip as usize,
+            remaining: frames,
+            duration: Duration::from_secs_f64(frames as f64 / OPUS_SAMPLE_RATE as f64),
+        })
+    }
+
+    /// Decodes the next packet into the internal buffer.
+    /// Returns `false` if the end of the stream was reached.
+    fn decode_next(&mut self) -> bool {
+        let channels = self.channels as usize;
+
+        while self.remaining > 0 {
+            let packet = match self.reader.read_packet() {
+                Ok(Some(packet)) => packet,
+                _ => return false,
+            };
+            if packet.data.is_empty() {
+                continue;
+            }
+            let Some(decoded) = self
+                .decoder
+                .decode(&packet.data, &mut self.buffer, MAX_FRAME_SIZE)
+            else {
+                continue;
+            };
+
+            let skipped = decoded.min(self.skip);
+            let kept = (decoded - skipped).min(self.remaining as usize);
+            self.skip -= skipped;
+            self.remaining -= kept as u64;
+
+            if kept > 0 {
+                self.pos = skipped * channels;
+                self.len = (skipped + kept) * channels;
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+impl<R: Read + Seek> Iterator for OpusDecoder<R> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.pos >= self.len && !self.decode_next() {
+            return None;
+        }
+
+        let sample = self.buffer[self.pos];
+        self.pos += 1;
+        Some(sample)
+    }
+}
+
+impl<R: Read + Seek> Source for OpusDecoder<R> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        OPUS_SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(self.duration)
+    }
+}
+
+/// Reads the next packet from the Ogg stream, failing if there isn't one.
+fn read_packet<R: Read + Seek>(reader: &mut PacketReader<R>) -> Result<ogg::Packet, String> {
+    reader
+        .read_packet_expected()
+        .map_err(|e| format!("Malformed Ogg stream: {e}"))
+}
+
+/// Parses an `OpusTags` packet into the vendor string and `(KEY, value)` pairs.
+fn parse_comments(packet: &[u8]) -> Result<(String, Vec<(String, String)>), String> {
+    if packet.len() < 8 || &packet[0..8] != b"OpusTags" {
+        return Err("Missing OpusTags header".to_owned());
+    }
+
+    let mut pos = 8;
+    let read_u32 = |pos: &mut usize| -> Option<usize> {
+        let bytes = packet.get(*pos..*pos + 4)?;
+        *pos += 4;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    };
+
+    let malformed = || "Malformed OpusTags header".to_owned();
+    let vendor_len = read_u32(&mut pos).ok_or_else(malformed)?;
+    let vendor = packet.get(pos..pos + vendor_len).ok_or_else(malformed)?;
+    let vendor = String::from_utf8_lossy(vendor).into_owned();
+    pos += vendor_len;
+    let count = read_u32(&mut pos).ok_or_else(malformed)?;
+
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        let len = read_u32(&mut pos).ok_or_else(malformed)?;
+        let raw = packet.get(pos..pos + len).ok_or_else(malformed)?;
+        pos += len;
+
+        let comment = String::from_utf8_lossy(raw);
+        if let Some((key, value)) = comment.split_once('=') {
+            comments.push((key.to_ascii_uppercase(), value.to_owned()));
+        }
+    }
+
+    Ok((vendor, comments))
+}
+
+/// Finds the granule position of the last Ogg page in the stream.
+///
+/// ### Notes
+/// Only complete pages with a valid checksum are used, so the capture pattern (`OggS`)
+/// appearing inside audio data isn't taken for a page. Pages which don't end a packet have no
+/// granule position (`-1`), so the last one which has is used.
+fn last_granule_position<R: Read + Seek>(data: &mut R) -> std::io::Result<Option<u64>> {
+    let size = data.seek(SeekFrom::End(0))?;
+    let start = size.saturating_sub(MAX_PAGE_SIZE * 2);
+    data.seek(SeekFrom::Start(start))?;
+
+    let mut tail = Vec::new();
+    data.read_to_end(&mut tail)?;
+
+    Ok((0..tail.len())
+        .rev()
+        .filter(|&page| tail[page..].starts_with(b"OggS"))
+        .find_map(|page| page_granule_position(&tail[page..])))
+}
+
+/// Returns the granule position of the Ogg page at the start of `data`, or `None` if it isn't
+/// a complete, valid page or it has no granule position.
+fn page_granule_position(data: &[u8]) -> Option<u64> {
+    let header = data.get(..PAGE_HEADER_SIZE)?;
+    /* Only version 0 exists, and only the lowest 3 bits of the header type are used */
+    if header[4] != 0 || header[5] & !0x07 != 0 {
+        return None;
+    }
+
+    let segments = header[26] as usize;
+    let table = data.get(PAGE_HEADER_SIZE..PAGE_HEADER_SIZE + segments)?;
+    let len = PAGE_HEADER_SIZE + segments + table.iter().map(|&len| len as usize).sum::<usize>();
+    let page = data.get(..len)?;
+
+    /* The checksum is calculated with its own field set to zero */
+    let checksum = u32::from_le_bytes(page[22..26].try_into().unwrap());
+    let mut copy = page.to_vec();
+    copy[22..26].fill(0);
+    if crc32(&copy) != checksum {
+        return None;
+    }
+
+    match u64::from_le_bytes(page[6..14].try_into().unwrap()) {
+        u64::MAX => None,
+        granule => Some(granule),
+    }
+}
+
+/// Calculates the checksum of an Ogg page (CRC-32 with the polynomial `0x04C11DB7`,
+/// without reflection or a final XOR).
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0u32;
+    for &byte in data {
+        crc ^= (byte as u32) << 24;
+        for _ in 0..8 {
+            crc = match crc & 0x8000_0000 != 0 {
+                true => (crc << 1) ^ 0x04C1_1DB7,
+                false => crc << 1,
+            };
+        }
+    }
+    crc
+}
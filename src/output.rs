@@ -0,0 +1,579 @@
+use crate::dlna::Renderer;
+use crate::history;
+use crate::paths;
+use crate::snapcast::Snapcast;
+use rodio::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use rodio::cpal::{
+    self, BufferSize, Device, FromSample, Host, OutputCallbackInfo, SampleFormat, SizedSample,
+    Stream, StreamConfig, SupportedBufferSize, SupportedStreamConfig,
+};
+use rodio::source::UniformSourceIterator;
+use rodio::Source;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Length of the chunks written by [`pump()`](pump).
+const CHUNK_DURATION: Duration = Duration::from_millis(20);
+/// How far [`pump()`](pump) may get ahead of real time.
+const MAX_LEAD: Duration = Duration::from_millis(300);
+/// Delay before an audio device is opened again after it failed,
+/// doubled after every attempt which fails.
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+/// Longest delay between attempts to open a failed audio device again.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(8);
+/// Name of the log of failures and recoveries of audio devices, inside the state directory.
+const LOG_FILE_NAME: &str = "output.log";
+
+/// Where the audio is played.
+#[derive(Debug, Clone)]
+pub enum Output {
+    /// An audio device
+    Device(Backend),
+    /// A DLNA renderer on the local network
+    Dlna(Renderer),
+    /// A source of a Snapcast server, for synchronized multi-room playback
+    Snapcast(Snapcast),
+}
+
+impl Output {
+    /// Returns the amount of channels the output can play.
+    /// Audio with more channels is downmixed to stereo.
+    pub fn channels(&self) -> u16 {
+        match self {
+            Self::Device(backend) => backend.channels(),
+            /* Renderers can't be queried, and most of them only support stereo */
+            Self::Dlna(_) => 2,
+            Self::Snapcast(snapcast) => snapcast.channels(),
+        }
+    }
+
+    /// Returns the sample rate the output plays at.
+    /// Returns `None` if it plays the audio at its own sample rate.
+    pub fn sample_rate(&self) -> Option<u32> {
+        match self {
+            Self::Device(backend) => backend.config().map(|config| config.sample_rate().0),
+            Self::Dlna(_) => None,
+            Self::Snapcast(snapcast) => Some(snapcast.sample_rate()),
+        }
+    }
+
+    /// Returns the bit depth the output plays at.
+    /// Returns `None` if it takes floating point samples (or if it's unknown).
+    pub fn bits(&self) -> Option<u16> {
+        match self {
+            Self::Device(backend) => backend
+                .config()
+                .map(|config| config.sample_format())
+                .filter(|format| !format.is_float())
+                .map(|format| format.sample_size() as u16 * 8),
+            /* Both are fed by `pump()` */
+            Self::Dlna(_) | Self::Snapcast(_) => Some(16),
+        }
+    }
+
+    /// Returns the known part of the latency of the output, before it plays.
+    ///
+    /// ### Notes
+    /// Audio devices report their latency once they play (see
+    /// [`DeviceMeter::latency()`](DeviceMeter::latency)), so it's zero for them until then.
+    /// Audio written by [`pump()`](pump) is up to [`MAX_LEAD`](MAX_LEAD) ahead, and renderers
+    /// (or Snapcast clients) add their own buffering on top of it.
+    pub fn latency(&self) -> Duration {
+        match self {
+            Self::Device(_) => Duration::ZERO,
+            Self::Dlna(_) | Self::Snapcast(_) => MAX_LEAD,
+        }
+    }
+}
+
+/// An audio backend (a `cpal` host, like ALSA or CoreAudio) and one of its devices.
+/// By default, the backend and device picked by the system are used.
+#[derive(Debug, Clone, Default)]
+pub struct Backend {
+    /// Name of the backend
+    pub host: Option<String>,
+    /// Name of the output device
+    pub device: Option<String>,
+    /// Size of the output buffer (in milliseconds), `None` lets the backend choose it.
+    /// Larger buffers add latency, but the audio has more room before it drops out.
+    pub buffer_ms: Option<u64>,
+}
+
+/// A stream playing audio on an audio device.
+///
+/// It's opened using `cpal` instead of `rodio`, so the size of its buffer can be chosen.
+/// If the stream fails (e.g. when the system is suspended, or the sound server restarts),
+/// the device is opened again with increasing delays (see [`poll()`](Self::poll)),
+/// and the audio continues where it stopped.
+pub struct DeviceStream {
+    /// The stream, `None` while the device is failed
+    stream: Option<Stream>,
+    /// The backend and device the stream is opened on
+    backend: Backend,
+    /// The played audio, shared with the stream
+    source: SharedSource,
+    /// The buffer of the stream
+    meter: DeviceMeter,
+    /// The format the device was opened with (`None` while it's failed)
+    format: Option<OutputFormat>,
+    /// The first error of the stream (if it failed), set by `cpal`
+    error: Arc<Mutex<Option<String>>>,
+    /// While the device is failed, when it's opened again and the delay before that
+    retry: Option<(Instant, Duration)>,
+    /// Attempts to open the device again since it failed
+    attempts: u32,
+}
+
+/// The format an output actually plays at, after it was opened.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputFormat {
+    /// Sample rate (in Hz)
+    pub sample_rate: u32,
+    /// Amount of channels
+    pub channels: u16,
+    /// Format of the samples (e.g. `i16` or `f32`)
+    pub sample_format: SampleFormat,
+}
+
+/// What happened to a [`DeviceStream`](DeviceStream), see [`DeviceStream::poll()`](DeviceStream::poll).
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    /// The stream failed with an error, the device is opened again
+    Failed(String),
+    /// The device was opened again (after this many attempts), and plays again
+    Recovered(u32),
+}
+
+/// Audio played by a [`DeviceStream`](DeviceStream).
+type BoxedSource = Box<dyn Source<Item = f32> + Send>;
+
+/// Audio shared by the streams of a [`DeviceStream`](DeviceStream),
+/// so a stream opened again continues where the failed one stopped.
+///
+/// ### Notes
+/// The stream which plays the audio takes it out (see [`StreamSource`](StreamSource)),
+/// so the audio callback doesn't lock it for every sample. It's only handed back
+/// when the stream is closed.
+#[derive(Clone)]
+struct SharedSource(Arc<Mutex<Option<BoxedSource>>>);
+
+/// The audio of a [`SharedSource`](SharedSource) while a stream plays it,
+/// which is handed back once the stream is closed.
+struct StreamSource {
+    /// The audio (only `None` while it's handed back)
+    source: Option<BoxedSource>,
+    /// Where the audio is handed back to
+    shared: SharedSource,
+}
+
+/// Shows the buffer of a [`DeviceStream`](DeviceStream) while it plays.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceMeter {
+    /// Sample rate of the stream
+    sample_rate: Arc<AtomicU32>,
+    /// Amount of frames the device asked for in its last callback
+    frames: Arc<AtomicU32>,
+    /// Time from the last callback until its audio is heard (in microseconds),
+    /// `0` if the backend doesn't report it
+    latency: Arc<AtomicU64>,
+}
+
+impl Backend {
+    /// Finds the output device.
+    ///
+    /// ## Errors
+    /// Fails if the backend isn't available, or if it has no such device.
+    pub fn device(&self) -> Result<Device, String> {
+        let host = match &self.host {
+            Some(name) => find_host(name)?,
+            None => cpal::default_host(),
+        };
+
+        match &self.device {
+            Some(name) => host
+                .output_devices()
+                .map_err(|e| format!("Unable to list the devices of {}: {e}", host.id().name()))?
+                .find(|device| device.name().is_ok_and(|device| device == *name))
+                .ok_or_else(|| format!("Audio device \"{name}\" not found")),
+            None => host
+                .default_output_device()
+                .ok_or_else(|| format!("{} has no output device", host.id().name())),
+        }
+    }
+
+    /// Opens the output device, and plays `source` on it.
+    ///
+    /// ### Notes
+    /// If the device can't be found or opened (e.g. while it's unplugged), the stream starts
+    /// out failed: [`poll()`](DeviceStream::poll) reports why, and opens it again like
+    /// a stream which failed while playing.
+    pub fn open(&self, source: impl Source<Item = f32> + Send + 'static) -> DeviceStream {
+        let mut stream = DeviceStream {
+            stream: None,
+            backend: self.clone(),
+            source: SharedSource(Arc::new(Mutex::new(Some(Box::new(source))))),
+            meter: DeviceMeter::default(),
+            format: None,
+            error: Arc::default(),
+            retry: None,
+            attempts: 0,
+        };
+        if let Err(e) = stream.start() {
+            stream.error = Arc::new(Mutex::new(Some(e)));
+        }
+        stream
+    }
+
+    /// Returns the configuration to open the output device with, and its sample format.
+    /// The [buffer size](Self::buffer_ms) is kept within what the device supports.
+    ///
+    /// ## Errors
+    /// Fails if the device can't be queried.
+    fn stream_config(&self, device: &Device) -> Result<(StreamConfig, SampleFormat), String> {
+        let supported = device
+            .default_output_config()
+            .map_err(|e| format!("Unable to open audio device: {e}"))?;
+        let mut config = supported.config();
+        if let Some(ms) = self.buffer_ms {
+            let frames = (config.sample_rate.0 as u64 * ms / 1000).max(1) as u32;
+            config.buffer_size = BufferSize::Fixed(match supported.buffer_size() {
+                SupportedBufferSize::Range { min, max } => frames.clamp(*min, *max),
+                SupportedBufferSize::Unknown => frames,
+            });
+        }
+
+        Ok((config, supported.sample_format()))
+    }
+
+    /// Returns the amount of channels used by the output device.
+    /// If the device can't be queried, stereo is assumed.
+    pub fn channels(&self) -> u16 {
+        self.config().map_or(2, |config| config.channels())
+    }
+
+    /// Returns the configuration the output device is opened with (by `rodio`).
+    /// Returns `None` if the device can't be queried.
+    fn config(&self) -> Option<SupportedStreamConfig> {
+        self.device().ok()?.default_output_config().ok()
+    }
+}
+
+impl DeviceStream {
+    /// Opens the device and starts a stream on it, which continues the audio.
+    /// The audio is converted to the sample rate, channels and sample format of the stream.
+    ///
+    /// ## Errors
+    /// Fails if the stream can't be started, if the device uses an unusual sample format,
+    /// or if the closed stream didn't hand back the audio yet.
+    fn start(&mut self) -> Result<(), String> {
+        /* The failed stream is closed first (which hands back the audio),
+        as some backends only allow one stream */
+        self.stream = None;
+        self.format = None;
+        let device = self.backend.device()?;
+        let (config, format) = self.backend.stream_config(&device)?;
+        let source = self
+            .source
+            .take()
+            .ok_or("The audio is still held by the closed stream")?;
+        self.meter
+            .sample_rate
+            .store(config.sample_rate.0, Ordering::Relaxed);
+        let source =
+            UniformSourceIterator::<_, f32>::new(source, config.channels, config.sample_rate.0);
+
+        let (meter, error) = (&self.meter, &self.error);
+        let stream = match format {
+            SampleFormat::F32 => build::<f32>(&device, &config, source, meter, error),
+            SampleFormat::F64 => build::<f64>(&device, &config, source, meter, error),
+            SampleFormat::I16 => build::<i16>(&device, &config, source, meter, error),
+            SampleFormat::I32 => build::<i32>(&device, &config, source, meter, error),
+            SampleFormat::U16 => build::<u16>(&device, &config, source, meter, error),
+            SampleFormat::U8 => build::<u8>(&device, &config, source, meter, error),
+            format => return Err(format!("Unsupported sample format {format}")),
+        }
+        .map_err(|e| format!("Unable to open audio device: {e}"))?;
+        stream
+            .play()
+            .map_err(|e| format!("Unable to start audio device: {e}"))?;
+
+        self.stream = Some(stream);
+        self.format = Some(OutputFormat {
+            sample_rate: config.sample_rate.0,
+            channels: config.channels,
+            sample_format: format,
+        });
+        Ok(())
+    }
+
+    /// Checks whether the stream failed, and opens the device again once it's time to.
+    /// Failures, recoveries and failed attempts are written to the log
+    /// (see [`LOG_FILE_NAME`](LOG_FILE_NAME)).
+    pub fn poll(&mut self) -> Option<StreamEvent> {
+        let error = self.error.lock().ok().and_then(|mut error| error.take());
+        match (error, self.retry) {
+            (Some(error), None) => {
+                self.stream = None;
+                self.retry = Some((Instant::now() + RETRY_DELAY, RETRY_DELAY));
+                self.attempts = 0;
+                log(&format!("Audio device failed: {error}"));
+                Some(StreamEvent::Failed(error))
+            }
+            (_, Some((at, delay))) if Instant::now() >= at => {
+                self.attempts += 1;
+                match self.start() {
+                    Ok(()) => {
+                        self.retry = None;
+                        log(&format!(
+                            "Audio device recovered after {} attempt(s)",
+                            self.attempts
+                        ));
+                        Some(StreamEvent::Recovered(self.attempts))
+                    }
+                    Err(e) => {
+                        let delay = (delay * 2).min(MAX_RETRY_DELAY);
+                        self.retry = Some((Instant::now() + delay, delay));
+                        log(&format!("Attempt {} failed: {e}", self.attempts));
+                        None
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns whether the stream failed, and the device wasn't opened again yet.
+    pub fn is_failed(&self) -> bool {
+        self.stream.is_none()
+    }
+
+    /// Returns a meter showing the buffer of the stream.
+    pub fn meter(&self) -> &DeviceMeter {
+        &self.meter
+    }
+
+    /// Returns the format the device plays at, which may change when it's opened again.
+    /// Returns `None` while the device is failed.
+    pub fn format(&self) -> Option<OutputFormat> {
+        self.format
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} Hz, {} ch, {}",
+            self.sample_rate, self.channels, self.sample_format
+        )
+    }
+}
+
+impl SharedSource {
+    /// Takes the audio out, for a stream to play it.
+    /// Returns `None` if another stream still holds it.
+    fn take(&self) -> Option<StreamSource> {
+        let source = self.0.lock().ok()?.take()?;
+        Some(StreamSource {
+            source: Some(source),
+            shared: self.clone(),
+        })
+    }
+}
+
+impl Iterator for StreamSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.source.as_mut()?.next()
+    }
+}
+
+impl Source for StreamSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.as_ref()?.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.as_ref().map_or(2, |source| source.channels())
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source
+            .as_ref()
+            .map_or(44100, |source| source.sample_rate())
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.as_ref()?.total_duration()
+    }
+}
+
+impl Drop for StreamSource {
+    fn drop(&mut self) {
+        /* The callback of the stream (and with it the audio) is dropped when it's closed */
+        if let (Some(source), Ok(mut shared)) = (self.source.take(), self.shared.0.lock()) {
+            *shared = Some(source);
+        }
+    }
+}
+
+impl DeviceMeter {
+    /// Returns how many frames the device asks for at once (`0` until it first asked).
+    pub fn buffer_frames(&self) -> u32 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// Returns the size of the buffer the device asks for at once.
+    pub fn buffer(&self) -> Duration {
+        let sample_rate = self.sample_rate.load(Ordering::Relaxed).max(1);
+        Duration::from_secs_f64(self.buffer_frames() as f64 / sample_rate as f64)
+    }
+
+    /// Returns how long it takes for the audio to be heard after it's taken by the device,
+    /// as reported by the backend. If the backend doesn't report it, the [buffer](Self::buffer)
+    /// is used instead.
+    pub fn latency(&self) -> Duration {
+        match self.latency.load(Ordering::Relaxed) {
+            0 => self.buffer(),
+            micros => Duration::from_micros(micros),
+        }
+    }
+}
+
+/// Builds an output stream of a sample format, which plays the audio of `source`
+/// (already converted to the sample rate and channels of the stream).
+fn build<T: SizedSample + FromSample<f32>>(
+    device: &Device,
+    config: &StreamConfig,
+    mut source: impl Iterator<Item = f32> + Send + 'static,
+    meter: &DeviceMeter,
+    error: &Arc<Mutex<Option<String>>>,
+) -> Result<Stream, cpal::BuildStreamError> {
+    let channels = config.channels.max(1) as usize;
+    let (frames, latency) = (Arc::clone(&meter.frames), Arc::clone(&meter.latency));
+    let error = Arc::clone(error);
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], info: &OutputCallbackInfo| {
+            /* Silence is played while the sink has nothing to play */
+            data.iter_mut()
+                .for_each(|sample| *sample = T::from_sample(source.next().unwrap_or(0.0)));
+
+            frames.store((data.len() / channels) as u32, Ordering::Relaxed);
+            let timestamp = info.timestamp();
+            if let Some(delay) = timestamp.playback.duration_since(&timestamp.callback) {
+                latency.store(delay.as_micros() as u64, Ordering::Relaxed);
+            }
+        },
+        /* Errors are handled in `DeviceStream::poll()` (printing them would garble the UI) */
+        move |e| {
+            if let Ok(mut error) = error.lock() {
+                error.get_or_insert_with(|| e.to_string());
+            }
+        },
+        None,
+    )
+}
+
+/// Returns the names of the available backends, and the names of their output devices.
+pub fn backends() -> Vec<(String, Vec<String>)> {
+    cpal::available_hosts()
+        .into_iter()
+        .filter_map(|id| {
+            let host = cpal::host_from_id(id).ok()?;
+            let devices = host
+                .output_devices()
+                .map(|devices| devices.filter_map(|device| device.name().ok()).collect())
+                .unwrap_or_default();
+            Some((id.name().to_owned(), devices))
+        })
+        .collect()
+}
+
+/// Writes audio to `writer` as signed 16-bit little-endian PCM, at the speed it's played.
+///
+/// # Arguments
+/// * `source` - The audio, its samples are only taken when they're written
+/// * `channels` - Amount of channels of the audio
+/// * `sample_rate` - Sample rate of the audio
+/// * `writer` - Where to write the audio (e.g. a network connection)
+/// * `stop` - Stops writing when it's set
+///
+/// ## Errors
+/// Fails if writing fails, for example when the connection is closed.
+pub fn pump(
+    source: &mut impl Iterator<Item = f32>,
+    channels: u16,
+    sample_rate: u32,
+    mut writer: impl Write,
+    stop: &AtomicBool,
+) -> io::Result<()> {
+    let chunk_samples =
+        (sample_rate as f64 * CHUNK_DURATION.as_secs_f64()) as usize * channels as usize;
+    let samples_per_second = sample_rate as f64 * channels as f64;
+
+    let started = Instant::now();
+    let mut written: u64 = 0;
+    let mut chunk = Vec::with_capacity(chunk_samples * 2);
+
+    while !stop.load(Ordering::Relaxed) {
+        chunk.clear();
+        for sample in source.by_ref().take(chunk_samples) {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+            chunk.extend_from_slice(&sample.to_le_bytes());
+        }
+        if chunk.is_empty() {
+            break;
+        }
+
+        writer.write_all(&chunk)?;
+        written += (chunk.len() / 2) as u64;
+
+        /* Don't get too far ahead, so pausing and skipping take effect quickly */
+        let position = Duration::from_secs_f64(written as f64 / samples_per_second);
+        if let Some(lead) = position.checked_sub(started.elapsed() + MAX_LEAD) {
+            sleep(lead);
+        }
+    }
+
+    writer.flush()
+}
+
+/// Appends a line to the log of audio devices (in the state directory), after the time.
+/// The log is only kept for troubleshooting, so it's not an error if it can't be written.
+fn log(message: &str) {
+    let Some(dir) = paths::state_dir() else {
+        return;
+    };
+    let _ = fs::create_dir_all(&dir);
+    let Ok(mut file) = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(LOG_FILE_NAME))
+    else {
+        return;
+    };
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let _ = writeln!(file, "{} {message}", history::format_timestamp(now));
+}
+
+/// Finds an available backend by its name (ignoring case).
+fn find_host(name: &str) -> Result<Host, String> {
+    let id = cpal::available_hosts()
+        .into_iter()
+        .find(|id| id.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("Audio backend \"{name}\" is not available"))?;
+
+    cpal::host_from_id(id).map_err(|e| format!("Unable to use {}: {e}", id.name()))
+}
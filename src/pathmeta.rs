@@ -0,0 +1,74 @@
+use crate::audioinfo::AudioMeta;
+use std::path::Path;
+
+/// Separator between the parts of a file name, like in `01 - Artist - Title`.
+const SEPARATOR: &str = " - ";
+
+/// Infers metadata from the path of a file, for files without tags.
+/// Fields which can't be inferred are `"Unknown"`.
+///
+/// These file names are recognized:
+/// - `01 - Artist - Title`
+/// - `Artist - Title`
+/// - `01 - Title`, `01. Title`, `01 Title` and `01_Title`
+/// - `Title` (any other name)
+///
+/// If the file name starts with a track number but has no artist, the file is taken to be
+/// in an `Artist/Album` directory, like `Artist/Album/01 Title.ogg`.
+///
+/// # Arguments
+/// * `path` - Path to the file
+/// * `folders` - Whether every file is taken to be in an `Artist/Album` directory,
+///   so the album (and the artist, unless it's in the file name) are always taken from it
+pub fn infer(path: &str, folders: bool) -> AudioMeta {
+    let path = Path::new(path);
+    let mut meta = AudioMeta::unknown();
+    let Some(stem) = path.file_stem().map(|stem| stem.to_string_lossy()) else {
+        return meta;
+    };
+    let stem = stem.replace('_', " ");
+
+    let (track, rest) = split_track_number(&stem);
+    let parts: Vec<&str> = rest.splitn(2, SEPARATOR).map(str::trim).collect();
+    match parts.as_slice() {
+        [artist, title] if !artist.is_empty() && !title.is_empty() => {
+            meta.artist = artist.to_string();
+            meta.title = title.to_string();
+        }
+        _ if !rest.trim().is_empty() => meta.title = rest.trim().to_owned(),
+        _ => meta.title = stem.trim().to_owned(),
+    }
+    meta.track = track;
+
+    let numbered = track.is_some() && meta.artist == "Unknown";
+    if folders || numbered {
+        let mut parents = path.ancestors().skip(1).filter_map(Path::file_name);
+        if let (Some(album), Some(artist)) = (parents.next(), parents.next()) {
+            meta.album = album.to_string_lossy().into_owned();
+            if meta.artist == "Unknown" {
+                meta.artist = artist.to_string_lossy().into_owned();
+            }
+        }
+    }
+
+    meta
+}
+
+/// Splits the track number (1 to 3 digits) off the start of a file name,
+/// along with the separator after it (`" - "`, `". "` or `" "`).
+/// Returns `None` and the whole name if it doesn't start with a track number.
+fn split_track_number(name: &str) -> (Option<u32>, &str) {
+    let digits = name.chars().take_while(char::is_ascii_digit).count();
+    if !(1..=3).contains(&digits) {
+        return (None, name);
+    }
+
+    let (number, rest) = name.split_at(digits);
+    let rest = [SEPARATOR, ". ", ".", " "]
+        .iter()
+        .find_map(|separator| rest.strip_prefix(separator));
+    match rest {
+        Some(rest) if !rest.trim().is_empty() => (number.parse().ok(), rest),
+        _ => (None, name),
+    }
+}
@@ -0,0 +1,112 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Name of the directory of the player, inside each of the base directories.
+const APP_DIR_NAME: &str = "rustyplay";
+
+/// A kind of files, each kept in its own base directory
+/// (see the [XDG base directory specification](https://specifications.freedesktop.org/basedir-spec/latest/)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BaseDir {
+    /// Settings written by the user (`XDG_CONFIG_HOME`)
+    Config,
+    /// Data which should be kept, like the library and the listening history (`XDG_DATA_HOME`)
+    Data,
+    /// State which is kept between runs, like the saved queue (`XDG_STATE_HOME`)
+    State,
+    /// Files which can be created again, like downloads (`XDG_CACHE_HOME`)
+    Cache,
+}
+
+impl BaseDir {
+    /// Returns the environment variable which overrides the directory.
+    fn variable(&self) -> &'static str {
+        match self {
+            Self::Config => "XDG_CONFIG_HOME",
+            Self::Data => "XDG_DATA_HOME",
+            Self::State => "XDG_STATE_HOME",
+            Self::Cache => "XDG_CACHE_HOME",
+        }
+    }
+
+    /// Returns the default directory of the XDG specification, in the home directory.
+    fn home_default(&self) -> Option<PathBuf> {
+        let home = PathBuf::from(env::var_os("HOME")?);
+        Some(match self {
+            Self::Config => home.join(".config"),
+            Self::Data => home.join(".local").join("share"),
+            Self::State => home.join(".local").join("state"),
+            Self::Cache => home.join(".cache"),
+        })
+    }
+
+    /// Returns the usual directory of the platform.
+    #[cfg(target_os = "macos")]
+    fn platform_default(&self) -> Option<PathBuf> {
+        /* Earlier versions used the XDG directories on macOS too, so they're kept if they exist */
+        if let Some(dir) = self.home_default() {
+            if dir.join(APP_DIR_NAME).exists() {
+                return Some(dir);
+            }
+        }
+
+        let library = PathBuf::from(env::var_os("HOME")?).join("Library");
+        Some(match self {
+            Self::Cache => library.join("Caches"),
+            Self::Config | Self::Data | Self::State => library.join("Application Support"),
+        })
+    }
+
+    /// Returns the usual directory of the platform.
+    #[cfg(windows)]
+    fn platform_default(&self) -> Option<PathBuf> {
+        let variable = match self {
+            Self::Config => "APPDATA",
+            Self::Data | Self::State | Self::Cache => "LOCALAPPDATA",
+        };
+        env::var_os(variable).map(PathBuf::from)
+    }
+
+    /// Returns the usual directory of the platform.
+    #[cfg(not(any(target_os = "macos", windows)))]
+    fn platform_default(&self) -> Option<PathBuf> {
+        self.home_default()
+    }
+
+    /// Returns the directory of the player inside the base directory.
+    /// The environment variable takes precedence (if it's an absolute path, as the specification
+    /// requires), then the usual directory of the platform is used.
+    fn app_dir(&self) -> Option<PathBuf> {
+        env::var_os(self.variable())
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| self.platform_default())
+            .map(|dir| dir.join(APP_DIR_NAME))
+    }
+}
+
+/// Returns the directory of the configuration file.
+/// Returns `None` if the home directory is unknown.
+pub fn config_dir() -> Option<PathBuf> {
+    BaseDir::Config.app_dir()
+}
+
+/// Returns the directory of the data which should be kept (like the music library).
+/// Returns `None` if the home directory is unknown.
+pub fn data_dir() -> Option<PathBuf> {
+    BaseDir::Data.app_dir()
+}
+
+/// Returns the directory of the state kept between runs (like the saved queue).
+/// Returns `None` if the home directory is unknown.
+pub fn state_dir() -> Option<PathBuf> {
+    BaseDir::State.app_dir()
+}
+
+/// Returns the directory of files which can be created again (like downloads).
+/// Falls back to the temporary directory if the home directory is unknown.
+pub fn cache_dir() -> PathBuf {
+    BaseDir::Cache
+        .app_dir()
+        .unwrap_or_else(|| env::temp_dir().join(APP_DIR_NAME))
+}
@@ -1,17 +1,30 @@
+use crate::ape::ApeInfo;
+use crate::audioinfo::{AudioFile, AudioFormat, ChannelLayout};
+use crate::dlna::RendererStream;
+use crate::dsp::{Downmix, Effects, GainMeter, Meters, Peak};
+use crate::ffmpeg::FfmpegDecoder;
+use crate::opus::{OpusDecoder, OpusInfo};
+use crate::output::{DeviceMeter, DeviceStream, Output, OutputFormat, StreamEvent};
+use crate::prefetch::{Prefetch, PrefetchMeter};
+use crate::recorder::{self, RecordingHandle};
+use crate::snapcast::SnapcastStream;
+use crate::stream::{self, BufferHealth, BufferedStream};
+use crate::tap::Tap;
+use crate::wavpack::WavPackInfo;
 use pausable_clock::PausableClock;
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+use rodio::cpal::SampleFormat;
+use rodio::{Decoder, Sink, Source};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::BufReader;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
-const VOL_CHANGE_AMOUNT: u8 = 10;
-
 /// This structure represents an audio player.
 pub struct Player {
-    /// *Unused but needs to be kept in memory.*
-    _stream: OutputStream,
-    /// *Unused but needs to be kept in memory.*
-    _stream_handle: OutputStreamHandle,
+    /// Where the audio is played.
+    connection: Connection,
     /// A "controller" kind of object.  
     /// It allows, for example, to pause the audio and resume it.
     sink: Sink,
@@ -22,49 +35,379 @@ pub struct Player {
     /// *This is used to calculate the playtime*  
     /// When the audio is paused, the clock is paused too.
     clock: PausableClock,
+    /// Whether the audio is downmixed to stereo.
+    downmixed: bool,
+    /// The position where playback started.
+    offset: Duration,
+    /// How long it takes for the audio to be heard, subtracted from the playtime.
+    latency: Duration,
+    /// Format of the audio
+    format: AudioFormat,
+    /// Sample rate of the decoded audio
+    sample_rate: u32,
+    /// Amount of channels of the decoded audio
+    channels: u16,
+    /// Health of the buffer, if the audio is streamed
+    buffer: Option<BufferHealth>,
+    /// What the effects do to the audio
+    meters: Meters,
+    /// The decoded audio kept in memory (if it's decoded ahead)
+    prefetch: Option<PrefetchMeter>,
+    /// How the audio is converted for the output (if it is)
+    conversion: Option<String>,
+    /// The recording of the played audio (if it's recorded)
+    recording: Option<RecordingHandle>,
+}
+
+/// The connection to the output of a [`Player`](Player).
+enum Connection {
+    /// The audio device
+    Device(DeviceStream),
+    /// A DLNA renderer, which is also told when playback is paused or resumed,
+    /// and the format of the audio it's sent
+    Renderer(RendererStream, OutputFormat),
+    /// A Snapcast server
+    Snapcast {
+        /// *Unused but needs to be kept in memory.*
+        _stream: SnapcastStream,
+        /// The format the server expects
+        format: OutputFormat,
+    },
+}
+
+/// A decoder which can be used for a format, instead of the one which is normally used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecoderKind {
+    /// The decoder which is normally used for the format
+    /// (`rodio` for FLAC, WAV and Ogg Vorbis, the [`OpusDecoder`](OpusDecoder) for Opus,
+    /// and `ffmpeg` for WavPack and Monkey's Audio)
+    #[default]
+    Default,
+    /// `ffmpeg`, see [`FfmpegDecoder`](FfmpegDecoder)
+    Ffmpeg,
+}
+
+/// Which decoder to use for files with an extension, and its options.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DecoderOverride {
+    /// The decoder to use
+    pub decoder: DecoderKind,
+    /// Extra options of `ffmpeg` (e.g. `["-drc_scale", "0"]`), used whenever it decodes these files
+    pub ffmpeg_args: Vec<String>,
+}
+
+/// When audio with more than two channels is mixed into stereo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownmixMode {
+    /// When the output has fewer channels than the audio
+    #[default]
+    Auto,
+    /// Always, even if the output could play all channels (e.g. for headphones)
+    Always,
+    /// Never, the output drops the extra channels
+    Never,
+}
+
+impl DownmixMode {
+    /// Returns the mode which follows this one, to switch between them with a key.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Auto => Self::Always,
+            Self::Always => Self::Never,
+            Self::Never => Self::Auto,
+        }
+    }
+
+    /// Returns the name of the mode, like in the configuration file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Always => "always",
+            Self::Never => "never",
+        }
+    }
+}
+
+/// How files are decoded.
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions {
+    /// How much audio is decoded ahead of playback (see [`Prefetch`](Prefetch)),
+    /// if it's zero the audio is decoded while it's played
+    pub buffer: Duration,
+    /// Decoders to use instead of the usual ones, by file extension (in lowercase)
+    pub decoders: BTreeMap<String, DecoderOverride>,
+    /// When audio with more than two channels is mixed into stereo
+    pub downmix: DownmixMode,
+}
+
+impl DecodeOptions {
+    /// Returns the decoder to use for a file, and the extra options of `ffmpeg`.
+    fn decoder(&self, file: &str) -> (DecoderKind, &[String]) {
+        let extension = Path::new(file)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match self.decoders.get(&extension) {
+            Some(decoder) => (decoder.decoder, &decoder.ffmpeg_args),
+            None => (DecoderKind::Default, &[]),
+        }
+    }
+}
+
+/// Decoded audio, ready to be played (or recorded).
+pub struct DecodedAudio {
+    /// The audio, downmixed to stereo if needed
+    pub source: Box<dyn Source<Item = f32> + Send>,
+    /// Format of the audio
+    pub format: AudioFormat,
+    /// Whether the audio is downmixed to stereo
+    pub downmixed: bool,
+    /// Health of the buffer, if the audio is streamed
+    pub buffer: Option<BufferHealth>,
+    /// What the effects do to the audio
+    pub meters: Meters,
+    /// The decoded audio kept in memory (if it's decoded ahead)
+    pub prefetch: Option<PrefetchMeter>,
 }
 
 impl Player {
-    /// Creates a new player from a given file.  
+    /// Creates a new player from a given file, HTTP(S) URL or [`STDIN`](stream::STDIN),
+    /// which plays on `output` with `effects` applied, decoded with the given `options`
+    /// (see [`decode()`](decode)).  
+    /// *The playback is paused by default.*
+    ///
+    /// ## Errors
+    /// Fails if the file can't be opened or decoded, or if the DLNA renderer
+    /// or the Snapcast server can't be reached.
+    pub fn new(
+        file: &str,
+        output: &Output,
+        effects: &Effects,
+        options: &DecodeOptions,
+    ) -> Result<Player, String> {
+        Self::new_at(file, Duration::ZERO, output, effects, options)
+    }
+
+    /// Creates a new player from a given file, starting at `offset`.  
+    /// *The playback is paused by default.*
+    ///
+    /// ### Notes
+    /// The audio before `offset` is still decoded (and thrown away), so this takes
+    /// longer for larger offsets.  
+    /// URLs and the standard input are streamed, see [`BufferedStream`](BufferedStream).
+    ///
+    /// ## Errors
+    /// Fails if the file can't be opened or decoded, or if the DLNA renderer
+    /// or the Snapcast server can't be reached.
+    pub fn new_at(
+        file: &str,
+        offset: Duration,
+        output: &Output,
+        effects: &Effects,
+        options: &DecodeOptions,
+    ) -> Result<Player, String> {
+        let decoded = decode(file, offset, output.channels(), effects, options)?;
+        Self::from_decoded(file, decoded, offset, None, output, effects)
+    }
+
+    /// Creates a new player from a given file, which records the played audio
+    /// into `recording` (see [`recorder::record()`](recorder::record)).  
     /// *The playback is paused by default.*
-    pub fn new(file: &str) -> Player {
-        let (_stream, _stream_handle) =
-            OutputStream::try_default().expect("Unable to open audio device");
+    ///
+    /// ## Errors
+    /// Fails if the file can't be opened or decoded, if the output file can't be created,
+    /// or if the DLNA renderer or the Snapcast server can't be reached.
+    pub fn new_recording(
+        file: &str,
+        recording: &Path,
+        output: &Output,
+        effects: &Effects,
+        options: &DecodeOptions,
+    ) -> Result<Player, String> {
+        let mut decoded = decode(file, Duration::ZERO, output.channels(), effects, options)?;
+        let (source, handle) = recorder::record(decoded.source, recording)?;
+        decoded.source = Box::new(source);
+
+        Self::from_decoded(file, decoded, Duration::ZERO, Some(handle), output, effects)
+    }
 
-        let sink = Sink::try_new(&_stream_handle).expect("Unable to create Sink");
+    /// Creates a new player playing decoded audio, which is converted for the output
+    /// (see [`Effects::convert()`](Effects::convert)).
+    ///
+    /// ## Errors
+    /// Fails if the DLNA renderer can't be told to play the audio,
+    /// or if the Snapcast server can't be reached.
+    fn from_decoded(
+        file: &str,
+        decoded: DecodedAudio,
+        offset: Duration,
+        recording: Option<RecordingHandle>,
+        output: &Output,
+        effects: &Effects,
+    ) -> Result<Player, String> {
+        let sample_rate = decoded.source.sample_rate();
+        let channels = decoded.source.channels();
+        let (source, conversion) =
+            effects.convert(decoded.source, output.sample_rate(), output.bits());
+        let source = Peak::new(source, effects.levels.clone());
+        let source = Tap::new(source, effects.taps.clone());
 
-        let file = BufReader::new(File::open(file).expect("Unable to open file"));
+        let (sink, connection) = match output {
+            Output::Device(backend) => {
+                /* The mixed audio is taken from the sink and played on the device */
+                let (sink, mixed) = Sink::new_idle();
+                let stream = backend.open(mixed);
+                (sink, Connection::Device(stream))
+            }
+            Output::Dlna(renderer) => {
+                /* The mixed audio is taken from the sink and sent to the renderer */
+                let (sink, mixed) = Sink::new_idle();
+                let title = Path::new(file)
+                    .file_stem()
+                    .map_or(file.into(), |name| name.to_string_lossy());
+                let stream = RendererStream::start(renderer, &title, mixed, channels, sample_rate)?;
+                /* The audio is sent as 16-bit WAV, in the format it was decoded in */
+                let format = OutputFormat {
+                    sample_rate,
+                    channels,
+                    sample_format: SampleFormat::I16,
+                };
+                (sink, Connection::Renderer(stream, format))
+            }
+            Output::Snapcast(snapcast) => {
+                let (sink, mixed) = Sink::new_idle();
+                let stream = SnapcastStream::start(snapcast, mixed)?;
+                let format = OutputFormat {
+                    sample_rate: snapcast.sample_rate(),
+                    channels: snapcast.channels(),
+                    sample_format: SampleFormat::I16,
+                };
+                (
+                    sink,
+                    Connection::Snapcast {
+                        _stream: stream,
+                        format,
+                    },
+                )
+            }
+        };
 
-        let source = Decoder::new(file).expect("Unable to create decoder");
-        /* type: Decoder<BufReader<File>> */
+        sink.append(source);
 
         let start_time = Instant::now();
         let clock = PausableClock::default();
 
         // Start playing
-        sink.append(source);
         sink.pause();
         clock.pause();
 
-        Player {
-            _stream,
-            _stream_handle,
+        Ok(Player {
+            connection,
             sink,
             start_time,
             clock,
+            downmixed: decoded.downmixed,
+            offset,
+            latency: Duration::ZERO,
+            format: decoded.format,
+            sample_rate,
+            channels,
+            buffer: decoded.buffer,
+            meters: decoded.meters,
+            prefetch: decoded.prefetch,
+            conversion,
+            recording,
+        })
+    }
+
+    /// Creates a decoder for a local file.
+    /// The decoder can be chosen per file extension, see [`DecodeOptions`](DecodeOptions).
+    ///
+    /// ## Errors
+    /// Fails if the file can't be opened or decoded.
+    pub fn decode_file(
+        file: &str,
+        format: AudioFormat,
+        options: &DecodeOptions,
+    ) -> Result<Box<dyn Source<Item = f32> + Send>, String> {
+        let (decoder, ffmpeg_args) = options.decoder(file);
+        if decoder == DecoderKind::Ffmpeg {
+            let info = AudioFile::open(file)?;
+            return Ok(Box::new(FfmpegDecoder::new(
+                file,
+                info.layout.channels(),
+                info.sample_rate as u32,
+                Duration::from_secs_f64(info.length),
+                ffmpeg_args,
+            )?));
         }
+
+        let reader =
+            BufReader::new(File::open(file).map_err(|e| format!("Unable to open {file}: {e}"))?);
+        Ok(match format {
+            AudioFormat::OPUS => {
+                /* rodio can't decode Opus, so a custom decoder is used */
+                let frames = OpusInfo::probe(file)
+                    .map_err(|e| format!("Unable to read Opus file: {e}"))?
+                    .frames;
+                Box::new(OpusDecoder::new(reader, frames)?)
+            }
+            AudioFormat::WAVPACK => {
+                let info = WavPackInfo::probe(file)?;
+                let duration = Duration::from_secs_f64(info.length());
+                Box::new(FfmpegDecoder::new(
+                    file,
+                    info.channels,
+                    info.sample_rate,
+                    duration,
+                    ffmpeg_args,
+                )?)
+            }
+            AudioFormat::APE => {
+                let info = ApeInfo::probe(file)?;
+                let duration = Duration::from_secs_f64(info.length());
+                Box::new(FfmpegDecoder::new(
+                    file,
+                    info.channels,
+                    info.sample_rate,
+                    duration,
+                    ffmpeg_args,
+                )?)
+            }
+            _ => {
+                let source =
+                    Decoder::new(reader).map_err(|e| format!("Unable to decode {file}: {e}"))?;
+                /* type: Decoder<BufReader<File>> */
+                Box::new(source.convert_samples())
+            }
+        })
     }
 
     /// Pauses the audio playback.
     pub fn pause(&self) {
         self.sink.pause();
         self.clock.pause();
+        if let Connection::Renderer(stream, _) = &self.connection {
+            /* If the renderer misses it, it plays silence instead */
+            let _ = stream.pause();
+        }
     }
 
     /// Resumes the audio playback.
+    /// If the audio device failed, the playtime only continues once it plays again.
     pub fn play(&self) {
         self.sink.play();
-        self.clock.resume();
+        if !self.is_output_failed() {
+            self.clock.resume();
+        }
+        if let Connection::Renderer(stream, _) = &self.connection {
+            /* The stream is already served, the renderer only needs to be reminded */
+            let _ = stream.play();
+        }
     }
 
     /// Mutes the audio playback.
@@ -87,6 +430,98 @@ impl Player {
         self.sink.is_paused()
     }
 
+    /// Returns whether the audio is downmixed to stereo, because the
+    /// output has less channels than the audio file.
+    pub fn is_downmixed(&self) -> bool {
+        self.downmixed
+    }
+
+    /// Returns the format of the audio.
+    pub fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    /// Returns the sample rate of the decoded audio.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns the amount of channels of the decoded audio.
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Returns how the audio is converted for the output (e.g. resampled), if it is.
+    pub fn conversion(&self) -> Option<&str> {
+        self.conversion.as_deref()
+    }
+
+    /// Returns how full the buffer is (from `0.0` to `1.0`) if the audio is streamed.
+    /// Returns `None` for local files.
+    pub fn buffer_health(&self) -> Option<f64> {
+        self.buffer.as_ref().map(BufferHealth::level)
+    }
+
+    /// Returns the gain applied to the audio (see [`GainMeter`](GainMeter)).
+    /// Returns `None` if the gain isn't changed.
+    pub fn gain(&self) -> Option<&GainMeter> {
+        self.meters.gain.as_ref()
+    }
+
+    /// Returns how much decoded audio is kept in memory (see [`PrefetchMeter`](PrefetchMeter)).
+    /// Returns `None` if the audio isn't decoded ahead.
+    pub fn decode_buffer(&self) -> Option<&PrefetchMeter> {
+        self.prefetch.as_ref()
+    }
+
+    /// Checks whether the audio device failed or plays again (see [`DeviceStream::poll()`](DeviceStream::poll)).
+    /// The playtime stops while the device is failed, so it still matches the played audio
+    /// once the device plays again.
+    pub fn poll_output(&mut self) -> Option<StreamEvent> {
+        let Connection::Device(stream) = &mut self.connection else {
+            return None;
+        };
+
+        let event = stream.poll()?;
+        match event {
+            StreamEvent::Failed(_) => {
+                self.clock.pause();
+            }
+            StreamEvent::Recovered(_) if !self.sink.is_paused() => {
+                self.clock.resume();
+            }
+            StreamEvent::Recovered(_) => (),
+        }
+        Some(event)
+    }
+
+    /// Returns whether the audio device failed, and wasn't opened again yet.
+    pub fn is_output_failed(&self) -> bool {
+        match &self.connection {
+            Connection::Device(stream) => stream.is_failed(),
+            Connection::Renderer(..) | Connection::Snapcast { .. } => false,
+        }
+    }
+
+    /// Returns the buffer of the audio device (see [`DeviceMeter`](DeviceMeter)).
+    /// Returns `None` if the audio isn't played on an audio device.
+    pub fn device_buffer(&self) -> Option<&DeviceMeter> {
+        match &self.connection {
+            Connection::Device(stream) => Some(stream.meter()),
+            Connection::Renderer(..) | Connection::Snapcast { .. } => None,
+        }
+    }
+
+    /// Returns the format the output plays at (after any conversion by the output itself),
+    /// to be compared with the format of the file.
+    /// Returns `None` while the audio device is failed.
+    pub fn output_format(&self) -> Option<OutputFormat> {
+        match &self.connection {
+            Connection::Device(stream) => stream.format(),
+            Connection::Renderer(_, format) | Connection::Snapcast { format, .. } => Some(*format),
+        }
+    }
+
     /// Returns whether the audio playback is finished or not.
     pub fn is_finished(&self) -> bool {
         self.sink.empty()
@@ -97,35 +532,126 @@ impl Player {
         self.sink.stop();
     }
 
-    /// Returns the current playtime.
-    pub fn playtime(&self) -> Duration {
-        Instant::from(self.clock.now()) - self.start_time
+    /// Returns whether the played audio is recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
     }
 
-    pub fn inc_volume(&self) {
-        let current = self.get_volume();
-        if current == 100 {
-            return;
+    /// Waits until the recording (if there is one) is written.  
+    /// The player should be [destroyed](Self::destroy) (or finished) first.
+    pub fn finish_recording(&mut self) -> Result<(), String> {
+        match self.recording.take() {
+            Some(recording) => recording.finish(),
+            None => Ok(()),
         }
+    }
 
-        self.set_volume(current + VOL_CHANGE_AMOUNT);
+    /// Returns how long the audio was played, not including the offset.
+    pub fn listened(&self) -> Duration {
+        Instant::from(self.clock.now()) - self.start_time
     }
 
-    pub fn dec_volume(&self) {
-        let current = self.get_volume();
-        if current == 10 {
-            return;
-        }
+    /// Returns the current playtime, which is the position being heard.  
+    /// If the player started at an offset, it's included, and so is the skipped silence.
+    pub fn playtime(&self) -> Duration {
+        self.listened().saturating_sub(self.latency) + self.offset + self.skipped()
+    }
+
+    /// Returns how much silence was skipped (see [`SkipSilence`](crate::dsp::SkipSilence)).
+    pub fn skipped(&self) -> Duration {
+        self.meters.silence.skipped()
+    }
+
+    /// Sets the latency of the output, so the [playtime](Self::playtime) matches what's heard.
+    pub fn set_latency(&mut self, latency: Duration) {
+        self.latency = latency;
+    }
 
-        self.set_volume(current - VOL_CHANGE_AMOUNT);
+    /// Returns the latency of the output, see [`set_latency()`](Self::set_latency).
+    pub fn latency(&self) -> Duration {
+        self.latency
     }
 
     pub fn get_volume(&self) -> u8 {
-        (self.sink.volume() * 100.0) as u8
+        (self.sink.volume() * 100.0).round() as u8
     }
 
-    fn set_volume(&self, val: u8) {
+    pub fn set_volume(&self, val: u8) {
         let float = val as f32 / 100.0;
         self.sink.set_volume(float);
     }
 }
+
+/// Decodes a file (or stream), starting at `offset`, and applies the `effects`.  
+/// Audio with more channels than the output (`max_channels`) is downmixed to stereo,
+/// unless [`options.downmix`](DecodeOptions::downmix) says otherwise.  
+/// Up to [`options.buffer`](DecodeOptions::buffer) of audio is decoded ahead in the background
+/// (see [`Prefetch`](Prefetch)), if it's zero the audio is decoded while it's played.
+///
+/// ## Errors
+/// Fails if the file (or stream) can't be opened or decoded.
+pub fn decode(
+    file: &str,
+    offset: Duration,
+    max_channels: u16,
+    effects: &Effects,
+    options: &DecodeOptions,
+) -> Result<DecodedAudio, String> {
+    let (format, source, buffer) = match stream::is_stream(file) {
+        true => {
+            let stream = BufferedStream::open(file)?;
+            let format = stream.format();
+            let buffer = stream.health();
+            let source =
+                Decoder::new(stream).map_err(|e| format!("Unable to decode {file}: {e}"))?;
+            let source: Box<dyn Source<Item = f32> + Send> = Box::new(source.convert_samples());
+            (format, source, Some(buffer))
+        }
+        false => {
+            let format =
+                AudioFormat::from_path(file).map_err(|_| format!("Unsupported format: {file}"))?;
+            (format, Player::decode_file(file, format, options)?, None)
+        }
+    };
+
+    let source: Box<dyn Source<Item = f32> + Send> = match offset.is_zero() {
+        true => source,
+        false => Box::new(source.skip_duration(offset)),
+    };
+
+    /* The effects can be changed while playing, so they're applied after the buffer */
+    let (source, prefetch): (Box<dyn Source<Item = f32> + Send>, _) = match options.buffer.is_zero()
+    {
+        true => (source, None),
+        false => {
+            let prefetch = Prefetch::start(source, options.buffer);
+            let meter = prefetch.meter();
+            (Box::new(prefetch), Some(meter))
+        }
+    };
+
+    /* rodio would simply drop the extra channels, so they're mixed into stereo instead */
+    let channels = source.channels();
+    let speakers = ChannelLayout::from_channels(channels).speakers(format);
+    let downmix = match options.downmix {
+        DownmixMode::Auto => channels > max_channels,
+        DownmixMode::Always => true,
+        DownmixMode::Never => false,
+    };
+    let (source, downmixed): (Box<dyn Source<Item = f32> + Send>, bool) = match speakers {
+        Some(speakers) if channels > 2 && downmix => {
+            (Box::new(Downmix::new(source, &speakers)), true)
+        }
+        _ => (source, false),
+    };
+    let (source, meters) = effects.apply(source);
+
+    Ok(DecodedAudio {
+        source,
+        format,
+        downmixed,
+        buffer,
+        meters,
+        prefetch,
+    })
+}
@@ -0,0 +1,286 @@
+use crate::audioinfo::AudioMeta;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// File extensions of the supported playlist formats.
+const PLAYLIST_EXTENSIONS: [&str; 4] = ["m3u", "m3u8", "pls", "xspf"];
+
+/// An entry of a playlist.
+#[derive(Debug, Clone)]
+pub struct PlaylistEntry {
+    /// Path to the audio file, or a URL if the entry is not a local file
+    pub location: String,
+    /// Metadata stored in the playlist (`"Unknown"` if not available)
+    pub metadata: AudioMeta,
+}
+
+impl PlaylistEntry {
+    /// Creates an entry without metadata.
+    pub fn new(location: String) -> Self {
+        Self {
+            location,
+            metadata: AudioMeta::unknown(),
+        }
+    }
+
+    /// Returns whether the entry is a local file (and not a URL).
+    pub fn is_local(&self) -> bool {
+        !self.location.contains("://")
+    }
+}
+
+/// Returns whether the file is a playlist (based on its extension).
+pub fn is_playlist(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| PLAYLIST_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Loads an M3U, PLS or XSPF playlist.
+///
+/// Relative paths are resolved relative to the directory of the playlist,
+/// and `file://` URLs are converted into paths.
+///
+/// ### Notes
+/// The metadata supported by each format is read: `#EXTINF` lines of M3U playlists
+/// (`Artist - Title`), `Title` keys of PLS playlists and the `title`, `creator`, `album`
+/// and `trackNum` elements of XSPF playlists.
+pub fn load(path: &Path) -> Result<Vec<PlaylistEntry>, String> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {e}", path.display()))?;
+    let text = text.trim_start_matches('\u{feff}');
+
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    let mut entries = match extension.as_deref() {
+        Some("m3u" | "m3u8") => parse_m3u(text),
+        Some("pls") => parse_pls(text),
+        Some("xspf") => parse_xspf(text)
+            .map_err(|e| format!("Invalid XSPF playlist {}: {e}", path.display()))?,
+        _ => return Err(format!("Unsupported playlist {}", path.display())),
+    };
+
+    let base = path.parent().unwrap_or(Path::new(""));
+    for entry in &mut entries {
+        entry.location = resolve(base, &entry.location);
+    }
+
+    Ok(entries)
+}
+
+/// Parses an M3U (or extended M3U) playlist.
+fn parse_m3u(text: &str) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut info: Option<AudioMeta> = None;
+
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            /* #EXTINF:<length>,<artist> - <title> */
+            let Some((_, name)) = extinf.split_once(',') else {
+                continue;
+            };
+            let mut meta = AudioMeta::unknown();
+            match name.split_once(" - ") {
+                Some((artist, title)) => {
+                    meta.artist = artist.trim().to_owned();
+                    meta.title = title.trim().to_owned();
+                }
+                None => meta.title = name.trim().to_owned(),
+            }
+            info = Some(meta);
+        } else if !line.starts_with('#') {
+            entries.push(PlaylistEntry {
+                location: line.to_owned(),
+                metadata: info.take().unwrap_or_else(AudioMeta::unknown),
+            });
+        }
+    }
+
+    entries
+}
+
+/// Writes an M3U playlist.
+/// Entries with metadata get an `#EXTINF` line (`Artist - Title`), with an unknown length.
+pub fn to_m3u(entries: &[PlaylistEntry]) -> String {
+    let mut text = String::from("#EXTM3U\n");
+
+    for entry in entries {
+        if entry.metadata != AudioMeta::unknown() {
+            text.push_str(&format!(
+                "#EXTINF:-1,{} - {}\n",
+                entry.metadata.artist, entry.metadata.title
+            ));
+        }
+        text.push_str(&entry.location);
+        text.push('\n');
+    }
+
+    text
+}
+
+/// Parses a PLS playlist.
+fn parse_pls(text: &str) -> Vec<PlaylistEntry> {
+    let mut entries: BTreeMap<u32, PlaylistEntry> = BTreeMap::new();
+
+    for line in text.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+
+        /* Keys are numbered: File1, Title1, Length1, File2, ... */
+        let (name, number) = key.split_at(key.trim_end_matches(|c: char| c.is_ascii_digit()).len());
+        let Ok(number) = number.parse::<u32>() else {
+            continue;
+        };
+
+        let entry = entries
+            .entry(number)
+            .or_insert_with(|| PlaylistEntry::new(String::new()));
+        match name {
+            "file" => entry.location = value.to_owned(),
+            "title" => entry.metadata.title = value.to_owned(),
+            _ => (),
+        }
+    }
+
+    entries
+        .into_values()
+        .filter(|entry| !entry.location.is_empty())
+        .collect()
+}
+
+/// Parses an XSPF playlist.
+fn parse_xspf(text: &str) -> Result<Vec<PlaylistEntry>, String> {
+    let mut reader = Reader::from_str(text);
+    reader.trim_text(true);
+
+    let mut entries = Vec::new();
+    let mut track: Option<PlaylistEntry> = None;
+    let mut element = String::new();
+
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(start) => {
+                element = String::from_utf8_lossy(start.local_name().as_ref()).into_owned();
+                if element == "track" {
+                    track = Some(PlaylistEntry::new(String::new()));
+                }
+            }
+            Event::End(end) => {
+                if end.local_name().as_ref() == b"track" {
+                    entries.extend(track.take().filter(|entry| !entry.location.is_empty()));
+                }
+                element.clear();
+            }
+            Event::Text(content) => {
+                let Some(entry) = track.as_mut() else {
+                    continue;
+                };
+                let value = content.unescape().map_err(|e| e.to_string())?.into_owned();
+
+                match element.as_str() {
+                    /* A track can have several locations, the first one is used */
+                    "location" if entry.location.is_empty() => entry.location = value,
+                    "title" => entry.metadata.title = value,
+                    "creator" => entry.metadata.artist = value,
+                    "album" => entry.metadata.album = value,
+                    "trackNum" => entry.metadata.track = value.parse().ok(),
+                    _ => (),
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Resolves the location of an entry.
+/// `file://` URLs are converted into paths, and relative paths are joined to `base`.
+fn resolve(base: &Path, location: &str) -> String {
+    let location = match location.strip_prefix("file://") {
+        Some(path) => percent_decode(path),
+        None if location.contains("://") => return location.to_owned(),
+        None => location.to_owned(),
+    };
+
+    base.join(location).to_string_lossy().into_owned()
+}
+
+/// Decodes the `%XX` escapes of a URL.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_decode_decodes_escapes() {
+        assert_eq!(
+            percent_decode("My%20Music/%C3%84rzte.flac"),
+            "My Music/Ärzte.flac"
+        );
+        assert_eq!(percent_decode("100%25"), "100%");
+    }
+
+    #[test]
+    fn percent_decode_keeps_invalid_escapes() {
+        assert_eq!(percent_decode("50%"), "50%");
+        assert_eq!(percent_decode("%zz%2"), "%zz%2");
+        assert_eq!(percent_decode("%FF"), "\u{fffd}");
+    }
+
+    #[test]
+    fn resolve_joins_relative_paths() {
+        let base = Path::new("/music/playlists");
+        assert_eq!(resolve(base, "../a b.flac"), "/music/playlists/../a b.flac");
+        assert_eq!(resolve(base, "/music/c.flac"), "/music/c.flac");
+    }
+
+    #[test]
+    fn resolve_converts_file_urls() {
+        let base = Path::new("/music");
+        assert_eq!(resolve(base, "file:///music/a%20b.flac"), "/music/a b.flac");
+    }
+
+    #[test]
+    fn resolve_keeps_other_urls() {
+        let base = Path::new("/music");
+        assert_eq!(
+            resolve(base, "https://radio.example/stream%20.mp3"),
+            "https://radio.example/stream%20.mp3"
+        );
+    }
+}
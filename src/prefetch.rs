@@ -0,0 +1,149 @@
+use rodio::Source;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Amount of samples decoded at once.
+const CHUNK_SIZE: usize = 4096;
+/// How much audio is decoded ahead, unless configured otherwise.
+pub const DEFAULT_BUFFER: Duration = Duration::from_millis(500);
+
+/// A [`Source`](Source) which decodes its input ahead of playback, in a background thread.
+///
+/// The audio is decoded in chunks of [`CHUNK_SIZE`](CHUNK_SIZE) samples, and decoding waits
+/// while the buffer is full, so only a bounded amount of decoded audio is kept in memory
+/// (no matter how long the file is), and slow decoding doesn't interrupt playback.
+///
+/// ### Notes
+/// The amount of channels and the sample rate are taken from the input when decoding starts,
+/// they're expected not to change.
+pub struct Prefetch {
+    /// Receives the decoded chunks
+    chunks: Receiver<Vec<f32>>,
+    /// The chunk being played
+    chunk: Vec<f32>,
+    /// Index of the next sample of `chunk` to return
+    position: usize,
+    /// Amount of channels of the input
+    channels: u16,
+    /// Sample rate of the input
+    sample_rate: u32,
+    /// Length of the input (if it's known)
+    total_duration: Option<Duration>,
+    /// Amount of samples waiting in the buffer, see [`PrefetchMeter`](PrefetchMeter)
+    buffered: Arc<AtomicUsize>,
+    /// Maximum amount of samples in the buffer
+    capacity: usize,
+}
+
+/// Shows how much decoded audio a [`Prefetch`](Prefetch) keeps in memory.
+#[derive(Debug, Clone)]
+pub struct PrefetchMeter {
+    /// Amount of samples waiting in the buffer
+    buffered: Arc<AtomicUsize>,
+    /// Maximum amount of samples in the buffer
+    capacity: usize,
+}
+
+impl Prefetch {
+    /// Starts decoding a source in the background.
+    ///
+    /// # Arguments
+    /// * `input` - The source to decode
+    /// * `buffer` - How much audio to decode ahead (at least one chunk is)
+    pub fn start(input: Box<dyn Source<Item = f32> + Send>, buffer: Duration) -> Self {
+        let (channels, sample_rate) = (input.channels(), input.sample_rate());
+        let total_duration = input.total_duration();
+
+        let samples = buffer.as_secs_f64() * sample_rate as f64 * channels as f64;
+        let chunks = (samples as usize).div_ceil(CHUNK_SIZE).max(1);
+        /* One more chunk waits until it can be sent */
+        let (sender, receiver) = sync_channel(chunks - 1);
+        let buffered = Arc::new(AtomicUsize::new(0));
+
+        let decoder_buffered = Arc::clone(&buffered);
+        thread::spawn(move || {
+            let mut input = input;
+            loop {
+                let chunk: Vec<f32> = input.by_ref().take(CHUNK_SIZE).collect();
+                if chunk.is_empty() {
+                    break;
+                }
+
+                decoder_buffered.fetch_add(chunk.len(), Ordering::Relaxed);
+                /* The source was dropped, there's no one left to decode for */
+                if sender.send(chunk).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            chunks: receiver,
+            chunk: Vec::new(),
+            position: 0,
+            channels,
+            sample_rate,
+            total_duration,
+            buffered,
+            capacity: chunks * CHUNK_SIZE,
+        }
+    }
+
+    /// Returns a meter showing how much decoded audio is kept in memory.
+    pub fn meter(&self) -> PrefetchMeter {
+        PrefetchMeter {
+            buffered: Arc::clone(&self.buffered),
+            capacity: self.capacity,
+        }
+    }
+}
+
+impl Iterator for Prefetch {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position >= self.chunk.len() {
+            /* Waits if decoding fell behind */
+            self.chunk = self.chunks.recv().ok()?;
+            self.position = 0;
+            self.buffered.fetch_sub(self.chunk.len(), Ordering::Relaxed);
+        }
+
+        self.position += 1;
+        Some(self.chunk[self.position - 1])
+    }
+}
+
+impl Source for Prefetch {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+}
+
+impl PrefetchMeter {
+    /// Returns the size of the decoded audio waiting in the buffer (in bytes).
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffered.load(Ordering::Relaxed) * mem::size_of::<f32>()
+    }
+
+    /// Returns the maximum size of the buffer (in bytes).
+    pub fn capacity_bytes(&self) -> usize {
+        self.capacity * mem::size_of::<f32>()
+    }
+}
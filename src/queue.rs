@@ -0,0 +1,431 @@
+use crate::audioinfo::AudioMeta;
+use crate::library::Library;
+use crate::sort::SortMode;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of queue states which can be restored by undoing.
+const UNDO_LIMIT: usize = 20;
+
+/// Represents the play queue.
+///
+/// The queue is a list of audio files and the position of the one currently playing.
+/// It's never empty.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Queue {
+    /// Paths to the audio files
+    entries: Vec<String>,
+    /// Index of the current entry
+    index: usize,
+    /// Metadata of entries known from other sources (like playlists), by path
+    #[serde(default)]
+    metadata: HashMap<String, AudioMeta>,
+}
+
+/// Previous states of the queue, saved before destructive changes (like removing entries),
+/// so that they can be undone.
+#[derive(Debug, Default)]
+pub struct UndoStack {
+    /// The saved states, oldest first
+    states: VecDeque<Queue>,
+}
+
+/// What to play after the current track finishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepeatMode {
+    /// Play the next entry, and stop after the last one
+    #[default]
+    Off,
+    /// Play the current entry again
+    One,
+    /// Play the next entry, and start over after the last one
+    All,
+}
+
+/// The queue, shown in the `Lyrics` subwindow, where its entries can be rearranged.
+pub struct QueueView {
+    /// Text shown for each entry, by path
+    labels: HashMap<String, String>,
+    /// Index of the selected entry
+    selected: usize,
+    /// Indexes of the marked entries, which are changed as a group
+    marked: BTreeSet<usize>,
+}
+
+impl Queue {
+    /// Creates a new queue, starting at the first entry.
+    ///
+    /// ## Panics
+    /// Panics if `entries` is empty.
+    pub fn new(entries: Vec<String>) -> Self {
+        assert!(!entries.is_empty(), "The queue can't be empty");
+
+        Self {
+            entries,
+            index: 0,
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// Returns the path to the current entry.
+    pub fn current(&self) -> &str {
+        &self.entries[self.index]
+    }
+
+    /// Returns the index of the current entry.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns all entries.
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Moves to the next entry.
+    /// Returns `false` if the current entry is the last one.
+    pub fn next(&mut self) -> bool {
+        if self.index + 1 >= self.entries.len() {
+            return false;
+        }
+
+        self.index += 1;
+        true
+    }
+
+    /// Moves to the previous entry.
+    /// Returns `false` if the current entry is the first one.
+    pub fn previous(&mut self) -> bool {
+        if self.index == 0 {
+            return false;
+        }
+
+        self.index -= 1;
+        true
+    }
+
+    /// Moves to the entry at `index`.
+    ///
+    /// ## Panics
+    /// Panics if `index` is out of bounds.
+    pub fn jump(&mut self, index: usize) {
+        assert!(index < self.entries.len(), "Queue index out of bounds");
+
+        self.index = index;
+    }
+
+    /// Replaces the entries with the same entries in a different order.
+    /// The current entry stays current.
+    pub fn reorder(&mut self, entries: Vec<String>) {
+        debug_assert_eq!(entries.len(), self.entries.len());
+
+        let current = self.current().to_owned();
+        self.index = entries
+            .iter()
+            .position(|path| *path == current)
+            .unwrap_or(0);
+        self.entries = entries;
+    }
+
+    /// Returns the metadata of an entry known from other sources (like playlists).
+    /// The tags of the file should be preferred.
+    pub fn metadata(&self, path: &str) -> Option<&AudioMeta> {
+        self.metadata.get(path)
+    }
+
+    /// Sets the metadata of an entry known from other sources (like playlists).
+    pub fn set_metadata(&mut self, path: String, metadata: AudioMeta) {
+        self.metadata.insert(path, metadata);
+    }
+
+    /// Appends an entry to the end of the queue.
+    pub fn push(&mut self, path: String) {
+        self.entries.push(path);
+    }
+
+    /// Inserts entries right after the current entry, so that they're played next.
+    pub fn insert_next(&mut self, paths: Vec<String>) {
+        self.entries.splice(self.index + 1..self.index + 1, paths);
+    }
+
+    /// Moves the entries at `indexes` up by one, as a group.
+    /// Returns `false` if one of them is the first entry.
+    pub fn move_up(&mut self, indexes: &[usize]) -> bool {
+        let indexes = self.valid_indexes(indexes);
+        if indexes.is_empty() || indexes[0] == 0 {
+            return false;
+        }
+
+        for index in indexes {
+            self.swap(index - 1, index);
+        }
+        true
+    }
+
+    /// Moves the entries at `indexes` down by one, as a group.
+    /// Returns `false` if one of them is the last entry.
+    pub fn move_down(&mut self, indexes: &[usize]) -> bool {
+        let indexes = self.valid_indexes(indexes);
+        if indexes
+            .last()
+            .is_none_or(|last| last + 1 >= self.entries.len())
+        {
+            return false;
+        }
+
+        for index in indexes.into_iter().rev() {
+            self.swap(index, index + 1);
+        }
+        true
+    }
+
+    /// Swaps two entries. The current entry stays current.
+    fn swap(&mut self, a: usize, b: usize) {
+        self.entries.swap(a, b);
+        if self.index == a {
+            self.index = b;
+        } else if self.index == b {
+            self.index = a;
+        }
+    }
+
+    /// Removes the entries at `indexes`.
+    /// The current entry is skipped, as it can't be removed while it's playing.
+    ///
+    /// Returns the number of removed entries.
+    pub fn remove(&mut self, indexes: &[usize]) -> usize {
+        self.take(indexes).len()
+    }
+
+    /// Moves the entries at `indexes` right after the current entry (keeping their order),
+    /// so that they're played next. The current entry is skipped.
+    ///
+    /// Returns the number of moved entries.
+    pub fn play_next(&mut self, indexes: &[usize]) -> usize {
+        let taken = self.take(indexes);
+        let count = taken.len();
+        self.entries.splice(self.index + 1..self.index + 1, taken);
+        count
+    }
+
+    /// Removes the entries at `indexes` (except the current entry), and returns them in order.
+    fn take(&mut self, indexes: &[usize]) -> Vec<String> {
+        let mut taken = Vec::new();
+        for index in self.valid_indexes(indexes).into_iter().rev() {
+            if index == self.index {
+                continue;
+            }
+
+            taken.push(self.entries.remove(index));
+            if index < self.index {
+                self.index -= 1;
+            }
+        }
+
+        taken.reverse();
+        taken
+    }
+
+    /// Returns the indexes which are in bounds, sorted and without duplicates.
+    fn valid_indexes(&self, indexes: &[usize]) -> Vec<usize> {
+        let mut valid: Vec<usize> = indexes
+            .iter()
+            .copied()
+            .filter(|index| *index < self.entries.len())
+            .collect();
+        valid.sort_unstable();
+        valid.dedup();
+        valid
+    }
+
+    /// Removes every entry except the current one.
+    pub fn clear(&mut self) {
+        let current = self.entries.swap_remove(self.index);
+        self.entries = vec![current];
+        self.index = 0;
+    }
+
+    /// Shuffles the entries after the current one.
+    pub fn shuffle(&mut self) {
+        /* xorshift, seeded with the time (the order doesn't have to be unpredictable) */
+        let mut random = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64)
+            | 1;
+        let upcoming = &mut self.entries[self.index + 1..];
+        for i in (1..upcoming.len()).rev() {
+            random ^= random << 13;
+            random ^= random >> 7;
+            random ^= random << 17;
+            upcoming.swap(i, (random % (i as u64 + 1)) as usize);
+        }
+    }
+
+    /// Replaces the entries with a previous state of the queue (see [`UndoStack`](UndoStack)).
+    ///
+    /// The current entry stays current, so playback isn't interrupted. If it isn't in the previous
+    /// state, it's inserted where the previous current entry was.
+    pub fn restore(&mut self, previous: Queue) {
+        let current = self.current().to_owned();
+        self.entries = previous.entries;
+        self.index = previous.index.min(self.entries.len());
+
+        match self.entries.iter().position(|path| *path == current) {
+            Some(index) => self.index = index,
+            None => self.entries.insert(self.index, current),
+        }
+    }
+}
+
+impl QueueView {
+    /// Creates a new view of the queue, with the current entry selected.
+    ///
+    /// ### Notes
+    /// Like in the search, the tags of the entries are taken from the library
+    /// (or the playlist they came from), other entries are shown by their file name.
+    pub fn new(queue: &Queue, library: Option<&Library>) -> Self {
+        let mut labels: HashMap<String, String> = queue
+            .entries()
+            .iter()
+            .map(|path| {
+                let label = match queue.metadata(path) {
+                    Some(meta) => label(path, meta),
+                    None => label(path, &AudioMeta::unknown()),
+                };
+                (path.clone(), label)
+            })
+            .collect();
+
+        let tracks = library
+            .and_then(|library| library.tracks(SortMode::default()).ok())
+            .unwrap_or_default();
+        for track in tracks {
+            if let Some(known) = labels.get_mut(&track.path) {
+                *known = label(&track.path, &track.metadata);
+            }
+        }
+
+        Self {
+            labels,
+            selected: queue.index(),
+            marked: BTreeSet::new(),
+        }
+    }
+
+    /// Returns the text shown for an entry.
+    pub fn label<'a>(&'a self, path: &'a str) -> &'a str {
+        self.labels.get(path).map_or(path, String::as_str)
+    }
+
+    /// Returns the index of the selected entry.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Selects an entry, limited to the entries of the queue.
+    pub fn select(&mut self, index: usize, queue: &Queue) {
+        self.selected = index.min(queue.entries().len() - 1);
+    }
+
+    /// Returns whether an entry is marked.
+    pub fn is_marked(&self, index: usize) -> bool {
+        self.marked.contains(&index)
+    }
+
+    /// Returns the number of marked entries.
+    pub fn marked_count(&self) -> usize {
+        self.marked.len()
+    }
+
+    /// Marks the selected entry, or unmarks it if it's marked.
+    pub fn toggle_mark(&mut self) {
+        if !self.marked.remove(&self.selected) {
+            self.marked.insert(self.selected);
+        }
+    }
+
+    /// Marks the selected entry, then selects and marks another one
+    /// (like selecting with `Shift` and the arrow keys in other programs).
+    pub fn mark_to(&mut self, index: usize, queue: &Queue) {
+        self.marked.insert(self.selected);
+        self.select(index, queue);
+        self.marked.insert(self.selected);
+    }
+
+    /// Unmarks every entry.
+    pub fn clear_marks(&mut self) {
+        self.marked.clear();
+    }
+
+    /// Returns the entries to change: the marked entries, or the selected entry if none is marked.
+    pub fn group(&self) -> Vec<usize> {
+        match self.marked.is_empty() {
+            true => vec![self.selected],
+            false => self.marked.iter().copied().collect(),
+        }
+    }
+
+    /// Moves the selection and the marks along with the entries, after they were moved up or down.
+    pub fn follow_move(&mut self, up: bool, queue: &Queue) {
+        let moved = |index: usize| match up {
+            true => index.saturating_sub(1),
+            false => index + 1,
+        };
+        if self.marked.is_empty() || self.marked.contains(&self.selected) {
+            self.select(moved(self.selected), queue);
+        }
+        self.marked = self.marked.iter().map(|index| moved(*index)).collect();
+    }
+}
+
+impl UndoStack {
+    /// Saves a state of the queue, before it's changed.
+    /// Only the last few states are kept.
+    pub fn push(&mut self, queue: &Queue) {
+        if self.states.len() == UNDO_LIMIT {
+            self.states.pop_front();
+        }
+        self.states.push_back(queue.clone());
+    }
+
+    /// Takes the last saved state of the queue.
+    pub fn pop(&mut self) -> Option<Queue> {
+        self.states.pop_back()
+    }
+}
+
+impl RepeatMode {
+    /// Finds a mode by its name (`off`, `one` or `all`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "off" => Some(Self::Off),
+            "one" => Some(Self::One),
+            "all" => Some(Self::All),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the mode.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::One => "one",
+            Self::All => "all",
+        }
+    }
+}
+
+/// Returns the text shown for an entry of the queue.
+/// If the title is unknown, the file name is shown instead.
+fn label(path: &str, meta: &AudioMeta) -> String {
+    match (meta.title.as_str(), meta.artist.as_str()) {
+        ("Unknown", _) => Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_owned()),
+        (title, "Unknown") => title.to_owned(),
+        (title, artist) => format!("{artist} - {title}"),
+    }
+}
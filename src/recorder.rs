@@ -0,0 +1,244 @@
+use crate::dsp::Effects;
+use crate::player::{self, DecodeOptions};
+use rodio::Source;
+use sndfile::{Endian, MajorFormat, OpenOptions, SndFile, SndFileIO, SubtypeFormat, WriteOptions};
+use std::mem;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// Amount of samples written at once.
+const CHUNK_SIZE: usize = 8192;
+/// Maximum time to wait for a recording to be written after playback stopped.
+const FINISH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Format of a recording (or a converted file).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Wav,
+    Flac,
+}
+
+/// A [`Source`](Source) which records the audio passing through it into a file.
+/// The file is written in a background thread, so playback isn't slowed down.
+pub struct Recording<S> {
+    /// The recorded audio
+    source: S,
+    /// Samples which weren't sent to the writer yet
+    chunk: Vec<f32>,
+    /// Sends the samples to the writer thread
+    writer: Sender<Vec<f32>>,
+}
+
+/// Used to wait until a [`Recording`](Recording) is written.
+pub struct RecordingHandle {
+    /// Receives the result once the file is written
+    done: Receiver<Result<(), String>>,
+}
+
+impl OutputFormat {
+    /// Finds the format by the extension of the file (`.wav` or `.flac`).
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "wav" => Some(Self::Wav),
+            "flac" => Some(Self::Flac),
+            _ => None,
+        }
+    }
+}
+
+impl<S> Recording<S> {
+    /// Sends the collected samples to the writer thread.
+    fn flush(&mut self) {
+        if self.chunk.is_empty() {
+            return;
+        }
+
+        let chunk = mem::replace(&mut self.chunk, Vec::with_capacity(CHUNK_SIZE));
+        /* If writing failed, the error is reported by `RecordingHandle::finish()` */
+        let _ = self.writer.send(chunk);
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Recording<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let Some(sample) = self.source.next() else {
+            self.flush();
+            return None;
+        };
+
+        self.chunk.push(sample);
+        if self.chunk.len() >= CHUNK_SIZE {
+            self.flush();
+        }
+
+        Some(sample)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Recording<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S> Drop for Recording<S> {
+    fn drop(&mut self) {
+        /* Playback was stopped early, the audio played so far is still kept */
+        self.flush();
+    }
+}
+
+impl RecordingHandle {
+    /// Waits until the recording is written, which happens once the [`Recording`](Recording)
+    /// is finished or dropped.
+    ///
+    /// ## Errors
+    /// Fails if the file couldn't be written, or if writing didn't finish in time.
+    pub fn finish(self) -> Result<(), String> {
+        self.done
+            .recv_timeout(FINISH_TIMEOUT)
+            .unwrap_or_else(|_| Err("The recording wasn't finished".to_owned()))
+    }
+}
+
+/// Records `source` into a WAV or FLAC file (24-bit).
+/// Returns the source to play instead, and a handle to wait until the file is written.
+///
+/// # Arguments
+/// * `source` - The audio to record
+/// * `output` - Path to the recording, its extension selects the format
+///
+/// ## Errors
+/// Fails if the format isn't supported or the file can't be created.
+pub fn record<S: Source<Item = f32>>(
+    source: S,
+    output: &Path,
+) -> Result<(Recording<S>, RecordingHandle), String> {
+    let (channels, sample_rate) = (source.channels(), source.sample_rate());
+    let (writer, samples) = channel::<Vec<f32>>();
+    let (ready_tx, ready) = channel();
+    let (done_tx, done) = channel();
+
+    /* The file is opened in the writer thread, as `SndFile` can't be sent to it */
+    let path = output.to_path_buf();
+    thread::spawn(move || {
+        let mut file = match create(&path, channels, sample_rate) {
+            Ok(file) => {
+                let _ = ready_tx.send(Ok(()));
+                file
+            }
+            Err(e) => {
+                let _ = ready_tx.send(Err(e));
+                return;
+            }
+        };
+
+        let mut result = Ok(());
+        for chunk in samples {
+            if result.is_ok() && file.write_from_slice(&chunk).is_err() {
+                result = Err(format!("Unable to write {}", path.display()));
+            }
+        }
+
+        /* The file is finalized when it's closed */
+        drop(file);
+        let _ = done_tx.send(result);
+    });
+
+    ready
+        .recv()
+        .map_err(|_| format!("Unable to create {}", output.display()))??;
+
+    let recording = Recording {
+        source,
+        chunk: Vec::with_capacity(CHUNK_SIZE),
+        writer,
+    };
+    Ok((recording, RecordingHandle { done }))
+}
+
+/// Converts a file (or stream) into a WAV or FLAC file (24-bit), as fast as possible.
+/// The audio is processed the same way as when it's played (e.g. downmixed).
+///
+/// # Arguments
+/// * `input` - The file to convert
+/// * `output` - Path to the converted file, its extension selects the format
+/// * `max_channels` - Audio with more channels is downmixed to stereo (see [`player::decode()`](player::decode))
+/// * `effects` - Effects applied to the audio
+/// * `options` - How the input is decoded
+/// * `progress` - Called after every written chunk, with the amount of audio converted so far
+///
+/// ## Errors
+/// Fails if the input can't be opened or decoded (see [`player::decode()`](player::decode)),
+/// or if the output can't be written.
+pub fn convert(
+    input: &str,
+    output: &Path,
+    max_channels: u16,
+    effects: &Effects,
+    options: &DecodeOptions,
+    mut progress: impl FnMut(Duration),
+) -> Result<(), String> {
+    /* Nothing is played, so there's no need to decode ahead */
+    let options = DecodeOptions {
+        buffer: Duration::ZERO,
+        ..options.clone()
+    };
+    let mut source = player::decode(input, Duration::ZERO, max_channels, effects, &options)?.source;
+    let (channels, sample_rate) = (source.channels(), source.sample_rate());
+    let mut file = create(output, channels, sample_rate)?;
+
+    let samples_per_second = channels as f64 * sample_rate as f64;
+    let mut written = 0;
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    loop {
+        chunk.clear();
+        chunk.extend(source.by_ref().take(CHUNK_SIZE));
+        if chunk.is_empty() {
+            break;
+        }
+
+        file.write_from_slice(&chunk)
+            .map_err(|()| format!("Unable to write {}", output.display()))?;
+        written += chunk.len();
+        progress(Duration::from_secs_f64(written as f64 / samples_per_second));
+    }
+
+    Ok(())
+}
+
+/// Creates the output file.
+fn create(output: &Path, channels: u16, sample_rate: u32) -> Result<SndFile, String> {
+    let format = match OutputFormat::from_path(output) {
+        Some(OutputFormat::Wav) => MajorFormat::WAV,
+        Some(OutputFormat::Flac) => MajorFormat::FLAC,
+        None => return Err("Only WAV and FLAC files can be written".to_owned()),
+    };
+
+    OpenOptions::WriteOnly(WriteOptions::new(
+        format,
+        SubtypeFormat::PCM_24,
+        Endian::File,
+        sample_rate as usize,
+        channels as usize,
+    ))
+    .from_path(output)
+    .map_err(|e| format!("Unable to create {}: {e:?}", output.display()))
+}
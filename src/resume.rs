@@ -0,0 +1,78 @@
+use crate::paths;
+use crate::queue::Queue;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+/// Name of the saved queue file.
+const QUEUE_FILE_NAME: &str = "queue.json";
+
+/// The queue and playback position, saved on exit so playback can be resumed
+/// where the user left off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedQueue {
+    /// The play queue (including the current entry)
+    pub queue: Queue,
+    /// Playback position in the current entry, in seconds
+    pub position: f64,
+}
+
+impl SavedQueue {
+    /// Loads the saved queue.
+    /// Returns `None` if no queue was saved.
+    ///
+    /// ## Errors
+    /// Returns an error if the file exists but can't be read or contains an invalid queue.
+    pub fn load() -> Result<Option<Self>, String> {
+        let Some(path) = Self::path() else {
+            return Ok(None);
+        };
+
+        let saved: Self = match File::open(&path) {
+            Ok(file) => serde_json::from_reader(file)
+                .map_err(|e| format!("Invalid saved queue {}: {e}", path.display()))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Unable to open {}: {e}", path.display())),
+        };
+
+        if saved.queue.index() >= saved.queue.entries().len() {
+            return Err(format!("Invalid saved queue {}", path.display()));
+        }
+
+        Ok(Some(saved))
+    }
+
+    /// Saves the queue, creating its directory if needed.
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path().ok_or("Unable to find the state directory")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Unable to create {}: {e}", dir.display()))?;
+        }
+
+        let file =
+            File::create(&path).map_err(|e| format!("Unable to create {}: {e}", path.display()))?;
+        serde_json::to_writer(file, self)
+            .map_err(|e| format!("Unable to save {}: {e}", path.display()))
+    }
+
+    /// Removes the saved queue (if there is one), so it's not offered again.
+    pub fn remove() -> Result<(), String> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Unable to remove {}: {e}", path.display())),
+        }
+    }
+
+    /// Returns the path to the saved queue (in the [state directory](paths::state_dir)).
+    /// Returns `None` if the home directory is unknown.
+    pub fn path() -> Option<PathBuf> {
+        Some(paths::state_dir()?.join(QUEUE_FILE_NAME))
+    }
+}
@@ -0,0 +1,192 @@
+use crate::alarm;
+use crate::clock;
+use crate::timer::Timer;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How often the scheduler thread reads the clock.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Days an [entry of the schedule](ScheduleEntry) applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Day {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+    /// Monday to Friday
+    Weekdays,
+    /// Saturday and Sunday
+    Weekends,
+}
+
+impl Day {
+    /// Returns whether a day of the week (from `0` for Sunday to `6` for Saturday, like
+    /// [`libc::tm::tm_wday`](libc::tm)) is one of these days.
+    fn includes(self, weekday: i32) -> bool {
+        match self {
+            Self::Monday => weekday == 1,
+            Self::Tuesday => weekday == 2,
+            Self::Wednesday => weekday == 3,
+            Self::Thursday => weekday == 4,
+            Self::Friday => weekday == 5,
+            Self::Saturday => weekday == 6,
+            Self::Sunday => weekday == 0,
+            Self::Weekdays => (1..=5).contains(&weekday),
+            Self::Weekends => weekday == 0 || weekday == 6,
+        }
+    }
+}
+
+/// What an [entry of the schedule](ScheduleEntry) does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduledAction {
+    /// Pauses playback (after fading the audio out)
+    #[default]
+    Pause,
+    /// Starts playback (fading the audio in), or plays a smart playlist
+    Play,
+}
+
+/// An entry of the schedule, which pauses or starts playback at a given (local) time,
+/// e.g. `{ "at": "23:00", "days": ["weekdays"], "action": "pause", "fade_secs": 60 }`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScheduleEntry {
+    /// The time of day, like `08:00`
+    pub at: String,
+    /// The days it applies to (every day if none are set)
+    pub days: Vec<Day>,
+    /// What it does
+    pub action: ScheduledAction,
+    /// How long the audio fades out before pausing, or fades in after starting (in seconds)
+    pub fade_secs: u64,
+    /// Name of a smart playlist to play, replacing the queue (only used to start playback)
+    pub playlist: Option<String>,
+}
+
+impl ScheduleEntry {
+    /// Returns how long the audio fades.
+    pub fn fade(&self) -> Duration {
+        Duration::from_secs(self.fade_secs)
+    }
+
+    /// Returns whether the entry applies to a (local) time.
+    fn is_due(&self, now: &libc::tm) -> bool {
+        let on_day = self.days.is_empty() || self.days.iter().any(|day| day.includes(now.tm_wday));
+        on_day
+            && alarm::parse_time(&self.at).is_ok_and(|(hour, minute)| {
+                (hour as i32, minute as i32) == (now.tm_hour, now.tm_min)
+            })
+    }
+}
+
+/// Runs the schedule in a background thread, which reads the clock every few seconds and
+/// reports the entries which are due (each once in the minute it's set to).
+///
+/// ### Notes
+/// The entries can be replaced while it runs (e.g. after the configuration was reloaded).
+/// The thread stops once an entry is due and the scheduler was dropped.
+pub struct Scheduler {
+    /// The entries of the schedule, shared with the thread
+    entries: Arc<Mutex<Vec<ScheduleEntry>>>,
+    /// The entries which are due
+    due: Receiver<ScheduleEntry>,
+}
+
+impl Scheduler {
+    /// Starts the scheduler thread.
+    pub fn start(entries: Vec<ScheduleEntry>) -> Self {
+        let entries = Arc::new(Mutex::new(entries));
+        let (tx, due) = channel();
+
+        let shared = Arc::clone(&entries);
+        thread::spawn(move || {
+            /* The minute which was last checked, so entries aren't reported twice */
+            let mut checked = None;
+            loop {
+                if let Some(now) = clock::local_time() {
+                    let minute = (now.tm_yday, now.tm_hour, now.tm_min);
+                    if checked != Some(minute) {
+                        checked = Some(minute);
+                        let entries = shared.lock().map(|e| e.clone()).unwrap_or_default();
+                        for entry in entries.into_iter().filter(|entry| entry.is_due(&now)) {
+                            if tx.send(entry).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+                thread::sleep(CHECK_INTERVAL);
+            }
+        });
+
+        Self { entries, due }
+    }
+
+    /// Replaces the entries of the schedule.
+    pub fn set_entries(&self, entries: Vec<ScheduleEntry>) {
+        if let Ok(mut shared) = self.entries.lock() {
+            *shared = entries;
+        }
+    }
+
+    /// Returns the next entry which is due (if any).
+    pub fn poll(&self) -> Option<ScheduleEntry> {
+        self.due.try_recv().ok()
+    }
+}
+
+/// Fades the audio out before a scheduled pause, or in after scheduled playback started.
+pub struct ScheduledFade {
+    /// What was scheduled
+    action: ScheduledAction,
+    /// Time until the fade is done
+    timer: Timer,
+}
+
+impl ScheduledFade {
+    /// Starts a fade, which takes `len`.
+    pub fn new(action: ScheduledAction, len: Duration) -> Self {
+        Self {
+            action,
+            timer: Timer::new(len),
+        }
+    }
+
+    /// Returns what was scheduled.
+    pub fn action(&self) -> ScheduledAction {
+        self.action
+    }
+
+    /// Returns the level the audio is faded to (from `1.0` to `0.0` before pausing, and from
+    /// `0.0` to `1.0` after starting, rising slowly at first as loudness isn't linear).
+    pub fn fade_level(&self) -> f32 {
+        match self.action {
+            ScheduledAction::Pause => 1.0 - self.timer.progress(),
+            ScheduledAction::Play => self.timer.progress().powi(2),
+        }
+    }
+
+    /// Returns whether the fade is done.
+    pub fn expired(&self) -> bool {
+        self.timer.expired()
+    }
+
+    /// Stops the fade while the audio is paused, and lets it continue once it plays.
+    pub fn set_playing(&mut self, playing: bool) {
+        match (playing, self.timer.is_paused()) {
+            (true, true) => self.timer.resume(),
+            (false, false) => self.timer.pause(),
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,421 @@
+use crate::audioinfo::AudioMeta;
+use crate::fuzzy;
+use crate::library::Library;
+use crate::queue::Queue;
+use crate::sort::SortMode;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Maximum number of results to keep.
+const MAX_RESULTS: usize = 50;
+
+/// Where to search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchScope {
+    /// The current queue
+    Queue,
+    /// The music library
+    Library,
+}
+
+/// An item which can be found by searching.
+#[derive(Debug, Clone)]
+pub struct SearchItem {
+    /// Path to the audio file
+    pub path: String,
+    /// Text shown in the results
+    pub label: String,
+    /// Genre of the track, which may color the result
+    pub genre: Option<String>,
+    /// Text to match the query against (title, artist, album and path)
+    haystack: String,
+}
+
+/// What to do with the selected result.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchAction {
+    /// Jump to the entry of the queue at the given index
+    Jump(usize),
+    /// Append the files to the queue
+    Enqueue(Vec<String>),
+}
+
+/// An incremental fuzzy search over the queue or the library.
+///
+/// The results are updated every time the query changes.
+pub struct Search {
+    /// The text typed by the user
+    query: String,
+    /// Where to search
+    scope: SearchScope,
+    /// Items of the queue
+    queue_items: Vec<SearchItem>,
+    /// Items of the library
+    library_items: Vec<SearchItem>,
+    /// Indexes of the matching items (of the current scope), best match first
+    results: Vec<usize>,
+    /// Index of the selected result
+    selected: usize,
+    /// Paths of the marked library tracks, in the order they were marked
+    marked: Vec<String>,
+}
+
+/// A quick launcher, which searches the library database as the query is typed,
+/// to play or enqueue one of the matching tracks.
+pub struct Launcher {
+    /// The text typed by the user
+    query: String,
+    /// The matching tracks, ordered by the configured sort mode
+    results: Vec<SearchItem>,
+    /// Index of the selected result
+    selected: usize,
+    /// Paths of the marked tracks, in the order they were marked
+    marked: Vec<String>,
+}
+
+impl SearchItem {
+    /// Creates a new item.
+    /// If the title is unknown, the file name is shown instead.
+    fn new(path: &str, meta: &AudioMeta) -> Self {
+        let (title, album, artist) = (
+            meta.title.as_str(),
+            meta.album.as_str(),
+            meta.artist.as_str(),
+        );
+        let file_name = Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_owned());
+
+        let label = match (title, artist) {
+            ("Unknown", _) => file_name,
+            (title, "Unknown") => title.to_owned(),
+            (title, artist) => format!("{artist} - {title}"),
+        };
+
+        let mut haystack: Vec<&str> = [title, artist, album]
+            .into_iter()
+            .filter(|tag| *tag != "Unknown")
+            .collect();
+        haystack.push(path);
+
+        Self {
+            path: path.to_owned(),
+            label,
+            genre: meta.genre.clone(),
+            haystack: haystack.join(" "),
+        }
+    }
+}
+
+impl Search {
+    /// Creates a new search, starting in the queue.
+    ///
+    /// # Arguments
+    /// * `queue` - The current queue
+    /// * `library` - The music library (if available)
+    /// * `sort` - How to order the library, before any query is typed
+    ///
+    /// ### Notes
+    /// The tags of queue entries are taken from the library (or the playlist they came from),
+    /// other entries can only be found by their path.
+    pub fn new(queue: &Queue, library: Option<&Library>, sort: SortMode) -> Self {
+        let library_items = library_items(library, sort);
+
+        let known: HashMap<&str, &SearchItem> = library_items
+            .iter()
+            .map(|item| (item.path.as_str(), item))
+            .collect();
+        let queue_items = queue
+            .entries()
+            .iter()
+            .map(
+                |path| match (known.get(path.as_str()), queue.metadata(path)) {
+                    (Some(item), _) => (*item).clone(),
+                    (None, Some(meta)) => SearchItem::new(path, meta),
+                    (None, None) => SearchItem::new(path, &AudioMeta::unknown()),
+                },
+            )
+            .collect();
+
+        let mut search = Self {
+            query: String::new(),
+            scope: SearchScope::Queue,
+            queue_items,
+            library_items,
+            results: Vec::new(),
+            selected: 0,
+            marked: Vec::new(),
+        };
+        search.update();
+        search
+    }
+
+    /// Reads the library again, after it was updated.  
+    /// The query is kept, but the selection is reset.
+    pub fn reload_library(&mut self, library: &Library, sort: SortMode) {
+        self.library_items = library_items(Some(library), sort);
+        self.update();
+    }
+
+    /// Returns the query.
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Returns where the search is done.
+    pub fn scope(&self) -> SearchScope {
+        self.scope
+    }
+
+    /// Returns the matching items, best match first.
+    pub fn results(&self) -> impl Iterator<Item = &SearchItem> {
+        let items = self.items();
+        self.results.iter().map(move |&index| &items[index])
+    }
+
+    /// Returns the index of the selected result.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Appends a character to the query.
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.update();
+    }
+
+    /// Removes the last character of the query.
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.update();
+    }
+
+    /// Switches between searching the queue and the library.
+    pub fn toggle_scope(&mut self) {
+        self.scope = match self.scope {
+            SearchScope::Queue => SearchScope::Library,
+            SearchScope::Library => SearchScope::Queue,
+        };
+        self.update();
+    }
+
+    /// Selects the next result.
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.results.len() {
+            self.selected += 1;
+        }
+    }
+
+    /// Selects the previous result.
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Returns whether a result is marked.
+    /// Only library tracks can be marked.
+    pub fn is_marked(&self, item: &SearchItem) -> bool {
+        self.scope == SearchScope::Library && self.marked.contains(&item.path)
+    }
+
+    /// Marks the selected result and the next one, to add several library tracks at once.
+    /// In the queue, only the next result is selected.
+    pub fn mark_next(&mut self) {
+        self.mark_selected();
+        self.select_next();
+        self.mark_selected();
+    }
+
+    /// Marks the selected result and the previous one, to add several library tracks at once.
+    /// In the queue, only the previous result is selected.
+    pub fn mark_previous(&mut self) {
+        self.mark_selected();
+        self.select_previous();
+        self.mark_selected();
+    }
+
+    /// Marks the selected result (if it's a library track).
+    fn mark_selected(&mut self) {
+        if self.scope != SearchScope::Library {
+            return;
+        }
+        if let Some(&index) = self.results.get(self.selected) {
+            mark(&mut self.marked, &self.library_items[index].path);
+        }
+    }
+
+    /// Returns what to do with the selected result.
+    /// Queue entries are jumped to, library tracks are added to the queue
+    /// (every marked track, if any are marked).
+    pub fn action(&self) -> Option<SearchAction> {
+        if self.scope == SearchScope::Library && !self.marked.is_empty() {
+            return Some(SearchAction::Enqueue(self.marked.clone()));
+        }
+        let index = *self.results.get(self.selected)?;
+
+        Some(match self.scope {
+            SearchScope::Queue => SearchAction::Jump(index),
+            SearchScope::Library => {
+                SearchAction::Enqueue(vec![self.library_items[index].path.clone()])
+            }
+        })
+    }
+
+    /// Returns the items of the current scope.
+    fn items(&self) -> &[SearchItem] {
+        match self.scope {
+            SearchScope::Queue => &self.queue_items,
+            SearchScope::Library => &self.library_items,
+        }
+    }
+
+    /// Matches the query against the items of the current scope.
+    fn update(&mut self) {
+        let mut results: Vec<(usize, i64)> = self
+            .items()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| Some((index, fuzzy::score(&self.query, &item.haystack)?)))
+            .collect();
+
+        /* Stable sort, so items with the same score stay in their original order */
+        results.sort_by_key(|(_, score)| Reverse(*score));
+        self.results = results
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(index, _)| index)
+            .collect();
+        self.selected = 0;
+    }
+}
+
+impl Launcher {
+    /// Creates a new launcher, with an empty query.
+    pub fn new() -> Self {
+        Self {
+            query: String::new(),
+            results: Vec::new(),
+            selected: 0,
+            marked: Vec::new(),
+        }
+    }
+
+    /// Returns the query.
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Returns the matching tracks.
+    pub fn results(&self) -> &[SearchItem] {
+        &self.results
+    }
+
+    /// Returns the index of the selected result.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Returns the selected track.
+    pub fn selected_item(&self) -> Option<&SearchItem> {
+        self.results.get(self.selected)
+    }
+
+    /// Returns whether a track is marked.
+    pub fn is_marked(&self, item: &SearchItem) -> bool {
+        self.marked.contains(&item.path)
+    }
+
+    /// Returns the tracks to play or enqueue: the marked tracks (in the order they were marked),
+    /// or the selected track if none is marked.
+    pub fn group(&self) -> Vec<String> {
+        match (self.marked.is_empty(), self.selected_item()) {
+            (false, _) => self.marked.clone(),
+            (true, Some(item)) => vec![item.path.clone()],
+            (true, None) => Vec::new(),
+        }
+    }
+
+    /// Unmarks every track.
+    pub fn clear_marks(&mut self) {
+        self.marked.clear();
+    }
+
+    /// Marks the selected track and the next one, to play or enqueue several tracks at once.
+    pub fn mark_next(&mut self) {
+        self.mark_selected();
+        self.select_next();
+        self.mark_selected();
+    }
+
+    /// Marks the selected track and the previous one, to play or enqueue several tracks at once.
+    pub fn mark_previous(&mut self) {
+        self.mark_selected();
+        self.select_previous();
+        self.mark_selected();
+    }
+
+    /// Marks the selected track.
+    fn mark_selected(&mut self) {
+        if let Some(item) = self.results.get(self.selected) {
+            mark(&mut self.marked, &item.path);
+        }
+    }
+
+    /// Appends a character to the query, and searches again.
+    pub fn push(&mut self, c: char, library: &Library, sort: SortMode) -> Result<(), String> {
+        self.query.push(c);
+        self.update(library, sort)
+    }
+
+    /// Removes the last character of the query, and searches again.
+    pub fn pop(&mut self, library: &Library, sort: SortMode) -> Result<(), String> {
+        self.query.pop();
+        self.update(library, sort)
+    }
+
+    /// Selects the next result.
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.results.len() {
+            self.selected += 1;
+        }
+    }
+
+    /// Selects the previous result.
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Searches the library for the query.
+    /// Nothing is shown until something is typed.
+    fn update(&mut self, library: &Library, sort: SortMode) -> Result<(), String> {
+        self.selected = 0;
+        self.results = match self.query.trim() {
+            "" => Vec::new(),
+            query => library
+                .search(query, sort)?
+                .iter()
+                .take(MAX_RESULTS)
+                .map(|track| SearchItem::new(&track.path, &track.metadata))
+                .collect(),
+        };
+        Ok(())
+    }
+}
+
+/// Marks a track, unless it's already marked.
+fn mark(marked: &mut Vec<String>, path: &str) {
+    if !marked.iter().any(|marked| marked == path) {
+        marked.push(path.to_owned());
+    }
+}
+
+/// Creates the items of every track in the library.
+fn library_items(library: Option<&Library>, sort: SortMode) -> Vec<SearchItem> {
+    library
+        .and_then(|library| library.tracks(sort).ok())
+        .unwrap_or_default()
+        .iter()
+        .map(|track| SearchItem::new(&track.path, &track.metadata))
+        .collect()
+}
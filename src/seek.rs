@@ -0,0 +1,218 @@
+use std::time::{Duration, Instant};
+
+/// If a seek key isn't repeated for this long, it was released.
+/// Terminals only report key presses, which they repeat while a key is held.
+const RELEASE_GAP: Duration = Duration::from_millis(200);
+/// Time between steps while a seek key is held (repeats in between are ignored).
+const STEP_INTERVAL: Duration = Duration::from_millis(200);
+/// Step sizes, and how long a seek key has to be held for them.
+const STEPS: [(Duration, Duration); 3] = [
+    (Duration::ZERO, Duration::from_secs(5)),
+    (Duration::from_secs(2), Duration::from_secs(15)),
+    (Duration::from_secs(5), Duration::from_secs(60)),
+];
+
+/// Which way to seek.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekDirection {
+    /// Towards the start of the track
+    Back,
+    /// Towards the end of the track
+    Forward,
+}
+
+/// Seeking with a key which is being held, like on hardware players: the longer the key is held,
+/// the larger the steps (5 seconds, then 15 seconds, then a minute).
+///
+/// The first press seeks right away. While the key is held, only the target moves (and is shown),
+/// and it's seeked to once the key is released, since seeking restarts the player.
+#[derive(Debug, Clone, Copy)]
+pub struct HeldSeek {
+    /// Which way it's seeking
+    direction: SeekDirection,
+    /// When the key was pressed
+    pressed: Instant,
+    /// When the key was last repeated
+    repeated: Instant,
+    /// When the target last moved
+    stepped: Instant,
+    /// Where to seek to
+    target: Duration,
+    /// Where the first press seeked to
+    seeked: Duration,
+    /// Length of the track
+    length: Duration,
+}
+
+impl HeldSeek {
+    /// Starts seeking with a key which was just pressed, by the smallest step.
+    ///
+    /// # Arguments
+    /// * `direction` - Which way to seek
+    /// * `from` - The position in the track
+    /// * `length` - Length of the track
+    pub fn start(direction: SeekDirection, from: Duration, length: Duration) -> Self {
+        let now = Instant::now();
+        let mut seek = Self {
+            direction,
+            pressed: now,
+            repeated: now,
+            stepped: now,
+            target: from,
+            seeked: from,
+            length,
+        };
+        seek.step();
+        seek.seeked = seek.target;
+        seek
+    }
+
+    /// Handles the key being repeated, which moves the target if it's time for the next step.
+    /// Returns whether the target moved.
+    pub fn repeat(&mut self) -> bool {
+        self.repeated = Instant::now();
+        if self.stepped.elapsed() < STEP_INTERVAL {
+            return false;
+        }
+
+        self.step();
+        true
+    }
+
+    /// Moves the target by a step.
+    fn step(&mut self) {
+        let step = self.step_size();
+        self.stepped = Instant::now();
+        self.target = match self.direction {
+            SeekDirection::Back => self.target.saturating_sub(step),
+            SeekDirection::Forward => (self.target + step).min(self.length),
+        };
+    }
+
+    /// Returns which way it's seeking.
+    pub fn direction(&self) -> SeekDirection {
+        self.direction
+    }
+
+    /// Returns where to seek to.
+    pub fn target(&self) -> Duration {
+        self.target
+    }
+
+    /// Returns where to seek to, unless it's where the first press already seeked to.
+    pub fn pending(&self) -> Option<Duration> {
+        (self.target != self.seeked).then_some(self.target)
+    }
+
+    /// Returns the current step size, which depends on how long the key was held.
+    pub fn step_size(&self) -> Duration {
+        let held = self.pressed.elapsed();
+        STEPS
+            .into_iter()
+            .rev()
+            .find(|(after, _)| held >= *after)
+            .map_or(STEPS[0].1, |(_, step)| step)
+    }
+
+    /// Returns whether the key was released.
+    pub fn is_released(&self) -> bool {
+        self.repeated.elapsed() >= RELEASE_GAP
+    }
+}
+
+/// Parses a position in a track, typed into the jump-to-time prompt.
+/// The position is either a time (`m:ss` or `h:mm:ss`) or a percentage of the track (e.g. `50%`).
+///
+/// ## Errors
+/// Fails if the position is invalid, or if it's past the end of the track.
+pub fn parse_position(input: &str, length: Duration) -> Result<Duration, String> {
+    let input = input.trim();
+    let invalid = || format!("Invalid position: {input} (expected mm:ss or a percentage)");
+
+    if let Some(percent) = input.strip_suffix('%') {
+        let percent: f64 = percent.trim().parse().map_err(|_| invalid())?;
+        if !(0.0..=100.0).contains(&percent) {
+            return Err(invalid());
+        }
+        return Ok(length.mul_f64(percent / 100.0));
+    }
+
+    let parts = input
+        .split(':')
+        .map(|part| part.trim().parse::<u64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let seconds = match parts[..] {
+        [minutes, seconds] if seconds < 60 => minutes * 60 + seconds,
+        [hours, minutes, seconds] if minutes < 60 && seconds < 60 => {
+            hours * 3600 + minutes * 60 + seconds
+        }
+        _ => return Err(invalid()),
+    };
+
+    let position = Duration::from_secs(seconds);
+    if position > length {
+        let length = length.as_secs();
+        return Err(format!(
+            "The track is only {:02}:{:02} long",
+            length / 60,
+            length % 60
+        ));
+    }
+    Ok(position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LENGTH: Duration = Duration::from_secs(2 * 3600);
+
+    #[test]
+    fn parse_position_reads_times() {
+        assert_eq!(parse_position("1:23", LENGTH), Ok(Duration::from_secs(83)));
+        assert_eq!(parse_position(" 0:05 ", LENGTH), Ok(Duration::from_secs(5)));
+        assert_eq!(
+            parse_position("90:00", LENGTH),
+            Ok(Duration::from_secs(5400))
+        );
+        assert_eq!(
+            parse_position("1:02:03", LENGTH),
+            Ok(Duration::from_secs(3723))
+        );
+    }
+
+    #[test]
+    fn parse_position_reads_percentages() {
+        assert_eq!(parse_position("50%", LENGTH), Ok(LENGTH / 2));
+        assert_eq!(parse_position("0%", LENGTH), Ok(Duration::ZERO));
+        assert_eq!(parse_position("100 %", LENGTH), Ok(LENGTH));
+    }
+
+    #[test]
+    fn parse_position_rejects_invalid_positions() {
+        for input in [
+            "", "83", "1:60", "1:60:00", "1:2:3:4", "a:bc", "-5%", "101%", "x%",
+        ] {
+            assert_eq!(
+                parse_position(input, LENGTH),
+                Err(format!(
+                    "Invalid position: {} (expected mm:ss or a percentage)",
+                    input.trim()
+                )),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_position_rejects_positions_past_the_end() {
+        assert_eq!(
+            parse_position("3:01", Duration::from_secs(180)),
+            Err("The track is only 03:00 long".to_owned())
+        );
+        assert_eq!(
+            parse_position("3:00", Duration::from_secs(180)),
+            Ok(Duration::from_secs(180))
+        );
+    }
+}
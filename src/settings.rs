@@ -0,0 +1,83 @@
+use crate::config::Config;
+use crate::library::Library;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs::File;
+use std::path::Path;
+
+/// Version of the format of settings files, increased when it changes incompatibly.
+const FORMAT_VERSION: u32 = 1;
+
+/// The user's settings, bundled into a single file to move them to another machine:
+/// the configuration file (with its theme, key bindings and profiles) and the favorites.
+#[derive(Serialize, Deserialize)]
+pub struct Settings {
+    /// Version of the format of the file
+    version: u32,
+    /// Options of the configuration file, as they are written in it
+    config: Map<String, Value>,
+    /// Paths (or URLs) of the favorites, in the order they were added
+    favorites: Vec<String>,
+}
+
+impl Settings {
+    /// Collects the settings of this machine.
+    ///
+    /// ### Notes
+    /// Only the configuration file is included, options set by environment variables
+    /// or on the command line are left out.
+    pub fn collect() -> Result<Self, String> {
+        let favorites = Library::open()?.favorites()?;
+        Ok(Self {
+            version: FORMAT_VERSION,
+            config: Config::load()?,
+            favorites: favorites.into_iter().map(|entry| entry.location).collect(),
+        })
+    }
+
+    /// Writes the settings to a file.
+    pub fn export(&self, path: &Path) -> Result<(), String> {
+        let file =
+            File::create(path).map_err(|e| format!("Unable to create {}: {e}", path.display()))?;
+        serde_json::to_writer_pretty(file, self)
+            .map_err(|e| format!("Unable to write {}: {e}", path.display()))
+    }
+
+    /// Reads settings exported by [`export()`](Self::export).
+    ///
+    /// ## Errors
+    /// Fails if the file can't be read, or if it was written by a newer version of the player.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let file =
+            File::open(path).map_err(|e| format!("Unable to open {}: {e}", path.display()))?;
+        let settings: Self = serde_json::from_reader(file)
+            .map_err(|e| format!("Invalid settings file {}: {e}", path.display()))?;
+
+        match settings.version {
+            FORMAT_VERSION => Ok(settings),
+            version => Err(format!(
+                "{} was exported by a newer version (format {version}), please update the player",
+                path.display()
+            )),
+        }
+    }
+
+    /// Applies the settings to this machine: replaces the configuration file (the previous one is
+    /// kept as a backup), and adds the favorites to the ones which are already there.
+    /// Returns the number of favorites.
+    ///
+    /// ## Errors
+    /// Fails if the configuration is invalid, in which case nothing is changed.
+    ///
+    /// ### Notes
+    /// Favorites are stored by path, so the music should be at the same place on both machines.
+    pub fn import(self) -> Result<usize, String> {
+        Config::replace(self.config)?;
+
+        let library = Library::open()?;
+        for path in &self.favorites {
+            library.set_favorite(path, true)?;
+        }
+        Ok(self.favorites.len())
+    }
+}
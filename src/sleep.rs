@@ -0,0 +1,55 @@
+use crate::timer::Timer;
+use std::time::Duration;
+
+/// How long the audio fades out before the sleep timer pauses playback.
+pub const SLEEP_FADE: Duration = Duration::from_secs(30);
+/// Longest time the sleep timer can be set to (in minutes).
+pub const MAX_SLEEP_MINUTES: u32 = 720;
+
+/// Pauses playback after a while, e.g. when falling asleep to music.
+/// The audio fades out over the last [`SLEEP_FADE`](SLEEP_FADE) before it's paused.
+///
+/// ### Notes
+/// The time only runs while the audio is playing, so pausing doesn't use it up.
+pub struct SleepTimer {
+    /// Time until playback is paused
+    timer: Timer,
+}
+
+impl SleepTimer {
+    /// Arms a sleep timer, which pauses playback after `minutes` of playing.
+    pub fn new(minutes: u32) -> Self {
+        Self {
+            timer: Timer::new(Duration::from_secs(minutes as u64 * 60)),
+        }
+    }
+
+    /// Returns the time left until playback is paused.
+    pub fn remaining(&self) -> Duration {
+        self.timer.remaining()
+    }
+
+    /// Returns whether the audio is fading out.
+    pub fn is_fading(&self) -> bool {
+        !self.timer.expired() && self.timer.remaining() <= SLEEP_FADE
+    }
+
+    /// Returns the level the audio is faded to (from `1.0` to `0.0` over the fade).
+    pub fn fade_level(&self) -> f32 {
+        (self.timer.remaining().as_secs_f32() / SLEEP_FADE.as_secs_f32()).min(1.0)
+    }
+
+    /// Returns whether the time is up, and playback should be paused.
+    pub fn expired(&self) -> bool {
+        self.timer.expired()
+    }
+
+    /// Stops the time while the audio is paused, and lets it run again once it plays.
+    pub fn set_playing(&mut self, playing: bool) {
+        match (playing, self.timer.is_paused()) {
+            (true, true) => self.timer.resume(),
+            (false, false) => self.timer.pause(),
+            _ => (),
+        }
+    }
+}
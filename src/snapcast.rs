@@ -0,0 +1,170 @@
+use crate::output;
+use rodio::source::UniformSourceIterator;
+use rodio::Source;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Sample rate expected by Snapcast servers, unless configured otherwise.
+const DEFAULT_SAMPLE_RATE: u32 = 48000;
+/// Amount of channels expected by Snapcast servers, unless configured otherwise.
+const DEFAULT_CHANNELS: u16 = 2;
+/// Maximum time to wait for the server to accept the connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A source of a Snapcast server, which the audio is fed into.
+///
+/// Written as `/path/to/fifo` (a `pipe` source) or `tcp://host:port` (a `tcp` source in
+/// server mode), optionally followed by the sample format of the source,
+/// like `?sampleformat=44100:16:2`.
+#[derive(Debug, Clone)]
+pub struct Snapcast {
+    /// Where the audio is written
+    target: Target,
+    /// Sample rate expected by the server
+    sample_rate: u32,
+    /// Amount of channels expected by the server
+    channels: u16,
+}
+
+/// Where the audio is written.
+#[derive(Debug, Clone)]
+enum Target {
+    /// A named pipe read by the server
+    Pipe(PathBuf),
+    /// A TCP port the server listens on (`host:port`)
+    Tcp(String),
+}
+
+/// Playback on a Snapcast server.
+/// The audio is written in a background thread, which stops once the stream is dropped.
+pub struct SnapcastStream {
+    /// Stops the writer thread
+    stop: Arc<AtomicBool>,
+    /// The writer thread
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Snapcast {
+    /// Parses the location of a Snapcast source.
+    ///
+    /// ## Errors
+    /// Fails if the sample format is invalid or not 16-bit.
+    pub fn parse(location: &str) -> Result<Self, String> {
+        let (target, options) = location.split_once('?').unwrap_or((location, ""));
+        let target = match target.strip_prefix("tcp://") {
+            Some(address) => Target::Tcp(address.to_owned()),
+            None => Target::Pipe(PathBuf::from(target)),
+        };
+
+        let mut snapcast = Snapcast {
+            target,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            channels: DEFAULT_CHANNELS,
+        };
+
+        for option in options.split('&').filter(|option| !option.is_empty()) {
+            let Some(format) = option.strip_prefix("sampleformat=") else {
+                return Err(format!("Unknown Snapcast option: {option}"));
+            };
+
+            let invalid = || format!("Invalid Snapcast sample format: {format}");
+            let parts: Vec<&str> = format.split(':').collect();
+            let [rate, bits, channels] = parts[..] else {
+                return Err(invalid());
+            };
+            if bits != "16" {
+                return Err("Only 16-bit Snapcast sources are supported".to_owned());
+            }
+            snapcast.sample_rate = rate.parse().map_err(|_| invalid())?;
+            snapcast.channels = channels.parse().map_err(|_| invalid())?;
+            if snapcast.sample_rate == 0 || snapcast.channels == 0 {
+                return Err(invalid());
+            }
+        }
+
+        Ok(snapcast)
+    }
+
+    /// Returns the amount of channels expected by the server.
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Returns the sample rate expected by the server.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+impl SnapcastStream {
+    /// Starts feeding audio to a Snapcast server.
+    /// The audio is converted to the sample format of the server. While the `source` is
+    /// paused (or empty), silence is written, so the server keeps the clients in sync.
+    ///
+    /// ## Errors
+    /// Fails if the pipe can't be opened, or if the server can't be reached.
+    ///
+    /// ### Notes
+    /// Opening the pipe waits until the server opens it for reading.
+    pub fn start(
+        snapcast: &Snapcast,
+        source: impl Source<Item = f32> + Send + 'static,
+    ) -> Result<Self, String> {
+        let writer: Box<dyn Write + Send> = match &snapcast.target {
+            Target::Pipe(path) => Box::new(
+                OpenOptions::new()
+                    .write(true)
+                    .open(path)
+                    .map_err(|e| format!("Unable to open {}: {e}", path.display()))?,
+            ),
+            Target::Tcp(address) => Box::new(connect(address)?),
+        };
+
+        let mut source =
+            UniformSourceIterator::new(source, snapcast.channels, snapcast.sample_rate);
+        let (channels, sample_rate) = (snapcast.channels, snapcast.sample_rate);
+        let stop = Arc::new(AtomicBool::new(false));
+        let writer_stop = Arc::clone(&stop);
+        let writer = thread::spawn(move || {
+            if output::pump(&mut source, channels, sample_rate, writer, &writer_stop).is_err() {
+                /* The server went away, the audio is thrown away so the track still ends */
+                let _ = output::pump(&mut source, channels, sample_rate, io::sink(), &writer_stop);
+            }
+        });
+
+        Ok(SnapcastStream {
+            stop,
+            writer: Some(writer),
+        })
+    }
+}
+
+impl Drop for SnapcastStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        /* The next track writes to the same pipe, so the audio mustn't overlap */
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Connects to the TCP source of a Snapcast server.
+fn connect(address: &str) -> Result<TcpStream, String> {
+    let failed = |e: io::Error| format!("Unable to connect to {address}: {e}");
+
+    let address = address
+        .to_socket_addrs()
+        .map_err(failed)?
+        .next()
+        .ok_or_else(|| format!("Invalid address: {address}"))?;
+    let connection = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).map_err(failed)?;
+    connection.set_nodelay(true).map_err(failed)?;
+    Ok(connection)
+}
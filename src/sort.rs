@@ -0,0 +1,136 @@
+use crate::library::Track;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+
+/// How to order tracks loaded from a directory or listed from the library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortMode {
+    /// By path
+    #[default]
+    Path,
+    /// By album, then by track number
+    TrackNumber,
+    /// By title
+    Title,
+    /// By artist (the album artist, so compilations stay together), then by album and track number
+    Artist,
+    /// Newest first
+    DateAdded,
+    /// Shortest first
+    Duration,
+    /// Most played first
+    PlayCount,
+    /// Shuffled
+    Random,
+}
+
+impl SortMode {
+    /// All sort modes, in the order they're cycled through.
+    const ALL: [SortMode; 8] = [
+        Self::Path,
+        Self::TrackNumber,
+        Self::Title,
+        Self::Artist,
+        Self::DateAdded,
+        Self::Duration,
+        Self::PlayCount,
+        Self::Random,
+    ];
+
+    /// Returns the mode following this one.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|mode| *mode == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Returns whether sorting needs the tags (or the length) of the tracks,
+    /// which have to be read from the files if they aren't in the library.
+    pub fn reads_tags(self) -> bool {
+        !matches!(
+            self,
+            Self::Path | Self::DateAdded | Self::PlayCount | Self::Random
+        )
+    }
+
+    /// Sorts `tracks` in place.
+    ///
+    /// ### Notes
+    /// Text is compared case-insensitively, unknown tags and track numbers are placed last.
+    /// Tracks which compare equal are ordered by their path.
+    pub fn sort(self, tracks: &mut [Track]) {
+        match self {
+            Self::Path => tracks.sort_by(|a, b| a.path.cmp(&b.path)),
+            Self::TrackNumber => tracks.sort_by(|a, b| {
+                compare_tag(&a.metadata.album, &b.metadata.album)
+                    .then_with(|| compare_track_number(a, b))
+                    .then_with(|| a.path.cmp(&b.path))
+            }),
+            Self::Title => tracks.sort_by(|a, b| {
+                compare_tag(&a.metadata.title, &b.metadata.title).then_with(|| a.path.cmp(&b.path))
+            }),
+            Self::Artist => tracks.sort_by(|a, b| {
+                compare_tag(a.metadata.album_artist(), b.metadata.album_artist())
+                    .then_with(|| compare_tag(&a.metadata.album, &b.metadata.album))
+                    .then_with(|| compare_track_number(a, b))
+                    .then_with(|| a.path.cmp(&b.path))
+            }),
+            Self::DateAdded => {
+                tracks.sort_by(|a, b| b.added.cmp(&a.added).then_with(|| a.path.cmp(&b.path)))
+            }
+            Self::Duration => tracks.sort_by(|a, b| {
+                a.length
+                    .total_cmp(&b.length)
+                    .then_with(|| a.path.cmp(&b.path))
+            }),
+            Self::PlayCount => {
+                tracks.sort_by(|a, b| b.plays.cmp(&a.plays).then_with(|| a.path.cmp(&b.path)))
+            }
+            Self::Random => {
+                /* Every `RandomState` is seeded differently, which is enough for shuffling */
+                let state = RandomState::new();
+                tracks.sort_by_cached_key(|track| state.hash_one(&track.path));
+            }
+        }
+    }
+}
+
+impl fmt::Display for SortMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Path => "Path",
+            Self::TrackNumber => "Track number",
+            Self::Title => "Title",
+            Self::Artist => "Artist",
+            Self::DateAdded => "Date added",
+            Self::Duration => "Duration",
+            Self::PlayCount => "Play count",
+            Self::Random => "Random",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// Compares two tags case-insensitively, placing `"Unknown"` last.
+fn compare_tag(a: &str, b: &str) -> Ordering {
+    match (a == "Unknown", b == "Unknown") {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.to_lowercase().cmp(&b.to_lowercase()),
+    }
+}
+
+/// Compares the track numbers of two tracks, placing missing track numbers last.
+fn compare_track_number(a: &Track, b: &Track) -> Ordering {
+    match (a.metadata.track, b.metadata.track) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
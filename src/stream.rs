@@ -0,0 +1,376 @@
+use crate::audioinfo::AudioFormat;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+use std::os::fd::{AsFd, AsRawFd};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+/// Maximum time to wait for the server to accept the connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Size of the chunks read from the connection (or the standard input).
+const CHUNK_SIZE: usize = 16 * 1024;
+/// The buffer is considered healthy (full) when this many bytes are buffered ahead.
+const BUFFER_TARGET: u64 = 256 * 1024;
+/// Amount of data buffered before playback starts.
+const PREBUFFER_SIZE: u64 = 64 * 1024;
+/// Downloading pauses when this many bytes are buffered ahead.
+const MAX_BUFFERED: u64 = 16 * 1024 * 1024;
+/// Amount of data used to recognize the format of a stream.
+const SNIFF_SIZE: usize = 36;
+/// Amount of data kept behind the read position, so decoders can seek back a little
+/// (e.g. when probing the format).
+const KEEP_BEHIND: u64 = 1024 * 1024;
+
+/// Location used to read audio from the standard input.
+pub const STDIN: &str = "-";
+
+/// The standard input, once it was taken over by [`capture_stdin()`](capture_stdin).
+static STDIN_AUDIO: Mutex<Option<File>> = Mutex::new(None);
+
+/// Returns whether the location is an HTTP or HTTPS URL.
+pub fn is_url(location: &str) -> bool {
+    let lowercase = location.to_ascii_lowercase();
+    lowercase.starts_with("http://") || lowercase.starts_with("https://")
+}
+
+/// Returns whether the location is streamed (an HTTP(S) URL or the standard input),
+/// instead of being a local file.
+pub fn is_stream(location: &str) -> bool {
+    location == STDIN || is_url(location)
+}
+
+/// Takes over the standard input so audio can be read from it, and replaces it with the
+/// terminal, so the TUI can still read key presses.
+///
+/// ### Notes
+/// This must be called before the TUI is created.
+pub fn capture_stdin() -> Result<(), String> {
+    let audio = io::stdin()
+        .as_fd()
+        .try_clone_to_owned()
+        .map_err(|e| format!("Unable to read the standard input: {e}"))?;
+    let tty = File::open("/dev/tty").map_err(|e| format!("Unable to open the terminal: {e}"))?;
+
+    /* ncurses reads key presses from the standard input */
+    if unsafe { libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) } < 0 {
+        let e = io::Error::last_os_error();
+        return Err(format!("Unable to open the terminal: {e}"));
+    }
+
+    *STDIN_AUDIO.lock().unwrap() = Some(File::from(audio));
+    Ok(())
+}
+
+/// Audio streamed over HTTP(S) or read from the standard input.
+/// The data is read in a background thread and buffered, so short network hiccups
+/// (or a slow program writing the audio) don't interrupt playback.
+///
+/// ### Notes
+/// Only a part of the data is kept in memory, so seeking far back fails.
+/// Reading blocks until enough data is available.
+pub struct BufferedStream {
+    /// State shared with the download thread
+    shared: Arc<Shared>,
+    /// Length of the stream in bytes (if it's known)
+    length: Option<u64>,
+    /// Format of the audio
+    format: AudioFormat,
+}
+
+/// A handle used to check how much of a [`BufferedStream`](BufferedStream) is buffered,
+/// while the stream itself is owned by the decoder.
+#[derive(Clone)]
+pub struct BufferHealth(Arc<Shared>);
+
+/// State shared by the reader and the download thread.
+struct Shared {
+    state: Mutex<State>,
+    /// Notified whenever data is downloaded or read
+    changed: Condvar,
+}
+
+struct State {
+    /// Downloaded data which wasn't discarded yet
+    data: VecDeque<u8>,
+    /// Position of the first byte of `data` in the stream
+    start: u64,
+    /// Position of the reader
+    position: u64,
+    /// Whether the whole stream was downloaded
+    finished: bool,
+    /// The error which stopped downloading
+    error: Option<String>,
+    /// Whether the reader was dropped
+    closed: bool,
+}
+
+impl BufferedStream {
+    /// Opens a stream, and returns once enough data is buffered to start playback.
+    ///
+    /// # Arguments
+    /// * `location` - An HTTP or HTTPS URL, or [`STDIN`](STDIN)
+    ///
+    /// ## Errors
+    /// Fails if the server can't be reached or responds with an error, if the standard
+    /// input wasn't [captured](capture_stdin) (or was already read), or if the format
+    /// of the audio can't be recognized or played from a stream.
+    ///
+    /// ### Notes
+    /// The format of HTTP(S) streams is recognized by the extension of the URL or the
+    /// `Content-Type` header, falling back to the first bytes of the audio (like for the
+    /// standard input).
+    pub fn open(location: &str) -> Result<Self, String> {
+        if location == STDIN {
+            let stdin = STDIN_AUDIO
+                .lock()
+                .unwrap()
+                .take()
+                .ok_or("The standard input can only be played once")?;
+            return Self::start(stdin, None, None);
+        }
+
+        let response = ureq::AgentBuilder::new()
+            .timeout_connect(CONNECT_TIMEOUT)
+            .build()
+            .get(location)
+            .call()
+            .map_err(|e| format!("Unable to open the stream: {e}"))?;
+
+        let format = stream_format(location, response.content_type());
+        let length = response
+            .header("Content-Length")
+            .and_then(|length| length.parse().ok());
+
+        Self::start(response.into_reader(), length, format)
+    }
+
+    /// Starts reading `reader` in the background, and waits until enough data is buffered.
+    /// If `format` is `None`, it's recognized by the first bytes of the audio.
+    fn start(
+        reader: impl Read + Send + 'static,
+        length: Option<u64>,
+        format: Option<AudioFormat>,
+    ) -> Result<Self, String> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                data: VecDeque::new(),
+                start: 0,
+                position: 0,
+                finished: false,
+                error: None,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        });
+
+        let download_shared = Arc::clone(&shared);
+        thread::spawn(move || download(reader, &download_shared));
+
+        let head: Vec<u8> = {
+            let state = shared.wait_until(|state| {
+                state.buffered() >= PREBUFFER_SIZE || state.finished || state.error.is_some()
+            });
+            state.data.iter().take(SNIFF_SIZE).copied().collect()
+        };
+
+        /* Opus, WavPack and Monkey's Audio files are probed before decoding, which needs a local file */
+        let format = match format.or_else(|| sniff_format(&head)) {
+            Some(format @ (AudioFormat::FLAC | AudioFormat::WAV | AudioFormat::OGG)) => Ok(format),
+            Some(format) => Err(format!("Streaming {format} files is not supported")),
+            None => Err("Unsupported stream format".to_owned()),
+        };
+
+        match format {
+            Ok(format) => Ok(Self {
+                shared,
+                length,
+                format,
+            }),
+            Err(e) => {
+                shared.close();
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns the format of the audio.
+    pub fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    /// Returns a handle to check the health of the buffer.
+    pub fn health(&self) -> BufferHealth {
+        BufferHealth(Arc::clone(&self.shared))
+    }
+}
+
+impl Shared {
+    /// Blocks until `condition` is true, and returns the locked state.
+    fn wait_until(&self, condition: impl Fn(&State) -> bool) -> MutexGuard<'_, State> {
+        let mut state = self.state.lock().unwrap();
+        while !condition(&state) {
+            state = self.changed.wait(state).unwrap();
+        }
+        state
+    }
+
+    /// Stops the download thread.
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+}
+
+impl BufferHealth {
+    /// Returns how full the buffer is, from `0.0` (empty) to `1.0` (full).
+    /// Once the whole stream is downloaded, the buffer is always full.
+    pub fn level(&self) -> f64 {
+        let state = self.0.state.lock().unwrap();
+        match state.finished {
+            true => 1.0,
+            false => (state.buffered() as f64 / BUFFER_TARGET as f64).min(1.0),
+        }
+    }
+}
+
+impl State {
+    /// Returns the position following the last downloaded byte.
+    fn end(&self) -> u64 {
+        self.start + self.data.len() as u64
+    }
+
+    /// Returns the amount of data buffered ahead of the reader.
+    fn buffered(&self) -> u64 {
+        self.end().saturating_sub(self.position)
+    }
+}
+
+impl Read for BufferedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.shared.wait_until(|state| {
+            state.position < state.end() || state.finished || state.error.is_some()
+        });
+
+        if state.position >= state.end() {
+            return match &state.error {
+                Some(e) => Err(io::Error::other(e.clone())),
+                None => Ok(0),
+            };
+        }
+
+        let offset = (state.position - state.start) as usize;
+        let count = buf.len().min(state.data.len() - offset);
+        for (dest, byte) in buf.iter_mut().zip(state.data.range(offset..offset + count)) {
+            *dest = *byte;
+        }
+        state.position += count as u64;
+
+        /* Discard the data which is too far behind */
+        let discard = state
+            .position
+            .saturating_sub(state.start + KEEP_BEHIND)
+            .min(state.data.len() as u64);
+        state.data.drain(..discard as usize);
+        state.start += discard;
+
+        self.shared.changed.notify_all();
+        Ok(count)
+    }
+}
+
+impl Seek for BufferedStream {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let mut state = self.shared.state.lock().unwrap();
+
+        let length = match state.finished {
+            true => Some(state.end()),
+            false => self.length,
+        };
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => state.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => {
+                let length = length.ok_or_else(|| {
+                    io::Error::new(
+                        ErrorKind::Unsupported,
+                        "The length of the stream is unknown",
+                    )
+                })?;
+                length.checked_add_signed(offset)
+            }
+        }
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "Invalid seek position"))?;
+
+        if target < state.start {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "Unable to seek back that far in a stream",
+            ));
+        }
+
+        state.position = target;
+        self.shared.changed.notify_all();
+        Ok(target)
+    }
+}
+
+impl Drop for BufferedStream {
+    fn drop(&mut self) {
+        self.shared.close();
+    }
+}
+
+/// Downloads `body` into the shared buffer, until it ends or the reader is dropped.
+fn download(mut body: impl Read, shared: &Shared) {
+    let mut chunk = vec![0; CHUNK_SIZE];
+
+    loop {
+        let result = body.read(&mut chunk);
+
+        let mut state = shared.state.lock().unwrap();
+        match result {
+            Ok(0) => state.finished = true,
+            Ok(count) => state.data.extend(&chunk[..count]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => state.error = Some(format!("Stream interrupted: {e}")),
+        }
+        shared.changed.notify_all();
+
+        while !state.closed && state.buffered() >= MAX_BUFFERED {
+            state = shared.changed.wait(state).unwrap();
+        }
+        if state.closed || state.finished || state.error.is_some() {
+            return;
+        }
+    }
+}
+
+/// Finds the format of a stream by the extension in its URL (ignoring the query),
+/// or by its MIME type if the URL has no known extension.
+fn stream_format(url: &str, content_type: &str) -> Option<AudioFormat> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    if let Ok(format) = AudioFormat::from_path(path) {
+        return Some(format);
+    }
+
+    match content_type.to_ascii_lowercase().as_str() {
+        "audio/flac" | "audio/x-flac" => Some(AudioFormat::FLAC),
+        "audio/wav" | "audio/x-wav" | "audio/wave" | "audio/vnd.wave" => Some(AudioFormat::WAV),
+        "audio/ogg" | "audio/vorbis" | "application/ogg" => Some(AudioFormat::OGG),
+        _ => None,
+    }
+}
+
+/// Recognizes the format of audio by its first bytes.
+fn sniff_format(head: &[u8]) -> Option<AudioFormat> {
+    match head.get(..4)? {
+        b"RIFF" => Some(AudioFormat::WAV),
+        b"fLaC" => Some(AudioFormat::FLAC),
+        /* The first page of an Ogg stream contains the header of the codec */
+        b"OggS" if head.get(28..36) == Some(b"OpusHead".as_slice()) => Some(AudioFormat::OPUS),
+        b"OggS" => Some(AudioFormat::OGG),
+        _ => None,
+    }
+}
@@ -0,0 +1,106 @@
+use crate::acoustid::Fingerprint;
+use crate::audioinfo::{AudioFile, AudioMeta};
+use crate::musicbrainz::MusicBrainz;
+use crate::stream;
+use crate::tagwriter::TagWriter;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Tags found for the `"Unknown"` fields of a file, waiting to be written.
+pub struct TagFix {
+    /// Path of the file
+    pub file: String,
+    /// Metadata of the file, with the missing fields filled in
+    pub metadata: AudioMeta,
+    /// Writes the filled fields to the file
+    pub writer: TagWriter,
+}
+
+/// A message of [`fix_in_background()`](fix_in_background).
+pub enum FixProgress {
+    /// How many files were looked up so far, out of how many
+    Checked(usize, usize),
+    /// The fixes which were found, and how many lookups failed
+    Done(Vec<TagFix>, usize),
+}
+
+/// Looks up the missing tags of files (like the entries of the queue) in a background thread,
+/// so playback is not blocked. Nothing is written, the fixes are only prepared
+/// (see [`TagWriter`](TagWriter)).
+///
+/// Files where the title, album or artist is `"Unknown"` are identified by their fingerprint
+/// on AcoustID if none of them is tagged (and an API key is set), or looked up on MusicBrainz.
+/// Only the `"Unknown"` fields are filled in, tags which are set are never replaced.
+///
+/// # Arguments
+/// * `files` - Paths of the files, with the metadata known from other sources (like playlists),
+///   which helps finding them
+/// * `folders` - Whether missing tags are inferred from the directories of the file too,
+///   see [`AudioMeta::infer_missing()`](AudioMeta::infer_missing)
+/// * `acoustid_key` - API key of AcoustID (if it's set)
+///
+/// ### Notes
+/// The channel receives the progress after each file, and the fixes at the end.
+/// Streams, and files which can't be read, are left out.
+pub fn fix_in_background(
+    files: Vec<(String, Option<AudioMeta>)>,
+    folders: bool,
+    acoustid_key: Option<String>,
+) -> Receiver<FixProgress> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let files: Vec<(AudioFile, Option<AudioMeta>)> = files
+            .into_iter()
+            .filter(|(file, _)| !stream::is_stream(file))
+            .filter_map(|(file, known)| Some((AudioFile::open(&file).ok()?, known)))
+            .filter(|(afile, _)| afile.metadata.is_incomplete())
+            .collect();
+
+        let musicbrainz = MusicBrainz::new();
+        let (mut fixes, mut failed) = (Vec::new(), 0);
+        for (index, (afile, known)) in files.iter().enumerate() {
+            /* The tags stored in the file, completed like the ones of the playing track */
+            let mut hints = afile.metadata.clone();
+            if let Some(known) = known {
+                hints.fill_missing(known);
+            }
+            hints.infer_missing(&afile.file_name, folders);
+
+            let found = match &acoustid_key {
+                Some(key) if hints.is_untagged() => {
+                    Fingerprint::compute(&afile.file_name).and_then(|fp| fp.lookup(key))
+                }
+                _ => musicbrainz.search(&hints, afile.length),
+            };
+            match found {
+                Ok(Some(found)) => {
+                    let mut metadata = afile.metadata.clone();
+                    metadata.fill_missing(&found);
+                    let writer =
+                        TagWriter::new(&afile.file_name, afile.format, &afile.metadata, &metadata);
+                    if !writer.is_empty() {
+                        fixes.push(TagFix {
+                            file: afile.file_name.clone(),
+                            metadata,
+                            writer,
+                        });
+                    }
+                }
+                Ok(None) => (),
+                Err(_) => failed += 1,
+            }
+
+            if tx
+                .send(FixProgress::Checked(index + 1, files.len()))
+                .is_err()
+            {
+                return;
+            }
+        }
+
+        let _ = tx.send(FixProgress::Done(fixes, failed));
+    });
+
+    rx
+}
@@ -0,0 +1,447 @@
+use crate::ape::ApeTag;
+use crate::audioinfo::{AudioFormat, AudioMeta};
+use crate::comments::{VorbisComment, FLAC_COMMENT_BLOCK};
+use ogg::{PacketReader, PacketWriteEndInfo, PacketWriter};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+/// Names of the fields that can be written, in the same order as the key tables below.
+const FIELDS: [&str; 4] = ["Title", "Album", "Artist", "Rating"];
+/// Vorbis comment field names (FLAC, Ogg Vorbis and Opus)
+const VORBIS_KEYS: [&str; 4] = ["TITLE", "ALBUM", "ARTIST", "RATING"];
+/// APEv2 item keys (WavPack and Monkey's Audio)
+const APE_KEYS: [&str; 4] = ["Title", "Album", "Artist", "Rating"];
+/// RIFF `INFO` chunk IDs (WAV), as read by `sndfile` (and `IRTD`, the rating used by Windows)
+const RIFF_KEYS: [&[u8; 4]; 4] = [b"INAM", b"IPRD", b"IART", b"IRTD"];
+
+/// Amount of padding to add when the metadata of a FLAC file has to be rewritten,
+/// so later changes can be done in-place.
+const FLAC_PADDING: usize = 4096;
+/// Maximum size of a FLAC metadata block.
+const FLAC_MAX_BLOCK_SIZE: usize = (1 << 24) - 1;
+/// FLAC metadata block type: padding
+const FLAC_PADDING_BLOCK: u8 = 1;
+
+/// A change of a single field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagChange {
+    /// Name of the field
+    pub field: &'static str,
+    /// Current value
+    pub old: String,
+    /// New value
+    pub new: String,
+}
+
+/// Writes metadata back to an audio file.
+///
+/// Creating a [`TagWriter`](TagWriter) does not modify the file, so the list of
+/// [`changes()`](Self::changes()) can be used as a preview (dry-run) before
+/// calling [`apply()`](Self::apply()).
+///
+/// ### Notes
+/// `"Unknown"` values are never written, so existing tags are never removed.
+#[derive(Debug, Clone)]
+pub struct TagWriter {
+    /// Path to the audio file
+    file: String,
+    /// Format of the audio file
+    format: AudioFormat,
+    /// Fields to write
+    changes: Vec<TagChange>,
+}
+
+impl TagWriter {
+    /// Prepares writing metadata to a file.
+    ///
+    /// # Arguments
+    /// * `file` - A [`String`](String) containing the path to the audio file.
+    /// * `format` - Format of the audio file
+    /// * `current` - Metadata currently stored in the file
+    /// * `new` - Metadata to write
+    pub fn new(file: &str, format: AudioFormat, current: &AudioMeta, new: &AudioMeta) -> Self {
+        let current = [&current.title, &current.album, &current.artist];
+        let new = [&new.title, &new.album, &new.artist];
+
+        let changes = FIELDS
+            .iter()
+            .zip(current.iter().zip(new.iter()))
+            .filter(|(_, (old, new))| old != new && new.as_str() != "Unknown")
+            .map(|(field, (old, new))| TagChange {
+                field,
+                old: old.to_string(),
+                new: new.to_string(),
+            })
+            .collect();
+
+        Self {
+            file: file.to_owned(),
+            format,
+            changes,
+        }
+    }
+
+    /// Prepares writing a rating (from 1 to 5 stars) to the `RATING` tag of a file.
+    ///
+    /// # Arguments
+    /// * `file` - A [`String`](String) containing the path to the audio file.
+    /// * `format` - Format of the audio file
+    /// * `rating` - The rating
+    pub fn rating(file: &str, format: AudioFormat, rating: u8) -> Self {
+        Self {
+            file: file.to_owned(),
+            format,
+            changes: vec![TagChange {
+                field: "Rating",
+                old: String::new(),
+                new: rating.to_string(),
+            }],
+        }
+    }
+
+    /// Returns the changes that [`apply()`](Self::apply()) will make.
+    pub fn changes(&self) -> &[TagChange] {
+        &self.changes
+    }
+
+    /// Returns whether there's nothing to write.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Writes the changes to the file.
+    ///
+    /// ### Notes
+    /// If the file has to be rewritten, it's first written to a temporary file
+    /// which then replaces the original, so a failure never leaves a broken file behind.
+    pub fn apply(&self) -> Result<(), String> {
+        match self.format {
+            AudioFormat::FLAC => self.write_flac(),
+            AudioFormat::OGG => self.write_ogg(b"\x03vorbis"),
+            AudioFormat::OPUS => self.write_ogg(b"OpusTags"),
+            AudioFormat::WAV => self.write_wav(),
+            AudioFormat::WAVPACK | AudioFormat::APE => self.write_ape(),
+        }
+    }
+
+    /// Returns the changes as `(key, value)` pairs, using the given key table.
+    fn updates<'a, K>(&'a self, keys: &'a [K; 4]) -> impl Iterator<Item = (&'a K, &'a str)> {
+        self.changes.iter().map(|change| {
+            let index = FIELDS.iter().position(|f| *f == change.field).unwrap();
+            (&keys[index], change.new.as_str())
+        })
+    }
+
+    /// Writes the Vorbis comment block of a FLAC file.
+    fn write_flac(&self) -> Result<(), String> {
+        let mut input = BufReader::new(
+            File::open(&self.file).map_err(|e| format!("Unable to open file: {e}"))?,
+        );
+        let read_error = |e: io::Error| format!("Unable to read file: {e}");
+
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic).map_err(read_error)?;
+        if &magic != b"fLaC" {
+            return Err("Missing FLAC header".to_owned());
+        }
+
+        let mut blocks = Vec::new();
+        loop {
+            let mut header = [0u8; 4];
+            input.read_exact(&mut header).map_err(read_error)?;
+            let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+            let mut data = vec![0u8; len];
+            input.read_exact(&mut data).map_err(read_error)?;
+            blocks.push((header[0] & 0x7F, data));
+
+            if header[0] & 0x80 != 0 {
+                break;
+            }
+        }
+        let old_size = blocks.iter().map(|(_, data)| 4 + data.len()).sum::<usize>() + 4;
+
+        let mut comment = match blocks
+            .iter()
+            .position(|(kind, _)| *kind == FLAC_COMMENT_BLOCK)
+        {
+            Some(index) => VorbisComment::parse(&blocks.remove(index).1)?,
+            None => VorbisComment::new(),
+        };
+        for (key, value) in self.updates(&VORBIS_KEYS) {
+            comment.set(key, value);
+        }
+        let comment = comment.to_bytes();
+        if comment.len() > FLAC_MAX_BLOCK_SIZE {
+            return Err("Tags are too large".to_owned());
+        }
+
+        /* The comment block goes right after STREAMINFO, which must be the first block */
+        blocks.insert(1.min(blocks.len()), (FLAC_COMMENT_BLOCK, comment));
+        blocks.retain(|(kind, _)| *kind != FLAC_PADDING_BLOCK);
+        let new_size = blocks.iter().map(|(_, data)| 4 + data.len()).sum::<usize>() + 4;
+
+        /* If the new blocks fit in the old space, the padding can absorb the difference */
+        let in_place = new_size + 4 <= old_size;
+        let padding = if in_place {
+            old_size - new_size - 4
+        } else {
+            FLAC_PADDING
+        };
+        blocks.push((FLAC_PADDING_BLOCK, vec![0u8; padding]));
+
+        let mut metadata = b"fLaC".to_vec();
+        for (index, (kind, data)) in blocks.iter().enumerate() {
+            let last = if index == blocks.len() - 1 { 0x80 } else { 0 };
+            metadata.push(kind | last);
+            metadata.extend(&(data.len() as u32).to_be_bytes()[1..]);
+            metadata.extend(data);
+        }
+
+        if in_place {
+            let mut output = OpenOptions::new()
+                .write(true)
+                .open(&self.file)
+                .map_err(|e| format!("Unable to open file for writing: {e}"))?;
+            return output
+                .write_all(&metadata)
+                .map_err(|e| format!("Unable to write file: {e}"));
+        }
+
+        replace_file(&self.file, |output| {
+            output.write_all(&metadata)?;
+            io::copy(&mut input, output)?;
+            Ok(())
+        })
+    }
+
+    /// Writes the comment header of an Ogg Vorbis or Opus file.
+    /// The whole file is rewritten, as the header may span multiple pages.
+    ///
+    /// # Arguments
+    /// * `magic` - Signature of the comment header packet
+    fn write_ogg(&self, magic: &[u8]) -> Result<(), String> {
+        let input = BufReader::new(
+            File::open(&self.file).map_err(|e| format!("Unable to open file: {e}"))?,
+        );
+        let mut reader = PacketReader::new(input);
+
+        /* The comment header is always the second packet of the first stream */
+        let mut first_serial = None;
+        let mut index = 0;
+
+        replace_file(&self.file, |output| {
+            let mut writer = PacketWriter::new(output);
+
+            while let Some(packet) = reader.read_packet().map_err(io::Error::other)? {
+                let serial = packet.stream_serial();
+                let absgp = packet.absgp_page();
+                let end = if packet.last_in_stream() {
+                    PacketWriteEndInfo::EndStream
+                } else if packet.last_in_page() {
+                    PacketWriteEndInfo::EndPage
+                } else {
+                    PacketWriteEndInfo::NormalPacket
+                };
+
+                let mut data = packet.data;
+                if *first_serial.get_or_insert(serial) == serial {
+                    if index == 1 {
+                        data = self.update_comment_packet(&data, magic)?;
+                    }
+                    index += 1;
+                }
+
+                writer.write_packet(data.into_boxed_slice(), serial, end, absgp)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Updates the comments in an Ogg comment header packet.
+    fn update_comment_packet(&self, packet: &[u8], magic: &[u8]) -> io::Result<Vec<u8>> {
+        if !packet.starts_with(magic) {
+            return Err(io::Error::other("Missing comment header"));
+        }
+
+        let mut comment = VorbisComment::parse(&packet[magic.len()..]).map_err(io::Error::other)?;
+        for (key, value) in self.updates(&VORBIS_KEYS) {
+            comment.set(key, value);
+        }
+
+        let mut result = magic.to_vec();
+        result.extend(comment.to_bytes());
+        Ok(result)
+    }
+
+    /// Writes the `LIST`/`INFO` chunk of a WAV file.
+    fn write_wav(&self) -> Result<(), String> {
+        let mut input = BufReader::new(
+            File::open(&self.file).map_err(|e| format!("Unable to open file: {e}"))?,
+        );
+        let read_error = |e: io::Error| format!("Unable to read file: {e}");
+
+        let mut header = [0u8; 12];
+        input.read_exact(&mut header).map_err(read_error)?;
+        if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+            return Err("Missing RIFF/WAVE header".to_owned());
+        }
+
+        /* (ID, offset, size) of every chunk */
+        let mut chunks = Vec::new();
+        let mut info = None;
+        loop {
+            let mut chunk_header = [0u8; 8];
+            match input.read_exact(&mut chunk_header) {
+                Ok(()) => (),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(read_error(e)),
+            }
+            let id: [u8; 4] = chunk_header[0..4].try_into().unwrap();
+            let size = u32::from_le_bytes(chunk_header[4..8].try_into().unwrap()) as u64;
+            let offset = input.stream_position().map_err(read_error)?;
+
+            if &id == b"LIST" && info.is_none() {
+                let mut data = vec![0u8; size as usize];
+                input.read_exact(&mut data).map_err(read_error)?;
+                if data.starts_with(b"INFO") {
+                    info = Some((chunks.len(), parse_info_chunk(&data[4..])));
+                }
+            }
+
+            chunks.push((id, offset, size));
+            /* Chunks are padded to an even size */
+            input
+                .seek(SeekFrom::Start(offset + size + (size & 1)))
+                .map_err(read_error)?;
+        }
+
+        let (position, mut items) = info.unwrap_or((chunks.len(), Vec::new()));
+        for (key, value) in self.updates(&RIFF_KEYS) {
+            items.retain(|(k, _)| k != *key);
+            let mut value = value.as_bytes().to_vec();
+            value.push(0);
+            items.push((**key, value));
+        }
+
+        let mut list = b"INFO".to_vec();
+        for (key, value) in &items {
+            list.extend(key);
+            list.extend((value.len() as u32).to_le_bytes());
+            list.extend(value);
+            if value.len() % 2 == 1 {
+                list.push(0);
+            }
+        }
+
+        replace_file(&self.file, |output| {
+            output.write_all(&header)?;
+
+            for (index, (id, offset, size)) in chunks.iter().enumerate() {
+                /* Replace the old chunk */
+                if index == position {
+                    write_chunk(output, b"LIST", &list)?;
+                    continue;
+                }
+
+                output.write_all(id)?;
+                output.write_all(&(*size as u32).to_le_bytes())?;
+                input.seek(SeekFrom::Start(*offset))?;
+                io::copy(&mut (&mut input).take(size + (size & 1)), output)?;
+            }
+            if position == chunks.len() {
+                write_chunk(output, b"LIST", &list)?;
+            }
+
+            /* Update the size in the RIFF header */
+            let size = output.stream_position()? - 8;
+            output.seek(SeekFrom::Start(4))?;
+            output.write_all(&(size as u32).to_le_bytes())?;
+            Ok(())
+        })
+    }
+
+    /// Writes the APEv2 tag of a WavPack or Monkey's Audio file.
+    /// The tag is replaced in-place, the audio data is not rewritten.
+    fn write_ape(&self) -> Result<(), String> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.file)
+            .map_err(|e| format!("Unable to open file for writing: {e}"))?;
+
+        let read_error = |e: io::Error| format!("Unable to read tags: {e}");
+        let mut tag = ApeTag::read(&mut file).map_err(read_error)?;
+        let position = ApeTag::locate(&mut file).map_err(read_error)?;
+        for (key, value) in self.updates(&APE_KEYS) {
+            tag.set(key, value);
+        }
+
+        /* Keep the ID3v1 tag following the APEv2 tag (if there's one) */
+        let mut trailer = Vec::new();
+        file.seek(SeekFrom::Start(position.end))
+            .and_then(|_| file.read_to_end(&mut trailer))
+            .map_err(read_error)?;
+
+        file.set_len(position.start)
+            .and_then(|_| file.seek(SeekFrom::End(0)))
+            .and_then(|_| file.write_all(&tag.to_bytes()))
+            .and_then(|_| file.write_all(&trailer))
+            .map_err(|e| format!("Unable to write file: {e}"))
+    }
+}
+
+/// Parses the sub-chunks of a `LIST`/`INFO` chunk (without the `INFO` signature).
+fn parse_info_chunk(data: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+    let mut items = Vec::new();
+    let mut pos = 0;
+
+    while pos + 8 <= data.len() {
+        let id: [u8; 4] = data[pos..pos + 4].try_into().unwrap();
+        let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let Some(value) = data.get(pos + 8..pos + 8 + size) else {
+            break;
+        };
+
+        items.push((id, value.to_vec()));
+        pos += 8 + size + (size & 1);
+    }
+
+    items
+}
+
+/// Writes a RIFF chunk (padded to an even size).
+fn write_chunk<W: Write>(output: &mut W, id: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    output.write_all(id)?;
+    output.write_all(&(data.len() as u32).to_le_bytes())?;
+    output.write_all(data)?;
+    if data.len() % 2 == 1 {
+        output.write_all(&[0])?;
+    }
+    Ok(())
+}
+
+/// Replaces a file with new contents.
+///
+/// The contents are written to a temporary file next to the original file, which is then
+/// renamed over the original. If `write` fails, the original file is left untouched.
+fn replace_file<F>(file: &str, write: F) -> Result<(), String>
+where
+    F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+{
+    let temp = format!("{file}.tmp");
+    let result = File::create(&temp).and_then(|output| {
+        let mut output = BufWriter::new(output);
+        write(&mut output)?;
+        output.flush()?;
+
+        let permissions = fs::metadata(file)?.permissions();
+        fs::set_permissions(&temp, permissions)?;
+        fs::rename(&temp, file)
+    });
+
+    result.map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        format!("Unable to write file: {e}")
+    })
+}
@@ -0,0 +1,138 @@
+use rodio::Source;
+use std::mem;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Amount of samples sent to the listeners at once.
+const CHUNK_SIZE: usize = 1024;
+/// Amount of chunks a listener can fall behind, before chunks are dropped.
+const MAX_PENDING: usize = 32;
+
+/// A part of the played audio, sent to the listeners of [`Taps`](Taps).
+#[derive(Debug)]
+pub struct TapChunk {
+    /// The samples (interleaved)
+    pub samples: Vec<f32>,
+    /// Amount of channels
+    pub channels: u16,
+    /// Sample rate
+    pub sample_rate: u32,
+}
+
+/// Listeners of the played audio (e.g. visualizers), kept for the whole session.
+/// Every [`Tap`](Tap) created from it sends the audio it plays to all of them.
+#[derive(Debug, Clone, Default)]
+pub struct Taps {
+    /// Sends the audio to the listeners
+    listeners: Arc<Mutex<Vec<SyncSender<Arc<TapChunk>>>>>,
+}
+
+/// A [`Source`](Source) which sends the audio passing through it to the listeners of
+/// [`Taps`](Taps), as it's played.
+///
+/// ### Notes
+/// Listeners which fall behind miss chunks of the audio, so they can't slow down playback.
+pub struct Tap<S: Source<Item = f32>> {
+    /// Input source
+    input: S,
+    /// Samples which weren't sent yet
+    chunk: Vec<f32>,
+    /// Where the samples are sent
+    taps: Taps,
+}
+
+impl Taps {
+    /// Adds a listener, which receives the played audio until the receiver is dropped.
+    pub fn subscribe(&self) -> Receiver<Arc<TapChunk>> {
+        let (sender, receiver) = sync_channel(MAX_PENDING);
+        self.listeners.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Sends a chunk to every listener, and forgets the ones which are gone.
+    fn send(&self, chunk: TapChunk) {
+        let chunk = Arc::new(chunk);
+        self.listeners.lock().unwrap().retain(|listener| {
+            match listener.try_send(Arc::clone(&chunk)) {
+                Ok(()) | Err(TrySendError::Full(_)) => true,
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+    }
+
+    /// Returns whether anyone is listening.
+    fn is_empty(&self) -> bool {
+        self.listeners.lock().unwrap().is_empty()
+    }
+}
+
+impl<S: Source<Item = f32>> Tap<S> {
+    /// Creates a new tap.
+    ///
+    /// # Arguments
+    /// * `input` - The played audio
+    /// * `taps` - The listeners to send it to
+    pub fn new(input: S, taps: Taps) -> Self {
+        Self {
+            input,
+            chunk: Vec::with_capacity(CHUNK_SIZE),
+            taps,
+        }
+    }
+
+    /// Sends the collected samples to the listeners.
+    fn flush(&mut self) {
+        if self.chunk.is_empty() {
+            return;
+        }
+
+        let samples = mem::replace(&mut self.chunk, Vec::with_capacity(CHUNK_SIZE));
+        /* Without listeners, the samples are simply thrown away */
+        if !self.taps.is_empty() {
+            self.taps.send(TapChunk {
+                samples,
+                channels: self.input.channels(),
+                sample_rate: self.input.sample_rate(),
+            });
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Tap<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let Some(sample) = self.input.next() else {
+            self.flush();
+            return None;
+        };
+
+        self.chunk.push(sample);
+        /* Chunks contain whole frames */
+        let channels = self.input.channels().max(1) as usize;
+        if self.chunk.len() >= CHUNK_SIZE && self.chunk.len().is_multiple_of(channels) {
+            self.flush();
+        }
+
+        Some(sample)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Tap<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
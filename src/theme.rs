@@ -0,0 +1,132 @@
+use crate::visualizer::{Color, DEFAULT_COLORS};
+use ncurses::COLORS;
+use serde::{Deserialize, Serialize};
+
+/// Amount of colors of terminals which take any RGB color ("direct color" terminals).
+const DIRECT_COLORS: i32 = 1 << 24;
+
+/// A preset of the colors and attributes of the TUI.
+///
+/// Every indicator also has a cue which doesn't depend on colors (like its text, or being reversed),
+/// so nothing is lost with [`Theme::NoColor`], or to colorblindness.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    /// Green, yellow and red
+    #[default]
+    Default,
+    /// Bright, bold colors, and reversed highlights
+    HighContrast,
+    /// Blue, cyan and yellow, which are told apart without seeing red and green (deuteranopia)
+    Deuteranopia,
+    /// No colors, only attributes (dim, normal and bold)
+    NoColor,
+}
+
+impl Theme {
+    /// Returns the colors of the visualizer, from the quietest to the loudest parts of the audio.
+    pub fn visualizer_colors(&self) -> [Color; 3] {
+        match self {
+            Self::Default | Self::NoColor => DEFAULT_COLORS,
+            Self::HighContrast => [Color::White, Color::Yellow, Color::Red],
+            Self::Deuteranopia => [Color::Blue, Color::Cyan, Color::Yellow],
+        }
+    }
+
+    /// Returns the color of the clipping indicator.
+    pub fn clip_color(&self) -> Color {
+        match self {
+            Self::Deuteranopia => Color::Yellow,
+            _ => Color::Red,
+        }
+    }
+
+    /// Returns whether colors are used at all.
+    pub fn has_colors(&self) -> bool {
+        *self != Self::NoColor
+    }
+
+    /// Returns whether highlights (like the active line of the lyrics) are reversed, to stand out more.
+    pub fn reverse_highlights(&self) -> bool {
+        *self == Self::HighContrast
+    }
+}
+
+/// How many colors the terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// The 8 basic colors (or none at all)
+    Basic,
+    /// The 256 colors of `xterm`
+    Extended,
+    /// Any RGB color
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// Finds out how many colors the terminal can show, from its `terminfo` entry.
+    /// Should only be called after the colors were started.
+    ///
+    /// ### Notes
+    /// Truecolor is only used if the `terminfo` entry supports it (like `xterm-direct`),
+    /// otherwise the closest of the 256 colors are used.
+    pub fn detect() -> Self {
+        match COLORS() {
+            colors if colors >= DIRECT_COLORS => Self::TrueColor,
+            colors if colors >= 256 => Self::Extended,
+            _ => Self::Basic,
+        }
+    }
+
+    /// Returns the number of the terminal color closest to an RGB color.
+    ///
+    /// ### Notes
+    /// Returns `None` for [`ColorDepth::Basic`], which can't show gradients.
+    pub fn color(&self, (red, green, blue): (u8, u8, u8)) -> Option<i32> {
+        /* The 6x6x6 color cube of xterm starts at color 16 */
+        let cube = |value: u8| (value as i32 * 5 + 127) / 255;
+        match self {
+            Self::Basic => None,
+            Self::Extended => Some(16 + 36 * cube(red) + 6 * cube(green) + cube(blue)),
+            Self::TrueColor => Some((red as i32) << 16 | (green as i32) << 8 | blue as i32),
+        }
+    }
+}
+
+/// Returns the RGB value of a [`Color`](Color), as most terminals show it.
+fn rgb(color: Color) -> (u8, u8, u8) {
+    match color {
+        Color::Black => (40, 40, 40),
+        Color::Red => (230, 50, 40),
+        Color::Green => (50, 200, 60),
+        Color::Yellow => (230, 200, 40),
+        Color::Blue => (50, 100, 230),
+        Color::Magenta => (200, 60, 200),
+        Color::Cyan => (40, 200, 210),
+        Color::White => (230, 230, 230),
+    }
+}
+
+/// Blends three colors into a gradient of `steps` colors, from the first to the last one
+/// (through the one in the middle).
+pub fn gradient(colors: [Color; 3], steps: usize) -> Vec<(u8, u8, u8)> {
+    let blend = |from: u8, to: u8, amount: f32| {
+        (from as f32 + (to as f32 - from as f32) * amount).round() as u8
+    };
+
+    (0..steps)
+        .map(|step| {
+            let position = step as f32 / steps.saturating_sub(1).max(1) as f32 * 2.0;
+            let (from, to) = match position < 1.0 {
+                true => (rgb(colors[0]), rgb(colors[1])),
+                false => (rgb(colors[1]), rgb(colors[2])),
+            };
+            let amount = position - position.floor().min(1.0);
+            (
+                blend(from.0, to.0, amount),
+                blend(from.1, to.1, amount),
+                blend(from.2, to.2, amount),
+            )
+        })
+        .collect()
+}
@@ -3,10 +3,14 @@ use std::time::{Duration, Instant};
 /// Represents a timer/countdown.
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub struct Timer {
-    /// Time at which the timer was started/created.
-    start: Instant,
+    /// Time at which the timer was started/created, or resumed (`None` while it's paused).
+    start: Option<Instant>,
+    /// Time which passed before the timer was last paused.
+    elapsed: Duration,
     /// Duration of the timer.
     len: Duration,
+    /// Whether the timer starts again once it expired (see [`tick()`](Timer::tick)).
+    repeat: bool,
 }
 
 /// Methods for [`Timer`](Timer).
@@ -14,13 +18,93 @@ impl Timer {
     /// Creates a new timer with the given duration.
     pub fn new(len: Duration) -> Self {
         Timer {
-            start: Instant::now(),
+            start: Some(Instant::now()),
+            elapsed: Duration::ZERO,
             len,
+            repeat: false,
         }
     }
 
+    /// Creates a new timer with the given duration, which starts again each time it expired
+    /// (see [`tick()`](Timer::tick)).
+    pub fn repeating(len: Duration) -> Self {
+        Timer {
+            repeat: true,
+            ..Timer::new(len)
+        }
+    }
+
+    /// Returns how long the timer has been running (not counting the time it was paused).
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed + self.start.map_or(Duration::ZERO, |start| start.elapsed())
+    }
+
     /// Returns whether the timer has expired.
     pub fn expired(&self) -> bool {
-        self.start.elapsed() >= self.len
+        self.elapsed() >= self.len
+    }
+
+    /// Returns how long it takes until the timer expires (zero if it already has).
+    pub fn remaining(&self) -> Duration {
+        self.len.saturating_sub(self.elapsed())
+    }
+
+    /// Returns how much of the timer has passed, from `0.0` (just started) to `1.0` (expired).
+    pub fn progress(&self) -> f32 {
+        match self.len.is_zero() {
+            true => 1.0,
+            false => (self.elapsed().as_secs_f32() / self.len.as_secs_f32()).min(1.0),
+        }
+    }
+
+    /// Returns whether the timer has expired, and starts it again if it did and it's
+    /// [repeating](Timer::repeating).
+    ///
+    /// ### Notes
+    /// A repeating timer starts again when this is called, not when it expired,
+    /// so it doesn't catch up after it wasn't checked for a while.
+    pub fn tick(&mut self) -> bool {
+        let expired = self.expired();
+        if expired && self.repeat {
+            self.restart();
+        }
+        expired
+    }
+
+    /// Starts the timer again from zero (it keeps running if it was paused).
+    pub fn restart(&mut self) {
+        self.start = Some(Instant::now());
+        self.elapsed = Duration::ZERO;
+    }
+
+    /// Starts the timer again from zero, with another duration.
+    pub fn reset(&mut self, len: Duration) {
+        self.len = len;
+        self.restart();
+    }
+
+    /// Makes the timer expire right away (e.g. so a repeating timer fires on the next tick).
+    pub fn expire(&mut self) {
+        self.elapsed = self.len;
+        self.start = self.start.map(|_| Instant::now());
+    }
+
+    /// Pauses the timer, so it doesn't expire until it's resumed.
+    pub fn pause(&mut self) {
+        if let Some(start) = self.start.take() {
+            self.elapsed += start.elapsed();
+        }
+    }
+
+    /// Resumes the timer after it was paused.
+    pub fn resume(&mut self) {
+        if self.start.is_none() {
+            self.start = Some(Instant::now());
+        }
+    }
+
+    /// Returns whether the timer is paused.
+    pub fn is_paused(&self) -> bool {
+        self.start.is_none()
     }
 }
@@ -0,0 +1,236 @@
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// Maximum time to wait for a response of a translation server.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Name of the directory of cached translations, inside the cache directory.
+const CACHE_DIR_NAME: &str = "translations";
+
+/// Where lyrics are translated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Backend {
+    /// A [LibreTranslate](https://libretranslate.com) compatible server, with an optional API key
+    Server {
+        url: String,
+        api_key: Option<String>,
+    },
+    /// An offline dictionary: a JSON object of lines (or single words) and their translations
+    Dictionary(PathBuf),
+}
+
+/// Translates lyrics into one language.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Translator {
+    /// Where the lyrics are translated
+    backend: Backend,
+    /// Code of the language the lyrics are translated into (e.g. `en`)
+    language: String,
+}
+
+/// A request of the LibreTranslate API.
+#[derive(Serialize)]
+struct TranslateRequest<'a> {
+    q: &'a [String],
+    source: &'a str,
+    target: &'a str,
+    format: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key: Option<&'a str>,
+}
+
+/// The response of the LibreTranslate API.
+#[derive(Deserialize)]
+struct TranslateResponse {
+    #[serde(rename = "translatedText")]
+    translated_text: Option<Vec<String>>,
+    error: Option<String>,
+}
+
+impl Translator {
+    /// Creates a translator.
+    ///
+    /// # Arguments
+    /// * `endpoint` - URL of a translation server (`http://` or `https://`),
+    ///   or the path to an offline dictionary
+    /// * `api_key` - API key of the translation server (not used by dictionaries)
+    /// * `language` - Code of the language to translate into (e.g. `en`)
+    pub fn new(endpoint: &str, api_key: Option<String>, language: String) -> Self {
+        let backend = match endpoint.starts_with("http://") || endpoint.starts_with("https://") {
+            true => Backend::Server {
+                url: endpoint.to_owned(),
+                api_key,
+            },
+            false => Backend::Dictionary(PathBuf::from(endpoint)),
+        };
+
+        Self { backend, language }
+    }
+
+    /// Translates lines of lyrics.
+    /// Returns a translation for each line, which is empty if the line has none.
+    ///
+    /// # Arguments
+    /// * `lines` - The lines to translate
+    /// * `source` - Code of the language the lyrics are in, or `None` to detect it
+    ///   (dictionaries don't need it)
+    ///
+    /// ## Errors
+    /// Fails if the server can't be reached or returns an error, or if the dictionary can't be read.
+    pub fn translate(&self, lines: &[String], source: Option<&str>) -> Result<Vec<String>, String> {
+        match &self.backend {
+            Backend::Server { url, api_key } => {
+                self.translate_online(url, api_key.as_deref(), lines, source)
+            }
+            Backend::Dictionary(path) => translate_offline(path, lines),
+        }
+    }
+
+    /// Translates lines of lyrics on a LibreTranslate server, all of them in one request.
+    /// The language of the lyrics is detected by the server if it isn't known.
+    fn translate_online(
+        &self,
+        url: &str,
+        api_key: Option<&str>,
+        lines: &[String],
+        source: Option<&str>,
+    ) -> Result<Vec<String>, String> {
+        let request = TranslateRequest {
+            q: lines,
+            source: source.unwrap_or("auto"),
+            target: &self.language,
+            format: "text",
+            api_key,
+        };
+
+        let response: TranslateResponse = ureq::AgentBuilder::new()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .post(url)
+            .send_json(request)
+            .or_else(|e| match e {
+                /* The API also describes errors in the response body */
+                ureq::Error::Status(_, response) => Ok(response),
+                e => Err(format!("Request failed: {e}")),
+            })?
+            .into_json()
+            .map_err(|e| format!("Invalid response: {e}"))?;
+
+        match (response.translated_text, response.error) {
+            (_, Some(error)) => Err(error),
+            (Some(translated), None) if translated.len() == lines.len() => Ok(translated),
+            _ => Err("Invalid response: the lines don't match".to_owned()),
+        }
+    }
+}
+
+/// Translates lines of lyrics using an offline dictionary.
+/// Lines which aren't in the dictionary are translated word by word (unknown words are kept),
+/// and lines without any known words have no translation.
+fn translate_offline(path: &Path, lines: &[String]) -> Result<Vec<String>, String> {
+    let file = File::open(path).map_err(|e| format!("Unable to open {}: {e}", path.display()))?;
+    let dictionary: HashMap<String, String> = serde_json::from_reader(file)
+        .map_err(|e| format!("Invalid dictionary {}: {e}", path.display()))?;
+    let dictionary: HashMap<String, &String> = dictionary
+        .iter()
+        .map(|(text, translation)| (text.to_lowercase(), translation))
+        .collect();
+
+    let translate_word = |word: &str| {
+        let bare = word.trim_matches(|c: char| !c.is_alphanumeric());
+        dictionary
+            .get(&bare.to_lowercase())
+            .map(|translation| word.replacen(bare, translation, 1))
+    };
+
+    Ok(lines
+        .iter()
+        .map(|line| {
+            if let Some(translation) = dictionary.get(&line.trim().to_lowercase()) {
+                return translation.to_string();
+            }
+
+            let words: Vec<(&str, Option<String>)> = line
+                .split_whitespace()
+                .map(|word| (word, translate_word(word)))
+                .collect();
+            match words.iter().any(|(_, translation)| translation.is_some()) {
+                true => words
+                    .into_iter()
+                    .map(|(word, translation)| translation.unwrap_or_else(|| word.to_owned()))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                false => String::new(),
+            }
+        })
+        .collect())
+}
+
+/// Returns where the translation of a track's lyrics is cached.
+/// The lyrics are part of the name, so changed lyrics are translated again.
+fn cache_path(track: &str, language: &str, lines: &[String]) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    (track, lines).hash(&mut hasher);
+
+    paths::cache_dir()
+        .join(CACHE_DIR_NAME)
+        .join(format!("{:016x}.{language}.json", hasher.finish()))
+}
+
+/// Translates the lyrics of a track in a background thread, so playback is not blocked.
+/// Translations of servers are cached per track and language, so they're only requested once
+/// (dictionaries are read again, as they may have changed).
+///
+/// The returned channel receives a single message: a translation for each line
+/// (empty if the line has none), or an error message.
+///
+/// # Arguments
+/// * `translator` - Translates the lyrics
+/// * `track` - Path of the track, the translation is cached for it
+/// * `lines` - The lines of the lyrics
+/// * `source` - Code of the language the lyrics are in, or `None` to detect it
+pub fn translate_in_background(
+    translator: Translator,
+    track: String,
+    lines: Vec<String>,
+    source: Option<String>,
+) -> Receiver<Result<Vec<String>, String>> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let path = match translator.backend {
+            Backend::Server { .. } => Some(cache_path(&track, &translator.language, &lines)),
+            Backend::Dictionary(_) => None,
+        };
+        let cached = path
+            .as_ref()
+            .and_then(|path| File::open(path).ok())
+            .and_then(|file| serde_json::from_reader::<_, Vec<String>>(file).ok())
+            .filter(|cached| cached.len() == lines.len());
+        if let Some(cached) = cached {
+            let _ = tx.send(Ok(cached));
+            return;
+        }
+
+        let result = translator.translate(&lines, source.as_deref());
+        if let (Ok(translated), Some(path)) = (&result, path) {
+            /* The translation is still shown if it can't be cached */
+            if let Some(dir) = path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            if let Ok(file) = File::create(&path) {
+                let _ = serde_json::to_writer(file, translated);
+            }
+        }
+        let _ = tx.send(result);
+    });
+
+    rx
+}
@@ -0,0 +1,705 @@
+use crate::tap::{TapChunk, Taps};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::ops::RangeInclusive;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How fast the bars fall, unless configured otherwise.
+pub const DEFAULT_GRAVITY: f32 = 1.0;
+/// How much the bars are smoothed, unless configured otherwise.
+pub const DEFAULT_SMOOTHING: f32 = 0.7;
+/// Amount of samples analyzed at once, unless configured otherwise.
+pub const DEFAULT_FFT_SIZE: usize = 2048;
+/// Allowed amounts of samples analyzed at once (which must also be a power of 2).
+pub const FFT_SIZES: RangeInclusive<usize> = 256..=16384;
+/// How often the visualizers are updated (per second), unless configured otherwise.
+pub const DEFAULT_FRAME_RATE: u32 = 60;
+/// Allowed frame rates of the visualizers.
+pub const FRAME_RATES: RangeInclusive<u32> = 1..=100;
+/// Colors of the visualizers, unless configured otherwise.
+pub const DEFAULT_COLORS: [Color; 3] = [Color::Green, Color::Yellow, Color::Red];
+/// Lowest frequency shown (in Hz).
+const MIN_FREQUENCY: f32 = 50.0;
+/// Highest frequency shown (in Hz).
+const MAX_FREQUENCY: f32 = 12000.0;
+/// Levels this far below full scale (in dB) are shown as empty bars.
+const RANGE_DB: f32 = 60.0;
+/// How fast the bars accelerate while falling, in heights per second squared (at gravity 1).
+const FALL_ACCELERATION: f32 = 6.0;
+/// Width of the spectrum's bars (unless their amount is configured), and of the space between them (in characters).
+const BAR: (usize, usize) = (2, 1);
+/// Amount of frames shown by the scope.
+const SCOPE_FRAMES: usize = 2048;
+/// Length of the audio the VU meter averages.
+const VU_WINDOW: Duration = Duration::from_millis(300);
+/// Levels this far below full scale (in dB) are shown as an empty VU meter.
+const VU_RANGE_DB: f32 = 48.0;
+/// How long the VU meter holds its peaks.
+const VU_PEAK_HOLD: Duration = Duration::from_millis(1500);
+/// If no audio was played for this long (e.g. while paused), the visualizers show silence.
+const IDLE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A visualizer of the played audio, which can be shown in the [`Slot`](Slot) below the lyrics.
+pub trait Visualizer {
+    /// Updates the visualizer with the audio heard since the last update,
+    /// and draws it into `frame` (which is empty, and as large as the slot).
+    ///
+    /// # Arguments
+    /// * `frame` - Where to draw the visualizer
+    /// * `elapsed` - Time since the last update
+    fn render(&mut self, frame: &mut Frame, elapsed: Duration);
+}
+
+/// Which visualizer is shown below the lyrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VisualizerKind {
+    /// Bars showing the level of the frequencies, see [`Spectrum`](Spectrum)
+    #[default]
+    Spectrum,
+    /// The level of the left and right channels, see [`VuMeter`](VuMeter)
+    Vu,
+    /// The waveform, see [`Scope`](Scope)
+    Scope,
+}
+
+/// The space below the lyrics, showing one [`Visualizer`](Visualizer) at a time.
+pub struct Slot {
+    /// The shown visualizer
+    visualizer: Box<dyn Visualizer>,
+    /// Which visualizer is shown
+    kind: VisualizerKind,
+    /// Time between updates of the visualizer
+    interval: Duration,
+    /// When the visualizer was last updated
+    updated: Instant,
+}
+
+/// A color of the terminal, in which the visualizers can be drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+/// Settings of the [`Spectrum`](Spectrum).
+#[derive(Debug, Clone, Copy)]
+pub struct SpectrumSettings {
+    /// Amount of samples analyzed at once (a power of 2), more samples separate
+    /// the low frequencies better but react slower
+    pub fft_size: usize,
+    /// Amount of bars, or `None` to fill the width with bars of the default width
+    pub bars: Option<usize>,
+    /// How fast the bars fall (`1.0` is the default speed)
+    pub gravity: f32,
+    /// How much the bars are smoothed (from `0.0` to `1.0`)
+    pub smoothing: f32,
+}
+
+/// Characters drawn by a [`Visualizer`](Visualizer), as rows from the top to the bottom.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// Rows of characters
+    pub cells: Vec<Vec<Cell>>,
+}
+
+/// A character drawn by a [`Visualizer`](Visualizer).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    /// The character
+    pub symbol: char,
+    /// How loud the part of the audio it shows is, which decides its color
+    pub heat: Heat,
+    /// Where it is from the quietest (`0.0`) to the loudest (`1.0`) parts,
+    /// which decides its color on terminals with more than 8 colors
+    pub level: f32,
+}
+
+/// How loud the part of the audio shown by a [`Cell`](Cell) is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Heat {
+    /// Quiet (green by default)
+    Low,
+    /// In between (yellow by default)
+    Medium,
+    /// Loud (red by default)
+    High,
+}
+
+/// The audio which was heard recently, received from [`Taps`](Taps).
+struct Heard {
+    /// Receives the played audio
+    chunks: Receiver<Arc<TapChunk>>,
+    /// Received chunks (and when), waiting until they're heard
+    pending: VecDeque<(Instant, Arc<TapChunk>)>,
+    /// Latency of the output, the audio is heard this long after it's received
+    latency: Duration,
+    /// The last frames which were heard, as left and right samples
+    frames: VecDeque<[f32; 2]>,
+    /// Amount of frames kept
+    capacity: usize,
+    /// Sample rate of the audio
+    sample_rate: u32,
+    /// When audio was last heard
+    heard: Instant,
+}
+
+/// A spectrum analyzer, which shows the played audio as bars (like [cava](https://github.com/karlstav/cava)).
+///
+/// The bars are spread over the frequencies logarithmically, and their height is the level
+/// of those frequencies (in dB). They rise smoothly, and fall with a configurable gravity.
+pub struct Spectrum {
+    /// The audio to analyze
+    heard: Heard,
+    /// Height of the bars (from `0.0` to `1.0`)
+    bars: Vec<f32>,
+    /// How fast the bars are falling
+    falling: Vec<f32>,
+    /// How the bars are analyzed and drawn
+    settings: SpectrumSettings,
+}
+
+/// A VU meter, which shows the loudness of the left and right channels as horizontal bars.
+///
+/// The loudness is the RMS level over [`VU_WINDOW`](VU_WINDOW), and the highest level
+/// of the last [`VU_PEAK_HOLD`](VU_PEAK_HOLD) is marked on each bar.
+pub struct VuMeter {
+    /// The audio to measure
+    heard: Heard,
+    /// The held peaks of the channels (from `0.0` to `1.0`), and when they were reached
+    peaks: [(f32, Instant); 2],
+}
+
+/// An oscilloscope, which draws the waveform of the played audio with braille characters.
+///
+/// Stereo audio can also be drawn as a lissajous figure (a vectorscope): the sum of the
+/// channels goes up and down, and their difference left and right. Mono audio is a vertical
+/// line, and the wider the figure, the wider the stereo image.
+pub struct Scope {
+    /// The audio to draw
+    heard: Heard,
+    /// Whether to draw a lissajous figure, instead of the waveform
+    lissajous: bool,
+}
+
+/// Dots which are drawn with braille characters, 2 dots wide and 4 dots high each.
+struct Braille {
+    /// Rows of characters, as the bits of their dots (from `0x2800`)
+    cells: Vec<Vec<u8>>,
+}
+
+impl VisualizerKind {
+    /// Returns the name of the visualizer, shown when switching to it.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Spectrum => "Spectrum",
+            Self::Vu => "VU meter",
+            Self::Scope => "Scope",
+        }
+    }
+
+    /// Returns the visualizer shown after this one, or `None` if the slot is empty then.
+    pub fn next(self) -> Option<Self> {
+        match self {
+            Self::Spectrum => Some(Self::Vu),
+            Self::Vu => Some(Self::Scope),
+            Self::Scope => None,
+        }
+    }
+}
+
+impl Slot {
+    /// Shows a visualizer in the slot.
+    ///
+    /// # Arguments
+    /// * `visualizer` - The visualizer to show
+    /// * `kind` - Which visualizer it is
+    /// * `frame_rate` - How often the visualizer is updated (per second)
+    pub fn new(visualizer: Box<dyn Visualizer>, kind: VisualizerKind, frame_rate: u32) -> Self {
+        Self {
+            visualizer,
+            kind,
+            interval: Duration::from_secs(1) / frame_rate.max(1),
+            updated: Instant::now(),
+        }
+    }
+
+    /// Returns which visualizer is shown.
+    pub fn kind(&self) -> VisualizerKind {
+        self.kind
+    }
+
+    /// Draws the visualizer into a frame of `width` by `height` characters.
+    /// Returns `None` if it's too early to draw it again (so it's drawn at a steady rate).
+    pub fn render(&mut self, width: usize, height: usize) -> Option<Frame> {
+        let elapsed = self.updated.elapsed();
+        if elapsed < self.interval {
+            return None;
+        }
+        self.updated = Instant::now();
+
+        let mut frame = Frame::new(width, height);
+        self.visualizer.render(&mut frame, elapsed);
+        Some(frame)
+    }
+}
+
+impl Frame {
+    /// Creates an empty frame, `width` characters wide and `height` characters high.
+    fn new(width: usize, height: usize) -> Self {
+        let blank = Cell {
+            symbol: ' ',
+            heat: Heat::Low,
+            level: 0.0,
+        };
+        Self {
+            cells: vec![vec![blank; width]; height],
+        }
+    }
+
+    /// Returns the width of the frame (in characters).
+    fn width(&self) -> usize {
+        self.cells.first().map_or(0, Vec::len)
+    }
+
+    /// Returns the height of the frame (in characters).
+    fn height(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Draws a character, unless it's outside of the frame.
+    fn set(&mut self, x: usize, y: usize, symbol: char, heat: Heat, level: f32) {
+        if let Some(cell) = self.cells.get_mut(y).and_then(|row| row.get_mut(x)) {
+            *cell = Cell {
+                symbol,
+                heat,
+                level,
+            };
+        }
+    }
+}
+
+impl Heard {
+    /// Starts listening to the played audio.
+    fn new(taps: &Taps, latency: Duration, capacity: usize) -> Self {
+        Self {
+            chunks: taps.subscribe(),
+            pending: VecDeque::new(),
+            latency,
+            frames: VecDeque::from(vec![[0.0; 2]; capacity]),
+            capacity,
+            sample_rate: 44100,
+            heard: Instant::now(),
+        }
+    }
+
+    /// Takes the received audio which was heard by now.
+    fn receive(&mut self) {
+        self.pending
+            .extend(self.chunks.try_iter().map(|chunk| (Instant::now(), chunk)));
+
+        while let Some((received, chunk)) = self.pending.front() {
+            if received.elapsed() < self.latency {
+                break;
+            }
+
+            let channels = chunk.channels.max(1) as usize;
+            for frame in chunk.samples.chunks(channels) {
+                self.frames
+                    .push_back([frame[0], *frame.get(1).unwrap_or(&frame[0])]);
+            }
+            self.sample_rate = chunk.sample_rate;
+            self.heard = Instant::now();
+            self.pending.pop_front();
+        }
+
+        let excess = self.frames.len().saturating_sub(self.capacity);
+        self.frames.drain(..excess);
+    }
+
+    /// Returns whether nothing was heard for a while (e.g. because playback is paused).
+    fn is_idle(&self) -> bool {
+        self.heard.elapsed() >= IDLE_TIMEOUT
+    }
+
+    /// Returns the last `count` frames heard, or silence if nothing was heard for a while.
+    fn last(&self, count: usize) -> Vec<[f32; 2]> {
+        let count = count.min(self.frames.len());
+        match self.is_idle() {
+            true => vec![[0.0; 2]; count],
+            false => self
+                .frames
+                .range(self.frames.len() - count..)
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+impl Spectrum {
+    /// Creates a spectrum analyzer, which listens to the played audio until it's dropped.
+    ///
+    /// # Arguments
+    /// * `taps` - The played audio
+    /// * `latency` - Latency of the output, so the bars match what's heard
+    /// * `settings` - How the bars are analyzed and drawn
+    ///
+    /// ## Panics
+    /// Panics if the FFT size isn't a power of 2.
+    pub fn new(taps: &Taps, latency: Duration, settings: SpectrumSettings) -> Self {
+        assert!(settings.fft_size.is_power_of_two());
+
+        Self {
+            heard: Heard::new(taps, latency, settings.fft_size),
+            bars: Vec::new(),
+            falling: Vec::new(),
+            settings: SpectrumSettings {
+                gravity: settings.gravity.max(0.0),
+                smoothing: settings.smoothing.clamp(0.0, 0.99),
+                ..settings
+            },
+        }
+    }
+
+    /// Moves the bars towards the levels of the audio heard since the last update.
+    fn update(&mut self, count: usize, elapsed: Duration) {
+        self.heard.receive();
+        let levels = match self.heard.is_idle() {
+            true => vec![0.0; count],
+            false => self.analyze(count),
+        };
+
+        self.bars.resize(count, 0.0);
+        self.falling.resize(count, 0.0);
+        let elapsed = elapsed.as_secs_f32();
+        for ((bar, falling), level) in self.bars.iter_mut().zip(&mut self.falling).zip(levels) {
+            if level >= *bar {
+                *bar += (level - *bar) * (1.0 - self.settings.smoothing);
+                *falling = 0.0;
+            } else {
+                *falling += FALL_ACCELERATION * self.settings.gravity * elapsed;
+                *bar = (*bar - *falling * elapsed).max(level);
+            }
+        }
+    }
+
+    /// Returns the level of `count` frequency bands of the heard audio (from `0.0` to `1.0`).
+    fn analyze(&self, count: usize) -> Vec<f32> {
+        let size = self.settings.fft_size;
+        /* Hann window */
+        let mut bins: Vec<(f32, f32)> = self
+            .heard
+            .frames
+            .iter()
+            .enumerate()
+            .map(|(i, [left, right])| {
+                let window = 0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos();
+                ((left + right) / 2.0 * window, 0.0)
+            })
+            .collect();
+        fft(&mut bins);
+
+        /* A full scale sine wave peaks at a quarter of the size, after windowing */
+        let full_scale = size as f32 / 4.0;
+        let sample_rate = self.heard.sample_rate as f32;
+        let resolution = sample_rate / size as f32;
+        let max_frequency = MAX_FREQUENCY.min(sample_rate / 2.0);
+        let ratio = (max_frequency / MIN_FREQUENCY).powf(1.0 / count.max(1) as f32);
+
+        (0..count)
+            .map(|bar| {
+                let low = MIN_FREQUENCY * ratio.powi(bar as i32);
+                /* Every band contains at least one bin */
+                let first = ((low / resolution) as usize).max(1);
+                let last = ((low * ratio / resolution) as usize).max(first + 1);
+
+                let magnitude = bins[first..last.min(size / 2)]
+                    .iter()
+                    .map(|(re, im)| (re * re + im * im).sqrt())
+                    .fold(0.0, f32::max);
+                let db = 20.0 * (magnitude / full_scale).max(f32::MIN_POSITIVE).log10();
+                ((db + RANGE_DB) / RANGE_DB).clamp(0.0, 1.0)
+            })
+            .collect()
+    }
+}
+
+impl Visualizer for Spectrum {
+    /// Draws the bars from the bottom of the frame, colored by their height.
+    /// Unicode characters 0x2581 - 0x2588 are used to draw eighths of a row.
+    fn render(&mut self, frame: &mut Frame, elapsed: Duration) {
+        let (width, spacing) = BAR;
+        let height = frame.height();
+
+        /* A configured amount of bars is spread over the width (at least 1 character each) */
+        let fitting = (frame.width() + spacing) / (1 + spacing);
+        let count = match self.settings.bars {
+            Some(bars) => bars.clamp(1, fitting.max(1)),
+            None => (frame.width() + spacing) / (width + spacing),
+        };
+        let width = match self.settings.bars {
+            Some(_) => ((frame.width() + spacing) / count)
+                .saturating_sub(spacing)
+                .max(1),
+            None => width,
+        };
+        self.update(count, elapsed);
+
+        for row in 0..height {
+            let heat = match row * 3 / height {
+                0 => Heat::Low,
+                1 => Heat::Medium,
+                _ => Heat::High,
+            };
+            let level = row as f32 / height.saturating_sub(1).max(1) as f32;
+
+            for (index, bar) in self.bars.iter().enumerate() {
+                /* Eighths of the row which are filled */
+                let filled = ((bar * (height * 8) as f32) as usize).saturating_sub(row * 8);
+                let symbol = match filled.min(8) {
+                    0 => continue,
+                    eighths => char::from_u32(0x2580 + eighths as u32).unwrap(),
+                };
+
+                for x in 0..width {
+                    frame.set(
+                        index * (width + spacing) + x,
+                        height - 1 - row,
+                        symbol,
+                        heat,
+                        level,
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl VuMeter {
+    /// Creates a VU meter, which listens to the played audio until it's dropped.
+    ///
+    /// # Arguments
+    /// * `taps` - The played audio
+    /// * `latency` - Latency of the output, so the meter matches what's heard
+    pub fn new(taps: &Taps, latency: Duration) -> Self {
+        Self {
+            /* Enough for 96 kHz audio */
+            heard: Heard::new(taps, latency, 32768),
+            peaks: [(0.0, Instant::now()); 2],
+        }
+    }
+
+    /// Returns the loudness of the left and right channels (from `0.0` to `1.0`).
+    fn measure(&mut self) -> [f32; 2] {
+        self.heard.receive();
+        let count = VU_WINDOW.as_secs_f64() * self.heard.sample_rate as f64;
+        let frames = self.heard.last(count as usize);
+
+        [0, 1].map(|channel| {
+            let power = frames
+                .iter()
+                .map(|frame| frame[channel].powi(2))
+                .sum::<f32>()
+                / frames.len().max(1) as f32;
+            let db = 10.0 * power.max(f32::MIN_POSITIVE).log10();
+            ((db + VU_RANGE_DB) / VU_RANGE_DB).clamp(0.0, 1.0)
+        })
+    }
+}
+
+impl Visualizer for VuMeter {
+    /// Draws a bar for each channel, colored like a VU meter (the last 6 dB are red,
+    /// and the 12 dB below them yellow).
+    /// Unicode characters 0x2589 - 0x258F are used to draw eighths of a column.
+    fn render(&mut self, frame: &mut Frame, _elapsed: Duration) {
+        let levels = self.measure();
+        /* The bars start after the names of the channels */
+        let width = frame.width().saturating_sub(2);
+        let position = |x: usize| (x + 1) as f32 / width as f32;
+        let heat = |x: usize| match position(x) {
+            position if position > 1.0 - 6.0 / VU_RANGE_DB => Heat::High,
+            position if position > 1.0 - 18.0 / VU_RANGE_DB => Heat::Medium,
+            _ => Heat::Low,
+        };
+
+        /* The channels take half of the rows each, with an empty row above and below */
+        let rows = frame.height().saturating_sub(2) / 2;
+        for (channel, (level, name)) in levels.into_iter().zip(['L', 'R']).enumerate() {
+            let (peak, reached) = &mut self.peaks[channel];
+            if level >= *peak || reached.elapsed() >= VU_PEAK_HOLD {
+                (*peak, *reached) = (level, Instant::now());
+            }
+            let peak = (*peak * width as f32) as usize;
+
+            /* Eighths of a column which are filled */
+            let filled = (level * (width * 8) as f32) as usize;
+            for y in (0..rows).map(|row| 1 + channel * rows + row) {
+                frame.set(0, y, name, Heat::Low, 0.0);
+                for x in 0..width {
+                    let symbol = match filled.saturating_sub(x * 8).min(8) {
+                        0 if x == peak && peak > 0 => '\u{2595}',
+                        0 => continue,
+                        eighths => char::from_u32(0x2590 - eighths as u32).unwrap(),
+                    };
+                    frame.set(2 + x, y, symbol, heat(x), position(x));
+                }
+            }
+        }
+    }
+}
+
+impl Scope {
+    /// Creates an oscilloscope, which listens to the played audio until it's dropped.
+    ///
+    /// # Arguments
+    /// * `taps` - The played audio
+    /// * `latency` - Latency of the output, so the scope matches what's heard
+    /// * `lissajous` - Whether to draw a lissajous figure, instead of the waveform
+    pub fn new(taps: &Taps, latency: Duration, lissajous: bool) -> Self {
+        Self {
+            heard: Heard::new(taps, latency, SCOPE_FRAMES),
+            lissajous,
+        }
+    }
+
+    /// Draws the waveform (of the channels summed to mono), with time going to the right.
+    fn draw_waveform(canvas: &mut Braille, frames: &[[f32; 2]]) {
+        let (width, height) = (canvas.width(), canvas.height());
+        if width == 0 || height == 0 {
+            return;
+        }
+        let row = |sample: f32| {
+            ((1.0 - sample.clamp(-1.0, 1.0)) / 2.0 * (height - 1) as f32).round() as usize
+        };
+
+        /* Every column covers the range of its samples, connected to the previous column */
+        let mut previous = row(0.0);
+        for x in 0..width {
+            let first = x * frames.len() / width;
+            let last = ((x + 1) * frames.len() / width).max(first + 1);
+            let rows: Vec<usize> = frames[first..last]
+                .iter()
+                .map(|[left, right]| row((left + right) / 2.0))
+                .collect();
+
+            let top = rows.iter().copied().fold(previous, usize::min);
+            let bottom = rows.iter().copied().fold(previous, usize::max);
+            for y in top..=bottom {
+                canvas.set(x, y);
+            }
+            previous = rows[rows.len() - 1];
+        }
+    }
+
+    /// Draws a lissajous figure, in a square in the middle of the canvas.
+    fn draw_lissajous(canvas: &mut Braille, frames: &[[f32; 2]]) {
+        let size = canvas.width().min(canvas.height());
+        if size == 0 {
+            return;
+        }
+        let (left_margin, top_margin) = ((canvas.width() - size) / 2, (canvas.height() - size) / 2);
+        let position = |value: f32| {
+            ((value.clamp(-1.0, 1.0) + 1.0) / 2.0 * (size - 1) as f32).round() as usize
+        };
+
+        for [left, right] in frames {
+            let (side, mid) = ((left - right) / 2.0, (left + right) / 2.0);
+            canvas.set(left_margin + position(side), top_margin + position(-mid));
+        }
+    }
+}
+
+impl Visualizer for Scope {
+    /// Draws the audio heard recently with braille characters.
+    fn render(&mut self, frame: &mut Frame, _elapsed: Duration) {
+        self.heard.receive();
+        let frames = self.heard.last(SCOPE_FRAMES);
+
+        let mut canvas = Braille::new(frame.width(), frame.height());
+        match self.lissajous {
+            true => Self::draw_lissajous(&mut canvas, &frames),
+            false => Self::draw_waveform(&mut canvas, &frames),
+        }
+
+        for (y, row) in canvas.cells.iter().enumerate() {
+            for (x, dots) in row.iter().enumerate().filter(|(_, dots)| **dots != 0) {
+                let symbol = char::from_u32(0x2800 + *dots as u32).unwrap();
+                frame.set(x, y, symbol, Heat::Low, 0.0);
+            }
+        }
+    }
+}
+
+impl Braille {
+    /// Creates an empty canvas, `width` characters wide and `height` characters high.
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            cells: vec![vec![0; width]; height],
+        }
+    }
+
+    /// Returns the width of the canvas (in dots).
+    fn width(&self) -> usize {
+        self.cells.first().map_or(0, Vec::len) * 2
+    }
+
+    /// Returns the height of the canvas (in dots).
+    fn height(&self) -> usize {
+        self.cells.len() * 4
+    }
+
+    /// Draws a dot.
+    fn set(&mut self, x: usize, y: usize) {
+        /* The bits of the dots, from the top to the bottom */
+        const DOTS: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+        if let Some(cell) = self.cells.get_mut(y / 4).and_then(|row| row.get_mut(x / 2)) {
+            *cell |= DOTS[x % 2][y % 4];
+        }
+    }
+}
+
+/// Transforms the samples into their spectrum, in place (radix-2 FFT).
+///
+/// ## Panics
+/// Panics if the amount of samples isn't a power of 2.
+fn fft(data: &mut [(f32, f32)]) {
+    let n = data.len();
+    assert!(n.is_power_of_two());
+
+    /* Bit-reversal permutation */
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= n {
+        let angle = -2.0 * PI / size as f32;
+        for start in (0..n).step_by(size) {
+            for k in 0..size / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (re, im) = data[start + k + size / 2];
+                let odd = (re * cos - im * sin, re * sin + im * cos);
+                let even = data[start + k];
+
+                data[start + k] = (even.0 + odd.0, even.1 + odd.1);
+                data[start + k + size / 2] = (even.0 - odd.0, even.1 - odd.1);
+            }
+        }
+        size *= 2;
+    }
+}
@@ -0,0 +1,156 @@
+use crate::library::{Library, ScanSummary};
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait after the last change before updating the library,
+/// so files which are still being copied (or written) aren't read too early.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+/// How long to wait after the last change of the configuration file before reloading it.
+const CONFIG_SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Watches the library roots in a background thread, and updates the library when
+/// audio files are added, removed or modified (for example retagged).
+///
+/// An incremental scan is done first, to catch up with the changes made while the
+/// player wasn't running.
+///
+/// Returns a channel which receives a summary of every update which changed the library.
+/// If an error occurs, it's sent and watching stops.
+///
+/// # Arguments
+/// * `roots` - The library roots
+/// * `folder_metadata` - Whether untagged files take their album and artist from their
+///   directories, see [`Library::set_folder_metadata()`](Library::set_folder_metadata)
+///
+/// ### Notes
+/// The thread stops after the next update once the receiver is dropped.
+pub fn watch_in_background(
+    roots: Vec<PathBuf>,
+    folder_metadata: bool,
+) -> Receiver<Result<ScanSummary, String>> {
+    let (tx, rx) = channel();
+
+    thread::spawn(move || {
+        if let Err(e) = watch(&roots, folder_metadata, &tx) {
+            let _ = tx.send(Err(e));
+        }
+    });
+
+    rx
+}
+
+/// Watches the library roots until the receiving side of `updates` is dropped.
+fn watch(
+    roots: &[PathBuf],
+    folder_metadata: bool,
+    updates: &Sender<Result<ScanSummary, String>>,
+) -> Result<(), String> {
+    /* A separate connection, so the player can keep reading the library */
+    let mut library = Library::open()?;
+    library.set_folder_metadata(folder_metadata);
+
+    let (tx, events) = channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).map_err(|e| format!("Unable to watch the library: {e}"))?;
+    for root in roots {
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .map_err(|e| format!("Unable to watch {}: {e}", root.display()))?;
+    }
+
+    /* Start watching before scanning, so no change is missed in between */
+    let summary = library.scan(roots)?;
+    if summary.has_changes() && updates.send(Ok(summary)).is_err() {
+        return Ok(());
+    }
+
+    let mut pending: HashSet<PathBuf> = HashSet::new();
+    loop {
+        let event = if pending.is_empty() {
+            events.recv().map_err(|_| "The library watcher stopped")?
+        } else {
+            match events.recv_timeout(SETTLE_TIME) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => {
+                    let paths: Vec<PathBuf> = pending.drain().collect();
+                    let summary = library.update(&paths)?;
+                    if summary.has_changes() && updates.send(Ok(summary)).is_err() {
+                        return Ok(());
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err("The library watcher stopped".to_owned())
+                }
+            }
+        };
+
+        /* Errors are about single paths, which are picked up by the next scan */
+        let Ok(event) = event else {
+            continue;
+        };
+
+        match event.kind {
+            EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_)) => (),
+            _ => pending.extend(event.paths),
+        }
+    }
+}
+
+/// Watches the configuration file in a background thread, so it can be reloaded when it changes.
+///
+/// Returns a channel which receives a message after every change, once the file wasn't changed
+/// for a moment (editors often write files in several steps).
+///
+/// ### Notes
+/// The directory of the file is watched, since editors often replace the file instead of
+/// writing to it. If it can't be watched, the channel is closed.
+/// The thread stops after the next change once the receiver is dropped.
+pub fn watch_config(path: PathBuf) -> Receiver<()> {
+    let (tx, rx) = channel();
+
+    thread::spawn(move || {
+        let Some(dir) = path.parent() else {
+            return;
+        };
+        let (events_tx, events) = channel();
+        let Ok(mut watcher) = notify::recommended_watcher(events_tx) else {
+            return;
+        };
+        if watcher.watch(dir, RecursiveMode::NonRecursive).is_err() {
+            return;
+        }
+
+        let changed = |event: &notify::Result<notify::Event>| match event {
+            Ok(event) => {
+                matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                    && event.paths.contains(&path)
+            }
+            Err(_) => false,
+        };
+
+        while let Ok(event) = events.recv() {
+            if !changed(&event) {
+                continue;
+            }
+            /* Wait until the file settles */
+            loop {
+                match events.recv_timeout(CONFIG_SETTLE_TIME) {
+                    Ok(_) => continue,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            if tx.send(()).is_err() {
+                return;
+            }
+        }
+    });
+
+    rx
+}
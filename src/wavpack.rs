@@ -0,0 +1,151 @@
+use crate::ape::{u32_at, ApeTag};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Standard sample rates, indexed by bits 23-26 of the block header flags.
+const SAMPLE_RATES: [u32; 15] = [
+    6000, 8000, 9600, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200, 96000,
+    192000,
+];
+
+/// Block header flag: the block contains mono audio
+const FLAG_MONO: u32 = 1 << 2;
+/// Block header flag: hybrid (lossy) mode
+const FLAG_HYBRID: u32 = 1 << 3;
+/// Block header flag: the last block of a frame (the blocks of a frame hold one or two
+/// channels each, so multichannel audio is split across several blocks)
+const FLAG_FINAL_BLOCK: u32 = 1 << 12;
+/// Most blocks read to count the channels of a frame, so a broken file isn't read to its end.
+const MAX_FRAME_BLOCKS: usize = 64;
+
+/// Compression mode of a WavPack file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WavPackMode {
+    /// Regular lossless compression
+    Lossless,
+    /// Hybrid mode without a correction file (lossy)
+    HybridLossy,
+    /// Hybrid mode with a `.wvc` correction file next to the audio file (lossless)
+    HybridCorrected,
+}
+
+/// Information about a WavPack (`.wv`) file.
+#[derive(Debug, Clone)]
+pub struct WavPackInfo {
+    /// Compression mode
+    pub mode: WavPackMode,
+    /// Number of channels
+    pub channels: u16,
+    /// Sample rate
+    pub sample_rate: u32,
+    /// Amount of samples (per channel)
+    pub frames: u64,
+    /// APEv2 tag
+    pub tag: ApeTag,
+}
+
+impl WavPackInfo {
+    /// Reads the block headers of the first frame, and the tags of a WavPack file.
+    ///
+    /// # Arguments
+    /// * `file` - A [`String`](String) containing the path to the audio file.
+    ///
+    /// ### Notes
+    /// Files with a custom (non-standard) sample rate are not supported.
+    pub fn probe(file: &str) -> Result<Self, String> {
+        let mut reader =
+            BufReader::new(File::open(file).map_err(|e| format!("Unable to open file: {e}"))?);
+        let mut header = [0u8; 32];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| "File is too short".to_owned())?;
+
+        if &header[0..4] != b"wvpk" {
+            return Err("Missing WavPack block header".to_owned());
+        }
+
+        let flags = u32_at(&header, 24);
+        let rate_index = ((flags >> 23) & 0xF) as usize;
+        let sample_rate = *SAMPLE_RATES
+            .get(rate_index)
+            .ok_or("Unsupported sample rate")?;
+
+        let total_samples = u32_at(&header, 12);
+        if total_samples == u32::MAX {
+            return Err("Unknown track length".to_owned());
+        }
+        /* Version 4.0+ files store the upper 8 bits of the sample count separately */
+        let frames = ((header[11] as u64) << 32) | total_samples as u64;
+
+        let channels = count_channels(&mut reader, &header)?;
+
+        let mode = if flags & FLAG_HYBRID == 0 {
+            WavPackMode::Lossless
+        } else if Path::new(file).with_extension("wvc").exists() {
+            WavPackMode::HybridCorrected
+        } else {
+            WavPackMode::HybridLossy
+        };
+
+        Ok(Self {
+            mode,
+            channels,
+            sample_rate,
+            frames,
+            tag: ApeTag::read(&mut reader).map_err(|e| format!("Unable to read tags: {e}"))?,
+        })
+    }
+
+    /// Returns the length of the track in seconds.
+    pub fn length(&self) -> f64 {
+        self.frames as f64 / self.sample_rate as f64
+    }
+}
+
+/// Adds up the channels of the blocks of the first frame, starting with the block of `header`
+/// (which was just read), until the block which is flagged as the last one.
+///
+/// ## Errors
+/// Fails if the frame ends before its last block.
+fn count_channels(reader: &mut BufReader<File>, header: &[u8; 32]) -> Result<u16, String> {
+    let mut header = *header;
+    let mut channels = 0;
+
+    for _ in 0..MAX_FRAME_BLOCKS {
+        let flags = u32_at(&header, 24);
+        channels += match flags & FLAG_MONO != 0 {
+            true => 1,
+            false => 2,
+        };
+        if flags & FLAG_FINAL_BLOCK != 0 {
+            return Ok(channels);
+        }
+
+        /* The block size doesn't count the ID and the size itself */
+        let rest = (u32_at(&header, 4) as i64 + 8 - header.len() as i64).max(0);
+        reader
+            .seek_relative(rest)
+            .and_then(|()| reader.read_exact(&mut header))
+            .map_err(|_| "Truncated WavPack frame".to_owned())?;
+        if &header[0..4] != b"wvpk" {
+            return Err("Missing WavPack block header".to_owned());
+        }
+    }
+
+    Err("Too many WavPack blocks in a frame".to_owned())
+}
+
+impl std::fmt::Display for WavPackMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Lossless => "Lossless",
+                Self::HybridLossy => "Hybrid",
+                Self::HybridCorrected => "Hybrid + Correction",
+            }
+        )
+    }
+}
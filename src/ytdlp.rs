@@ -0,0 +1,114 @@
+use crate::audioinfo::AudioMeta;
+use crate::paths;
+use serde::Deserialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Hosts of the sites which are played using `yt-dlp`.
+const SUPPORTED_HOSTS: [&str; 5] = [
+    "youtube.com",
+    "youtu.be",
+    "music.youtube.com",
+    "soundcloud.com",
+    "m.soundcloud.com",
+];
+/// Name of the directory (inside the cache directory) the audio is downloaded to.
+const DOWNLOAD_DIR_NAME: &str = "ytdlp";
+
+/// A track downloaded by `yt-dlp`.
+#[derive(Debug, Clone)]
+pub struct Download {
+    /// Path to the downloaded Opus file
+    pub path: String,
+    /// Metadata of the track
+    pub metadata: AudioMeta,
+}
+
+/// The details of a video (or track) printed by `yt-dlp`.
+#[derive(Debug, Deserialize)]
+struct VideoInfo {
+    id: String,
+    title: Option<String>,
+    uploader: Option<String>,
+    /* Only set for music (e.g. on YouTube Music) */
+    track: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+}
+
+/// Returns whether the URL points to YouTube or SoundCloud.
+pub fn is_supported(url: &str) -> bool {
+    let Some((_, rest)) = url.split_once("://") else {
+        return false;
+    };
+    let host = rest
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+
+    SUPPORTED_HOSTS.contains(&host)
+}
+
+/// Downloads the audio of a YouTube video or SoundCloud track into the [cache directory](paths::cache_dir).
+/// The uploader and title are used as the artist and title, unless the site knows
+/// the actual artist and track name.
+///
+/// ### Notes
+/// This uses [`yt-dlp`](https://github.com/yt-dlp/yt-dlp) (and `ffmpeg`), and fails if it's
+/// not installed. The audio is converted to Opus, which doesn't re-encode YouTube audio.
+/// Files which were already downloaded are reused.
+pub fn download(url: &str) -> Result<Download, String> {
+    let dir = paths::cache_dir().join(DOWNLOAD_DIR_NAME);
+    fs::create_dir_all(&dir).map_err(|e| format!("Unable to create {}: {e}", dir.display()))?;
+
+    let output = Command::new("yt-dlp")
+        .args(["--no-playlist", "--no-simulate", "--dump-json", "--quiet"])
+        .args([
+            "--format",
+            "bestaudio",
+            "--extract-audio",
+            "--audio-format",
+            "opus",
+        ])
+        .arg("--output")
+        .arg(dir.join("%(id)s.%(ext)s"))
+        .arg(url)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => "yt-dlp is not installed".to_owned(),
+            _ => format!("Unable to start yt-dlp: {e}"),
+        })?;
+
+    if !output.status.success() {
+        return Err(format!("yt-dlp was unable to download {url}"));
+    }
+
+    let info: VideoInfo = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Invalid yt-dlp output: {e}"))?;
+    let path: PathBuf = dir.join(format!("{}.opus", info.id));
+    if !path.exists() {
+        return Err(format!("yt-dlp didn't download {url}"));
+    }
+
+    let mut metadata = AudioMeta::unknown();
+    if let Some(title) = info.track.or(info.title) {
+        metadata.title = title;
+    }
+    if let Some(artist) = info.artist.or(info.uploader) {
+        metadata.artist = artist;
+    }
+    if let Some(album) = info.album {
+        metadata.album = album;
+    }
+
+    Ok(Download {
+        path: path.to_string_lossy().into_owned(),
+        metadata,
+    })
+}